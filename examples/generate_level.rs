@@ -0,0 +1,35 @@
+//! Generate a random winding level, and check hobbits can make it there and back again.
+//!
+//! `cargo run --example generate_level [seed] > assets/levels/generated.level`
+//!
+//! Without a seed, a random one is picked. It's printed so the level can be generated again.
+
+use std::collections::HashSet;
+
+use bevy::math::vec2;
+use there_and_back_again::levels::{
+    generator::{generate_content, GeneratorSettings},
+    Level,
+};
+
+fn main() {
+    let seed = std::env::args()
+        .nth(1)
+        .map(|seed| seed.parse().expect("the seed should be a number"))
+        .unwrap_or_else(rand::random::<u32>);
+    eprintln!("seed: {}", seed);
+
+    let content = generate_content(seed, &GeneratorSettings::default());
+    let level = Level::parse(&content, "generated.level").expect("generated level should be valid");
+
+    let navmesh = level.as_navmesh(vec![]);
+    let spawn = level.spawns[0].cell;
+    let chest = level.chests[0].cell;
+    let from = vec2(spawn.1 as f32 * 4.0, spawn.2 as f32 * 4.0);
+    let to = vec2(chest.1 as f32 * 4.0, chest.2 as f32 * 4.0);
+    assert!(navmesh
+        .path_on_layers(from, to, HashSet::from([2]))
+        .is_some());
+
+    println!("{}", content);
+}
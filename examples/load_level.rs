@@ -0,0 +1,59 @@
+//! Read a level file, check it can be played and print what's in it.
+//!
+//! `cargo run --example load_level -- assets/levels/05.level`
+
+use there_and_back_again::levels::{Level, Tile};
+
+fn main() {
+    let file = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "assets/levels/01.level".to_string());
+    let content = std::fs::read_to_string(&file).expect("level file should be readable");
+    let level = match Level::parse(&content, &file) {
+        Ok(level) => level,
+        Err(err) => {
+            eprintln!("{}: {}", file, err);
+            std::process::exit(1);
+        }
+    };
+    if let Err(errors) = level.validate() {
+        for err in errors {
+            eprintln!("{}: {}", file, err);
+        }
+    }
+
+    println!("{}", level.display_title(0));
+    if let Some(author) = &level.author {
+        println!("  by {}", author);
+    }
+    let floor = &level.floors[0];
+    println!("  {} x {} cells", floor[0].len(), floor.len());
+    println!(
+        "  {} walkable cells",
+        floor
+            .iter()
+            .flatten()
+            .filter(|tile| **tile != Tile::Empty)
+            .count()
+    );
+    for spawn in &level.spawns {
+        println!(
+            "  spawn for team {} at ({}, {}): {} hobbits every {}s",
+            spawn.team, spawn.cell.1, spawn.cell.2, spawn.hobbits, spawn.delay
+        );
+    }
+    for chest in &level.chests {
+        println!(
+            "  chest for team {} at ({}, {}){}",
+            chest.team,
+            chest.cell.1,
+            chest.cell.2,
+            chest
+                .treasures
+                .map(|treasures| format!(" with {} treasures", treasures))
+                .unwrap_or_default()
+        );
+    }
+    println!("  objectives: {:?}", level.objectives);
+    println!("  bonus: {:?}", level.bonus);
+}
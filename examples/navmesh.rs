@@ -0,0 +1,44 @@
+//! Build the navigation mesh of a level, and find the paths hobbits would take.
+//!
+//! `cargo run --example navmesh -- assets/levels/05.level`
+
+use std::collections::HashSet;
+
+use bevy::math::vec2;
+use there_and_back_again::levels::Level;
+
+fn main() {
+    let file = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "assets/levels/05.level".to_string());
+    let content = std::fs::read_to_string(&file).expect("level file should be readable");
+    let level = Level::parse(&content, &file).expect("level file should be valid");
+
+    let mut navmesh = level.as_navmesh(vec![]);
+    navmesh.set_delta(0.1);
+
+    for spawn in &level.spawns {
+        let home = vec2(spawn.cell.1 as f32 * 4.0, spawn.cell.2 as f32 * 4.0);
+        for chest in level.chests.iter().filter(|chest| chest.team == spawn.team) {
+            let treasure = vec2(chest.cell.1 as f32 * 4.0, chest.cell.2 as f32 * 4.0);
+            // going to the chest, `Out` tiles can't be used, and `In` tiles coming back
+            let there = navmesh.path_on_layers(home, treasure, HashSet::from([2]));
+            let back = navmesh.path_on_layers(treasure, home, HashSet::from([1]));
+            match (there, back) {
+                (Some(there), Some(back)) => println!(
+                    "({}, {}) -> ({}, {}): {:.1} there, {:.1} back",
+                    spawn.cell.1,
+                    spawn.cell.2,
+                    chest.cell.1,
+                    chest.cell.2,
+                    there.length,
+                    back.length
+                ),
+                _ => println!(
+                    "({}, {}) -> ({}, {}): no way there and back again",
+                    spawn.cell.1, spawn.cell.2, chest.cell.1, chest.cell.2
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! Play a level without rendering, placing obstacles at the start, and print how it went.
+//!
+//! `cargo run --example simulate -- assets/levels/03.level 6,1`
+
+use there_and_back_again::{
+    levels::Level,
+    simulation::{simulate_level, Action},
+};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let file = args
+        .next()
+        .unwrap_or_else(|| "assets/levels/01.level".to_string());
+    let content = std::fs::read_to_string(&file).expect("level file should be readable");
+    let level = Level::parse(&content, &file).expect("level file should be valid");
+
+    // obstacles as `x,y` cells
+    let actions = args
+        .map(|cell| {
+            let (x, y) = cell.split_once(',').expect("cells should be given as x,y");
+            Action {
+                at: 0.0,
+                cell: (x.parse().unwrap(), y.parse().unwrap()),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let outcome = simulate_level(&level, &actions);
+    println!(
+        "{:?} after {:.1}s: {:?} treasures, {} hobbits lost",
+        outcome.verdict, outcome.elapsed, outcome.scores, outcome.lost_hobbits
+    );
+}
@@ -0,0 +1,180 @@
+//! Screen reader support. Bevy names buttons from the text they show; buttons showing an icon or a
+//! number get an [`AccessibleName`] instead. What happens while playing is announced with
+//! [`Announce`].
+
+use bevy::{
+    a11y::{
+        accesskit::{Action, Live, NodeBuilder, NodeId, Role},
+        AccessibilityNode, ActionRequest,
+    },
+    prelude::*,
+};
+
+use crate::{assets::GameAssets, game::GameEvent, levels::Level, play::GameInProgress, GameState};
+
+/// Name read for a button, instead of the text it shows
+#[derive(Component)]
+pub struct AccessibleName(pub String);
+
+/// Message read by screen readers once they are done with what they were reading
+#[derive(Event)]
+pub struct Announce(pub String);
+
+/// Node holding the last announcement
+#[derive(Component)]
+struct Announcer;
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Announce>()
+            .add_systems(Startup, spawn_announcer)
+            .add_systems(OnEnter(GameState::InGame), announce_level)
+            .add_systems(
+                OnEnter(GameState::Win),
+                |mut announce: EventWriter<Announce>| {
+                    announce.send(Announce("Level won".to_string()));
+                },
+            )
+            .add_systems(
+                OnEnter(GameState::Lost),
+                |mut announce: EventWriter<Announce>| {
+                    announce.send(Announce("Level lost".to_string()));
+                },
+            )
+            .add_systems(
+                Update,
+                (
+                    announce_game_events.run_if(in_state(GameState::InGame)),
+                    press_requested_buttons,
+                    announce,
+                ),
+            )
+            // after bevy names the buttons from their text, in `PostUpdate`
+            .add_systems(Last, name_buttons);
+    }
+}
+
+fn spawn_announcer(mut commands: Commands) {
+    let mut node = NodeBuilder::new(Role::Status);
+    node.set_live(Live::Polite);
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            ..default()
+        },
+        AccessibilityNode::from(node),
+        Announcer,
+    ));
+}
+
+fn announce(
+    mut announcements: EventReader<Announce>,
+    mut announcer: Query<&mut AccessibilityNode, With<Announcer>>,
+) {
+    let Some(announcement) = announcements.read().last() else {
+        return;
+    };
+    for mut node in &mut announcer {
+        node.set_name(announcement.0.clone());
+    }
+}
+
+fn announce_level(
+    game: Res<GameInProgress>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+    mut announce: EventWriter<Announce>,
+) {
+    let Some(level) = levels.get(&assets.levels[game.level]) else {
+        return;
+    };
+    let mut announcement = format!("{} started", level.display_title(game.level));
+    for page in &level.message {
+        announcement.push_str(". ");
+        announcement.push_str(&page.text);
+    }
+    announce.send(Announce(announcement));
+}
+
+fn announce_game_events(mut events: EventReader<GameEvent>, mut announce: EventWriter<Announce>) {
+    for event in events.read() {
+        let announcement = match event {
+            GameEvent::TreasureTaken { .. } => "Treasure taken",
+            GameEvent::HomeWithTreasure { .. } => "Treasure brought home",
+            GameEvent::EscortedLost => "The escorted hobbit was lost",
+            _ => continue,
+        };
+        announce.send(Announce(announcement.to_string()));
+    }
+}
+
+/// Name buttons from their [`AccessibleName`], and again from their text when it changes, and let
+/// screen readers click them
+fn name_buttons(
+    mut buttons: Query<
+        (
+            &mut AccessibilityNode,
+            &Children,
+            Option<Ref<AccessibleName>>,
+        ),
+        With<Button>,
+    >,
+    texts: Query<Ref<Text>>,
+) {
+    for (mut node, children, accessible_name) in &mut buttons {
+        let added = node.is_added();
+        let labels = children
+            .iter()
+            .filter_map(|child| texts.get(*child).ok())
+            .collect::<Vec<_>>();
+        let changed = labels.iter().any(|text| text.is_changed())
+            || accessible_name
+                .as_ref()
+                .is_some_and(|name| name.is_changed());
+        if !added && !changed {
+            continue;
+        }
+        let name = match accessible_name {
+            Some(name) => name.0.clone(),
+            None => labels
+                .iter()
+                .flat_map(|text| text.sections.iter().map(|section| section.value.as_str()))
+                .collect::<Vec<_>>()
+                .join(""),
+        };
+        // texts change every frame for their size, don't update the tree for nothing
+        if added || node.name() != Some(name.as_str()) {
+            node.set_name(name);
+            node.add_action(Action::Default);
+        }
+    }
+}
+
+/// Press the buttons screen readers click, releasing them on the next frame
+fn press_requested_buttons(
+    mut requests: EventReader<ActionRequest>,
+    mut interactions: Query<&mut Interaction, With<Button>>,
+    mut pressed: Local<Vec<Entity>>,
+) {
+    // buttons react to the change of their interaction, release the ones pressed last frame
+    for entity in pressed.drain(..) {
+        if let Ok(mut interaction) = interactions.get_mut(entity) {
+            interaction.set_if_neq(Interaction::None);
+        }
+    }
+    for request in requests.read() {
+        if request.0.action != Action::Default {
+            continue;
+        }
+        let NodeId(bits) = request.0.target;
+        let entity = Entity::from_bits(bits);
+        if let Ok(mut interaction) = interactions.get_mut(entity) {
+            *interaction = Interaction::Pressed;
+            pressed.push(entity);
+        }
+    }
+}
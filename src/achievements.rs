@@ -0,0 +1,276 @@
+//! Achievements, unlocked once for good and saved between sessions. A toast shows when one is
+//! unlocked, and they are all listed on a page off the main menu.
+
+use bevy::{color::palettes, prelude::*};
+use bevy_pkv::PkvStore;
+
+use crate::{
+    assets::GameAssets, audio::AudioTrigger, menu::SwitchState, play::GameInProgress, stats::Stats,
+    ui, GameState,
+};
+
+const CURRENT_STATE: GameState = GameState::Achievements;
+
+/// Obstacles to place over all levels for [`Achievement::Builder`]
+const OBSTACLES_TO_PLACE: u32 = 100;
+/// Seconds to win a level in for [`Achievement::Swift`]
+const SWIFT_WIN: f32 = 60.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Achievement {
+    /// Win the last level of the first chapter
+    FirstChapter,
+    /// Win a level without losing a hobbit
+    Flawless,
+    /// Place [`OBSTACLES_TO_PLACE`] obstacles
+    Builder,
+    /// Win a level in less than [`SWIFT_WIN`] seconds
+    Swift,
+}
+
+impl Achievement {
+    /// Every achievement, in the order they are shown
+    pub fn all() -> [Achievement; 4] {
+        [
+            Achievement::FirstChapter,
+            Achievement::Flawless,
+            Achievement::Builder,
+            Achievement::Swift,
+        ]
+    }
+
+    pub(crate) fn key(&self) -> &'static str {
+        match self {
+            Achievement::FirstChapter => "achievement-first-chapter",
+            Achievement::Flawless => "achievement-flawless",
+            Achievement::Builder => "achievement-builder",
+            Achievement::Swift => "achievement-swift",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Achievement::FirstChapter => "Out of the Shire",
+            Achievement::Flawless => "No Hobbit Left Behind",
+            Achievement::Builder => "Master Builder",
+            Achievement::Swift => "Swift as the Wind",
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Achievement::FirstChapter => "Finish the first chapter".to_string(),
+            Achievement::Flawless => "Win a level without losing a hobbit".to_string(),
+            Achievement::Builder => format!("Place {} obstacles", OBSTACLES_TO_PLACE),
+            Achievement::Swift => format!("Win a level in less than {} seconds", SWIFT_WIN),
+        }
+    }
+}
+
+/// Unlocked achievements
+#[derive(Resource)]
+pub struct Achievements {
+    unlocked: Vec<Achievement>,
+}
+
+impl Achievements {
+    pub fn load(store: &PkvStore) -> Self {
+        Achievements {
+            unlocked: Achievement::all()
+                .into_iter()
+                .filter(|achievement| store.get::<bool>(achievement.key()).unwrap_or(false))
+                .collect(),
+        }
+    }
+
+    pub fn is_unlocked(&self, achievement: Achievement) -> bool {
+        self.unlocked.contains(&achievement)
+    }
+
+    /// Unlock and save the achievement, returns whether it was locked until now
+    fn unlock(&mut self, achievement: Achievement, store: &mut PkvStore) -> bool {
+        if self.is_unlocked(achievement) {
+            return false;
+        }
+        self.unlocked.push(achievement);
+        let _ = store.set(achievement.key(), &true);
+        true
+    }
+}
+
+/// Time the hobbits have been going in the level being played
+#[derive(Resource, Default)]
+struct LevelTime(f32);
+
+pub struct Plugin;
+impl bevy::prelude::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelTime>()
+            .add_systems(
+                OnEnter(GameState::InGame),
+                |mut level_time: ResMut<LevelTime>| level_time.0 = 0.0,
+            )
+            .add_systems(OnEnter(GameState::Win), check_win)
+            .add_systems(
+                Update,
+                (
+                    track_level_time,
+                    check_obstacles.run_if(resource_changed::<Stats>),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnEnter(CURRENT_STATE), spawn_achievements)
+            .add_systems(
+                Update,
+                (button_system, crate::menu::change_state_after_event)
+                    .run_if(in_state(CURRENT_STATE)),
+            );
+    }
+}
+
+fn notify(commands: &mut Commands, achievement: Achievement) {
+    ui::spawn_toast(
+        commands,
+        format!("Achievement unlocked: {}", achievement.name()),
+    );
+}
+
+fn track_level_time(mut level_time: ResMut<LevelTime>, time: Res<Time>) {
+    level_time.0 += time.delta_seconds();
+}
+
+fn check_obstacles(
+    mut commands: Commands,
+    stats: Res<Stats>,
+    mut achievements: ResMut<Achievements>,
+    mut store: ResMut<PkvStore>,
+) {
+    if stats.obstacles_placed >= OBSTACLES_TO_PLACE
+        && achievements.unlock(Achievement::Builder, &mut store)
+    {
+        notify(&mut commands, Achievement::Builder);
+    }
+}
+
+fn check_win(
+    mut commands: Commands,
+    game: Res<GameInProgress>,
+    assets: Res<GameAssets>,
+    level_time: Res<LevelTime>,
+    mut achievements: ResMut<Achievements>,
+    mut store: ResMut<PkvStore>,
+) {
+    let first_chapter_done = assets.is_campaign(game.level)
+        && assets
+            .chapters
+            .first()
+            .is_some_and(|chapter| *chapter.levels.end() == game.level);
+    for (achievement, reached) in [
+        (Achievement::FirstChapter, first_chapter_done),
+        (Achievement::Flawless, game.lost_hobbits == 0),
+        (Achievement::Swift, level_time.0 < SWIFT_WIN),
+    ] {
+        if reached && achievements.unlock(achievement, &mut store) {
+            notify(&mut commands, achievement);
+        }
+    }
+}
+
+fn spawn_achievements(mut commands: Commands, achievements: Res<Achievements>, stats: Res<Stats>) {
+    info!("Loading screen");
+
+    commands
+        .spawn((
+            ui::screen_root(25.0),
+            MenuItem::Root,
+            StateScoped(CURRENT_STATE),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((ui::panel(50.0, 70.0), MenuItem::Panel))
+                .with_children(|parent| {
+                    parent.spawn(ui::title("Achievements"));
+
+                    for achievement in Achievement::all() {
+                        let unlocked = achievements.is_unlocked(achievement);
+                        let mut description = achievement.description();
+                        if achievement == Achievement::Builder && !unlocked {
+                            description.push_str(&format!(
+                                " ({}/{})",
+                                stats.obstacles_placed, OBSTACLES_TO_PLACE
+                            ));
+                        }
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::Column,
+                                    align_items: AlignItems::Center,
+                                    margin: UiRect::top(Val::Percent(3.0)),
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    achievement.name(),
+                                    TextStyle {
+                                        font_size: 28.0,
+                                        color: if unlocked {
+                                            palettes::tailwind::AMBER_400.into()
+                                        } else {
+                                            palettes::tailwind::GRAY_500.into()
+                                        },
+                                        ..default()
+                                    },
+                                ));
+                                parent.spawn(TextBundle::from_section(
+                                    description,
+                                    TextStyle {
+                                        font_size: 18.0,
+                                        color: Color::WHITE,
+                                        ..default()
+                                    },
+                                ));
+                            });
+                    }
+
+                    ui::spawn_button(
+                        parent,
+                        "Back to Menu",
+                        Vec2::new(200.0, 40.0),
+                        UiRect::top(Val::Percent(6.0)),
+                    )
+                    .insert(MenuItem::Button);
+                });
+        });
+}
+
+#[derive(Component, PartialEq, Eq)]
+enum MenuItem {
+    Root,
+    Panel,
+    Button,
+}
+
+fn button_system(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, Changed<Interaction>>,
+    mut next_state: EventWriter<SwitchState>,
+    ui_items: Query<(Entity, &MenuItem)>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+) {
+    for interaction in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        audio_trigger.send(AudioTrigger::Click);
+
+        next_state.send(SwitchState(GameState::Menu));
+
+        for (entity, kind) in &ui_items {
+            if *kind == MenuItem::Root {
+                ui::slide_out(&mut commands, entity, 25.0);
+            }
+        }
+    }
+}
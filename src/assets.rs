@@ -1,8 +1,16 @@
+use std::ops::Range;
+
 #[cfg(not(target_arch = "wasm32"))]
 use bevy::asset::LoadedFolder;
 use bevy::prelude::*;
 
-use crate::levels::Level;
+#[cfg(feature = "mods")]
+use crate::extensions::GameExtensions;
+use crate::{
+    chapters::{Chapter, Chapters},
+    levels::{Bonus, Level},
+    voices::VoiceBank,
+};
 
 #[derive(Resource)]
 pub struct RawGameAssets {
@@ -17,12 +25,20 @@ pub struct RawGameAssets {
     pub levels: Handle<LoadedFolder>,
     #[cfg(target_arch = "wasm32")]
     pub levels: Vec<Handle<Level>>,
+    /// Levels made by the player, each loaded on its own so that a broken one doesn't stop the
+    /// others from loading
+    #[cfg(not(target_arch = "wasm32"))]
+    pub custom_levels: Vec<Handle<Level>>,
     pub wall: Handle<Scene>,
     pub wall_corner: Handle<Scene>,
     pub obstacle: Handle<Scene>,
     pub icon_obstacle: Handle<Image>,
+    pub icon_torch: Handle<Image>,
+    pub chapters: Handle<Chapters>,
 }
-#[derive(Resource)]
+/// Assets of the game once loaded. The default has no asset at all, for the headless
+/// [`crate::simulation`] that spawns levels without seeing them.
+#[derive(Resource, Default)]
 pub struct GameAssets {
     pub character: Handle<Scene>,
     pub character_walk: Handle<AnimationClip>,
@@ -33,7 +49,12 @@ pub struct GameAssets {
     pub floor: Handle<Scene>,
     pub chest: Handle<Scene>,
     pub coin_stack: Handle<Scene>,
+    /// Campaign levels, followed by the custom levels, then by downloaded levels
     pub levels: Vec<Handle<Level>>,
+    /// Indices of the custom levels in [`GameAssets::levels`]
+    pub custom_levels: Range<usize>,
+    /// Index in [`GameAssets::levels`] reused by each generated level
+    pub generated_level: Option<usize>,
     pub wall: Handle<Scene>,
     pub wall_corner: Handle<Scene>,
     pub out_material: Handle<StandardMaterial>,
@@ -42,4 +63,74 @@ pub struct GameAssets {
     pub undergrate_mesh: Handle<Mesh>,
     pub obstacle: Handle<Scene>,
     pub icon_obstacle: Handle<Image>,
+    pub icon_torch: Handle<Image>,
+    pub chapters: Vec<Chapter>,
+    pub torch_mesh: Handle<Mesh>,
+    pub torch_material: Handle<StandardMaterial>,
+    pub voices: VoiceBank,
+    #[cfg(feature = "mods")]
+    pub extensions: GameExtensions,
+}
+
+impl GameAssets {
+    /// Levels outside of the campaign have their own progress, by file
+    pub fn is_campaign(&self, level: usize) -> bool {
+        level < self.custom_levels.start
+    }
+
+    /// Replace the last generated level by a new one. Returns its index.
+    pub fn set_generated_level(&mut self, level: Handle<Level>) -> usize {
+        match self.generated_level {
+            Some(index) => {
+                self.levels[index] = level;
+                index
+            }
+            None => {
+                self.levels.push(level);
+                *self.generated_level.insert(self.levels.len() - 1)
+            }
+        }
+    }
+
+    pub fn bonus_icon(&self, bonus: Bonus) -> Handle<Image> {
+        match bonus {
+            Bonus::Obstacle => self.icon_obstacle.clone(),
+            Bonus::Torch => self.icon_torch.clone(),
+            #[cfg(feature = "mods")]
+            Bonus::Custom(index) => self
+                .extensions
+                .bonus(index)
+                .map(|bonus| bonus.icon())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Name of a bonus, as read by screen readers
+    pub fn bonus_name(&self, bonus: Bonus) -> String {
+        match bonus {
+            Bonus::Obstacle => "obstacle".to_string(),
+            Bonus::Torch => "torch".to_string(),
+            #[cfg(feature = "mods")]
+            Bonus::Custom(index) => self
+                .extensions
+                .bonus(index)
+                .map(|bonus| bonus.name().to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Scene of a placed bonus. Torches are built from a mesh instead.
+    pub fn bonus_scene(&self, bonus: Bonus) -> Option<Handle<Scene>> {
+        match bonus {
+            Bonus::Obstacle => Some(self.obstacle.clone()),
+            Bonus::Torch => None,
+            #[cfg(feature = "mods")]
+            Bonus::Custom(index) => Some(
+                self.extensions
+                    .bonus(index)
+                    .map(|bonus| bonus.scene())
+                    .unwrap_or_default(),
+            ),
+        }
+    }
 }
@@ -0,0 +1,290 @@
+//! Attract mode: after a minute without any input on the menu, the screen fades to a replay of a
+//! level the player won, played again by the [`Simulation`] with the obstacles of its last
+//! solution. Any input goes back to the menu.
+
+use std::time::Duration;
+
+use bevy::{color::palettes, ecs::system::EntityCommands, input::mouse::MouseMotion, prelude::*};
+use bevy_easings::{Ease, EaseFunction, EasingType};
+use bevy_pkv::PkvStore;
+use rand::seq::SliceRandom;
+
+use crate::{
+    assets::GameAssets,
+    camera::play_view,
+    layouts::{last_solution, last_won_version},
+    levels::{spawn_level, Level},
+    menu::SwitchState,
+    simulation::{Action, ActionKind, Simulation, TIMESTEP},
+    GameState,
+};
+
+/// Seconds without input on the menu before the attract mode starts
+const ATTRACT_AFTER: Duration = Duration::from_secs(60);
+/// Seconds to fade to or from black, the time it takes to switch state
+const FADE_DURATION: f32 = 1.0;
+
+/// Last time the player did anything
+#[derive(Resource, Default)]
+struct LastInput(Duration);
+
+/// Level replayed in attract mode. Each loop starts a new [`Simulation`], which builds a whole
+/// headless app with its physics: the hitch when the replay starts again is that setup.
+#[derive(Resource)]
+struct AttractReplay {
+    level: usize,
+    /// Obstacles of the solution, to play it again once it's decided
+    actions: Vec<Action>,
+    simulation: Simulation,
+    /// Time not simulated yet, less than a step
+    leftover: f32,
+}
+
+/// Fade out of the replay, going back to the menu
+#[derive(Component)]
+struct Leaving;
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LastInput>()
+            .add_systems(Update, record_input.run_if(any_input))
+            .add_systems(OnEnter(GameState::Menu), record_input)
+            .add_systems(
+                Update,
+                start_attract.run_if(in_state(GameState::Menu).and_then(idle)),
+            )
+            .add_systems(OnEnter(GameState::Attract), spawn_replay)
+            .add_systems(OnExit(GameState::Attract), |mut commands: Commands| {
+                commands.remove_resource::<AttractReplay>()
+            })
+            .add_systems(
+                Update,
+                (
+                    play_replay,
+                    display_replay,
+                    leave_attract.run_if(any_input),
+                    crate::menu::change_state_after_event,
+                )
+                    .run_if(in_state(GameState::Attract)),
+            );
+    }
+}
+
+/// Any key or button pressed, a touch, or the mouse moved
+fn any_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepad: Res<ButtonInput<GamepadButton>>,
+    touches: Res<Touches>,
+    mut motion: EventReader<MouseMotion>,
+) -> bool {
+    motion.read().count() > 0
+        || keyboard.get_just_pressed().next().is_some()
+        || mouse.get_just_pressed().next().is_some()
+        || gamepad.get_just_pressed().next().is_some()
+        || touches.any_just_pressed()
+}
+
+fn record_input(mut last_input: ResMut<LastInput>, time: Res<Time<Real>>) {
+    last_input.0 = time.elapsed();
+}
+
+fn idle(last_input: Res<LastInput>, time: Res<Time<Real>>) -> bool {
+    time.elapsed() - last_input.0 > ATTRACT_AFTER
+}
+
+/// A fullscreen black overlay, fading in or out
+fn spawn_fade<'a>(
+    commands: &'a mut Commands,
+    from: Color,
+    to: Color,
+    state: GameState,
+) -> EntityCommands<'a> {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: from.into(),
+            z_index: ZIndex::Global(10),
+            ..default()
+        },
+        BackgroundColor(from).ease_to(
+            BackgroundColor(to),
+            EaseFunction::QuadraticInOut,
+            EasingType::Once {
+                duration: Duration::from_secs_f32(FADE_DURATION),
+            },
+        ),
+        StateScoped(state),
+    ))
+}
+
+/// Pick a level won with a solution still valid, and fade to its replay
+fn start_attract(
+    mut commands: Commands,
+    mut last_input: ResMut<LastInput>,
+    time: Res<Time<Real>>,
+    store: Res<PkvStore>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+    mut next_state: EventWriter<SwitchState>,
+) {
+    // wait another minute if there is nothing to replay
+    last_input.0 = time.elapsed();
+    let replays = (1..assets.custom_levels.start)
+        .filter(|index| last_won_version(&store, *index).is_some())
+        .filter_map(|index| {
+            let level = levels.get(&assets.levels[index])?;
+            let solution = last_solution(&store, &level.file)
+                .filter(|layout| !layout.is_stale(level) && layout.is_valid_for(level))?;
+            let actions = solution
+                .cells
+                .iter()
+                .map(|cell| Action {
+                    at: 0.0,
+                    cell: *cell,
+                    kind: ActionKind::Place(None),
+                })
+                .collect::<Vec<_>>();
+            Some((index, level, actions))
+        })
+        .collect::<Vec<_>>();
+    let Some((index, level, actions)) = replays.choose(&mut rand::thread_rng()) else {
+        return;
+    };
+    info!("attract mode with level {}", index);
+    commands.insert_resource(AttractReplay {
+        level: *index,
+        actions: actions.clone(),
+        simulation: Simulation::new(level, actions),
+        leftover: 0.0,
+    });
+    spawn_fade(&mut commands, Color::NONE, Color::BLACK, GameState::Menu);
+    next_state.send(SwitchState(GameState::Attract));
+}
+
+fn spawn_replay(
+    mut commands: Commands,
+    replay: Res<AttractReplay>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+    mut camera: Query<&mut Transform, With<Camera>>,
+    store: Res<PkvStore>,
+) {
+    let level = levels.get(&assets.levels[replay.level]).unwrap();
+    let (level_size, _) = spawn_level(
+        &mut commands,
+        level,
+        assets.as_ref(),
+        StateScoped(GameState::Attract),
+    );
+    *camera.single_mut() = play_view(level_size);
+
+    let solution = last_solution(&store, &level.file)
+        .map(|layout| layout.cells)
+        .unwrap_or_default();
+    for (cell, bonus) in solution.iter().zip(&level.bonus) {
+        let transform =
+            Transform::from_translation(Vec3::new(cell.0 as f32 * 4.0, 0.4, cell.1 as f32 * 4.0));
+        if let Some(scene) = assets.bonus_scene(*bonus) {
+            commands.spawn((
+                SceneBundle {
+                    scene,
+                    transform: transform.with_scale(Vec3::splat(1.5)),
+                    ..default()
+                },
+                StateScoped(GameState::Attract),
+            ));
+        } else {
+            commands.spawn((
+                PbrBundle {
+                    mesh: assets.torch_mesh.clone(),
+                    material: assets.torch_material.clone(),
+                    transform: transform.with_translation(transform.translation.with_y(1.2)),
+                    ..default()
+                },
+                StateScoped(GameState::Attract),
+            ));
+        }
+    }
+
+    spawn_fade(&mut commands, Color::BLACK, Color::NONE, GameState::Attract);
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::SpaceBetween,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::vertical(Val::Percent(5.0)),
+                    ..default()
+                },
+                ..default()
+            },
+            StateScoped(GameState::Attract),
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_sections(
+                level.title_sections(replay.level, 40.0),
+            ));
+            parent.spawn(TextBundle::from_section(
+                "Press any key",
+                TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn play_replay(
+    mut replay: ResMut<AttractReplay>,
+    time: Res<Time>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+) {
+    if replay.simulation.verdict().is_some() {
+        let Some(level) = levels.get(&assets.levels[replay.level]) else {
+            return;
+        };
+        // a new headless app with its physics, not just a reset of the previous one
+        replay.simulation = Simulation::new(level, &replay.actions);
+    }
+    replay.leftover += time.delta_seconds();
+    while replay.leftover >= TIMESTEP {
+        replay.leftover -= TIMESTEP;
+        replay.simulation.step();
+    }
+}
+
+fn display_replay(replay: Res<AttractReplay>, mut gizmos: Gizmos) {
+    for (position, tired) in replay.simulation.hobbits() {
+        let color = if tired {
+            palettes::tailwind::AMBER_400
+        } else {
+            palettes::tailwind::GREEN_400
+        };
+        gizmos.circle(Vec3::new(position.x, 1.0, position.y), Dir3::Y, 0.5, color);
+    }
+}
+
+fn leave_attract(
+    mut commands: Commands,
+    mut next_state: EventWriter<SwitchState>,
+    leaving: Query<(), With<Leaving>>,
+) {
+    if !leaving.is_empty() {
+        return;
+    }
+    spawn_fade(&mut commands, Color::NONE, Color::BLACK, GameState::Attract).insert(Leaving);
+    next_state.send(SwitchState(GameState::Menu));
+}
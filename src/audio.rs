@@ -1,22 +1,124 @@
-use bevy::prelude::*;
+//! Music and sound effects, on two buses whose volume is set from the settings.
 
-use crate::GameState;
+use std::mem::{discriminant, Discriminant};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_pkv::PkvStore;
+use rand::Rng;
+
+use crate::{
+    assets::GameAssets,
+    bindings::{Action, InputMap},
+    game::{GameEvent, PathStatus},
+    levels::Level,
+    play::GameInProgress,
+    settings::Settings,
+    GameState,
+};
 
 pub struct Plugin;
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut App) {
         app.add_event::<AudioTrigger>()
+            .init_resource::<Muted>()
+            .init_resource::<Duck>()
+            .init_resource::<MusicIntensity>()
+            .init_resource::<Tension>()
             .add_systems(OnEnter(GameState::Loading), load_background_music)
-            .add_systems(OnEnter(GameState::InGame), switch_to_game_music)
+            .add_systems(
+                OnEnter(GameState::InGame),
+                (switch_to_game_music, load_stingers, reset_tension),
+            )
             .add_systems(OnExit(GameState::InGame), switch_to_menu_music)
-            .add_systems(Update, (fade_in, fade_out, play_audio_effect));
+            .add_systems(
+                Update,
+                (
+                    toggle_mute,
+                    fade_in,
+                    fade_out,
+                    (
+                        duck_music,
+                        apply_music_volume
+                            .run_if(resource_changed::<Settings>.or_else(resource_changed::<Duck>)),
+                    )
+                        .chain(),
+                    play_audio_effect,
+                    (update_intensity, apply_stem_volume)
+                        .chain()
+                        .run_if(in_state(GameState::InGame)),
+                ),
+            );
     }
 }
 
+/// Music is paused and effects are not played
+#[derive(Resource, Default)]
+pub struct Muted(pub bool);
+
+impl Muted {
+    pub fn load(store: &PkvStore) -> Self {
+        Muted(store.get::<bool>("muted").unwrap_or(false))
+    }
+}
+
+/// A soundtrack, on the music bus. Everything else is on the effects bus.
+#[derive(Component)]
+struct Music;
+
+/// Layer of the game music, with a volume following [`MusicIntensity`]
+#[derive(Component)]
+struct Stem;
+
+/// Percussion layered over the music of a level as it gets tense
+const PERCUSSION_STEM: &str = "audio/percussion.ogg";
+
+/// Tension of the level being played, between 0 and 1. The percussion stem is silent at 0 and at
+/// the music volume at 1.
+#[derive(Resource, Default)]
+pub struct MusicIntensity(pub f32);
+
+/// Tension added by each hobbit lost
+const LOST_TENSION: f32 = 0.2;
+/// Tension while no path leads to a chest
+const BLOCKED_TENSION: f32 = 0.5;
+/// Tension removed by a treasure brought back, for each treasure already brought back in a row
+const STREAK_RELIEF: f32 = 0.02;
+/// Tension lost every second, so that it fades when nothing happens
+const TENSION_DECAY: f32 = 0.03;
+/// Change of the intensity every second, so that the stem doesn't jump in and out
+const INTENSITY_RATE: f32 = 0.25;
+
+/// What makes the intensity of the music, reset for each level
+#[derive(Resource, Default)]
+struct Tension {
+    /// From the hobbits lost, fading with time and with treasures brought back
+    lost: f32,
+    /// Treasures brought back since the last hobbit lost
+    streak: u32,
+}
+
+/// The game soundtrack is only loaded when a level is first started, to not delay the menu. Levels
+/// can also name their own track.
 #[derive(Resource, Clone)]
 struct Soundtracks {
     menu: Handle<AudioSource>,
-    game: Handle<AudioSource>,
+    game: Option<Handle<AudioSource>>,
+}
+
+/// Share of its volume the music is lowered to, so that an important effect is heard
+const DUCK_VOLUME: f32 = 0.3;
+/// Seconds for the music to be back to its volume after being lowered
+const DUCK_TIME: f32 = 1.5;
+
+/// Seconds left before the music is back to its volume
+#[derive(Resource, Default)]
+struct Duck(f32);
+
+impl Duck {
+    /// Share of the music volume, lowered at once then rising back
+    fn factor(&self) -> f32 {
+        1.0 - (1.0 - DUCK_VOLUME) * self.0 / DUCK_TIME
+    }
 }
 
 #[derive(Resource, Clone)]
@@ -24,11 +126,16 @@ struct AudioEffects {
     click: Handle<AudioSource>,
     home: Handle<AudioSource>,
     hurt: Handle<AudioSource>,
-    lost: Handle<AudioSource>,
     obstacle: Handle<AudioSource>,
     spawn: Handle<AudioSource>,
     start: Handle<AudioSource>,
     treasure: Handle<AudioSource>,
+}
+
+/// Effects ending a level, loaded when a level is first started
+#[derive(Resource)]
+struct Stingers {
+    lost: Handle<AudioSource>,
     win: Handle<AudioSource>,
 }
 
@@ -37,11 +144,14 @@ struct FadeIn;
 #[derive(Component)]
 struct FadeOut;
 
-fn load_background_music(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn load_background_music(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    muted: Res<Muted>,
+) {
     let soundtracks = Soundtracks {
         menu: asset_server.load("music_zapsplat_game_music_zen_calm_soft_arpeggios_013.ogg"),
-        game: asset_server
-            .load("music_zapsplat_game_music_dark_atmospheric_slow_beat_zombie_019.ogg"),
+        game: None,
     };
     commands.insert_resource(soundtracks.clone());
 
@@ -51,9 +161,11 @@ fn load_background_music(mut commands: Commands, asset_server: Res<AssetServer>)
             settings: PlaybackSettings {
                 mode: bevy::audio::PlaybackMode::Loop,
                 volume: bevy::audio::Volume::ZERO,
+                paused: muted.0,
                 ..default()
             },
         },
+        Music,
         FadeIn,
     ));
 
@@ -61,11 +173,23 @@ fn load_background_music(mut commands: Commands, asset_server: Res<AssetServer>)
         click: asset_server.load("audio/click.ogg"),
         home: asset_server.load("audio/home.ogg"),
         hurt: asset_server.load("audio/hurt.ogg"),
-        lost: asset_server.load("audio/lost.ogg"),
         obstacle: asset_server.load("audio/obstacle.ogg"),
         spawn: asset_server.load("audio/spawn.ogg"),
         start: asset_server.load("audio/start.ogg"),
         treasure: asset_server.load("audio/treasure.ogg"),
+    });
+}
+
+fn load_stingers(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    stingers: Option<Res<Stingers>>,
+) {
+    if stingers.is_some() {
+        return;
+    }
+    commands.insert_resource(Stingers {
+        lost: asset_server.load("audio/lost.ogg"),
         win: asset_server.load("audio/win.ogg"),
     });
 }
@@ -76,12 +200,15 @@ const MAX_VOLUME: f32 = 0.08;
 fn fade_in(
     mut commands: Commands,
     mut audio_sink: Query<(&mut AudioSink, Entity), With<FadeIn>>,
-    time: Res<Time>,
+    time: Res<Time<Real>>,
+    settings: Res<Settings>,
+    duck: Res<Duck>,
 ) {
+    let max_volume = MAX_VOLUME * settings.music_volume * duck.factor();
     for (audio, entity) in audio_sink.iter_mut() {
         audio.set_volume(audio.volume() + time.delta_seconds() * MAX_VOLUME / FADE_TIME);
-        if audio.volume() >= MAX_VOLUME {
-            audio.set_volume(MAX_VOLUME);
+        if audio.volume() >= max_volume {
+            audio.set_volume(max_volume);
             commands.entity(entity).remove::<FadeIn>();
         }
     }
@@ -90,7 +217,7 @@ fn fade_in(
 fn fade_out(
     mut commands: Commands,
     mut audio_sink: Query<(&mut AudioSink, Entity), With<FadeOut>>,
-    time: Res<Time>,
+    time: Res<Time<Real>>,
 ) {
     for (audio, entity) in audio_sink.iter_mut() {
         audio.set_volume(audio.volume() - time.delta_seconds() * MAX_VOLUME / FADE_TIME);
@@ -100,87 +227,347 @@ fn fade_out(
     }
 }
 
-fn switch_to_game_music(
-    mut commands: Commands,
-    soundtracks: Res<Soundtracks>,
-    mut previous_soundtrack: Query<Entity, With<PlaybackSettings>>,
+fn duck_music(
+    mut audio_trigger: EventReader<AudioTrigger>,
+    mut duck: ResMut<Duck>,
+    time: Res<Time<Real>>,
 ) {
-    for entity in previous_soundtrack.iter_mut() {
-        commands.entity(entity).insert(FadeOut);
+    if audio_trigger
+        .read()
+        .any(|trigger| matches!(trigger, AudioTrigger::Hurt | AudioTrigger::Win))
+    {
+        duck.0 = DUCK_TIME;
+    } else if duck.0 > 0.0 {
+        duck.0 = (duck.0 - time.delta_seconds()).max(0.0);
     }
+}
+
+fn apply_music_volume(
+    music: Query<
+        &AudioSink,
+        (
+            With<Music>,
+            Without<Stem>,
+            Without<FadeIn>,
+            Without<FadeOut>,
+        ),
+    >,
+    settings: Res<Settings>,
+    duck: Res<Duck>,
+) {
+    for sink in &music {
+        sink.set_volume(MAX_VOLUME * settings.music_volume * duck.factor());
+    }
+}
+
+/// Fade out the soundtracks playing while fading in `track`, unless it's already playing
+fn crossfade_to(
+    commands: &mut Commands,
+    track: Handle<AudioSource>,
+    playing: &Query<(Entity, &Handle<AudioSource>), (With<Music>, Without<FadeOut>)>,
+    muted: &Muted,
+) {
+    let mut already_playing = false;
+    for (entity, source) in playing {
+        if *source == track && !already_playing {
+            already_playing = true;
+        } else {
+            commands.entity(entity).insert(FadeOut).remove::<FadeIn>();
+        }
+    }
+    if already_playing {
+        return;
+    }
+    // playback, and its fade in, only start once the track is loaded
     commands.spawn((
         AudioBundle {
-            source: soundtracks.game.clone(),
+            source: track,
             settings: PlaybackSettings {
                 mode: bevy::audio::PlaybackMode::Loop,
                 volume: bevy::audio::Volume::ZERO,
+                paused: muted.0,
                 ..default()
             },
         },
+        Music,
         FadeIn,
     ));
 }
 
-fn switch_to_menu_music(
+#[allow(clippy::too_many_arguments)]
+fn switch_to_game_music(
     mut commands: Commands,
-    soundtracks: Res<Soundtracks>,
-    mut previous_soundtrack: Query<Entity, With<PlaybackSettings>>,
+    mut soundtracks: ResMut<Soundtracks>,
+    asset_server: Res<AssetServer>,
+    playing: Query<(Entity, &Handle<AudioSource>), (With<Music>, Without<FadeOut>)>,
+    muted: Res<Muted>,
+    game: Res<GameInProgress>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
 ) {
-    for entity in previous_soundtrack.iter_mut() {
-        commands.entity(entity).insert(FadeOut);
-    }
+    let track = match levels
+        .get(&assets.levels[game.level])
+        .and_then(|level| level.music.as_ref())
+    {
+        Some(music) => asset_server.load(music.clone()),
+        None => soundtracks
+            .game
+            .get_or_insert_with(|| {
+                asset_server
+                    .load("music_zapsplat_game_music_dark_atmospheric_slow_beat_zombie_019.ogg")
+            })
+            .clone(),
+    };
+    crossfade_to(&mut commands, track, &playing, &muted);
+    // silent until the level gets tense
     commands.spawn((
         AudioBundle {
-            source: soundtracks.menu.clone(),
+            source: asset_server.load(PERCUSSION_STEM),
             settings: PlaybackSettings {
                 mode: bevy::audio::PlaybackMode::Loop,
                 volume: bevy::audio::Volume::ZERO,
+                paused: muted.0,
                 ..default()
             },
         },
-        FadeIn,
+        Music,
+        Stem,
     ));
 }
 
+fn reset_tension(mut tension: ResMut<Tension>, mut intensity: ResMut<MusicIntensity>) {
+    *tension = Tension::default();
+    intensity.0 = 0.0;
+}
+
+fn update_intensity(
+    mut game_events: EventReader<GameEvent>,
+    mut tension: ResMut<Tension>,
+    mut intensity: ResMut<MusicIntensity>,
+    path_status: Option<Res<PathStatus>>,
+    time: Res<Time>,
+) {
+    for event in game_events.read() {
+        match event {
+            GameEvent::CollidedWithHobbit { .. } | GameEvent::EscortedLost => {
+                tension.lost += LOST_TENSION;
+                tension.streak = 0;
+            }
+            GameEvent::HomeWithTreasure { .. } => {
+                tension.streak += 1;
+                tension.lost -= STREAK_RELIEF * tension.streak as f32;
+            }
+            _ => (),
+        }
+    }
+    tension.lost = (tension.lost - TENSION_DECAY * time.delta_seconds()).clamp(0.0, 1.0);
+
+    let blocked = if path_status.is_some_and(|status| *status == PathStatus::Blocked) {
+        BLOCKED_TENSION
+    } else {
+        0.0
+    };
+    let target = (tension.lost + blocked).min(1.0);
+    let step = INTENSITY_RATE * time.delta_seconds();
+    let value = intensity.0 + (target - intensity.0).clamp(-step, step);
+    if value != intensity.0 {
+        intensity.0 = value;
+    }
+}
+
+fn apply_stem_volume(
+    stems: Query<&AudioSink, (With<Stem>, Without<FadeOut>)>,
+    intensity: Res<MusicIntensity>,
+    settings: Res<Settings>,
+    duck: Res<Duck>,
+) {
+    for sink in &stems {
+        sink.set_volume(MAX_VOLUME * settings.music_volume * duck.factor() * intensity.0);
+    }
+}
+
+fn switch_to_menu_music(
+    mut commands: Commands,
+    soundtracks: Res<Soundtracks>,
+    playing: Query<(Entity, &Handle<AudioSource>), (With<Music>, Without<FadeOut>)>,
+    muted: Res<Muted>,
+) {
+    crossfade_to(&mut commands, soundtracks.menu.clone(), &playing, &muted);
+}
+
+fn toggle_mute(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    input_map: Res<InputMap>,
+    mut muted: ResMut<Muted>,
+    mut store: ResMut<PkvStore>,
+    music: Query<&AudioSink, Without<FadeOut>>,
+) {
+    if !input_map.just_pressed(&keyboard, Action::Mute) {
+        return;
+    }
+    muted.0 = !muted.0;
+    let _ = store.set("muted", &muted.0);
+    for sink in &music {
+        if muted.0 {
+            sink.pause();
+        } else {
+            sink.play();
+        }
+    }
+}
+
 #[derive(Event)]
 pub enum AudioTrigger {
     Click,
+    /// Every second at the end of a time limit
+    Countdown,
+    /// A step of a hobbit, with its speed in units per second
+    Footstep(f32),
     Home,
+    /// The pointer entered a button
+    Hover,
     Hurt,
     Lost,
     Obstacle,
+    ObstacleRemoved,
     Spawn,
     Start,
+    /// A skeleton swinging its sword
+    Swing,
     Treasure,
     Win,
 }
 
+/// Speed of a hobbit with a footstep at its normal volume
+const FOOTSTEP_SPEED: f32 = 8.0;
+
+impl AudioTrigger {
+    /// Volume of the effect, relative to the others
+    fn volume(&self) -> f32 {
+        match self {
+            AudioTrigger::Footstep(speed) => 0.15 * (speed / FOOTSTEP_SPEED).clamp(0.3, 1.5),
+            AudioTrigger::Hover => 0.3,
+            AudioTrigger::Swing => 0.4,
+            AudioTrigger::Spawn => 0.6,
+            _ => 1.0,
+        }
+    }
+
+    /// Playback speed of the effect, some effects reusing the sample of another at another pitch
+    fn pitch(&self) -> f32 {
+        match self {
+            AudioTrigger::Countdown => 0.6,
+            AudioTrigger::Footstep(_) => 0.45,
+            AudioTrigger::Hover => 1.5,
+            AudioTrigger::ObstacleRemoved => 0.75,
+            AudioTrigger::Swing => 1.8,
+            _ => 1.0,
+        }
+    }
+
+    /// Maximum random change of the pitch, so that effects played often don't sound the same
+    fn pitch_variation(&self) -> f32 {
+        match self {
+            AudioTrigger::Footstep(_) | AudioTrigger::Swing => 0.15,
+            AudioTrigger::Hurt | AudioTrigger::Obstacle | AudioTrigger::ObstacleRemoved => 0.08,
+            _ => 0.0,
+        }
+    }
+
+    /// Minimum seconds between two effects of this kind, so that a crowd doesn't saturate
+    fn min_gap(&self) -> f32 {
+        match self {
+            AudioTrigger::Footstep(_) => 0.1,
+            AudioTrigger::Swing => 0.2,
+            AudioTrigger::Hover => 0.05,
+            _ => 0.0,
+        }
+    }
+
+    /// Text shown for the effect when captions are enabled, `None` for the sounds of the interface
+    /// and of footsteps
+    pub fn caption(&self) -> Option<&'static str> {
+        match self {
+            AudioTrigger::Click | AudioTrigger::Footstep(_) | AudioTrigger::Hover => None,
+            AudioTrigger::Countdown => Some("[clock ticking]"),
+            AudioTrigger::Home => Some("[hobbit home]"),
+            AudioTrigger::Hurt => Some("[hobbit hurt]"),
+            AudioTrigger::Lost => Some("[level lost]"),
+            AudioTrigger::Obstacle => Some("[obstacle placed]"),
+            AudioTrigger::ObstacleRemoved => Some("[obstacle removed]"),
+            AudioTrigger::Spawn => Some("[hobbits spawn]"),
+            AudioTrigger::Start => Some("[level starts]"),
+            AudioTrigger::Swing => Some("[sword swing]"),
+            AudioTrigger::Treasure => Some("[coin collected]"),
+            AudioTrigger::Win => Some("[fanfare]"),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn play_audio_effect(
     mut commands: Commands,
     audio_effects: Res<AudioEffects>,
+    stingers: Option<Res<Stingers>>,
     mut audio_trigger: EventReader<AudioTrigger>,
     state: Res<State<GameState>>,
+    muted: Res<Muted>,
+    settings: Res<Settings>,
+    time: Res<Time<Real>>,
+    mut last_played: Local<HashMap<Discriminant<AudioTrigger>, f32>>,
 ) {
+    if muted.0 {
+        audio_trigger.clear();
+        return;
+    }
+    let now = time.elapsed_seconds();
     for trigger in audio_trigger.read() {
+        let last = last_played.entry(discriminant(trigger)).or_insert(f32::MIN);
+        if now - *last < trigger.min_gap() {
+            continue;
+        }
+        *last = now;
         let handle = match trigger {
-            AudioTrigger::Click => audio_effects.click.clone(),
+            AudioTrigger::Click
+            | AudioTrigger::Countdown
+            | AudioTrigger::Footstep(_)
+            | AudioTrigger::Hover => audio_effects.click.clone(),
             AudioTrigger::Home => audio_effects.home.clone(),
             AudioTrigger::Hurt => audio_effects.hurt.clone(),
-            AudioTrigger::Lost => audio_effects.lost.clone(),
-            AudioTrigger::Obstacle => audio_effects.obstacle.clone(),
+            AudioTrigger::Lost => match &stingers {
+                Some(stingers) => stingers.lost.clone(),
+                None => continue,
+            },
+            AudioTrigger::Obstacle | AudioTrigger::ObstacleRemoved | AudioTrigger::Swing => {
+                audio_effects.obstacle.clone()
+            }
             AudioTrigger::Spawn => audio_effects.spawn.clone(),
             AudioTrigger::Start => audio_effects.start.clone(),
             AudioTrigger::Treasure => audio_effects.treasure.clone(),
-            AudioTrigger::Win => audio_effects.win.clone(),
+            AudioTrigger::Win => match &stingers {
+                Some(stingers) => stingers.win.clone(),
+                None => continue,
+            },
+        };
+        let variation = trigger.pitch_variation();
+        let pitch = if variation > 0.0 {
+            trigger.pitch() * (1.0 + rand::thread_rng().gen_range(-variation..variation))
+        } else {
+            trigger.pitch()
         };
         commands.spawn(AudioBundle {
             source: handle,
             settings: PlaybackSettings {
                 mode: bevy::audio::PlaybackMode::Despawn,
-                volume: bevy::audio::Volume::new(match state.get() {
-                    GameState::Menu => 0.1,
-                    _ => 0.5,
-                }),
+                volume: bevy::audio::Volume::new(
+                    settings.effects_volume
+                        * trigger.volume()
+                        * match state.get() {
+                            GameState::Menu => 0.1,
+                            _ => 0.5,
+                        },
+                ),
+                speed: pitch,
                 ..default()
             },
         });
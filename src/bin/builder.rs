@@ -8,11 +8,13 @@ use bevy_easings::EasingsPlugin;
 use bevy_firework::plugin::ParticleSystemPlugin;
 
 use there_and_back_again::{
-    credits, game, level_selector,
+    accessibility, bindings, camera, captions, celebrations, chapters, colorblind, conditions,
+    credits, culling, dialog, effects, game, gamepad, hud, level_selector,
     levels::{self, Bonus, Level},
     loading, lost, menu,
     play::{self, GameInProgress},
-    win, GameProgress, GameState,
+    settings::Settings,
+    touch, ui, voices, win, xray, zones, GameProgress, GameState, DAYLIGHT,
 };
 
 fn main() {
@@ -50,12 +52,32 @@ fn main() {
         loading::Plugin,
         menu::Plugin,
         levels::Plugin,
+        chapters::Plugin,
+        conditions::Plugin,
+        effects::Plugin,
         credits::Plugin,
         game::Plugin,
         level_selector::Plugin,
         play::Plugin,
         win::Plugin,
         lost::Plugin,
+        voices::Plugin,
+    ))
+    .add_plugins((
+        zones::Plugin,
+        celebrations::Plugin,
+        culling::Plugin,
+        camera::Plugin,
+        hud::Plugin,
+        xray::Plugin,
+        gamepad::Plugin,
+        touch::Plugin,
+        bindings::Plugin,
+        dialog::Plugin,
+        ui::Plugin,
+        colorblind::Plugin,
+        accessibility::Plugin,
+        captions::Plugin,
         ReloadPlugin,
     ))
     .add_systems(Startup, camera);
@@ -67,20 +89,37 @@ fn main() {
             1
         },
     });
+    app.insert_resource(Settings {
+        hobbit_indicators: true,
+        hobbit_silhouettes: true,
+        celebrations: true,
+        camera_zoom: 1.0,
+        music_volume: 1.0,
+        effects_volume: 1.0,
+        colorblind: false,
+        reduced_motion: false,
+        ui_scale: 1.0,
+        font_scale: 1.0,
+        captions: false,
+        fullscreen: false,
+        resolution: 0,
+        fit_canvas: false,
+        vsync: true,
+        fps_cap: 0,
+    });
 
     #[cfg(feature = "debug")]
     app.add_plugins(PhysicsDebugPlugin::default());
 
     app.insert_resource(GameInProgress {
         level: 0,
-        score: 0,
-        lost_hobbits: 0,
         bonus: vec![
             Bonus::Obstacle,
             Bonus::Obstacle,
             Bonus::Obstacle,
             Bonus::Obstacle,
         ],
+        ..default()
     });
 
     app.run();
@@ -102,7 +141,7 @@ fn camera(mut commands: Commands) {
         transform: Transform::IDENTITY.looking_to(Vec3::new(1.0, -1.0, 1.0), Vec3::Y),
         directional_light: DirectionalLight {
             shadows_enabled: true,
-            illuminance: light_consts::lux::OVERCAST_DAY * 2.0,
+            illuminance: DAYLIGHT,
             ..default()
         },
         ..default()
@@ -112,13 +151,8 @@ fn camera(mut commands: Commands) {
 struct ReloadPlugin;
 impl bevy::app::Plugin for ReloadPlugin {
     fn build(&self, app: &mut App) {
+        // reload the level when its file changes
         app.add_systems(
-            OnEnter(GameState::Reload),
-            |mut next_state: ResMut<NextState<GameState>>| {
-                next_state.set(GameState::InGame);
-            },
-        )
-        .add_systems(
             Update,
             (|mut next_state: ResMut<NextState<GameState>>,
               asset_event: EventReader<AssetEvent<Level>>| {
@@ -0,0 +1,260 @@
+//! Keyboard shortcuts, kept in a resource mapping each action to its key so that players can
+//! change them from the settings.
+
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+
+/// Number of bonus buttons that have a shortcut
+pub const BONUS_SHORTCUTS: usize = 9;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    /// Select the bonus button at this index
+    SelectBonus(usize),
+    /// Unselect the selected bonus
+    CancelBonus,
+    Pause,
+    Restart,
+    Mute,
+    PanForward,
+    PanBack,
+    PanLeft,
+    PanRight,
+    RotateLeft,
+    RotateRight,
+    /// Show or hide the grid drawn while placing a bonus
+    ToggleGrid,
+}
+
+impl Action {
+    /// Every action that can be bound, in the order they are shown
+    pub fn all() -> Vec<Action> {
+        [
+            Action::PanForward,
+            Action::PanBack,
+            Action::PanLeft,
+            Action::PanRight,
+            Action::RotateLeft,
+            Action::RotateRight,
+            Action::Pause,
+            Action::Restart,
+            Action::Mute,
+            Action::CancelBonus,
+            Action::ToggleGrid,
+        ]
+        .into_iter()
+        .chain((0..BONUS_SHORTCUTS).map(Action::SelectBonus))
+        .collect()
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Action::SelectBonus(index) => format!("Bonus {}", index + 1),
+            Action::CancelBonus => "Cancel bonus".to_string(),
+            Action::Pause => "Pause".to_string(),
+            Action::Restart => "Restart".to_string(),
+            Action::Mute => "Mute".to_string(),
+            Action::PanForward => "Camera forward".to_string(),
+            Action::PanBack => "Camera back".to_string(),
+            Action::PanLeft => "Camera left".to_string(),
+            Action::PanRight => "Camera right".to_string(),
+            Action::RotateLeft => "Rotate left".to_string(),
+            Action::RotateRight => "Rotate right".to_string(),
+            Action::ToggleGrid => "Placement grid".to_string(),
+        }
+    }
+}
+
+/// Keys that can be bound to an action. Function keys are kept for debug tools.
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::Space,
+    KeyCode::Escape,
+    KeyCode::Tab,
+    KeyCode::Enter,
+    KeyCode::Backspace,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Semicolon,
+    KeyCode::Quote,
+    KeyCode::BracketLeft,
+    KeyCode::BracketRight,
+    KeyCode::Minus,
+    KeyCode::Equal,
+    KeyCode::Slash,
+    KeyCode::Backslash,
+    KeyCode::Backquote,
+];
+
+pub fn is_bindable(key: KeyCode) -> bool {
+    BINDABLE_KEYS.contains(&key)
+}
+
+/// Name of a key as shown to the player
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    name.strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+#[derive(Resource, Clone)]
+pub struct InputMap(pub Vec<(Action, KeyCode)>);
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let digits = [
+            KeyCode::Digit1,
+            KeyCode::Digit2,
+            KeyCode::Digit3,
+            KeyCode::Digit4,
+            KeyCode::Digit5,
+            KeyCode::Digit6,
+            KeyCode::Digit7,
+            KeyCode::Digit8,
+            KeyCode::Digit9,
+        ];
+        InputMap(
+            [
+                (Action::PanForward, KeyCode::KeyW),
+                (Action::PanBack, KeyCode::KeyS),
+                (Action::PanLeft, KeyCode::KeyA),
+                (Action::PanRight, KeyCode::KeyD),
+                (Action::RotateLeft, KeyCode::KeyQ),
+                (Action::RotateRight, KeyCode::KeyE),
+                (Action::Pause, KeyCode::KeyP),
+                (Action::Restart, KeyCode::KeyR),
+                (Action::Mute, KeyCode::KeyM),
+                (Action::CancelBonus, KeyCode::Escape),
+                (Action::ToggleGrid, KeyCode::KeyG),
+            ]
+            .into_iter()
+            .chain(
+                digits
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, key)| (Action::SelectBonus(index), key)),
+            )
+            .collect(),
+        )
+    }
+}
+
+impl InputMap {
+    /// Bindings changed by the player, over the default ones
+    pub fn load(store: &PkvStore) -> Self {
+        let mut map = InputMap::default();
+        let stored = store
+            .get::<Vec<(String, String)>>("input-map")
+            .unwrap_or_default();
+        for (action, key) in stored {
+            let action = Action::all()
+                .into_iter()
+                .find(|candidate| format!("{:?}", candidate) == action);
+            let key = BINDABLE_KEYS
+                .iter()
+                .copied()
+                .find(|candidate| format!("{:?}", candidate) == key);
+            if let (Some(action), Some(key)) = (action, key) {
+                map.bind(action, key);
+            }
+        }
+        map
+    }
+
+    pub fn save(&self, store: &mut PkvStore) {
+        let _ = store.set(
+            "input-map",
+            &self
+                .0
+                .iter()
+                .map(|(action, key)| (format!("{:?}", action), format!("{:?}", key)))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    pub fn key(&self, action: Action) -> Option<KeyCode> {
+        self.0
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .map(|(_, key)| *key)
+    }
+
+    pub fn pressed(&self, keyboard: &ButtonInput<KeyCode>, action: Action) -> bool {
+        self.key(action).is_some_and(|key| keyboard.pressed(key))
+    }
+
+    pub fn just_pressed(&self, keyboard: &ButtonInput<KeyCode>, action: Action) -> bool {
+        self.key(action)
+            .is_some_and(|key| keyboard.just_pressed(key))
+    }
+
+    /// Bind the key to the action. An action already using this key takes the previous key of
+    /// the action, so that every action keeps a key.
+    pub fn bind(&mut self, action: Action, key: KeyCode) {
+        let previous = self.key(action);
+        for (bound, bound_key) in self.0.iter_mut() {
+            if *bound == action {
+                *bound_key = key;
+            } else if *bound_key == key {
+                if let Some(previous) = previous {
+                    *bound_key = previous;
+                }
+            }
+        }
+        if previous.is_none() {
+            self.0.push((action, key));
+        }
+    }
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputMap>();
+    }
+}
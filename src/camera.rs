@@ -0,0 +1,226 @@
+//! Camera controls while playing: pan with WASD or by moving the cursor to the edge of the window,
+//! zoom with the mouse wheel and rotate around the level with Q and E. Keys can be changed in the
+//! controls screen.
+
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+use bevy_easings::EasingComponent;
+use bevy_pkv::PkvStore;
+
+use crate::{
+    bindings::{Action, InputMap},
+    game::ActiveLevel,
+    play::CameraIntro,
+    settings::Settings,
+    GameState,
+};
+
+/// Units per second when panning, at the default zoom
+const PAN_SPEED: f32 = 40.0;
+/// Pixels from the edge of the window where the cursor pans the camera
+const EDGE_SCROLL_MARGIN: f32 = 8.0;
+/// Radians per second when rotating
+const ROTATE_SPEED: f32 = 1.5;
+/// Zoom change for each line scrolled
+const ZOOM_STEP: f32 = 0.1;
+/// Closest zoom, as a share of the distance of the default view
+pub(crate) const MIN_ZOOM: f32 = 0.4;
+/// Furthest zoom, as a share of the distance of the default view
+pub(crate) const MAX_ZOOM: f32 = 1.5;
+/// How quickly the camera catches up with the controls
+const SMOOTHING: f32 = 8.0;
+
+/// View of the whole level used while playing, `level_size` being the size of the level in units
+/// as `(depth, width)`
+pub fn play_view(level_size: (usize, usize)) -> Transform {
+    let (depth, width) = (level_size.0 as f32, level_size.1 as f32);
+    let distance = (depth * 1.8).max(width);
+    Transform::from_translation(Vec3::new(width / 2.0, distance, depth * 1.2))
+        .looking_at(Vec3::new(width / 2.0, 0.0, depth / 4.0), Vec3::Y)
+}
+
+/// Where the camera looks at and from how far, changed by the player
+#[derive(Resource)]
+pub struct CameraRig {
+    pub focus: Vec3,
+    /// Offset from the focus of the camera in the default view
+    offset: Vec3,
+    /// Rotation around the focus, in radians
+    pub yaw: f32,
+    /// Share of the distance of the default view
+    pub zoom: f32,
+    /// Size of the level in units, the focus stays above it
+    bounds: Vec2,
+}
+
+impl CameraRig {
+    fn new(level_size: (usize, usize), zoom: f32) -> Self {
+        let view = play_view(level_size);
+        let (depth, width) = (level_size.0 as f32, level_size.1 as f32);
+        let focus = Vec3::new(width / 2.0, 0.0, depth / 4.0);
+        CameraRig {
+            focus,
+            offset: view.translation - focus,
+            yaw: 0.0,
+            zoom,
+            bounds: Vec2::new(width, depth),
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform::from_translation(
+            self.focus + Quat::from_rotation_y(self.yaw) * self.offset * self.zoom,
+        )
+        .looking_at(self.focus, Vec3::Y)
+    }
+
+    /// Move the focus by `direction` in the view of the camera, `x` to the right and `y` forward
+    pub fn pan(&mut self, direction: Vec2) {
+        let movement = Quat::from_rotation_y(self.yaw) * Vec3::new(direction.x, 0.0, -direction.y);
+        self.focus =
+            (self.focus + movement).clamp(Vec3::ZERO, Vec3::new(self.bounds.x, 0.0, self.bounds.y));
+    }
+
+    pub fn zoom_by(&mut self, change: f32) {
+        self.zoom = (self.zoom + change).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::InGame), remove_rig)
+            .add_systems(OnExit(GameState::InGame), save_zoom)
+            .add_systems(
+                Update,
+                (
+                    setup_rig.run_if(
+                        resource_exists::<ActiveLevel>.and_then(not(resource_exists::<CameraRig>)),
+                    ),
+                    (keyboard_controls, mouse_controls, follow_rig)
+                        .chain()
+                        .run_if(resource_exists::<CameraRig>),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+fn remove_rig(mut commands: Commands) {
+    commands.remove_resource::<CameraRig>();
+}
+
+fn setup_rig(mut commands: Commands, level: Res<ActiveLevel>, settings: Res<Settings>) {
+    let floor = &level.0.floors[0];
+    commands.insert_resource(CameraRig::new(
+        (floor.len() * 4, floor[0].len() * 4),
+        settings.camera_zoom,
+    ));
+}
+
+fn keyboard_controls(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    input_map: Res<InputMap>,
+    mut rig: ResMut<CameraRig>,
+    time: Res<Time<Real>>,
+) {
+    let mut direction = Vec2::ZERO;
+    for (action, towards) in [
+        (Action::PanForward, Vec2::Y),
+        (Action::PanBack, Vec2::NEG_Y),
+        (Action::PanLeft, Vec2::NEG_X),
+        (Action::PanRight, Vec2::X),
+    ] {
+        if input_map.pressed(&keyboard, action) {
+            direction += towards;
+        }
+    }
+    if direction != Vec2::ZERO {
+        let speed = PAN_SPEED * rig.zoom * time.delta_seconds();
+        rig.pan(direction.normalize() * speed);
+    }
+    if input_map.pressed(&keyboard, Action::RotateLeft) {
+        rig.yaw -= ROTATE_SPEED * time.delta_seconds();
+    }
+    if input_map.pressed(&keyboard, Action::RotateRight) {
+        rig.yaw += ROTATE_SPEED * time.delta_seconds();
+    }
+}
+
+fn mouse_controls(
+    mut wheel: EventReader<MouseWheel>,
+    windows: Query<&Window>,
+    mut rig: ResMut<CameraRig>,
+    time: Res<Time<Real>>,
+) {
+    for event in wheel.read() {
+        let lines = match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / 20.0,
+        };
+        rig.zoom_by(-lines * ZOOM_STEP);
+    }
+
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Some(cursor) = window.cursor_position().filter(|_| window.focused) else {
+        return;
+    };
+    let size = window.size();
+    let mut direction = Vec2::ZERO;
+    if cursor.x < EDGE_SCROLL_MARGIN {
+        direction.x -= 1.0;
+    } else if cursor.x > size.x - EDGE_SCROLL_MARGIN {
+        direction.x += 1.0;
+    }
+    // window coordinates go down
+    if cursor.y < EDGE_SCROLL_MARGIN {
+        direction.y += 1.0;
+    } else if cursor.y > size.y - EDGE_SCROLL_MARGIN {
+        direction.y -= 1.0;
+    }
+    if direction != Vec2::ZERO {
+        let speed = PAN_SPEED * rig.zoom * time.delta_seconds();
+        rig.pan(direction.normalize() * speed);
+    }
+}
+
+/// Move the camera towards the rig, unless it's playing the intro or another animation
+fn follow_rig(
+    rig: Res<CameraRig>,
+    mut cameras: Query<
+        &mut Transform,
+        (
+            With<Camera3d>,
+            Without<EasingComponent<Transform>>,
+            Without<CameraIntro>,
+        ),
+    >,
+    time: Res<Time<Real>>,
+) {
+    let Ok(mut transform) = cameras.get_single_mut() else {
+        return;
+    };
+    let target = rig.transform();
+    let blend = 1.0 - (-SMOOTHING * time.delta_seconds()).exp();
+    transform.translation = transform.translation.lerp(target.translation, blend);
+    transform.rotation = transform.rotation.slerp(target.rotation, blend);
+}
+
+fn save_zoom(
+    rig: Option<Res<CameraRig>>,
+    mut settings: ResMut<Settings>,
+    mut store: ResMut<PkvStore>,
+) {
+    let Some(rig) = rig else {
+        return;
+    };
+    if settings.camera_zoom != rig.zoom {
+        settings.camera_zoom = rig.zoom;
+        settings.save(&mut store);
+    }
+}
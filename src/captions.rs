@@ -0,0 +1,237 @@
+//! Captions of sound effects, for players who can't hear them. Enabled from the settings, they are
+//! shown in a strip at the bottom of the window, with an arrow toward sounds coming from outside of
+//! the view.
+
+use std::mem::{discriminant, Discriminant};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{audio::AudioTrigger, settings::Settings};
+
+/// Seconds a caption stays after its last sound
+const CAPTION_DURATION: f32 = 2.5;
+/// Captions shown at once, the oldest one goes away for a new one
+const MAX_CAPTIONS: usize = 4;
+
+/// Position in the level of a sound, sent along its [`AudioTrigger`] so that its caption can point
+/// toward it
+#[derive(Event)]
+pub struct SoundAt {
+    pub trigger: AudioTrigger,
+    pub position: Vec3,
+}
+
+/// Strip holding the captions
+#[derive(Component)]
+struct CaptionStrip;
+
+#[derive(Component)]
+struct Caption {
+    trigger: Discriminant<AudioTrigger>,
+    timer: Timer,
+    /// Sounds in a row, shown after the caption
+    count: u32,
+    position: Option<Vec3>,
+}
+
+#[derive(Component)]
+struct CaptionArrow;
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SoundAt>()
+            .add_systems(Startup, spawn_strip)
+            .add_systems(Update, (show_captions, point_arrows, expire_captions));
+    }
+}
+
+fn spawn_strip(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                bottom: Val::Percent(12.0),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            z_index: ZIndex::Global(5),
+            ..default()
+        },
+        CaptionStrip,
+    ));
+}
+
+fn caption_text(text: &str, count: u32) -> String {
+    if count > 1 {
+        format!("{} x{}", text, count)
+    } else {
+        text.to_string()
+    }
+}
+
+fn show_captions(
+    mut commands: Commands,
+    mut audio_trigger: EventReader<AudioTrigger>,
+    mut sounds_at: EventReader<SoundAt>,
+    settings: Res<Settings>,
+    strip: Query<Entity, With<CaptionStrip>>,
+    mut captions: Query<(Entity, &mut Caption, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    let positions = sounds_at
+        .read()
+        .map(|sound| (discriminant(&sound.trigger), sound.position))
+        .collect::<HashMap<_, _>>();
+    if !settings.captions {
+        audio_trigger.clear();
+        return;
+    }
+    let Ok(strip) = strip.get_single() else {
+        return;
+    };
+    // sounds of the same kind in a frame share a caption
+    let mut sounds: Vec<(Discriminant<AudioTrigger>, &str, u32)> = vec![];
+    for trigger in audio_trigger.read() {
+        let Some(text) = trigger.caption() else {
+            continue;
+        };
+        let kind = discriminant(trigger);
+        match sounds.iter_mut().find(|(other, _, _)| *other == kind) {
+            Some((_, _, count)) => *count += 1,
+            None => sounds.push((kind, text, 1)),
+        }
+    }
+
+    let mut shown = captions.iter().count();
+    for (kind, text, count) in sounds {
+        let position = positions.get(&kind).copied();
+        if let Some((_, mut caption, children)) = captions
+            .iter_mut()
+            .find(|(_, caption, _)| caption.trigger == kind)
+        {
+            caption.timer.reset();
+            caption.count += count;
+            caption.position = position;
+            if let Ok(mut label) = texts.get_mut(children[1]) {
+                label.sections[0].value = caption_text(text, caption.count);
+            }
+            continue;
+        }
+        if shown >= MAX_CAPTIONS {
+            if let Some((oldest, _, _)) = captions
+                .iter()
+                .max_by(|(_, a, _), (_, b, _)| a.timer.elapsed().cmp(&b.timer.elapsed()))
+            {
+                commands.entity(oldest).despawn_recursive();
+                shown -= 1;
+            }
+        }
+        shown += 1;
+        commands.entity(strip).with_children(|parent| {
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            padding: UiRect::axes(Val::Px(10.0), Val::Px(4.0)),
+                            column_gap: Val::Px(8.0),
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: Color::BLACK.with_alpha(0.7).into(),
+                        border_radius: BorderRadius::all(Val::Px(5.0)),
+                        ..default()
+                    },
+                    Caption {
+                        trigger: kind,
+                        timer: Timer::from_seconds(CAPTION_DURATION, TimerMode::Once),
+                        count,
+                        position,
+                    },
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle {
+                            text: Text::from_section(
+                                ">",
+                                TextStyle {
+                                    font_size: 20.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                },
+                            ),
+                            visibility: Visibility::Hidden,
+                            ..default()
+                        },
+                        CaptionArrow,
+                    ));
+                    parent.spawn(TextBundle::from_section(
+                        caption_text(text, count),
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
+        });
+    }
+}
+
+/// Show the arrow of captions of sounds outside of the view, turned toward them
+fn point_arrows(
+    captions: Query<(&Caption, &Children)>,
+    mut arrows: Query<(&mut Transform, &mut Visibility), With<CaptionArrow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+) {
+    let Ok((camera, camera_transform)) = camera.get_single() else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+    for (caption, children) in &captions {
+        let Ok((mut transform, mut visibility)) = arrows.get_mut(children[0]) else {
+            continue;
+        };
+        let Some(position) = caption.position else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        let on_screen = camera
+            .world_to_viewport(camera_transform, position)
+            .is_some_and(|point| {
+                point.x >= 0.0 && point.y >= 0.0 && point.x <= viewport.x && point.y <= viewport.y
+            });
+        if on_screen {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        }
+        // in the view of the camera, x goes right and y up, while the interface y goes down
+        let local = camera_transform
+            .affine()
+            .inverse()
+            .transform_point3(position);
+        let direction = Vec2::new(local.x, -local.y);
+        if direction == Vec2::ZERO {
+            continue;
+        }
+        transform.rotation = Quat::from_rotation_z(direction.y.atan2(direction.x));
+        visibility.set_if_neq(Visibility::Inherited);
+    }
+}
+
+fn expire_captions(
+    mut commands: Commands,
+    mut captions: Query<(Entity, &mut Caption)>,
+    time: Res<Time<Real>>,
+) {
+    for (entity, mut caption) in &mut captions {
+        if caption.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
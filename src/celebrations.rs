@@ -0,0 +1,179 @@
+//! Small celebrations when a team brings back a round number of treasures: hobbits near the chest
+//! cheer, confetti bursts out of the chest, and a banner shows the milestone.
+
+use std::time::Duration;
+
+use bevy::{color::palettes, prelude::*};
+use bevy_easings::{Ease, EaseFunction, EasingType};
+
+use crate::{
+    effects::{Effect, PlayEffect},
+    game::{ActiveLevel, Hobbit},
+    levels::AnimatedKind,
+    settings::Settings,
+    GameState,
+};
+
+/// Treasures to bring back between two celebrations
+pub const MILESTONE_EVERY: u32 = 5;
+/// Distance from a chest of hobbits cheering
+const CHEER_RADIUS: f32 = 8.0;
+const CHEER_DURATION: f32 = 1.2;
+const CHEER_HEIGHT: f32 = 0.6;
+/// Hops during a cheer
+const CHEER_HOPS: f32 = 3.0;
+const BANNER_DURATION: f32 = 2.0;
+
+/// Sent by the scoring when a team reaches a milestone
+#[derive(Event)]
+pub struct Milestone {
+    pub team: usize,
+    pub treasures: u32,
+}
+
+/// A hobbit hopping in joy
+#[derive(Component)]
+struct Cheering(Timer);
+
+#[derive(Component)]
+struct Banner(Timer);
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Milestone>().add_systems(
+            Update,
+            (
+                celebrate.run_if(
+                    resource_exists::<ActiveLevel>
+                        .and_then(|settings: Res<Settings>| settings.celebrations),
+                ),
+                cheer,
+                remove_banners,
+            )
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+fn celebrate(
+    mut commands: Commands,
+    mut milestones: EventReader<Milestone>,
+    level: Res<ActiveLevel>,
+    hobbits: Query<(Entity, &Transform), With<Hobbit>>,
+    mut effects: EventWriter<PlayEffect>,
+) {
+    for milestone in milestones.read() {
+        for chest in level
+            .0
+            .chests
+            .iter()
+            .filter(|chest| chest.team == milestone.team)
+        {
+            let position = Vec3::new(chest.cell.1 as f32 * 4.0, 1.0, chest.cell.2 as f32 * 4.0);
+            effects.send(PlayEffect {
+                effect: Effect::Confetti,
+                transform: Transform::from_translation(position),
+                follow: None,
+            });
+            for (entity, transform) in &hobbits {
+                if transform.translation.xz().distance(position.xz()) < CHEER_RADIUS {
+                    commands.entity(entity).insert(Cheering(Timer::from_seconds(
+                        CHEER_DURATION,
+                        TimerMode::Once,
+                    )));
+                }
+            }
+        }
+        spawn_banner(&mut commands, milestone);
+    }
+}
+
+fn spawn_banner(commands: &mut Commands, milestone: &Milestone) {
+    let style = Style {
+        position_type: PositionType::Absolute,
+        width: Val::Percent(100.0),
+        top: Val::Percent(-10.0),
+        justify_content: JustifyContent::Center,
+        ..default()
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                z_index: ZIndex::Global(2),
+                style: style.clone(),
+                ..default()
+            },
+            style.clone().ease_to(
+                Style {
+                    top: Val::Percent(12.0),
+                    ..style
+                },
+                EaseFunction::BounceOut,
+                EasingType::Once {
+                    duration: Duration::from_secs_f32(0.6),
+                },
+            ),
+            Banner(Timer::from_seconds(BANNER_DURATION, TimerMode::Once)),
+            StateScoped(GameState::InGame),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    background_color: palettes::tailwind::AMBER_400.into(),
+                    border_radius: BorderRadius::all(Val::Px(10.0)),
+                    style: Style {
+                        padding: UiRect::axes(Val::Px(20.0), Val::Px(8.0)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        format!("{} treasures home!", milestone.treasures),
+                        TextStyle {
+                            font_size: 30.0,
+                            color: palettes::tailwind::AMBER_900.into(),
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
+
+fn cheer(
+    mut commands: Commands,
+    mut hobbits: Query<(Entity, &mut Cheering, &Children)>,
+    mut models: Query<&mut Transform, With<AnimatedKind>>,
+    time: Res<Time>,
+) {
+    for (entity, mut cheering, children) in &mut hobbits {
+        let finished = cheering.0.tick(time.delta()).finished();
+        let height = if finished {
+            commands.entity(entity).remove::<Cheering>();
+            0.0
+        } else {
+            (cheering.0.fraction() * CHEER_HOPS * std::f32::consts::PI)
+                .sin()
+                .abs()
+                * CHEER_HEIGHT
+        };
+        for child in children {
+            if let Ok(mut model) = models.get_mut(*child) {
+                model.translation.y = -1.0 + height;
+            }
+        }
+    }
+}
+
+fn remove_banners(
+    mut commands: Commands,
+    mut banners: Query<(Entity, &mut Banner)>,
+    time: Res<Time>,
+) {
+    for (entity, mut banner) in &mut banners {
+        if banner.0.tick(time.delta()).just_finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::ops::RangeInclusive;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    reflect::TypePath,
+};
+use thiserror::Error;
+
+use crate::GameProgress;
+
+/// Levels grouped under a name in the level selector
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    pub name: String,
+    pub levels: RangeInclusive<usize>,
+    /// Levels of the previous chapter to win to unlock this one early. Otherwise it unlocks once
+    /// the previous chapter is done.
+    pub required_wins: Option<usize>,
+}
+
+impl Chapter {
+    pub fn is_unlocked(&self, progress: &GameProgress, won_in_previous: usize) -> bool {
+        *self.levels.start() <= progress.current_level
+            || self
+                .required_wins
+                .is_some_and(|required| won_in_previous >= required)
+    }
+
+    /// The level can be played: it has been reached, or it starts an unlocked chapter
+    pub fn is_playable(
+        &self,
+        level: usize,
+        progress: &GameProgress,
+        won_in_previous: usize,
+    ) -> bool {
+        level <= progress.current_level
+            || (level == *self.levels.start() && self.is_unlocked(progress, won_in_previous))
+    }
+}
+
+/// Chapters of the campaign, one per line as `name:first-last` or `name:first-last:wins`
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct Chapters(pub Vec<Chapter>);
+
+#[derive(Default)]
+struct ChaptersAssetLoader;
+
+/// Possible errors that can be produced by [`ChaptersAssetLoader`]
+#[non_exhaustive]
+#[derive(Debug, Error)]
+enum ChaptersAssetLoaderError {
+    /// An [IO](std::io) Error
+    #[error("Could not load file: {0}")]
+    Io(#[from] std::io::Error),
+    /// A line is not a chapter
+    #[error("Invalid chapter: {0}")]
+    Invalid(String),
+}
+
+impl AssetLoader for ChaptersAssetLoader {
+    type Asset = Chapters;
+    type Settings = ();
+    type Error = ChaptersAssetLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut content = String::new();
+        reader.read_to_string(&mut content).await?;
+
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                parse_chapter(line)
+                    .ok_or_else(|| ChaptersAssetLoaderError::Invalid(line.to_string()))
+            })
+            .collect::<Result<_, _>>()
+            .map(Chapters)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["chapters"]
+    }
+}
+
+/// Parse a chapter from a line `name:first-last` or `name:first-last:wins`
+fn parse_chapter(line: &str) -> Option<Chapter> {
+    let mut parts = line.split(':');
+    let name = parts.next()?.to_string();
+    let (first, last) = parts.next()?.split_once('-')?;
+    let required_wins = match parts.next() {
+        Some(wins) => Some(wins.trim().parse().ok()?),
+        None => None,
+    };
+    Some(Chapter {
+        name,
+        levels: first.trim().parse().ok()?..=last.trim().parse().ok()?,
+        required_wins,
+    })
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Chapters>()
+            .init_asset_loader::<ChaptersAssetLoader>();
+    }
+}
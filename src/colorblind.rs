@@ -0,0 +1,100 @@
+//! Colorblind mode: the green entry and red exit grates are hard to tell apart with the most common
+//! color blindness. When enabled from the settings, grates glow blue and orange instead, and get a
+//! glyph drawn over them so that they don't rely on color alone.
+
+use bevy::{color::palettes, prelude::*};
+
+use crate::{assets::GameAssets, game::ActiveLevel, levels::Tile, settings::Settings, GameState};
+
+/// Height of the glyphs, over the grates
+const GLYPH_HEIGHT: f32 = 0.3;
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                recolor_grates.run_if(
+                    resource_exists::<GameAssets>.and_then(
+                        resource_changed::<Settings>.or_else(resource_added::<GameAssets>),
+                    ),
+                ),
+                draw_grate_glyphs.run_if(
+                    in_state(GameState::InGame)
+                        .and_then(resource_exists::<ActiveLevel>)
+                        .and_then(|settings: Res<Settings>| settings.colorblind),
+                ),
+            ),
+        );
+    }
+}
+
+/// Base and emissive colors of the entry and exit grates
+fn grate_colors(colorblind: bool) -> [(Srgba, Srgba); 2] {
+    if colorblind {
+        [
+            (
+                palettes::tailwind::SKY_300,
+                palettes::tailwind::SKY_600 * 6.0,
+            ),
+            (
+                palettes::tailwind::ORANGE_500,
+                palettes::tailwind::ORANGE_800 * 6.0,
+            ),
+        ]
+    } else {
+        [
+            (
+                palettes::tailwind::GREEN_500,
+                palettes::tailwind::GREEN_700 * 6.0,
+            ),
+            (
+                palettes::tailwind::RED_500,
+                palettes::tailwind::RED_900 * 6.0,
+            ),
+        ]
+    }
+}
+
+fn recolor_grates(
+    settings: Res<Settings>,
+    assets: Res<GameAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let [entry, exit] = grate_colors(settings.colorblind);
+    for (handle, (base, emissive)) in [(&assets.in_material, entry), (&assets.out_material, exit)] {
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color = base.into();
+            material.emissive = emissive.into();
+        }
+    }
+}
+
+/// A ring over the entries and a cross over the exits
+fn draw_grate_glyphs(level: Res<ActiveLevel>, mut gizmos: Gizmos) {
+    let [(entry, _), (exit, _)] = grate_colors(true);
+    for (yi, row) in level.0.floors[0].iter().enumerate() {
+        for (xi, tile) in row.iter().enumerate() {
+            let center = vec3(xi as f32 * 4.0, GLYPH_HEIGHT, yi as f32 * 4.0);
+            match tile {
+                Tile::In => {
+                    gizmos.circle(center, Dir3::Y, 1.2, entry);
+                }
+                Tile::Out => {
+                    gizmos.line(
+                        center + vec3(-1.0, 0.0, -1.0),
+                        center + vec3(1.0, 0.0, 1.0),
+                        exit,
+                    );
+                    gizmos.line(
+                        center + vec3(-1.0, 0.0, 1.0),
+                        center + vec3(1.0, 0.0, -1.0),
+                        exit,
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Win and lose conditions of a level, one evaluator per objective.
+
+use bevy::prelude::*;
+
+use crate::{
+    assets::GameAssets,
+    difficulty::DifficultyMode,
+    levels::{Level, Objective},
+    mutators::Mutators,
+    new_game_plus::NewGamePlus,
+    play::{played_level, GameInProgress},
+    GameState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectiveStatus {
+    Pending,
+    Met,
+    Failed,
+}
+
+/// Decides whether an objective is met or failed from the progress of the game
+pub trait Condition: Send + Sync + 'static {
+    fn status(&self, game: &GameInProgress, level: &Level) -> ObjectiveStatus;
+}
+
+/// Every team brings this many treasures back home
+pub struct Treasures(pub u32);
+
+impl Condition for Treasures {
+    fn status(&self, game: &GameInProgress, level: &Level) -> ObjectiveStatus {
+        if (0..level.teams.len()).all(|team| game.score(team) >= self.0) {
+            ObjectiveStatus::Met
+        } else {
+            ObjectiveStatus::Pending
+        }
+    }
+}
+
+/// Fails once this many hobbits are lost
+pub struct MaxLosses(pub u32);
+
+impl Condition for MaxLosses {
+    fn status(&self, game: &GameInProgress, _level: &Level) -> ObjectiveStatus {
+        if game.lost_hobbits >= self.0 {
+            ObjectiveStatus::Failed
+        } else {
+            ObjectiveStatus::Met
+        }
+    }
+}
+
+/// Keep the hobbits going for this many seconds
+pub struct Survive(pub f32);
+
+impl Condition for Survive {
+    fn status(&self, game: &GameInProgress, _level: &Level) -> ObjectiveStatus {
+        if game.elapsed >= self.0 {
+            ObjectiveStatus::Met
+        } else {
+            ObjectiveStatus::Pending
+        }
+    }
+}
+
+/// Fails if the other objectives are not met after this many seconds
+pub struct TimeLimit(pub f32);
+
+impl Condition for TimeLimit {
+    fn status(&self, game: &GameInProgress, _level: &Level) -> ObjectiveStatus {
+        if game.elapsed >= self.0 {
+            ObjectiveStatus::Failed
+        } else {
+            ObjectiveStatus::Met
+        }
+    }
+}
+
+/// The hobbit to escort must make it back home
+pub struct Escort;
+
+impl Condition for Escort {
+    fn status(&self, game: &GameInProgress, _level: &Level) -> ObjectiveStatus {
+        match game.escorted {
+            Some(true) => ObjectiveStatus::Met,
+            Some(false) => ObjectiveStatus::Failed,
+            None => ObjectiveStatus::Pending,
+        }
+    }
+}
+
+pub fn evaluator(objective: &Objective) -> Box<dyn Condition> {
+    match objective {
+        Objective::Treasures(treasures) => Box::new(Treasures(*treasures)),
+        Objective::MaxLosses(losts) => Box::new(MaxLosses(*losts)),
+        Objective::Survive(seconds) => Box::new(Survive(*seconds)),
+        Objective::TimeLimit(seconds) => Box::new(TimeLimit(*seconds)),
+        Objective::Escort(_) => Box::new(Escort),
+    }
+}
+
+/// Conditions of the level being played, built from its objectives
+#[derive(Resource, Default)]
+pub struct Conditions(pub Vec<Box<dyn Condition>>);
+
+impl Conditions {
+    pub fn from_objectives(objectives: &[Objective]) -> Self {
+        Conditions(objectives.iter().map(evaluator).collect())
+    }
+
+    /// Every condition is met
+    pub fn is_won(&self, game: &GameInProgress, level: &Level) -> bool {
+        self.0
+            .iter()
+            .all(|condition| condition.status(game, level) == ObjectiveStatus::Met)
+    }
+
+    /// A condition failed
+    pub fn is_lost(&self, game: &GameInProgress, level: &Level) -> bool {
+        self.0
+            .iter()
+            .any(|condition| condition.status(game, level) == ObjectiveStatus::Failed)
+    }
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Conditions>()
+            .add_systems(OnEnter(GameState::InGame), build_conditions);
+    }
+}
+
+fn build_conditions(
+    mut conditions: ResMut<Conditions>,
+    game: Res<GameInProgress>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+    difficulty: Res<DifficultyMode>,
+    new_game_plus: Res<NewGamePlus>,
+    mutators: Res<Mutators>,
+) {
+    let level = levels.get(&assets.levels[game.level]).unwrap();
+    let level = played_level(
+        level,
+        game.level,
+        &assets,
+        *difficulty,
+        &new_game_plus,
+        &mutators,
+    );
+    *conditions = Conditions::from_objectives(&level.objectives);
+}
@@ -0,0 +1,235 @@
+//! Controls screen, to change the key bound to each action. Clicking an action waits for the next
+//! key pressed.
+
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+
+use crate::{
+    audio::AudioTrigger,
+    bindings::{is_bindable, key_name, Action, InputMap},
+    menu::SwitchState,
+    ui, GameState,
+};
+
+const CURRENT_STATE: GameState = GameState::Controls;
+
+pub struct Plugin;
+impl bevy::prelude::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(CURRENT_STATE), spawn_controls)
+            .add_systems(
+                Update,
+                (
+                    button_system,
+                    capture_key,
+                    crate::menu::change_state_after_event,
+                )
+                    .run_if(in_state(CURRENT_STATE)),
+            );
+    }
+}
+
+fn label(button: &ControlsButton, input_map: &InputMap) -> String {
+    match button {
+        ControlsButton::Bind(action) => format!(
+            "{}: {}",
+            action.label(),
+            input_map
+                .key(*action)
+                .map(key_name)
+                .unwrap_or_else(|| "-".to_string())
+        ),
+        ControlsButton::Reset => "Reset to defaults".to_string(),
+        ControlsButton::Back => "Back to Settings".to_string(),
+    }
+}
+
+fn spawn_controls(mut commands: Commands, input_map: Res<InputMap>) {
+    info!("Loading screen");
+
+    commands
+        .spawn((
+            ui::screen_root(15.0),
+            MenuItem::Root,
+            StateScoped(CURRENT_STATE),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((ui::panel(70.0, 80.0), MenuItem::Panel))
+                .with_children(|parent| {
+                    parent.spawn(ui::title("Controls"));
+
+                    let rows = [
+                        Action::all()
+                            .into_iter()
+                            .map(ControlsButton::Bind)
+                            .collect::<Vec<_>>(),
+                        vec![ControlsButton::Reset, ControlsButton::Back],
+                    ];
+                    for buttons in rows {
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    flex_wrap: FlexWrap::Wrap,
+                                    justify_content: JustifyContent::Center,
+                                    width: Val::Percent(95.0),
+                                    margin: UiRect::top(Val::Percent(2.0)),
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                for button in buttons {
+                                    spawn_button(parent, button, &input_map);
+                                }
+                            });
+                    }
+                });
+        });
+}
+
+fn spawn_button(parent: &mut ChildBuilder, button: ControlsButton, input_map: &InputMap) {
+    parent
+        .spawn((
+            ButtonBundle {
+                border_radius: BorderRadius::all(Val::Percent(10.0)),
+                style: Style {
+                    width: Val::Px(220.0),
+                    height: Val::Px(32.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    margin: UiRect::all(Val::Px(5.0)),
+                    ..default()
+                },
+                ..default()
+            },
+            MenuItem::Button,
+            ui::Highlight,
+            button,
+        ))
+        .with_children(|p| {
+            p.spawn(TextBundle {
+                text: Text::from_section(
+                    label(&button, input_map),
+                    TextStyle {
+                        font_size: 18.0,
+                        ..default()
+                    },
+                ),
+                ..default()
+            });
+        });
+}
+
+#[derive(Component, PartialEq, Eq)]
+enum MenuItem {
+    Root,
+    Panel,
+    Button,
+}
+
+#[derive(Component, Clone, Copy)]
+enum ControlsButton {
+    Bind(Action),
+    Reset,
+    Back,
+}
+
+/// The button is waiting for a key to bind to its action
+#[derive(Component)]
+struct AwaitingKey;
+
+fn refresh_labels(
+    buttons: &Query<(&ControlsButton, &Children)>,
+    texts: &mut Query<&mut Text>,
+    input_map: &InputMap,
+) {
+    for (button, children) in buttons {
+        if let Ok(mut text) = texts.get_mut(children[0]) {
+            text.sections[0].value = label(button, input_map);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn button_system(
+    mut commands: Commands,
+    interaction_query: Query<
+        (&Interaction, &ControlsButton, &Children, Entity),
+        Changed<Interaction>,
+    >,
+    mut next_state: EventWriter<SwitchState>,
+    ui_items: Query<(Entity, &MenuItem)>,
+    awaiting: Query<Entity, With<AwaitingKey>>,
+    all_buttons: Query<(&ControlsButton, &Children)>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+    mut input_map: ResMut<InputMap>,
+    mut store: ResMut<PkvStore>,
+    mut texts: Query<&mut Text>,
+) {
+    for (interaction, button, children, entity) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        audio_trigger.send(AudioTrigger::Click);
+
+        // a click anywhere stops waiting for a key
+        for waiting in &awaiting {
+            commands.entity(waiting).remove::<AwaitingKey>();
+        }
+        refresh_labels(&all_buttons, &mut texts, &input_map);
+
+        match button {
+            ControlsButton::Bind(action) => {
+                commands.entity(entity).insert(AwaitingKey);
+                if let Ok(mut text) = texts.get_mut(children[0]) {
+                    text.sections[0].value = format!("{}: press a key...", action.label());
+                }
+            }
+            ControlsButton::Reset => {
+                *input_map = InputMap::default();
+                input_map.save(&mut store);
+                refresh_labels(&all_buttons, &mut texts, &input_map);
+            }
+            ControlsButton::Back => {
+                next_state.send(SwitchState(GameState::Settings));
+
+                for (entity, kind) in &ui_items {
+                    if *kind == MenuItem::Root {
+                        ui::slide_out(&mut commands, entity, 15.0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Bind the next key pressed to the action of the button waiting for it
+#[allow(clippy::too_many_arguments)]
+fn capture_key(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    awaiting: Query<(Entity, &ControlsButton), With<AwaitingKey>>,
+    all_buttons: Query<(&ControlsButton, &Children)>,
+    mut input_map: ResMut<InputMap>,
+    mut store: ResMut<PkvStore>,
+    mut texts: Query<&mut Text>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+) {
+    let Ok((entity, ControlsButton::Bind(action))) = awaiting.get_single() else {
+        return;
+    };
+    let Some(key) = keyboard
+        .get_just_pressed()
+        .copied()
+        .find(|key| is_bindable(*key))
+    else {
+        return;
+    };
+    audio_trigger.send(AudioTrigger::Click);
+    commands.entity(entity).remove::<AwaitingKey>();
+    input_map.bind(*action, key);
+    input_map.save(&mut store);
+    // the action that had this key may have changed too
+    refresh_labels(&all_buttons, &mut texts, &input_map);
+}
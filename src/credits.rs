@@ -1,9 +1,6 @@
-use std::time::Duration;
+use bevy::prelude::*;
 
-use bevy::{color::palettes, prelude::*};
-use bevy_easings::{Ease, EaseFunction, EasingType};
-
-use crate::{audio::AudioTrigger, menu::SwitchState, GameState};
+use crate::{audio::AudioTrigger, menu::SwitchState, ui, GameState};
 
 const CURRENT_STATE: GameState = GameState::Credits;
 
@@ -24,77 +21,15 @@ fn spawn_credits(mut commands: Commands) {
 
     commands
         .spawn((
-            NodeBundle {
-                style: Style {
-                    width: Val::Percent(100.0),
-                    height: Val::Percent(100.0),
-                    left: Val::Percent(-100.0),
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::Start,
-                    ..default()
-                },
-                ..default()
-            },
-            Style {
-                width: Val::Percent(100.0),
-                height: Val::Percent(100.0),
-                left: Val::Percent(-100.0),
-                align_items: AlignItems::Center,
-                justify_content: JustifyContent::Start,
-                ..default()
-            }
-            .ease_to(
-                Style {
-                    width: Val::Percent(100.0),
-                    height: Val::Percent(100.0),
-                    left: Val::Percent(30.0),
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::Start,
-                    ..default()
-                },
-                EaseFunction::QuadraticOut,
-                EasingType::Once {
-                    duration: Duration::from_secs_f32(1.0),
-                },
-            ),
+            ui::screen_root(30.0),
             MenuItem::Root,
             StateScoped(CURRENT_STATE),
         ))
         .with_children(|parent| {
             parent
-                .spawn((
-                    NodeBundle {
-                        background_color: palettes::tailwind::GREEN_400.into(),
-                        border_radius: BorderRadius::all(Val::Percent(5.0)),
-                        z_index: ZIndex::Global(1),
-                        style: Style {
-                            flex_direction: FlexDirection::Column,
-                            justify_content: JustifyContent::Center,
-                            align_items: AlignItems::Center,
-                            width: Val::Percent(40.0),
-                            height: Val::Percent(60.0),
-                            ..default()
-                        },
-                        ..default()
-                    },
-                    MenuItem::Panel,
-                ))
+                .spawn((ui::panel(40.0, 60.0), MenuItem::Panel))
                 .with_children(|parent| {
-                    parent.spawn(TextBundle {
-                        text: Text::from_section(
-                            "Credits",
-                            TextStyle {
-                                font_size: 60.0,
-                                color: Color::WHITE,
-                                ..default()
-                            },
-                        ),
-                        style: Style {
-                            margin: UiRect::bottom(Val::Percent(5.0)),
-                            ..default()
-                        },
-                        ..default()
-                    });
+                    parent.spawn(ui::title("Credits"));
                     parent.spawn(TextBundle {
                         text: Text::from_section(
                             "made with Bevy Engine",
@@ -173,86 +108,17 @@ fn spawn_credits(mut commands: Commands) {
                         ..default()
                     });
 
-                    let button_height = 40.0;
-                    let style_easing = Style {
-                        width: Val::Px(200.0),
-                        height: Val::Px(button_height),
-                        border: UiRect::all(Val::Px(3.0)),
-                        align_items: AlignItems::Center,
-                        justify_content: JustifyContent::Center,
-                        margin: UiRect::top(Val::Percent(10.0)),
-                        justify_self: JustifySelf::End,
-                        ..default()
-                    };
-
-                    let style_easing = style_easing.ease_to(
-                        Style {
-                            width: Val::Px(200.0),
-                            height: Val::Px(button_height),
-                            border: UiRect::all(Val::Px(5.0)),
-                            margin: UiRect::top(Val::Percent(10.0)),
-                            justify_self: JustifySelf::End,
-
-                            ..default()
-                        },
-                        EaseFunction::QuadraticInOut,
-                        EasingType::PingPong {
-                            duration: Duration::from_secs_f32(1.0),
-                            pause: Some(Duration::from_secs_f32(0.5)),
-                        },
-                    );
-                    parent
-                        .spawn((
-                            ButtonBundle {
-                                background_color: palettes::tailwind::INDIGO_800.into(),
-                                border_radius: BorderRadius::all(Val::Percent(10.0)),
-                                border_color: BorderColor(palettes::tailwind::INDIGO_400.into()),
-                                style: Style {
-                                    width: Val::Px(200.0),
-                                    height: Val::Px(button_height),
-                                    border: UiRect::all(Val::Px(0.0)),
-                                    align_items: AlignItems::Center,
-                                    justify_content: JustifyContent::Center,
-                                    margin: UiRect::top(Val::Percent(10.0)),
-                                    justify_self: JustifySelf::End,
-                                    ..default()
-                                },
-                                ..default()
-                            },
-                            style_easing,
-                            MenuItem::Button,
-                        ))
-                        .with_children(|p| {
-                            p.spawn(TextBundle {
-                                text: Text::from_section(
-                                    "Back to Menu",
-                                    TextStyle {
-                                        font_size: 20.0,
-                                        ..default()
-                                    },
-                                ),
-                                ..default()
-                            });
-                        });
+                    ui::spawn_button(
+                        parent,
+                        "Back to Menu",
+                        Vec2::new(200.0, 40.0),
+                        UiRect::top(Val::Percent(10.0)),
+                    )
+                    .insert(MenuItem::Button);
                 });
         });
 }
 
-#[derive(Component, Default, Clone)]
-struct ImageColor {
-    color: Srgba,
-}
-
-impl bevy_easings::Lerp for ImageColor {
-    type Scalar = f32;
-
-    fn lerp(&self, other: &Self, scalar: &Self::Scalar) -> Self {
-        ImageColor {
-            color: self.color.mix(&other.color, *scalar),
-        }
-    }
-}
-
 #[derive(Component, PartialEq, Eq)]
 enum MenuItem {
     Root,
@@ -262,80 +128,23 @@ enum MenuItem {
 
 fn button_system(
     mut commands: Commands,
-    interaction_query: Query<(Ref<Interaction>, &BackgroundColor, Entity), Changed<Interaction>>,
+    interaction_query: Query<&Interaction, Changed<Interaction>>,
     mut next_state: EventWriter<SwitchState>,
     ui_items: Query<(Entity, &MenuItem)>,
     mut audio_trigger: EventWriter<AudioTrigger>,
 ) {
-    for (interaction, color, entity) in &interaction_query {
-        if interaction.is_added() {
+    for interaction in &interaction_query {
+        if *interaction != Interaction::Pressed {
             continue;
         }
-        match *interaction {
-            Interaction::Pressed => {
-                audio_trigger.send(AudioTrigger::Click);
+        audio_trigger.send(AudioTrigger::Click);
 
-                next_state.send(SwitchState(GameState::Menu));
-
-                for (entity, kind) in &ui_items {
-                    if *kind == MenuItem::Root {
-                        commands.entity(entity).insert(
-                            Style {
-                                width: Val::Percent(100.0),
-                                height: Val::Percent(100.0),
-                                left: Val::Percent(30.0),
-                                align_items: AlignItems::Center,
-                                justify_content: JustifyContent::Start,
-                                ..default()
-                            }
-                            .ease_to(
-                                Style {
-                                    width: Val::Percent(100.0),
-                                    height: Val::Percent(100.0),
-                                    left: Val::Percent(-100.0),
-                                    align_items: AlignItems::Center,
-                                    justify_content: JustifyContent::Start,
-                                    ..default()
-                                },
-                                EaseFunction::QuadraticOut,
-                                EasingType::Once {
-                                    duration: Duration::from_secs_f32(1.0),
-                                },
-                            ),
-                        );
-                    }
-                }
+        next_state.send(SwitchState(GameState::Menu));
 
-                commands.entity(entity).insert(color.ease_to(
-                    BUTTON_HOVERED,
-                    EaseFunction::QuadraticInOut,
-                    EasingType::Once {
-                        duration: Duration::from_secs_f32(0.25),
-                    },
-                ));
-            }
-            Interaction::Hovered => {
-                commands.entity(entity).insert(color.ease_to(
-                    BUTTON_HOVERED,
-                    EaseFunction::QuadraticInOut,
-                    EasingType::Once {
-                        duration: Duration::from_secs_f32(0.25),
-                    },
-                ));
-            }
-            Interaction::None => {
-                commands.entity(entity).insert(color.ease_to(
-                    BUTTON_IDLE,
-                    EaseFunction::QuadraticInOut,
-                    EasingType::Once {
-                        duration: Duration::from_secs_f32(0.25),
-                    },
-                ));
+        for (entity, kind) in &ui_items {
+            if *kind == MenuItem::Root {
+                ui::slide_out(&mut commands, entity, 30.0);
             }
         }
     }
 }
-
-const BUTTON_IDLE: BackgroundColor = BackgroundColor(Color::Srgba(palettes::tailwind::INDIGO_800));
-const BUTTON_HOVERED: BackgroundColor =
-    BackgroundColor(Color::Srgba(palettes::tailwind::AMBER_600));
@@ -0,0 +1,121 @@
+//! Particles and shadows far from the camera or out of view cost as much as the ones on screen.
+//! Particle spawners emit less the further they are, and barely out of view, and point lights stop
+//! casting shadows far away or out of view.
+
+use std::time::Duration;
+
+use bevy::{
+    math::Vec3A,
+    prelude::*,
+    render::primitives::{Frustum, Sphere},
+    time::common_conditions::on_timer,
+};
+use bevy_firework::core::ParticleSpawnerSettings;
+
+/// Spawners closer than this emit at their full rate
+const PARTICLES_NEAR: f32 = 250.0;
+/// Spawners further than this emit at [`PARTICLES_MIN_RATE`]
+const PARTICLES_FAR: f32 = 1000.0;
+/// Share of their rate far or out of view spawners keep, so that they don't pop when the camera
+/// comes closer
+const PARTICLES_MIN_RATE: f32 = 0.02;
+/// Size of the area around a spawner where its particles can be seen
+const PARTICLES_RADIUS: f32 = 5.0;
+/// Point lights further than this don't cast shadows
+const SHADOWS_DISTANCE: f32 = 500.0;
+/// Seconds between two updates, culling doesn't need to follow the camera every frame
+const CULLING_INTERVAL: f32 = 0.2;
+
+/// Settings the spawner or light was created with, before culling changed them
+#[derive(Component)]
+struct Unculled {
+    rate: f32,
+}
+
+#[derive(Component)]
+struct UnculledShadows;
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            (
+                remember_spawners,
+                remember_lights,
+                cull.run_if(on_timer(Duration::from_secs_f32(CULLING_INTERVAL))),
+            )
+                .chain()
+                .after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Spawners are inserted again every time a pooled effect plays, remember their rate each time
+fn remember_spawners(
+    mut commands: Commands,
+    spawners: Query<(Entity, &ParticleSpawnerSettings), Added<ParticleSpawnerSettings>>,
+) {
+    for (entity, settings) in &spawners {
+        // one shot effects are short and are played where something happens
+        if !settings.one_shot {
+            commands.entity(entity).insert(Unculled {
+                rate: settings.rate,
+            });
+        }
+    }
+}
+
+fn remember_lights(
+    mut commands: Commands,
+    lights: Query<(Entity, &PointLight), Added<PointLight>>,
+) {
+    for (entity, light) in &lights {
+        if light.shadows_enabled {
+            commands.entity(entity).insert(UnculledShadows);
+        }
+    }
+}
+
+fn cull(
+    cameras: Query<(&GlobalTransform, &Frustum), With<Camera3d>>,
+    mut spawners: Query<(&GlobalTransform, &mut ParticleSpawnerSettings, &Unculled)>,
+    mut lights: Query<(&GlobalTransform, &mut PointLight), With<UnculledShadows>>,
+) {
+    let Ok((camera, frustum)) = cameras.get_single() else {
+        return;
+    };
+    let in_view = |position: Vec3, radius: f32| {
+        frustum.intersects_sphere(
+            &Sphere {
+                center: Vec3A::from(position),
+                radius,
+            },
+            false,
+        )
+    };
+
+    for (transform, mut settings, unculled) in &mut spawners {
+        let position = transform.translation();
+        let rate = if in_view(position, PARTICLES_RADIUS) {
+            let distance = camera.translation().distance(position);
+            let closeness = 1.0
+                - ((distance - PARTICLES_NEAR) / (PARTICLES_FAR - PARTICLES_NEAR)).clamp(0.0, 1.0);
+            unculled.rate * closeness.max(PARTICLES_MIN_RATE)
+        } else {
+            unculled.rate * PARTICLES_MIN_RATE
+        };
+        if settings.rate != rate {
+            settings.rate = rate;
+        }
+    }
+
+    for (transform, mut light) in &mut lights {
+        let position = transform.translation();
+        let shadows = camera.translation().distance(position) < SHADOWS_DISTANCE
+            && in_view(position, light.range);
+        if light.shadows_enabled != shadows {
+            light.shadows_enabled = shadows;
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! A random level shared by everyone each day, seeded by the date.
+
+use bevy::{prelude::*, utils::SystemTime};
+use bevy_pkv::PkvStore;
+
+use crate::levels::{
+    generator::{generate, GeneratorSettings},
+    Level,
+};
+
+const DAILY_KEY: &str = "daily";
+
+/// The daily challenge was started, for the day
+#[derive(Resource)]
+pub struct DailyChallenge {
+    pub day: u32,
+}
+
+/// Days since the unix epoch, in UTC so that everyone gets the same level
+pub fn today() -> u32 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| (elapsed.as_secs() / (24 * 60 * 60)) as u32)
+        .unwrap_or_default()
+}
+
+pub fn daily_level(day: u32) -> Level {
+    let mut level = generate(
+        day,
+        &GeneratorSettings {
+            width: 16,
+            skeletons: 3,
+            ..default()
+        },
+    );
+    level.title = Some("Daily Challenge".to_string());
+    level.file = daily_file(day);
+    level
+}
+
+pub fn daily_file(day: u32) -> String {
+    format!("daily-{}.level", day)
+}
+
+/// Keep the fewest hobbits lost while winning the challenge of the day
+pub fn record_daily(store: &mut PkvStore, day: u32, lost_hobbits: u32) {
+    if daily_result(store, day).is_some_and(|best| best <= lost_hobbits) {
+        return;
+    }
+    let _ = store.set(DAILY_KEY, &(day, lost_hobbits));
+}
+
+/// Fewest hobbits lost while winning the challenge of the day, if it was completed
+pub fn daily_result(store: &PkvStore, day: u32) -> Option<u32> {
+    store
+        .get::<(u32, u32)>(DAILY_KEY)
+        .ok()
+        .filter(|(recorded, _)| *recorded == day)
+        .map(|(_, lost_hobbits)| lost_hobbits)
+}
@@ -0,0 +1,181 @@
+//! Modal dialog asking the player to confirm an action that can't be undone. Send an
+//! [`AskConfirmation`] to open it, and read [`Confirmed`] to act once the player agreed.
+
+use bevy::{prelude::*, ui::FocusPolicy};
+
+use crate::{audio::AudioTrigger, ui, GameState};
+
+/// Actions that need a confirmation
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConfirmedAction {
+    /// Leave the level being played, back to the menu
+    LeaveLevel,
+    /// Quit the game
+    Quit,
+    /// Replace the progress with an exported one
+    ImportProgress,
+    /// Resume the level autosaved when the game was closed
+    ResumeLevel,
+}
+
+#[derive(Event)]
+pub struct AskConfirmation {
+    pub message: String,
+    /// Label of the button confirming the action
+    pub confirm: String,
+    pub action: ConfirmedAction,
+}
+
+/// The player confirmed the action
+#[derive(Event)]
+pub struct Confirmed(pub ConfirmedAction);
+
+/// Root of an open dialog, covering the window so that nothing under it can be clicked
+#[derive(Component)]
+pub struct Dialog;
+
+#[derive(Component, Clone, Copy)]
+enum DialogButton {
+    Confirm(ConfirmedAction),
+    Cancel,
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AskConfirmation>()
+            .add_event::<Confirmed>()
+            .add_systems(Update, (open_dialog, button_system));
+    }
+}
+
+/// Run condition for systems that should ignore input while a dialog is open
+pub fn no_dialog(dialogs: Query<(), With<Dialog>>) -> bool {
+    dialogs.is_empty()
+}
+
+fn open_dialog(
+    mut commands: Commands,
+    mut requests: EventReader<AskConfirmation>,
+    dialogs: Query<(), With<Dialog>>,
+    state: Res<State<GameState>>,
+) {
+    // only one dialog at a time
+    let Some(request) = requests.read().last() else {
+        return;
+    };
+    if !dialogs.is_empty() {
+        return;
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    position_type: PositionType::Absolute,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::BLACK.with_alpha(0.5).into(),
+                focus_policy: FocusPolicy::Block,
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            Dialog,
+            StateScoped(*state.get()),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    NodeBundle {
+                        border_radius: BorderRadius::all(Val::Percent(5.0)),
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            padding: UiRect::all(Val::Px(30.0)),
+                            row_gap: Val::Px(30.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    ui::Panel,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        request.message.clone(),
+                        TextStyle {
+                            font_size: 30.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                column_gap: Val::Px(20.0),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for (button, label) in [
+                                (DialogButton::Cancel, "Cancel".to_string()),
+                                (
+                                    DialogButton::Confirm(request.action),
+                                    request.confirm.clone(),
+                                ),
+                            ] {
+                                parent
+                                    .spawn((
+                                        ButtonBundle {
+                                            border_radius: BorderRadius::all(Val::Percent(10.0)),
+                                            style: Style {
+                                                width: Val::Px(160.0),
+                                                height: Val::Px(40.0),
+                                                align_items: AlignItems::Center,
+                                                justify_content: JustifyContent::Center,
+                                                ..default()
+                                            },
+                                            ..default()
+                                        },
+                                        ui::Highlight,
+                                        button,
+                                    ))
+                                    .with_children(|p| {
+                                        p.spawn(TextBundle::from_section(
+                                            label,
+                                            TextStyle {
+                                                font_size: 20.0,
+                                                ..default()
+                                            },
+                                        ));
+                                    });
+                            }
+                        });
+                });
+        });
+}
+
+fn button_system(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &DialogButton), Changed<Interaction>>,
+    dialogs: Query<Entity, With<Dialog>>,
+    mut confirmed: EventWriter<Confirmed>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        audio_trigger.send(AudioTrigger::Click);
+        if let DialogButton::Confirm(action) = button {
+            confirmed.send(Confirmed(*action));
+        }
+        for dialog in &dialogs {
+            commands.entity(dialog).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Difficulty picked when starting the campaign, adjusting its levels as they are played: more
+//! hobbits can be lost and skeletons swing slower in easy, there is a bonus less and hobbits come
+//! out faster in hard. Custom levels are played as they are.
+
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assets::GameAssets,
+    game::SwingSpeed,
+    levels::{Level, Objective},
+    play::GameInProgress,
+    GameState,
+};
+
+/// Key of the store with the difficulty picked
+pub(crate) const DIFFICULTY_KEY: &str = "difficulty";
+
+/// Hobbits that can be lost are multiplied by this in easy
+const EASY_LOSSES: u32 = 2;
+/// Speed of the skeleton swings in easy
+const EASY_SWING_SPEED: f32 = 0.7;
+/// Spawn delays are multiplied by this in hard
+const HARD_SPAWN_DELAY: f32 = 0.75;
+
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DifficultyMode {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl DifficultyMode {
+    pub fn all() -> [DifficultyMode; 3] {
+        [
+            DifficultyMode::Easy,
+            DifficultyMode::Normal,
+            DifficultyMode::Hard,
+        ]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DifficultyMode::Easy => "Easy",
+            DifficultyMode::Normal => "Normal",
+            DifficultyMode::Hard => "Hard",
+        }
+    }
+
+    /// The difficulty picked when starting the campaign, if one was
+    pub fn load(store: &PkvStore) -> Option<Self> {
+        store.get::<DifficultyMode>(DIFFICULTY_KEY).ok()
+    }
+
+    pub fn save(&self, store: &mut PkvStore) {
+        let _ = store.set(DIFFICULTY_KEY, self);
+    }
+
+    /// The level as it's played in this difficulty
+    pub fn apply(&self, level: &Level) -> Level {
+        let mut level = level.clone();
+        match self {
+            DifficultyMode::Easy => {
+                for objective in &mut level.objectives {
+                    if let Objective::MaxLosses(losts) = objective {
+                        *losts *= EASY_LOSSES;
+                    }
+                }
+            }
+            DifficultyMode::Normal => (),
+            DifficultyMode::Hard => {
+                if level.bonus.len() > 1 {
+                    level.bonus.pop();
+                }
+                level.spawn_delay *= HARD_SPAWN_DELAY;
+                for spawn in &mut level.spawns {
+                    spawn.delay *= HARD_SPAWN_DELAY;
+                }
+            }
+        }
+        level
+    }
+
+    /// Speed of the skeleton swings, relative to normal
+    pub fn swing_speed(&self) -> f32 {
+        match self {
+            DifficultyMode::Easy => EASY_SWING_SPEED,
+            DifficultyMode::Normal | DifficultyMode::Hard => 1.0,
+        }
+    }
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::InGame), set_swing_speed)
+            .add_systems(OnExit(GameState::InGame), |mut commands: Commands| {
+                commands.insert_resource(SwingSpeed::default())
+            });
+    }
+}
+
+fn set_swing_speed(
+    mut swing_speed: ResMut<SwingSpeed>,
+    difficulty: Res<DifficultyMode>,
+    game: Res<GameInProgress>,
+    assets: Res<GameAssets>,
+) {
+    swing_speed.0 = if assets.is_campaign(game.level) {
+        difficulty.swing_speed()
+    } else {
+        1.0
+    };
+}
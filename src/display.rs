@@ -0,0 +1,102 @@
+//! Window settings: borderless fullscreen, toggled with F11 or from the settings, the resolution of
+//! the window on native, and whether the canvas fits the page on the web. Also VSync and a limit
+//! of frames per second, to spare the battery of laptops.
+
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    utils::Instant,
+    window::{PresentMode, PrimaryWindow, WindowMode},
+};
+use bevy_pkv::PkvStore;
+
+use crate::settings::Settings;
+
+/// Sizes of the window the player can pick from, in order. On the web, the size of the canvas when
+/// it doesn't fit the page.
+pub(crate) const RESOLUTIONS: [(f32, f32); 4] = [
+    (1280.0, 720.0),
+    (1600.0, 900.0),
+    (1920.0, 1080.0),
+    (2560.0, 1440.0),
+];
+
+/// Limits of frames per second the player can pick from, in order, 0 for no limit
+pub(crate) const FPS_CAPS: [u32; 4] = [0, 30, 60, 120];
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                toggle_fullscreen,
+                apply_settings.run_if(resource_changed::<Settings>),
+            )
+                .chain(),
+        )
+        .add_systems(Last, limit_frame_rate);
+    }
+}
+
+fn toggle_fullscreen(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut store: ResMut<PkvStore>,
+) {
+    if keyboard.just_pressed(KeyCode::F11) {
+        settings.fullscreen = !settings.fullscreen;
+        settings.save(&mut store);
+    }
+}
+
+fn apply_settings(
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut applied_resolution: Local<Option<usize>>,
+) {
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+    let mode = if settings.fullscreen {
+        WindowMode::BorderlessFullscreen
+    } else {
+        WindowMode::Windowed
+    };
+    if window.mode != mode {
+        window.mode = mode;
+    }
+    let present_mode = if settings.vsync {
+        PresentMode::AutoVsync
+    } else {
+        PresentMode::AutoNoVsync
+    };
+    if window.present_mode != present_mode {
+        window.present_mode = present_mode;
+    }
+    if window.fit_canvas_to_parent != settings.fit_canvas {
+        window.fit_canvas_to_parent = settings.fit_canvas;
+        *applied_resolution = None;
+    }
+    // only resize when the resolution changes, to keep the size the player gave to the window
+    if !settings.fit_canvas && *applied_resolution != Some(settings.resolution) {
+        let (width, height) = RESOLUTIONS[settings.resolution];
+        window.resolution.set(width, height);
+        *applied_resolution = Some(settings.resolution);
+    }
+}
+
+/// Sleep at the end of the frame until it took as long as the frame limit allows. The browser
+/// already paces frames on the web.
+fn limit_frame_rate(settings: Res<Settings>, mut frame_start: Local<Option<Instant>>) {
+    if settings.fps_cap != 0 && !cfg!(target_arch = "wasm32") {
+        let frame_duration = Duration::from_secs_f64(1.0 / settings.fps_cap as f64);
+        if let Some(elapsed) = frame_start.map(|start| start.elapsed()) {
+            if elapsed < frame_duration {
+                std::thread::sleep(frame_duration - elapsed);
+            }
+        }
+    }
+    *frame_start = Some(Instant::now());
+}
@@ -0,0 +1,262 @@
+//! Particle effects played often during a level. Their spawner entities are kept in a pool and
+//! reused, and each effect is played once while loading so that it doesn't hitch on first use.
+
+use std::f32::consts::{FRAC_PI_4, FRAC_PI_8};
+
+use bevy::{color::palettes, prelude::*, utils::HashMap};
+use bevy_firework::{
+    bevy_utilitarian::{
+        prelude::{Gradient, ParamCurve},
+        randomized_values::{RandF32, RandValue, RandVec3},
+    },
+    core::{BlendMode, ParticleSpawnerBundle, ParticleSpawnerSettings},
+    emission_shape::EmissionShape,
+};
+
+use crate::GameState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Effect {
+    /// Burst where a hobbit was lost
+    Death,
+    /// Sparkles around a hobbit carrying treasure
+    Treasure,
+    /// Puff out of a chest when a milestone is reached
+    Confetti,
+}
+
+impl Effect {
+    const ALL: [Effect; 3] = [Effect::Death, Effect::Treasure, Effect::Confetti];
+
+    /// How long the effect plays, `None` for effects that play as long as what they follow
+    fn duration(&self) -> Option<f32> {
+        match self {
+            Effect::Death => Some(0.5),
+            Effect::Treasure => None,
+            Effect::Confetti => Some(1.0),
+        }
+    }
+
+    fn settings(&self) -> ParticleSpawnerSettings {
+        match self {
+            Effect::Death => ParticleSpawnerSettings {
+                one_shot: true,
+                rate: 500.0,
+                emission_shape: EmissionShape::Circle {
+                    normal: Vec3::Y,
+                    radius: 1.0,
+                },
+                lifetime: RandF32::constant(0.4),
+                inherit_parent_velocity: true,
+                initial_velocity: RandVec3 {
+                    magnitude: RandF32 { min: 0., max: 10. },
+                    direction: Vec3::Y,
+                    spread: FRAC_PI_8,
+                },
+                initial_scale: RandF32 {
+                    min: 0.02,
+                    max: 0.08,
+                },
+                scale_curve: ParamCurve::constant(1.),
+                color: Gradient::constant(palettes::tailwind::RED_500.into()),
+                blend_mode: BlendMode::Blend,
+                linear_drag: 0.1,
+                pbr: false,
+                ..default()
+            },
+            Effect::Treasure => ParticleSpawnerSettings {
+                one_shot: false,
+                rate: 10.0,
+                emission_shape: EmissionShape::Circle {
+                    normal: Vec3::Y,
+                    radius: 0.5,
+                },
+                lifetime: RandF32::constant(0.25),
+                inherit_parent_velocity: true,
+                initial_velocity: RandVec3 {
+                    magnitude: RandF32 { min: 0., max: 10. },
+                    direction: Vec3::Y,
+                    spread: FRAC_PI_4,
+                },
+                initial_scale: RandF32 {
+                    min: 0.05,
+                    max: 0.1,
+                },
+                scale_curve: ParamCurve::constant(1.),
+                color: Gradient::constant((palettes::tailwind::YELLOW_800 * 5.0).into()),
+                blend_mode: BlendMode::Blend,
+                linear_drag: 0.1,
+                pbr: true,
+                ..default()
+            },
+            Effect::Confetti => ParticleSpawnerSettings {
+                one_shot: true,
+                rate: 300.0,
+                emission_shape: EmissionShape::Circle {
+                    normal: Vec3::Y,
+                    radius: 0.8,
+                },
+                lifetime: RandF32::constant(0.9),
+                initial_velocity: RandVec3 {
+                    magnitude: RandF32 { min: 4., max: 12. },
+                    direction: Vec3::Y,
+                    spread: FRAC_PI_4,
+                },
+                acceleration: Vec3::new(0., -9.8, 0.),
+                initial_scale: RandF32 {
+                    min: 0.06,
+                    max: 0.12,
+                },
+                scale_curve: ParamCurve::constant(1.),
+                color: Gradient::constant((palettes::tailwind::PINK_400 * 3.0).into()),
+                blend_mode: BlendMode::Blend,
+                linear_drag: 0.5,
+                pbr: false,
+                ..default()
+            },
+        }
+    }
+}
+
+/// Play an effect, at a position or following an entity until it's despawned
+#[derive(Event)]
+pub struct PlayEffect {
+    pub effect: Effect,
+    pub transform: Transform,
+    pub follow: Option<Entity>,
+}
+
+/// Stop an effect following an entity that is kept after the effect is no longer needed
+#[derive(Event)]
+pub struct StopEffect {
+    pub effect: Effect,
+    pub follow: Entity,
+}
+
+/// Spawner entities not playing, by effect
+#[derive(Resource, Default)]
+struct EffectPool(HashMap<Effect, Vec<Entity>>);
+
+#[derive(Component)]
+struct ActiveEffect {
+    effect: Effect,
+    follow: Option<Entity>,
+    timer: Option<Timer>,
+}
+
+/// Played while loading, released once loading is done
+#[derive(Component)]
+struct Prewarm;
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EffectPool>()
+            .add_event::<PlayEffect>()
+            .add_event::<StopEffect>()
+            .add_systems(OnEnter(GameState::Loading), prewarm_effects)
+            .add_systems(OnExit(GameState::Loading), release_prewarmed)
+            .add_systems(
+                PostUpdate,
+                (play_effects, follow_and_release)
+                    .chain()
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+fn prewarm_effects(mut effects: EventWriter<PlayEffect>, camera: Query<&Transform, With<Camera>>) {
+    let Ok(camera) = camera.get_single() else {
+        return;
+    };
+    for effect in Effect::ALL {
+        effects.send(PlayEffect {
+            effect,
+            transform: camera
+                .with_translation(camera.translation + camera.forward().as_vec3() * 10.0),
+            follow: None,
+        });
+    }
+}
+
+fn release_prewarmed(
+    mut commands: Commands,
+    mut pool: ResMut<EffectPool>,
+    prewarmed: Query<(Entity, &ActiveEffect), With<Prewarm>>,
+) {
+    for (entity, active) in &prewarmed {
+        commands.entity(entity).remove::<Prewarm>();
+        release(&mut commands, &mut pool, entity, active.effect);
+    }
+}
+
+fn play_effects(
+    mut commands: Commands,
+    mut pool: ResMut<EffectPool>,
+    mut events: EventReader<PlayEffect>,
+    state: Res<State<GameState>>,
+) {
+    for event in events.read() {
+        let entity = match pool.0.get_mut(&event.effect).and_then(Vec::pop) {
+            Some(entity) => entity,
+            None => commands.spawn_empty().id(),
+        };
+        // the transform of the bundle is replaced by where the effect plays
+        commands.entity(entity).insert((
+            ParticleSpawnerBundle::from_settings(event.effect.settings()),
+            ActiveEffect {
+                effect: event.effect,
+                follow: event.follow,
+                timer: event
+                    .effect
+                    .duration()
+                    .map(|duration| Timer::from_seconds(duration, TimerMode::Once)),
+            },
+        ));
+        commands.entity(entity).insert(event.transform);
+        if *state.get() == GameState::Loading {
+            commands.entity(entity).insert(Prewarm);
+        }
+    }
+}
+
+fn follow_and_release(
+    mut commands: Commands,
+    mut pool: ResMut<EffectPool>,
+    mut effects: Query<(Entity, &mut ActiveEffect, &mut Transform), Without<Prewarm>>,
+    followed: Query<&Transform, Without<ActiveEffect>>,
+    mut stopped: EventReader<StopEffect>,
+    time: Res<Time>,
+) {
+    let stopped = stopped
+        .read()
+        .map(|stop| (stop.effect, stop.follow))
+        .collect::<Vec<_>>();
+    for (entity, mut active, mut transform) in &mut effects {
+        let done = match (active.follow, active.timer.as_mut()) {
+            (Some(followed_entity), _) if stopped.contains(&(active.effect, followed_entity)) => {
+                true
+            }
+            (_, Some(timer)) => timer.tick(time.delta()).finished(),
+            (Some(followed_entity), None) => match followed.get(followed_entity) {
+                Ok(followed) => {
+                    transform.translation = followed.translation;
+                    false
+                }
+                Err(_) => true,
+            },
+            (None, None) => false,
+        };
+        if done {
+            release(&mut commands, &mut pool, entity, active.effect);
+        }
+    }
+}
+
+/// Stop the spawner and keep its entity for the next time the effect is played
+fn release(commands: &mut Commands, pool: &mut EffectPool, entity: Entity, effect: Effect) {
+    commands
+        .entity(entity)
+        .remove::<(ParticleSpawnerBundle, ActiveEffect)>();
+    pool.0.entry(effect).or_default().push(entity);
+}
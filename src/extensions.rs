@@ -0,0 +1,105 @@
+use std::sync::{Arc, RwLock};
+
+use bevy::prelude::*;
+
+use crate::{assets::GameAssets, game::GameEvent};
+
+/// A new kind of tile, placed in level files with its symbol
+pub trait TileBehavior: Send + Sync + 'static {
+    /// Character used for this tile in level files
+    fn symbol(&self) -> char;
+
+    /// Spawn what's on the tile. The floor is already there, and hobbits can walk on it.
+    fn spawn(&self, parent: &mut ChildBuilder<'_>, translation: Vec3, assets: &GameAssets);
+}
+
+/// A new kind of bonus, given to the player in level files with its name
+pub trait BonusKind: Send + Sync + 'static {
+    /// Name used for this bonus in level files
+    fn name(&self) -> &str;
+
+    /// Icon displayed in the bonus panel
+    fn icon(&self) -> Handle<Image>;
+
+    /// Scene spawned where the bonus is placed. Hobbits can't walk through it.
+    fn scene(&self) -> Handle<Scene>;
+}
+
+/// Reacts to every [`GameEvent`] during play
+pub trait GameEventHandler: Send + Sync + 'static {
+    fn handle(&self, event: &GameEvent, commands: &mut Commands);
+}
+
+#[derive(Default)]
+struct Registry {
+    tiles: Vec<Arc<dyn TileBehavior>>,
+    bonuses: Vec<Arc<dyn BonusKind>>,
+    event_handlers: Vec<Arc<dyn GameEventHandler>>,
+}
+
+/// Registration point for mods. Everything must be registered before levels are loaded, so
+/// from a plugin added after the game plugins.
+#[derive(Resource, Clone, Default)]
+pub struct GameExtensions(Arc<RwLock<Registry>>);
+
+impl GameExtensions {
+    pub fn register_tile(&self, tile: impl TileBehavior) {
+        self.0.write().unwrap().tiles.push(Arc::new(tile));
+    }
+
+    pub fn register_bonus(&self, bonus: impl BonusKind) {
+        self.0.write().unwrap().bonuses.push(Arc::new(bonus));
+    }
+
+    pub fn register_event_handler(&self, handler: impl GameEventHandler) {
+        self.0
+            .write()
+            .unwrap()
+            .event_handlers
+            .push(Arc::new(handler));
+    }
+
+    pub fn tile(&self, symbol: char) -> Option<Arc<dyn TileBehavior>> {
+        self.0
+            .read()
+            .unwrap()
+            .tiles
+            .iter()
+            .find(|tile| tile.symbol() == symbol)
+            .cloned()
+    }
+
+    pub fn bonus_index(&self, name: &str) -> Option<usize> {
+        self.0
+            .read()
+            .unwrap()
+            .bonuses
+            .iter()
+            .position(|bonus| bonus.name() == name)
+    }
+
+    pub fn bonus(&self, index: usize) -> Option<Arc<dyn BonusKind>> {
+        self.0.read().unwrap().bonuses.get(index).cloned()
+    }
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameExtensions>()
+            .add_systems(Update, dispatch_game_events);
+    }
+}
+
+fn dispatch_game_events(
+    mut commands: Commands,
+    mut game_events: EventReader<GameEvent>,
+    extensions: Res<GameExtensions>,
+) {
+    let handlers = extensions.0.read().unwrap().event_handlers.clone();
+    for event in game_events.read() {
+        for handler in &handlers {
+            handler.handle(event, &mut commands);
+        }
+    }
+}
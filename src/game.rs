@@ -1,6 +1,6 @@
 use std::{
-    collections::HashSet,
-    f32::consts::{FRAC_PI_2, FRAC_PI_4, FRAC_PI_8, PI, TAU},
+    collections::{HashMap, HashSet},
+    f32::consts::{FRAC_PI_2, FRAC_PI_4, PI, TAU},
     time::Duration,
 };
 
@@ -10,58 +10,204 @@ use avian3d::{
 };
 use bevy::{
     color::palettes,
+    diagnostic::Diagnostics,
     ecs::entity::EntityHashMap,
+    input::InputSystem,
     math::{vec2, vec3},
     prelude::*,
     scene::{SceneInstance, SceneInstanceReady},
+    ui::UiSystem,
 };
-use bevy_firework::{
-    bevy_utilitarian::{
-        prelude::{Gradient, ParamCurve},
-        randomized_values::{RandF32, RandValue, RandVec3},
-    },
-    core::{BlendMode, ParticleSpawnerBundle, ParticleSpawnerSettings},
-    emission_shape::EmissionShape,
-};
+use bevy_firework::core::ParticleSpawnerSettings;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     assets::GameAssets,
     audio::AudioTrigger,
-    levels::{AnimatedKind, Level},
+    captions::SoundAt,
+    effects::{Effect, PlayEffect, StopEffect},
+    levels::{AnimatedKind, HobbitKind, Level, NavMeshCells, Tile, TEAM_COLORS},
+    mutators::Mutators,
+    perf::PATH_QUERIES,
+    settings::Settings,
     GameState,
 };
 
 pub struct Plugin;
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(PathStatus::Open)
-            .add_event::<GameEvent>()
-            .add_systems(OnExit(GameState::Loading), prepare_animations)
-            .add_systems(
+        app.add_plugins(GameplayPlugin)
+            .init_resource::<SwingSpeed>()
+            .configure_sets(
                 PreUpdate,
-                colliding_hobbits.run_if(resource_exists::<ActiveLevel>),
+                PlayLoop::Placement
+                    .after(InputSystem)
+                    .after(UiSystem::Focus),
             )
+            .add_systems(OnExit(GameState::Loading), prepare_animations)
             .add_systems(
                 Update,
                 (
-                    spawn_hobbits,
                     add_animations,
-                    move_to_target,
-                    reach_target,
-                    give_target,
-                    reevaluate_path,
+                    play_swings,
+                    attach_treasure,
+                    drop_treasure,
+                    bend_particles,
+                    jump_over_gaps.run_if(|level: Res<ActiveLevel>| level.0.jump),
+                    display_hobbit_state
+                        .run_if(|settings: Res<Settings>| settings.hobbit_indicators),
+                    display_hovered_path.run_if(in_state(GameState::InGame)),
+                    display_teams.run_if(|level: Res<ActiveLevel>| level.0.teams.len() > 1),
+                    display_escorted.run_if(|level: Res<ActiveLevel>| level.0.escort().is_some()),
                     #[cfg(feature = "debug")]
                     display_paths,
                 )
                     .run_if(resource_exists::<ActiveLevel>),
             )
-            .add_systems(Update, set_weapons.run_if(resource_exists::<GameAssets>));
+            .add_systems(Update, set_weapons.run_if(resource_exists::<GameAssets>))
+            .add_systems(
+                Update,
+                apply_simulation_speed.run_if(resource_changed::<SimulationSpeed>),
+            )
+            .add_systems(OnExit(GameState::InGame), reset_simulation_speed);
     }
 }
 
+/// Rules of the game: hobbits come out, find their path, walk, loot chests and collide, with
+/// nothing to see or hear. The headless [`crate::simulation`] plays levels with only this plugin.
+pub struct GameplayPlugin;
+impl bevy::app::Plugin for GameplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PathStatus::Open)
+            .init_resource::<SimulationSpeed>()
+            .init_resource::<Mutators>()
+            .init_resource::<GameRng>()
+            .init_resource::<ChestTreasures>()
+            .init_resource::<ChestQueues>()
+            .init_resource::<HobbitGrid>()
+            .init_resource::<HeldSpawns>()
+            .init_resource::<Spawner>()
+            .init_resource::<PathRequestQueue>()
+            .init_resource::<Congestion>()
+            .init_resource::<GoalTolerances>()
+            .add_event::<GameEvent>()
+            .add_event::<SkipIntro>()
+            // sent by the gameplay, heard and seen only when the audio and effects plugins are there
+            .add_event::<AudioTrigger>()
+            .add_event::<SoundAt>()
+            .add_event::<PlayEffect>()
+            .configure_sets(
+                FixedUpdate,
+                (PlayLoop::Navigation, PlayLoop::Movement).chain(),
+            )
+            .add_systems(
+                FixedUpdate,
+                // with the collisions of the physics step that ended the previous gameplay step,
+                // hobbits of the demo don't get hurt
+                colliding_hobbits
+                    .before(PlayLoop::Navigation)
+                    .run_if(resource_exists::<ActiveLevel>.and_then(not(resource_exists::<Demo>))),
+            )
+            .add_systems(
+                FixedUpdate,
+                fill_chests
+                    .run_if(resource_exists_and_changed::<ActiveLevel>)
+                    .before(reach_target)
+                    .before(give_target),
+            )
+            .add_systems(
+                FixedUpdate,
+                (index_hobbits, track_congestion)
+                    .chain()
+                    .before(spawn_hobbits)
+                    .before(PlayLoop::Navigation)
+                    .run_if(resource_exists::<ActiveLevel>),
+            )
+            // gameplay runs at a fixed timestep so that it plays the same on every machine
+            .add_systems(
+                FixedUpdate,
+                (
+                    spawn_hobbits.before(PlayLoop::Navigation),
+                    (give_target, reevaluate_path).in_set(PlayLoop::Navigation),
+                    (move_to_target, reach_target, queue_at_chests, take_treasure)
+                        .in_set(PlayLoop::Movement),
+                    detect_near_misses.after(PlayLoop::Movement),
+                )
+                    .run_if(resource_exists::<ActiveLevel>),
+            );
+    }
+}
+
+/// Order of the play loop in a frame
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PlayLoop {
+    /// The player places or removes bonuses, and the cells they change are updated on the navmesh.
+    /// In `PreUpdate`, after input and UI interactions.
+    Placement,
+    /// Hobbits get a path on the navmesh, or have it updated. In `FixedUpdate`, like the rest of
+    /// the gameplay.
+    Navigation,
+    /// Hobbits follow their path, and interact with what they reach, in `FixedUpdate`
+    Movement,
+}
+
 #[derive(Resource)]
 pub struct NavMesh(pub polyanya::Mesh);
 
+/// Source of randomness during a level, seeded when the level starts so that it plays the same
+/// every time
+#[derive(Resource)]
+pub struct GameRng(pub StdRng);
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng(StdRng::seed_from_u64(0))
+    }
+}
+
+/// Speed of the hobbits simulation, changed from the play HUD. It's applied to the virtual time,
+/// what must not go faster or stop with the hobbits uses the real time.
+#[derive(Resource)]
+pub struct SimulationSpeed {
+    pub factor: f32,
+    pub paused: bool,
+}
+
+impl Default for SimulationSpeed {
+    fn default() -> Self {
+        SimulationSpeed {
+            factor: 1.0,
+            paused: false,
+        }
+    }
+}
+
+/// Speed of the skeleton swings, relative to their usual speed
+#[derive(Resource)]
+pub struct SwingSpeed(pub f32);
+
+impl Default for SwingSpeed {
+    fn default() -> Self {
+        SwingSpeed(1.0)
+    }
+}
+
+fn reset_simulation_speed(mut speed: ResMut<SimulationSpeed>) {
+    *speed = SimulationSpeed::default();
+}
+
+/// Faster or paused virtual time runs more or less fixed steps each frame, the gameplay and the
+/// physics always step by the same fixed timestep
+fn apply_simulation_speed(speed: Res<SimulationSpeed>, mut time: ResMut<Time<Virtual>>) {
+    time.set_relative_speed(speed.factor);
+    if speed.paused {
+        time.pause();
+    } else {
+        time.unpause();
+    }
+}
+
 #[derive(Resource, PartialEq, Eq)]
 pub enum PathStatus {
     Open,
@@ -71,6 +217,65 @@ pub enum PathStatus {
 #[derive(Resource)]
 pub struct ActiveLevel(pub Level);
 
+/// The level plays on its own as a demo, behind the menu: hobbits go back and forth between their
+/// start and the chest forever, and are never lost. Nothing is scored.
+#[derive(Resource)]
+pub struct Demo;
+
+/// Treasures left in each chest of [`Level::chests`], `None` for chests that never run out
+#[derive(Resource, Default, Clone)]
+pub struct ChestTreasures(pub Vec<Option<u32>>);
+
+impl ChestTreasures {
+    fn is_open(&self, chest: usize) -> bool {
+        self.0.get(chest).copied().flatten() != Some(0)
+    }
+
+    /// At least one chest of the team still has treasures
+    fn any_open(&self, level: &Level, team: usize) -> bool {
+        level
+            .chests
+            .iter()
+            .enumerate()
+            .any(|(index, chest)| chest.team == team && self.is_open(index))
+    }
+}
+
+/// Hobbits waiting at each chest of [`Level::chests`], the first one is taking its treasure
+#[derive(Resource, Default)]
+struct ChestQueues(Vec<Vec<Entity>>);
+
+fn fill_chests(
+    level: Res<ActiveLevel>,
+    mut chests: ResMut<ChestTreasures>,
+    mut queues: ResMut<ChestQueues>,
+) {
+    chests.0 = level.0.chests.iter().map(|chest| chest.treasures).collect();
+    queues.0 = vec![vec![]; level.0.chests.len()];
+}
+
+/// Path to the closest chest of the team that still has treasures
+fn path_to_chest(
+    level: &Level,
+    chests: &ChestTreasures,
+    navmesh: &polyanya::Mesh,
+    team: usize,
+    from: Vec2,
+) -> Option<polyanya::Path> {
+    let mut exclusion = HashSet::new();
+    exclusion.insert(2);
+    level
+        .chests
+        .iter()
+        .enumerate()
+        .filter(|(index, chest)| chest.team == team && chests.is_open(*index))
+        .filter_map(|(_, chest)| {
+            let to = vec2(chest.cell.1 as f32 * 4.0, chest.cell.2 as f32 * 4.0);
+            navmesh.path_on_layers(from, to, exclusion.clone())
+        })
+        .min_by(|a, b| a.length.total_cmp(&b.length))
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum HobbitState {
     #[allow(clippy::upper_case_acronyms)]
@@ -80,7 +285,240 @@ enum HobbitState {
 
 #[derive(Component)]
 pub struct Hobbit {
+    /// Rank of the hobbit in the spawn order, starting from 1
+    id: u32,
     state: HobbitState,
+    kind: HobbitKind,
+    /// Index of the team in [`Level::teams`]
+    team: usize,
+    /// Index of the spawn point in [`Level::spawns`], where the hobbit goes back to
+    spawn: usize,
+    carrying: u32,
+    health: u32,
+    invulnerability: Option<Timer>,
+    /// The hobbit of the escort objective
+    escorted: bool,
+    /// Distance walked since spawning, for fatigue
+    walked: f32,
+}
+
+impl Hobbit {
+    pub fn speed(&self, level: &Level) -> f32 {
+        self.kind.speed() * level.speed_factor(self.state == HobbitState::Tired, self.walked)
+    }
+
+    /// Index of the team in [`Level::teams`]
+    pub fn team(&self) -> usize {
+        self.team
+    }
+
+    pub fn kind(&self) -> HobbitKind {
+        self.kind
+    }
+
+    /// Still pushed away by the last hit, see [`KNOCKBACK_DURATION`]
+    fn knocked_back(&self) -> bool {
+        self.invulnerability
+            .as_ref()
+            .is_some_and(|timer| timer.elapsed_secs() < KNOCKBACK_DURATION)
+    }
+
+    /// Going back home, with or without treasure
+    pub fn is_tired(&self) -> bool {
+        self.state == HobbitState::Tired
+    }
+
+    /// Color to follow this hobbit in debug views, stable for its whole life
+    pub fn color(&self) -> Color {
+        // golden angle, so that consecutive hobbits have distinct colors
+        Color::hsl((self.id as f32 * 137.508) % 360.0, 0.8, 0.6)
+    }
+
+    pub fn save(&self, transform: &Transform) -> SavedHobbit {
+        SavedHobbit {
+            position: transform.translation.to_array(),
+            id: self.id,
+            tired: self.state == HobbitState::Tired,
+            kind: self.kind,
+            team: self.team,
+            spawn: self.spawn,
+            carrying: self.carrying,
+            health: self.health,
+            escorted: self.escorted,
+            walked: self.walked,
+        }
+    }
+}
+
+/// A hobbit kept in an autosave of the level, see [`crate::resume`]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedHobbit {
+    position: [f32; 3],
+    id: u32,
+    tired: bool,
+    kind: HobbitKind,
+    team: usize,
+    spawn: usize,
+    carrying: u32,
+    health: u32,
+    escorted: bool,
+    walked: f32,
+}
+
+/// Spawn again a hobbit from an autosave. It finds its path again on the next step.
+pub(crate) fn restore_hobbit(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    state: GameState,
+    saved: &SavedHobbit,
+) {
+    spawn_hobbit(
+        commands,
+        assets,
+        state,
+        Vec3::from_array(saved.position),
+        Hobbit {
+            id: saved.id,
+            state: if saved.tired {
+                HobbitState::Tired
+            } else {
+                HobbitState::LFG
+            },
+            kind: saved.kind,
+            team: saved.team,
+            spawn: saved.spawn,
+            carrying: saved.carrying,
+            health: saved.health,
+            invulnerability: None,
+            escorted: saved.escorted,
+            walked: saved.walked,
+        },
+    );
+}
+
+const HOBBIT_HEALTH: u32 = 3;
+const INVULNERABILITY_DURATION: f32 = 1.0;
+const KNOCKBACK_SPEED: f32 = 12.0;
+/// Seconds a hit hobbit doesn't steer, so that the knockback isn't clamped to its walking speed
+const KNOCKBACK_DURATION: f32 = 0.25;
+const JUMP_HEIGHT: f32 = 1.5;
+/// Distance from the spawn point of hobbits spawned together
+const BURST_SPREAD: f32 = 1.2;
+/// Distance from the chest of the ring where hobbits wait their turn
+const QUEUE_RADIUS: f32 = 1.5;
+/// Places in a ring around a chest, more hobbits wait in a wider ring
+const QUEUE_SLOTS: usize = 6;
+const QUEUE_RING_SPACING: f32 = 1.2;
+/// Time taken by a hobbit to fill its pockets at the chest
+const LOOTING_DURATION: f32 = 0.6;
+/// Distance in cells from a start where crowding hobbits hold its spawns, in levels that throttle
+/// spawns
+const THROTTLE_RADIUS: usize = 1;
+/// Spawns are held while this many hobbits are around the start
+const THROTTLE_CROWD: usize = 3;
+/// Time before checking again if a crowded start cleared
+const THROTTLE_RETRY: f32 = 0.5;
+/// How close hobbits need to get to where they are going
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct GoalTolerances {
+    /// Distance from home to be back with the treasure
+    pub home: f32,
+    /// Distance between the end of a path and a chest for the hobbit to queue at that chest
+    pub chest: f32,
+    /// A waypoint is passed when closer than the distance walked in this time at the current speed
+    pub waypoint_time: f32,
+    /// Waypoint tolerance of hobbits barely moving
+    pub waypoint_min: f32,
+    /// Distance from the end of the path where hobbits start slowing down
+    pub arrival_radius: f32,
+    /// Share of their speed hobbits keep right on the end of their path
+    pub arrival_min_speed: f32,
+}
+
+impl Default for GoalTolerances {
+    fn default() -> Self {
+        GoalTolerances {
+            home: 1.5,
+            chest: 1.0,
+            waypoint_time: 0.1,
+            waypoint_min: 0.2,
+            arrival_radius: 2.5,
+            arrival_min_speed: 0.2,
+        }
+    }
+}
+
+impl GoalTolerances {
+    /// Distance to a waypoint to go to the next one, at the given speed
+    pub fn waypoint(&self, speed: f32) -> f32 {
+        (speed * self.waypoint_time).max(self.waypoint_min)
+    }
+
+    /// Share of their speed hobbits keep at this distance from the end of their path, easing out
+    /// so that they settle on it instead of overshooting and orbiting around it
+    pub fn arrival(&self, distance: f32) -> f32 {
+        let t = (distance / self.arrival_radius).clamp(0.0, 1.0);
+        (t * (2.0 - t)).max(self.arrival_min_speed)
+    }
+}
+
+/// Room kept between hobbits walking together, on top of their size
+const SEPARATION_MARGIN: f32 = 0.4;
+/// How strongly hobbits steer away from their neighbours, compared to following their path
+const SEPARATION_WEIGHT: f32 = 1.5;
+
+/// Cell of a position on the ground
+fn cell_of(position: Vec2) -> (usize, usize) {
+    (
+        (position.x / 4.0).round().max(0.0) as usize,
+        (position.y / 4.0).round().max(0.0) as usize,
+    )
+}
+
+/// Hobbits by the cell they are in, updated at the start of every gameplay step
+#[derive(Resource, Default)]
+pub struct HobbitGrid(HashMap<(usize, usize), Vec<Entity>>);
+
+impl HobbitGrid {
+    /// Hobbits at most `radius` cells away from `cell`, diagonals included
+    pub fn around(&self, cell: (usize, usize), radius: usize) -> impl Iterator<Item = Entity> + '_ {
+        (cell.1.saturating_sub(radius)..=cell.1 + radius)
+            .flat_map(move |y| {
+                (cell.0.saturating_sub(radius)..=cell.0 + radius).map(move |x| (x, y))
+            })
+            .filter_map(move |cell| self.0.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+fn index_hobbits(mut grid: ResMut<HobbitGrid>, hobbits: Query<(Entity, &Transform), With<Hobbit>>) {
+    grid.0.clear();
+    for (entity, transform) in &hobbits {
+        grid.0
+            .entry(cell_of(transform.translation.xz()))
+            .or_default()
+            .push(entity);
+    }
+}
+
+/// Spawn points holding their hobbits because their start is crowded, by index in
+/// [`Level::spawns`]
+#[derive(Resource, Default)]
+pub struct HeldSpawns(pub Vec<bool>);
+
+impl HeldSpawns {
+    pub fn any(&self) -> bool {
+        self.0.iter().any(|held| *held)
+    }
+}
+
+/// Timer before the next hobbits of each spawn point of [`Level::spawns`], and the number of
+/// hobbits spawned since the start of the level
+#[derive(Resource, Default)]
+pub struct Spawner {
+    pub timers: Vec<Option<Timer>>,
+    pub spawned: usize,
 }
 
 #[derive(Component)]
@@ -96,68 +534,158 @@ fn spawn_hobbits(
     hobbits: Query<&Hobbit>,
     time: Res<Time>,
     level: Res<ActiveLevel>,
-    mut local_timer: Local<Option<Timer>>,
+    mut spawner: ResMut<Spawner>,
     assets: Res<GameAssets>,
     state: Res<State<GameState>>,
     mut path_status: ResMut<PathStatus>,
     mut audio_trigger: EventWriter<AudioTrigger>,
+    mutators: Res<Mutators>,
+    mut game_events: EventWriter<GameEvent>,
+    grid: Res<HobbitGrid>,
+    mut held: ResMut<HeldSpawns>,
+    mut skip_intro: EventReader<SkipIntro>,
+    mut sounds_at: EventWriter<SoundAt>,
 ) {
+    let spawner = &mut *spawner;
     let mut initial = false;
     if level.is_added() || level.is_changed() {
         initial = true;
-        *local_timer = None;
+        spawner.timers = vec![None; level.0.spawns.len()];
+        spawner.spawned = 0;
         *path_status = PathStatus::Open;
+        held.0 = vec![false; level.0.spawns.len()];
+    }
+    // until the first hobbit, timers are waiting for the intro to end
+    if skip_intro.read().count() > 0 && spawner.spawned == 0 {
+        for timer in spawner.timers.iter_mut().flatten() {
+            *timer = Timer::from_seconds(0.0, TimerMode::Once);
+        }
     }
     if matches!(*path_status, PathStatus::Blocked) {
         return;
     }
-    if let Some(timer) = local_timer.as_mut() {
-        if timer.tick(time.delta()).just_finished() {
-            commands
-                .spawn((
-                    SpatialBundle::from_transform(Transform::from_translation(Vec3::new(
-                        level.0.start.1 as f32 * 4.0,
-                        1.2,
-                        level.0.start.2 as f32 * 4.0,
-                    ))),
-                    RigidBody::Dynamic,
-                    LockedAxes::new().lock_rotation_x().lock_rotation_z(),
-                    Collider::capsule(0.5, 1.0),
-                    Hobbit {
-                        state: HobbitState::LFG,
-                    },
-                    StateScoped(*state.get()),
-                    ColliderKind::Hobbit,
-                    CollisionLayers::new(0b100, 0b111),
-                ))
-                .with_children(|p| {
-                    p.spawn((
-                        SceneBundle {
-                            scene: assets.character.clone(),
-                            transform: Transform::from_translation(vec3(0.0, -1.0, 0.0)),
-                            ..default()
+    // each spawn point has its own timer and number of hobbits
+    for (index, spawn) in level.0.spawns.iter().enumerate() {
+        if let Some(timer) = spawner.timers[index].as_mut() {
+            if timer.tick(time.delta()).just_finished() {
+                if level.0.throttle
+                    && grid
+                        .around((spawn.cell.1, spawn.cell.2), THROTTLE_RADIUS)
+                        .count()
+                        >= THROTTLE_CROWD
+                {
+                    // check again soon, instead of waiting for a full spawn delay
+                    if !held.0[index] {
+                        held.0[index] = true;
+                    }
+                    spawner.timers[index] =
+                        Some(Timer::from_seconds(THROTTLE_RETRY, TimerMode::Once));
+                    continue;
+                }
+                if held.0[index] {
+                    held.0[index] = false;
+                }
+                let alive = hobbits
+                    .iter()
+                    .filter(|hobbit| hobbit.spawn == index)
+                    .count() as u32;
+                let burst = spawn.burst.min(spawn.hobbits.saturating_sub(alive)).max(1);
+                for i in 0..burst {
+                    let kind = level.0.roster[spawner.spawned % level.0.roster.len()];
+                    spawner.spawned += 1;
+                    let id = spawner.spawned as u32;
+                    let escorted = level.0.escort() == Some(id);
+                    // hobbits of a burst are spread around the spawn point so they don't overlap
+                    let offset = if burst > 1 {
+                        Vec2::from_angle(TAU * i as f32 / burst as f32) * BURST_SPREAD
+                    } else {
+                        Vec2::ZERO
+                    };
+                    spawn_hobbit(
+                        &mut commands,
+                        &assets,
+                        *state.get(),
+                        Vec3::new(
+                            spawn.cell.1 as f32 * 4.0 + offset.x,
+                            1.2,
+                            spawn.cell.2 as f32 * 4.0 + offset.y,
+                        ),
+                        Hobbit {
+                            id,
+                            state: HobbitState::LFG,
+                            kind,
+                            team: spawn.team,
+                            spawn: index,
+                            carrying: 0,
+                            health: HOBBIT_HEALTH,
+                            invulnerability: None,
+                            escorted,
+                            walked: 0.0,
                         },
-                        AnimatedKind::Hobbit,
-                    ));
+                    );
+                }
+                audio_trigger.send(AudioTrigger::Spawn);
+                sounds_at.send(SoundAt {
+                    trigger: AudioTrigger::Spawn,
+                    position: Vec3::new(spawn.cell.1 as f32 * 4.0, 0.0, spawn.cell.2 as f32 * 4.0),
+                });
+                game_events.send(GameEvent::Spawned {
+                    team: spawn.team,
+                    hobbits: burst,
                 });
-            audio_trigger.send(AudioTrigger::Spawn);
 
-            *local_timer = None;
-        }
-    } else if hobbits.iter().len() < level.0.nb_hobbits as usize {
-        let timer = if initial {
-            if level.0.message.is_some() {
-                Timer::from_seconds(7.5, TimerMode::Once)
-            } else {
-                Timer::from_seconds(1.5, TimerMode::Once)
+                spawner.timers[index] = None;
             }
-        } else {
-            Timer::from_seconds(level.0.spawn_delay, TimerMode::Once)
-        };
-        *local_timer = Some(timer);
+        } else if hobbits
+            .iter()
+            .filter(|hobbit| hobbit.spawn == index)
+            .count()
+            < spawn.hobbits as usize
+        {
+            let timer = if initial {
+                // hobbits wait for the message to be read
+                Timer::from_seconds(1.5 + level.0.message_duration(), TimerMode::Once)
+            } else {
+                // faster hobbits come out more often, so that they stay as spread out
+                Timer::from_seconds(spawn.delay / mutators.hobbit_speed(), TimerMode::Once)
+            };
+            spawner.timers[index] = Some(timer);
+        }
     }
 }
 
+fn spawn_hobbit(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    state: GameState,
+    translation: Vec3,
+    hobbit: Hobbit,
+) {
+    let kind = hobbit.kind;
+    commands
+        .spawn((
+            SpatialBundle::from_transform(Transform::from_translation(translation)),
+            RigidBody::Dynamic,
+            LockedAxes::new().lock_rotation_x().lock_rotation_z(),
+            Collider::capsule(kind.radius(), 1.0),
+            hobbit,
+            StateScoped(state),
+            ColliderKind::Hobbit,
+            CollisionLayers::new(0b100, 0b111),
+        ))
+        .with_children(|p| {
+            p.spawn((
+                SceneBundle {
+                    scene: assets.character.clone(),
+                    transform: Transform::from_translation(vec3(0.0, -1.0, 0.0))
+                        .with_scale(Vec3::splat(kind.scale())),
+                    ..default()
+                },
+                AnimatedKind::Hobbit,
+            ));
+        });
+}
+
 #[derive(Resource)]
 struct WalkAnimations {
     animations: Vec<AnimationNodeIndex>,
@@ -208,6 +736,7 @@ fn add_animations(
     mut players: Query<(Entity, &Parent, &mut AnimationPlayer), Added<AnimationPlayer>>,
     parents: Query<&Parent>,
     animated: Query<&AnimatedKind>,
+    swing_speed: Res<SwingSpeed>,
 ) {
     for (entity, parent, mut player) in &mut players {
         match parents
@@ -218,12 +747,13 @@ fn add_animations(
                 let mut transitions = AnimationTransitions::new();
                 transitions
                     .play(&mut player, attack_animations.animations[0], Duration::ZERO)
-                    .set_speed(0.7)
+                    .set_speed(0.7 * swing_speed.0)
                     .repeat();
                 commands
                     .entity(entity)
                     .insert(attack_animations.graph.clone())
-                    .insert(transitions);
+                    .insert(transitions)
+                    .insert(Swings(0));
             }
             _ => {
                 let mut transitions = AnimationTransitions::new();
@@ -239,6 +769,26 @@ fn add_animations(
     }
 }
 
+/// Attack animations of a skeleton played until now, to hear each swing
+#[derive(Component)]
+struct Swings(u32);
+
+fn play_swings(
+    mut players: Query<(&AnimationPlayer, &mut Swings)>,
+    attack_animations: Res<AttackAnimations>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+) {
+    for (player, mut swings) in &mut players {
+        let Some(animation) = player.animation(attack_animations.animations[0]) else {
+            continue;
+        };
+        if animation.completions() != swings.0 {
+            swings.0 = animation.completions();
+            audio_trigger.send(AudioTrigger::Swing);
+        }
+    }
+}
+
 fn set_weapons(
     mut commands: Commands,
     mut scenes_loaded: EventReader<SceneInstanceReady>,
@@ -310,25 +860,93 @@ fn set_weapons(
         }
     }
 }
-const MAX_SPEED: f32 = 8.0;
+/// Distance walked by a hobbit between two footsteps
+const STEP_LENGTH: f32 = 1.5;
 
+#[allow(clippy::type_complexity)]
 fn move_to_target(
     time: Res<Time>,
-    mut bodies: Query<(Entity, &mut LinearVelocity, &Target, &mut Transform)>,
+    level: Res<ActiveLevel>,
+    grid: Res<HobbitGrid>,
+    tolerances: Res<GoalTolerances>,
+    mutators: Res<Mutators>,
+    mut bodies: Query<(
+        Entity,
+        &mut Hobbit,
+        &mut LinearVelocity,
+        &Target,
+        &mut Transform,
+        Option<&SteeringModifier>,
+    )>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
 ) {
     let delta_time = time.delta_seconds();
 
-    for (_, mut linvel, target, mut transform) in &mut bodies {
+    let neighbours = bodies
+        .iter()
+        .map(|(entity, hobbit, _, _, transform, _)| {
+            (
+                entity,
+                (
+                    transform.translation.xz(),
+                    hobbit.kind.radius(),
+                    hobbit.state,
+                ),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
+    for (entity, mut hobbit, mut linvel, target, mut transform, modifier) in &mut bodies {
+        if hobbit.knocked_back() {
+            continue;
+        }
+        let modifier = modifier.copied().unwrap_or_default();
         let full_direction = target.next - transform.translation;
-        let desired_velocity = full_direction.xz().normalize() * MAX_SPEED;
+        let mut max_speed = hobbit.speed(&level.0) * modifier.speed * mutators.hobbit_speed();
+        if target.path.is_empty() {
+            max_speed *= tolerances.arrival(full_direction.xz().length());
+        }
+        let direction = full_direction.xz().normalize();
+        // steer away from hobbits going the same way, so that they flow around each other instead
+        // of being shoved by the physics
+        let position = transform.translation.xz();
+        let separation = grid
+            .around(cell_of(position), 1)
+            .filter(|other| *other != entity)
+            .filter_map(|other| neighbours.get(&other))
+            .filter(|(_, _, state)| *state == hobbit.state)
+            .map(|(other, radius, _)| {
+                let away = position - *other;
+                let reach = hobbit.kind.radius() + radius + SEPARATION_MARGIN;
+                let distance = away.length();
+                if distance > 0.0 && distance < reach {
+                    away / distance * (1.0 - distance / reach)
+                } else {
+                    Vec2::ZERO
+                }
+            })
+            .sum::<Vec2>();
+        let desired_velocity = if modifier.waiting {
+            Vec2::ZERO
+        } else {
+            (direction - direction.perp() * modifier.lateral + separation * SEPARATION_WEIGHT)
+                .normalize_or_zero()
+                * max_speed
+        };
         let steering = desired_velocity - linvel.0.xz();
         linvel.x += steering.x * delta_time;
         linvel.z += steering.y * delta_time;
-        if linvel.length() > MAX_SPEED {
-            linvel.0 = linvel.normalize() * MAX_SPEED;
+        if linvel.length() > max_speed {
+            linvel.0 = linvel.normalize() * max_speed;
         }
-        if target.path.is_empty() && linvel.length() > full_direction.length() {
-            linvel.0 *= 0.9;
+        // wind can push hobbits faster than they walk
+        let wind = level.0.wind_at(transform.translation);
+        linvel.x += wind.x * delta_time;
+        linvel.z += wind.z * delta_time;
+        let steps = (hobbit.walked / STEP_LENGTH) as u32;
+        hobbit.walked += linvel.length() * delta_time;
+        if (hobbit.walked / STEP_LENGTH) as u32 != steps {
+            audio_trigger.send(AudioTrigger::Footstep(linvel.length()));
         }
         let mut new_rotation = -linvel.0.z.atan2(linvel.0.x) + FRAC_PI_2;
         if new_rotation > PI {
@@ -338,62 +956,95 @@ fn move_to_target(
     }
 }
 
+/// Changes how a hobbit steers, set by the zones it's in
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct SteeringModifier {
+    /// Multiplier of the speed
+    pub speed: f32,
+    /// Steer sideways from the path, to the left when positive
+    pub lateral: f32,
+    /// Stay in place
+    pub waiting: bool,
+}
+
+impl Default for SteeringModifier {
+    fn default() -> Self {
+        SteeringModifier {
+            speed: 1.0,
+            lateral: 0.0,
+            waiting: false,
+        }
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 fn reach_target(
     mut commands: Commands,
-    mut bodies: Query<(Entity, &mut Target, &Transform, &mut Hobbit)>,
+    mut bodies: Query<(
+        Entity,
+        &mut Target,
+        &Transform,
+        &mut Hobbit,
+        &LinearVelocity,
+        Has<Queued>,
+    )>,
     mut game_events: EventWriter<GameEvent>,
     mut audio_trigger: EventWriter<AudioTrigger>,
+    level: Res<ActiveLevel>,
+    mut queues: ResMut<ChestQueues>,
+    tolerances: Res<GoalTolerances>,
+    mut sounds_at: EventWriter<SoundAt>,
+    demo: Option<Res<Demo>>,
 ) {
-    for (entity, mut target, transform, mut hobbit) in &mut bodies {
+    for (entity, mut target, transform, mut hobbit, linvel, queued) in &mut bodies {
         if target.path.is_empty() {
             if matches!(hobbit.state, HobbitState::Tired)
-                && transform.translation.distance(target.next) < 1.5
+                && transform.translation.distance(target.next) < tolerances.home
+                && demo.is_some()
             {
-                game_events.send(GameEvent::HomeWithTreasure);
+                // back to the chest, with a new target
+                hobbit.state = HobbitState::LFG;
+                hobbit.carrying = 0;
+                hobbit.walked = 0.0;
+                commands
+                    .entity(entity)
+                    .remove::<(Target, CarriedTreasure)>();
+                continue;
+            }
+            if matches!(hobbit.state, HobbitState::Tired)
+                && transform.translation.distance(target.next) < tolerances.home
+            {
+                game_events.send(GameEvent::Home { team: hobbit.team });
+                for _ in 0..hobbit.carrying {
+                    game_events.send(GameEvent::HomeWithTreasure { team: hobbit.team });
+                }
+                if hobbit.escorted {
+                    game_events.send(GameEvent::EscortedHome);
+                }
                 commands.entity(entity).despawn_recursive();
                 audio_trigger.send(AudioTrigger::Home);
+                sounds_at.send(SoundAt {
+                    trigger: AudioTrigger::Home,
+                    position: transform.translation,
+                });
             }
 
             if matches!(hobbit.state, HobbitState::LFG)
-                && transform.translation.distance(target.next) < 1.0
+                && !queued
+                && transform.translation.distance(target.next) < QUEUE_RADIUS + 1.0
             {
-                hobbit.state = HobbitState::Tired;
-                commands.entity(entity).remove::<Target>();
-                commands.entity(entity).with_children(|parent| {
-                    parent.spawn(ParticleSpawnerBundle::from_settings(
-                        ParticleSpawnerSettings {
-                            one_shot: false,
-                            rate: 10.0,
-                            emission_shape: EmissionShape::Circle {
-                                normal: Vec3::Y,
-                                radius: 0.5,
-                            },
-                            lifetime: RandF32::constant(0.25),
-                            inherit_parent_velocity: true,
-                            initial_velocity: RandVec3 {
-                                magnitude: RandF32 { min: 0., max: 10. },
-                                direction: Vec3::Y,
-                                spread: FRAC_PI_4,
-                            },
-                            initial_scale: RandF32 {
-                                min: 0.05,
-                                max: 0.1,
-                            },
-                            scale_curve: ParamCurve::constant(1.),
-                            color: Gradient::constant(
-                                (palettes::tailwind::YELLOW_800 * 5.0).into(),
-                            ),
-                            blend_mode: BlendMode::Blend,
-                            linear_drag: 0.1,
-                            pbr: true,
-                            ..default()
-                        },
-                    ));
-                });
-                audio_trigger.send(AudioTrigger::Treasure);
+                let Some(chest) = level.0.chests.iter().position(|chest| {
+                    vec2(chest.cell.1 as f32 * 4.0, chest.cell.2 as f32 * 4.0)
+                        .distance(target.next.xz())
+                        < tolerances.chest
+                }) else {
+                    continue;
+                };
+                queues.0[chest].push(entity);
+                commands.entity(entity).insert(Queued { chest });
             }
-        } else if !target.path.is_empty()
-            && transform.translation.distance(target.next) < MAX_SPEED / 10.0
+        } else if transform.translation.distance(target.next)
+            < tolerances.waypoint(linvel.xz().length())
         {
             let next = target.path.pop().unwrap();
             target.next = vec3(next.x, 1.0, next.y);
@@ -401,14 +1052,378 @@ fn reach_target(
     }
 }
 
+/// Hobbit waiting its turn at a chest, by its index in [`Level::chests`]
+#[derive(Component)]
+struct Queued {
+    chest: usize,
+}
+
+/// Hobbit taking its treasure from the chest
+#[derive(Component)]
+struct Looting(Timer);
+
+/// Hobbits arriving at a chest wait in a ring around it and take their treasure one at a time
+fn queue_at_chests(
+    mut commands: Commands,
+    level: Res<ActiveLevel>,
+    chests: Res<ChestTreasures>,
+    mut queues: ResMut<ChestQueues>,
+    mut hobbits: Query<(&Transform, &mut Target, Has<Looting>), With<Queued>>,
+) {
+    for (index, queue) in queues.0.iter_mut().enumerate() {
+        queue.retain(|entity| hobbits.contains(*entity));
+        if !chests.is_open(index) {
+            // nothing left to wait for, they'll look for another chest
+            for entity in queue.drain(..) {
+                commands.entity(entity).remove::<(Queued, Target)>();
+            }
+            continue;
+        }
+        let cell = level.0.chests[index].cell;
+        let chest = vec3(cell.1 as f32 * 4.0, 1.0, cell.2 as f32 * 4.0);
+        for (rank, entity) in queue.iter().enumerate() {
+            let Ok((transform, mut target, looting)) = hobbits.get_mut(*entity) else {
+                continue;
+            };
+            if rank == 0 {
+                target.next = chest;
+                if !looting && transform.translation.distance(chest) < 1.0 {
+                    commands.entity(*entity).insert(Looting(Timer::from_seconds(
+                        LOOTING_DURATION,
+                        TimerMode::Once,
+                    )));
+                }
+            } else {
+                let slot = rank - 1;
+                let radius = QUEUE_RADIUS + (slot / QUEUE_SLOTS) as f32 * QUEUE_RING_SPACING;
+                let around =
+                    Vec2::from_angle(TAU * (slot % QUEUE_SLOTS) as f32 / QUEUE_SLOTS as f32)
+                        * radius;
+                target.next = chest + vec3(around.x, 0.0, around.y);
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn take_treasure(
+    mut commands: Commands,
+    mut hobbits: Query<(Entity, &mut Hobbit, &mut Looting, &Queued, &Children)>,
+    mut models: Query<&mut Transform, (With<AnimatedKind>, Without<Hobbit>)>,
+    mut game_events: EventWriter<GameEvent>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+    mut chests: ResMut<ChestTreasures>,
+    mut queues: ResMut<ChestQueues>,
+    time: Res<Time>,
+    mut effects: EventWriter<PlayEffect>,
+) {
+    for (entity, mut hobbit, mut looting, queued, children) in &mut hobbits {
+        // bow down to the chest
+        let bow = if looting.0.tick(time.delta()).finished() {
+            0.0
+        } else {
+            (looting.0.fraction() * PI).sin() * FRAC_PI_4
+        };
+        for child in children {
+            if let Ok(mut model) = models.get_mut(*child) {
+                model.rotation = Quat::from_rotation_x(bow);
+            }
+        }
+        if !looting.0.finished() {
+            continue;
+        }
+
+        queues.0[queued.chest].retain(|other| *other != entity);
+        commands
+            .entity(entity)
+            .remove::<(Looting, Queued, Target)>();
+        let capacity = hobbit.kind.capacity();
+        let taken = match chests.0.get_mut(queued.chest) {
+            Some(Some(left)) => {
+                let taken = capacity.min(*left);
+                *left -= taken;
+                if taken > 0 && *left == 0 {
+                    game_events.send(GameEvent::ChestEmptied {
+                        chest: queued.chest,
+                    });
+                }
+                taken
+            }
+            _ => capacity,
+        };
+        if taken == 0 {
+            // the chest was emptied while waiting, look for another one
+            continue;
+        }
+        hobbit.state = HobbitState::Tired;
+        hobbit.carrying = taken;
+        commands.entity(entity).insert(CarriedTreasure);
+        effects.send(PlayEffect {
+            effect: Effect::Treasure,
+            transform: Transform::default(),
+            follow: Some(entity),
+        });
+        audio_trigger.send(AudioTrigger::Treasure);
+        game_events.send(GameEvent::TreasureTaken { team: hobbit.team });
+    }
+}
+
+/// Wind added to the acceleration of a particle spawner
+#[derive(Component)]
+struct Blown(Vec3);
+
+/// Particles are blown by the wind where they are emitted. Updated when the spawner moves, when
+/// it starts playing, and when the level changes.
+fn bend_particles(
+    mut commands: Commands,
+    level: Res<ActiveLevel>,
+    mut spawners: Query<(
+        Entity,
+        Ref<GlobalTransform>,
+        &mut ParticleSpawnerSettings,
+        Option<&mut Blown>,
+    )>,
+) {
+    for (entity, transform, mut settings, blown) in &mut spawners {
+        if !(level.is_changed() || transform.is_changed() || settings.is_added()) {
+            continue;
+        }
+        let wind = level.0.wind_at(transform.translation());
+        // settings inserted again by a pooled spawner don't have the previous wind anymore
+        let previous = match &blown {
+            Some(blown) if !settings.is_added() => blown.0,
+            _ => Vec3::ZERO,
+        };
+        if previous != wind {
+            settings.acceleration += wind - previous;
+        }
+        match blown {
+            Some(mut blown) => blown.0 = wind,
+            None => {
+                commands.entity(entity).insert(Blown(wind));
+            }
+        }
+    }
+}
+
+/// Hobbits walk on an invisible bridge over gaps, their model follows an arc above it
+fn jump_over_gaps(
+    level: Res<ActiveLevel>,
+    hobbits: Query<(&Transform, &Children), With<Hobbit>>,
+    mut models: Query<&mut Transform, (With<AnimatedKind>, Without<Hobbit>)>,
+) {
+    for (transform, children) in &hobbits {
+        let (x, z) = (transform.translation.x / 4.0, transform.translation.z / 4.0);
+        let over_gap = x >= -0.5
+            && z >= -0.5
+            && level.0.floors[0]
+                .get(z.round() as usize)
+                .and_then(|row| row.get(x.round() as usize))
+                == Some(&Tile::Empty);
+        let height = if over_gap {
+            // 0 at the edges of the cell, 1 at its center
+            let from_center = (x - x.round()).abs().max((z - z.round()).abs()) * 2.0;
+            JUMP_HEIGHT * (1.0 - from_center * from_center)
+        } else {
+            0.0
+        };
+        for child in children {
+            if let Ok(mut model) = models.get_mut(*child) {
+                model.translation.y = -1.0 + height;
+            }
+        }
+    }
+}
+
+/// Marks a hobbit bringing treasure back home
+#[derive(Component)]
+struct CarriedTreasure;
+
+/// Treasure in the hand of a hobbit
+#[derive(Component)]
+struct TreasureStack;
+
+fn attach_treasure(
+    mut commands: Commands,
+    carriers: Query<(Entity, &Hobbit), Added<CarriedTreasure>>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    assets: Res<GameAssets>,
+) {
+    let hand_name = Name::new("hand.l");
+    for (entity, hobbit) in &carriers {
+        let Some(hand) = children.iter_descendants(entity).find(|e| {
+            names
+                .get(*e)
+                .map(|name| name == &hand_name)
+                .unwrap_or(false)
+        }) else {
+            continue;
+        };
+        commands.entity(hand).with_children(|p| {
+            p.spawn((
+                SceneBundle {
+                    scene: assets.coin_stack.clone(),
+                    transform: Transform::from_translation(vec3(0.0, 0.2, 0.0))
+                        .with_scale(Vec3::splat(0.3 + 0.15 * hobbit.carrying as f32)),
+                    ..default()
+                },
+                TreasureStack,
+            ));
+        });
+    }
+}
+
+/// Hobbits of the [`Demo`] drop their treasure at home and go back to the chest
+fn drop_treasure(
+    mut commands: Commands,
+    mut dropped: RemovedComponents<CarriedTreasure>,
+    children: Query<&Children>,
+    stacks: Query<(), With<TreasureStack>>,
+    mut effects: EventWriter<StopEffect>,
+) {
+    for entity in dropped.read() {
+        effects.send(StopEffect {
+            effect: Effect::Treasure,
+            follow: entity,
+        });
+        for descendant in children.iter_descendants(entity) {
+            if stacks.contains(descendant) {
+                commands.entity(descendant).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Path to where the hobbit is going: a chest to loot, or back home
+fn hobbit_path(
+    level: &Level,
+    chests: &ChestTreasures,
+    navmesh: &polyanya::Mesh,
+    hobbit: &Hobbit,
+    from: Vec2,
+) -> Option<polyanya::Path> {
+    match hobbit.state {
+        HobbitState::LFG => path_to_chest(level, chests, navmesh, hobbit.team, from),
+        HobbitState::Tired => {
+            let home = level.spawns[hobbit.spawn].cell;
+            let mut exclusion = HashSet::new();
+            exclusion.insert(1);
+            navmesh.path_on_layers(
+                from,
+                Vec2::new(home.1 as f32 * 4.0, home.2 as f32 * 4.0),
+                exclusion,
+            )
+        }
+    }
+}
+
+/// Hobbits in a cell for it to be crowded
+const CONGESTION_CROWD: usize = 3;
+/// Extra length counted for each crowded cell a path goes through
+const CONGESTION_COST: f32 = 6.0;
+
+/// Cells crowded with hobbits, so that other hobbits spread over other routes when there are some
+#[derive(Resource, Default)]
+pub struct Congestion {
+    crowded: HashSet<(usize, usize)>,
+    /// The navmesh with the crowded cells blocked, to find paths around them. It's kept from one
+    /// step to the next, only the cells that start or stop being crowded are updated.
+    detour: Option<(polyanya::Mesh, NavMeshCells)>,
+}
+
+impl Congestion {
+    /// Crowded cells a path goes through
+    fn crossed(&self, from: Vec2, path: &polyanya::Path) -> usize {
+        let mut crossed = HashSet::new();
+        let mut previous = from;
+        for point in &path.path {
+            let steps = previous.distance(*point).ceil().max(1.0) as usize;
+            for step in 0..=steps {
+                let cell = cell_of(previous.lerp(*point, step as f32 / steps as f32));
+                if self.crowded.contains(&cell) {
+                    crossed.insert(cell);
+                }
+            }
+            previous = *point;
+        }
+        crossed.len()
+    }
+
+    /// The shortest path found by `find`, or the path around crowded cells when it costs less
+    /// once each crowded cell counts as [`CONGESTION_COST`] more length
+    fn least_congested(
+        &self,
+        navmesh: &polyanya::Mesh,
+        from: Vec2,
+        find: impl Fn(&polyanya::Mesh) -> Option<polyanya::Path>,
+    ) -> Option<polyanya::Path> {
+        let shortest = find(navmesh)?;
+        let crossed = self.crossed(from, &shortest);
+        if crossed == 0 {
+            return Some(shortest);
+        }
+        // hobbits already in a crowded cell, or going to one, have no path around
+        let Some(detour) = self.detour.as_ref().and_then(|(detour, _)| find(detour)) else {
+            return Some(shortest);
+        };
+        if detour.length < shortest.length + crossed as f32 * CONGESTION_COST {
+            Some(detour)
+        } else {
+            Some(shortest)
+        }
+    }
+
+    /// Search precision of the detour, the same as the one of the navmesh
+    fn set_delta(&mut self, delta: f32) {
+        if let Some((detour, _)) = self.detour.as_mut() {
+            detour.set_delta(delta);
+        }
+    }
+}
+
+fn track_congestion(
+    grid: Res<HobbitGrid>,
+    navmesh: Res<NavMesh>,
+    mut congestion: ResMut<Congestion>,
+) {
+    let crowded = grid
+        .0
+        .iter()
+        .filter(|(_, hobbits)| hobbits.len() >= CONGESTION_CROWD)
+        .map(|(cell, _)| *cell)
+        .collect::<HashSet<_>>();
+    if crowded == congestion.crowded && !navmesh.is_changed() {
+        return;
+    }
+    if navmesh.is_changed() {
+        // obstacles were placed or removed, the detour starts again from the navmesh
+        congestion.detour = None;
+    }
+    if !crowded.is_empty() || congestion.detour.is_some() {
+        let (detour, cells) = congestion.detour.get_or_insert_with(|| {
+            // cells already blocked by obstacles keep their walls, their neighbours don't list them
+            let detour = navmesh.0.clone();
+            let cells = NavMeshCells::new(&detour);
+            (detour, cells)
+        });
+        cells.set_blocked(detour, &crowded.iter().copied().collect::<Vec<_>>());
+    }
+    congestion.crowded = crowded;
+}
+
+#[allow(clippy::too_many_arguments)]
 fn give_target(
     mut commands: Commands,
     level: Res<ActiveLevel>,
-    bodies: Query<(Entity, &Hobbit, &Transform), Without<Target>>,
+    mut bodies: Query<(Entity, &mut Hobbit, &Transform), Without<Target>>,
     navmesh: Res<NavMesh>,
+    chests: Res<ChestTreasures>,
     mut path_status: ResMut<PathStatus>,
     mut local_timer: Local<Option<Timer>>,
     time: Res<Time>,
+    congestion: Res<Congestion>,
+    mut diagnostics: Diagnostics,
 ) {
     if let Some(timer) = local_timer.as_mut() {
         if timer.tick(time.delta()).just_finished() {
@@ -417,28 +1432,18 @@ fn give_target(
             return;
         }
     }
-    for (entity, hobbit, transform) in &bodies {
+    let mut queries = 0;
+    for (entity, mut hobbit, transform) in &mut bodies {
         let from = vec2(transform.translation.x, transform.translation.z);
-        let (to, exclusion) = match hobbit.state {
-            HobbitState::LFG => {
-                let mut exclusion = HashSet::new();
-                exclusion.insert(2);
-                (
-                    Vec2::new(level.0.end.1 as f32 * 4.0, level.0.end.2 as f32 * 4.0),
-                    exclusion,
-                )
-            }
-
-            HobbitState::Tired => {
-                let mut exclusion = HashSet::new();
-                exclusion.insert(1);
-                (
-                    Vec2::new(level.0.start.1 as f32 * 4.0, level.0.start.2 as f32 * 4.0),
-                    exclusion,
-                )
-            }
-        };
-        if let Some(path) = navmesh.0.path_on_layers(from, to, exclusion) {
+        if hobbit.state == HobbitState::LFG && !chests.any_open(&level.0, hobbit.team) {
+            // nothing left to loot, go back home empty handed
+            hobbit.state = HobbitState::Tired;
+        }
+        queries += 1;
+        let path = congestion.least_congested(&navmesh.0, from, |mesh| {
+            hobbit_path(&level.0, &chests, mesh, &hobbit, from)
+        });
+        if let Some(path) = path {
             let (next, remaining) = path.path.split_first().unwrap();
             let mut remaining = remaining.to_vec();
             remaining.reverse();
@@ -454,17 +1459,58 @@ fn give_target(
             *local_timer = Some(Timer::from_seconds(0.5, TimerMode::Once));
         }
     }
+    diagnostics.add_measurement(&PATH_QUERIES, || queries as f64);
+}
+
+/// Paths computed again during a gameplay step at most, the other hobbits wait for the next steps
+const PATH_REQUESTS_PER_STEP: usize = 8;
+
+/// Hobbits waiting for their path to be computed again. Only a few are served each step, the ones
+/// closest to a skeleton first, so that many hobbits don't all search at once.
+#[derive(Resource, Default)]
+pub struct PathRequestQueue {
+    pending: Vec<Entity>,
+    /// Skeletons of the level
+    hazards: Vec<Vec2>,
+}
+
+impl PathRequestQueue {
+    /// Distance to the closest skeleton, hobbits closer to one are served first
+    fn danger(&self, position: Vec2) -> f32 {
+        self.hazards
+            .iter()
+            .map(|hazard| hazard.distance(position))
+            .fold(f32::MAX, f32::min)
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn reevaluate_path(
     mut commands: Commands,
     level: Res<ActiveLevel>,
-    mut bodies: Query<(Entity, &Hobbit, &Transform, &mut Target)>,
+    mut bodies: Query<(Entity, &Hobbit, &Transform, &mut Target), Without<Queued>>,
     mut navmesh: ResMut<NavMesh>,
+    chests: Res<ChestTreasures>,
     time: Res<Time>,
     mut local_timer: Local<Option<Timer>>,
     mut entity_deltas: Local<EntityHashMap<f32>>,
+    mut queue: ResMut<PathRequestQueue>,
+    mut congestion: ResMut<Congestion>,
+    mut diagnostics: Diagnostics,
 ) {
+    if level.is_changed() {
+        queue.pending.clear();
+        queue.hazards = level.0.floors[0]
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, tile)| **tile == Tile::Skeleton)
+                    .map(move |(x, _)| vec2(x as f32 * 4.0, y as f32 * 4.0))
+            })
+            .collect();
+    }
     if let Some(timer) = local_timer.as_mut() {
         if timer.tick(time.delta()).just_finished() {
             *local_timer = None;
@@ -472,155 +1518,362 @@ fn reevaluate_path(
             return;
         }
     }
-    let mut i = 0;
-    for (entity, hobbit, transform, mut target) in &mut bodies {
-        if target.reevaluate.tick(time.delta()).finished() {
-            let from = vec2(transform.translation.x, transform.translation.z);
-            let (to, exclusion) = match hobbit.state {
-                HobbitState::LFG => {
-                    let mut exclusion = HashSet::new();
-                    exclusion.insert(2);
-                    (
-                        Vec2::new(level.0.end.1 as f32 * 4.0, level.0.end.2 as f32 * 4.0),
-                        exclusion,
-                    )
-                }
+    for (entity, _, _, mut target) in &mut bodies {
+        if target.reevaluate.tick(time.delta()).finished() && !queue.pending.contains(&entity) {
+            queue.pending.push(entity);
+        }
+    }
+    let mut pending = std::mem::take(&mut queue.pending);
+    pending.retain(|entity| bodies.contains(*entity));
+    pending.sort_by(|a, b| {
+        let [a, b] = [a, b].map(|entity| {
+            let (_, _, transform, _) = bodies.get(*entity).unwrap();
+            queue.danger(transform.translation.xz())
+        });
+        a.total_cmp(&b)
+    });
+    queue.pending = pending.split_off(pending.len().min(PATH_REQUESTS_PER_STEP));
 
-                HobbitState::Tired => {
-                    let mut exclusion = HashSet::new();
-                    exclusion.insert(1);
-                    (
-                        Vec2::new(level.0.start.1 as f32 * 4.0, level.0.start.2 as f32 * 4.0),
-                        exclusion,
-                    )
-                }
-            };
-            let entity_delta = entity_deltas.get(&entity).cloned().unwrap_or(0.1);
-            navmesh.0.set_delta(entity_delta);
-            if let Some(path) = navmesh.0.path_on_layers(from, to, exclusion) {
-                i += 1;
-                let (next, remaining) = path.path.split_first().unwrap();
-                let mut remaining = remaining.to_vec();
-                remaining.reverse();
-                target.next = vec3(next.x, 1.0, next.y);
-                target.path = remaining;
-                target.reevaluate.reset();
-                entity_deltas.remove(&entity);
-            } else {
-                warn!("path blocked on recompute");
-                let delta = entity_deltas.entry(entity).or_insert(0.1);
-                *delta *= 3.0;
-                if *delta > 10.0 {
-                    commands.entity(entity).remove::<Target>();
-                }
-                *local_timer = Some(Timer::from_seconds(0.25, TimerMode::Once));
+    let mut i = 0;
+    let mut queries = 0;
+    for entity in pending {
+        let Ok((entity, hobbit, transform, mut target)) = bodies.get_mut(entity) else {
+            continue;
+        };
+        let from = vec2(transform.translation.x, transform.translation.z);
+        if hobbit.state == HobbitState::LFG && !chests.any_open(&level.0, hobbit.team) {
+            // the target will be given again, home this time
+            commands.entity(entity).remove::<Target>();
+            continue;
+        }
+        let entity_delta = entity_deltas.get(&entity).cloned().unwrap_or(0.1);
+        // the search precision doesn't change the navmesh, don't rebuild what depends on it
+        navmesh.bypass_change_detection().0.set_delta(entity_delta);
+        congestion.set_delta(entity_delta);
+        queries += 1;
+        let path = congestion.least_congested(&navmesh.0, from, |mesh| {
+            hobbit_path(&level.0, &chests, mesh, hobbit, from)
+        });
+        if let Some(path) = path {
+            i += 1;
+            let (next, remaining) = path.path.split_first().unwrap();
+            let mut remaining = remaining.to_vec();
+            remaining.reverse();
+            target.next = vec3(next.x, 1.0, next.y);
+            target.path = remaining;
+            target.reevaluate.reset();
+            entity_deltas.remove(&entity);
+        } else {
+            warn!("path blocked on recompute");
+            let delta = entity_deltas.entry(entity).or_insert(0.1);
+            *delta *= 3.0;
+            if *delta > 10.0 {
+                commands.entity(entity).remove::<Target>();
             }
-            navmesh.0.set_delta(0.1);
+            *local_timer = Some(Timer::from_seconds(0.25, TimerMode::Once));
         }
+        navmesh.bypass_change_detection().0.set_delta(0.1);
+        congestion.set_delta(0.1);
     }
+    diagnostics.add_measurement(&PATH_QUERIES, || queries as f64);
     if i != 0 {
-        info!("re-evaluating path for {} hobbits", i);
+        info!(
+            "re-evaluating path for {} hobbits, {} waiting",
+            i,
+            queue.pending.len()
+        );
     }
 }
 
-#[cfg(feature = "debug")]
-fn display_paths(query: Query<(&Transform, &Target)>, mut gizmos: Gizmos) {
-    use bevy::color::palettes;
+/// Ring above each hobbit: green going for the treasure, amber coming back with it, red when
+/// waiting for a path to open
+fn display_hobbit_state(
+    hobbits: Query<(&Transform, &Hobbit, Option<&Target>)>,
+    path_status: Res<PathStatus>,
+    mut gizmos: Gizmos,
+) {
+    for (transform, hobbit, target) in &hobbits {
+        let color = if target.is_none() && *path_status == PathStatus::Blocked {
+            palettes::tailwind::RED_500
+        } else {
+            match hobbit.state {
+                HobbitState::LFG => palettes::tailwind::GREEN_500,
+                HobbitState::Tired => palettes::tailwind::AMBER_400,
+            }
+        };
+        let position = transform.translation + Vec3::Y * 2.2 * hobbit.kind.scale();
+        gizmos.circle(position, Dir3::Y, 0.4, color);
+        gizmos.circle(position, Dir3::Y, 0.25, color);
+    }
+}
 
-    for (transform, target) in &query {
-        let mut path = target
-            .path
-            .iter()
-            .map(|v| vec3(v.x, 0.2, v.y))
-            .collect::<Vec<_>>();
-        path.push(vec3(target.next.x, 0.3, target.next.z));
-        path.push(vec3(transform.translation.x, 0.3, transform.translation.z));
-        gizmos.linestrip(path, palettes::tailwind::TEAL_300);
+/// Star under the hobbit to escort, so it can be followed
+fn display_escorted(hobbits: Query<(&Transform, &Hobbit)>, mut gizmos: Gizmos) {
+    for (transform, hobbit) in &hobbits {
+        if !hobbit.escorted {
+            continue;
+        }
+        let center = vec3(transform.translation.x, 0.3, transform.translation.z);
+        let points = (0..=10).map(|i| {
+            let radius = if i % 2 == 0 { 1.2 } else { 0.5 };
+            let angle = TAU * i as f32 / 10.0;
+            center + vec3(angle.cos(), 0.0, angle.sin()) * radius
+        });
+        gizmos.linestrip(points, palettes::tailwind::AMBER_300);
     }
 }
 
+/// Circle under each hobbit with the color of its team
+fn display_teams(hobbits: Query<(&Transform, &Hobbit)>, mut gizmos: Gizmos) {
+    for (transform, hobbit) in &hobbits {
+        gizmos.circle(
+            vec3(transform.translation.x, 0.25, transform.translation.z),
+            Dir3::Y,
+            0.8 * hobbit.kind.scale(),
+            TEAM_COLORS[hobbit.team],
+        );
+    }
+}
+
+/// Remaining path of a hobbit, from its current position to its destination
+fn path_points(transform: &Transform, target: &Target) -> Vec<Vec3> {
+    let mut path = vec![
+        vec3(transform.translation.x, 0.3, transform.translation.z),
+        vec3(target.next.x, 0.3, target.next.z),
+    ];
+    path.extend(target.path.iter().rev().map(|v| vec3(v.x, 0.3, v.y)));
+    path
+}
+
+fn display_hovered_path(
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    windows: Query<&Window>,
+    hobbits: Query<(&Transform, &Hobbit, Option<&Target>)>,
+    mut gizmos: Gizmos,
+) {
+    let Ok((camera, camera_transform)) = camera_query.get_single() else {
+        return;
+    };
+    let Some(cursor_position) = windows.single().cursor_position() else {
+        return;
+    };
+    let Some(ray) = camera.viewport_to_world(camera_transform, cursor_position) else {
+        return;
+    };
+
+    // Closest hobbit along the ray whose body is close enough to it
+    let Some((transform, hobbit, target)) = hobbits
+        .iter()
+        .filter_map(|(transform, hobbit, target)| {
+            let center = transform.translation + Vec3::Y * 0.5 * hobbit.kind.scale();
+            let along = (center - ray.origin).dot(*ray.direction).max(0.0);
+            let distance = ray.get_point(along).distance(center);
+            (distance < 1.2 * hobbit.kind.scale()).then_some((along, (transform, hobbit, target)))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, hovered)| hovered)
+    else {
+        return;
+    };
+
+    let color = match hobbit.state {
+        HobbitState::LFG => palettes::tailwind::TEAL_300,
+        HobbitState::Tired => palettes::tailwind::AMBER_300,
+    };
+    gizmos.circle(
+        vec3(transform.translation.x, 0.3, transform.translation.z),
+        Dir3::Y,
+        1.0,
+        color,
+    );
+    let Some(target) = target else {
+        return;
+    };
+    let path = path_points(transform, target);
+    gizmos.circle(*path.last().unwrap(), Dir3::Y, 0.5, color);
+    gizmos.linestrip(path, color);
+}
+
+#[cfg(feature = "debug")]
+fn display_paths(query: Query<(&Transform, &Target, &Hobbit)>, mut gizmos: Gizmos) {
+    for (transform, target, hobbit) in &query {
+        let color = hobbit.color();
+        gizmos.circle(
+            vec3(transform.translation.x, 0.3, transform.translation.z),
+            Dir3::Y,
+            0.8,
+            color,
+        );
+        gizmos.linestrip(path_points(transform, target), color);
+    }
+}
+
+/// Sent when the player skips the intro of the level, so that hobbits start coming right away
+#[derive(Event)]
+pub struct SkipIntro;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
 pub enum GameEvent {
-    HomeWithTreasure,
-    CollidedWithHobbit,
+    HomeWithTreasure {
+        team: usize,
+    },
+    CollidedWithHobbit {
+        with: ColliderKind,
+    },
+    /// The last treasure of the chest, by its index in [`Level::chests`], has been taken
+    ChestEmptied {
+        chest: usize,
+    },
+    /// The hobbit of the escort objective made it back home
+    EscortedHome,
+    /// The hobbit of the escort objective was lost
+    EscortedLost,
+    /// Hobbits came out of a spawn point of the team
+    Spawned {
+        team: usize,
+        hobbits: u32,
+    },
+    /// A hobbit made it back home, with or without treasure
+    Home {
+        team: usize,
+    },
+    /// A hobbit took treasure from a chest
+    TreasureTaken {
+        team: usize,
+    },
+    /// Two hobbits that could collide passed close to each other without touching
+    NearMiss,
 }
 
-#[derive(Component)]
-struct Explosion(Timer);
+/// Hobbits closer than this, without touching, narrowly missed each other
+const NEAR_MISS_DISTANCE: f32 = 1.5;
+
+/// Each pair of hobbits is only reported once while they stay close
+fn detect_near_misses(
+    hobbits: Query<(Entity, &Transform, &Hobbit)>,
+    mut close: Local<HashSet<(Entity, Entity)>>,
+    mut game_events: EventWriter<GameEvent>,
+) {
+    let mut still_close = HashSet::new();
+    for [(entity_a, transform_a, hobbit_a), (entity_b, transform_b, hobbit_b)] in
+        hobbits.iter_combinations()
+    {
+        if hobbit_a.state == hobbit_b.state && hobbit_a.team == hobbit_b.team {
+            continue;
+        }
+        let distance = transform_a.translation.distance(transform_b.translation)
+            - hobbit_a.kind.radius()
+            - hobbit_b.kind.radius();
+        if distance <= 0.0 || distance > NEAR_MISS_DISTANCE {
+            continue;
+        }
+        let pair = (entity_a.min(entity_b), entity_a.max(entity_b));
+        if !close.contains(&pair) {
+            game_events.send(GameEvent::NearMiss);
+        }
+        still_close.insert(pair);
+    }
+    *close = still_close;
+}
 
-#[derive(Component, PartialEq, Eq, Debug)]
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum ColliderKind {
     Hobbit,
     Blade,
 }
 
+#[allow(clippy::type_complexity)]
 fn colliding_hobbits(
     mut commands: Commands,
-    query: Query<(
-        Entity,
-        &CollidingEntities,
-        Option<&Hobbit>,
-        &Transform,
-        &ColliderKind,
+    mut queries: ParamSet<(
+        Query<(
+            Entity,
+            &CollidingEntities,
+            Option<&Hobbit>,
+            &GlobalTransform,
+            &ColliderKind,
+            Has<Queued>,
+        )>,
+        Query<(&mut Hobbit, &mut LinearVelocity, &Transform)>,
     )>,
     mut game_events: EventWriter<GameEvent>,
-    mut explosion_query: Query<(Entity, &mut Explosion)>,
     time: Res<Time>,
     mut audio_trigger: EventWriter<AudioTrigger>,
+    mut effects: EventWriter<PlayEffect>,
+    mut sounds_at: EventWriter<SoundAt>,
 ) {
-    for (entity, colliding_entities, hobbit, transform, _) in &query {
-        let Some(hobbit) = hobbit else {
-            continue;
-        };
-        for other_entity in colliding_entities.iter() {
-            if let Ok((_, _, other_hobbit, _, other_kind)) = query.get(*other_entity) {
-                if other_kind == &ColliderKind::Blade
-                    || (other_hobbit.is_some() && other_hobbit.unwrap().state != hobbit.state)
+    let mut hits = Vec::new();
+    {
+        let query = queries.p0();
+        for (entity, colliding_entities, hobbit, _, _, queued) in &query {
+            let Some(hobbit) = hobbit else {
+                continue;
+            };
+            for other_entity in colliding_entities.iter() {
+                if let Ok((_, _, other_hobbit, other_transform, other_kind, other_queued)) =
+                    query.get(*other_entity)
                 {
-                    audio_trigger.send(AudioTrigger::Hurt);
-
-                    game_events.send(GameEvent::CollidedWithHobbit);
-                    commands.entity(entity).despawn_recursive();
-                    commands
-                        .spawn(ParticleSpawnerBundle::from_settings(
-                            ParticleSpawnerSettings {
-                                one_shot: true,
-                                rate: 500.0,
-                                emission_shape: EmissionShape::Circle {
-                                    normal: Vec3::Y,
-                                    radius: 1.0,
-                                },
-                                lifetime: RandF32::constant(0.4),
-                                inherit_parent_velocity: true,
-                                initial_velocity: RandVec3 {
-                                    magnitude: RandF32 { min: 0., max: 10. },
-                                    direction: Vec3::Y,
-                                    spread: FRAC_PI_8,
-                                },
-                                initial_scale: RandF32 {
-                                    min: 0.02,
-                                    max: 0.08,
-                                },
-                                scale_curve: ParamCurve::constant(1.),
-                                color: Gradient::constant(palettes::tailwind::RED_500.into()),
-                                blend_mode: BlendMode::Blend,
-                                linear_drag: 0.1,
-                                pbr: false,
-                                ..default()
-                            },
-                        ))
-                        .insert((
-                            *transform,
-                            Explosion(Timer::from_seconds(0.5, TimerMode::Once)),
-                        ));
+                    // hobbits around a chest make way for each other
+                    if other_hobbit.is_some() && (queued || other_queued) {
+                        continue;
+                    }
+                    // hobbits of different teams must not cross paths
+                    if other_kind == &ColliderKind::Blade
+                        || other_hobbit.is_some_and(|other| {
+                            other.state != hobbit.state || other.team != hobbit.team
+                        })
+                    {
+                        hits.push((entity, other_transform.translation(), *other_kind));
+                        break;
+                    }
                 }
             }
         }
     }
 
-    for (entity, mut explosion) in &mut explosion_query {
-        if explosion.0.tick(time.delta()).just_finished() {
-            commands.entity(entity).despawn_recursive();
+    let mut hobbits = queries.p1();
+    for (mut hobbit, _, _) in &mut hobbits {
+        if let Some(timer) = hobbit.invulnerability.as_mut() {
+            if timer.tick(time.delta()).finished() {
+                hobbit.invulnerability = None;
+            }
+        }
+    }
+
+    for (entity, from, with) in hits {
+        let Ok((mut hobbit, mut linvel, transform)) = hobbits.get_mut(entity) else {
+            continue;
+        };
+        if hobbit.invulnerability.is_some() {
+            continue;
         }
+        audio_trigger.send(AudioTrigger::Hurt);
+        sounds_at.send(SoundAt {
+            trigger: AudioTrigger::Hurt,
+            position: transform.translation,
+        });
+        hobbit.health = hobbit.health.saturating_sub(1);
+
+        if hobbit.health > 0 {
+            hobbit.invulnerability = Some(Timer::from_seconds(
+                INVULNERABILITY_DURATION,
+                TimerMode::Once,
+            ));
+            let away = (transform.translation - from).xz().normalize_or_zero() * KNOCKBACK_SPEED;
+            linvel.x = away.x;
+            linvel.z = away.y;
+            continue;
+        }
+
+        game_events.send(GameEvent::CollidedWithHobbit { with });
+        if hobbit.escorted {
+            game_events.send(GameEvent::EscortedLost);
+        }
+        commands.entity(entity).despawn_recursive();
+        effects.send(PlayEffect {
+            effect: Effect::Death,
+            transform: *transform,
+            follow: None,
+        });
     }
 }
@@ -0,0 +1,183 @@
+//! Playing with a controller: the d-pad moves the focus between buttons and A presses the focused
+//! one, and while playing the right stick moves the cursor and the right trigger clicks, to place
+//! bonus on the level.
+
+use bevy::{input::InputSystem, prelude::*, ui::UiSystem, window::PrimaryWindow};
+
+use crate::GameState;
+
+/// Pixels per second the cursor moves with the right stick fully tilted
+const CURSOR_SPEED: f32 = 800.0;
+/// Stick tilt under which the cursor doesn't move
+const STICK_DEAD_ZONE: f32 = 0.15;
+/// Perpendicular distance is worth more than distance in the direction of the d-pad, so that the
+/// focus moves to the button in line before the one closest
+const OFF_AXIS_WEIGHT: f32 = 2.0;
+
+/// The button the controller acts on
+#[derive(Component)]
+pub struct Focused;
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PreUpdate,
+            (
+                (move_cursor, click_with_trigger)
+                    .chain()
+                    .run_if(in_state(GameState::InGame))
+                    .after(InputSystem)
+                    .before(UiSystem::Focus),
+                (move_focus, press_focused).chain().after(UiSystem::Focus),
+            ),
+        );
+    }
+}
+
+fn just_pressed(
+    gamepads: &Gamepads,
+    buttons: &ButtonInput<GamepadButton>,
+    button_type: GamepadButtonType,
+) -> bool {
+    gamepads
+        .iter()
+        .any(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, button_type)))
+}
+
+/// Move the focus to the closest visible button in the direction pressed on the d-pad
+fn move_focus(
+    mut commands: Commands,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    buttons: Query<(Entity, &GlobalTransform, &ViewVisibility), With<Button>>,
+    focused: Query<Entity, With<Focused>>,
+) {
+    let direction = [
+        (GamepadButtonType::DPadUp, Vec2::NEG_Y),
+        (GamepadButtonType::DPadDown, Vec2::Y),
+        (GamepadButtonType::DPadLeft, Vec2::NEG_X),
+        (GamepadButtonType::DPadRight, Vec2::X),
+    ]
+    .into_iter()
+    .find(|(button_type, _)| just_pressed(&gamepads, &gamepad_buttons, *button_type))
+    .map(|(_, direction)| direction);
+    if just_pressed(&gamepads, &gamepad_buttons, GamepadButtonType::East) {
+        for entity in &focused {
+            commands.entity(entity).remove::<(Focused, Outline)>();
+        }
+        return;
+    }
+    let Some(direction) = direction else {
+        return;
+    };
+
+    let visible = buttons
+        .iter()
+        .filter(|(_, _, visibility)| visibility.get())
+        .map(|(entity, transform, _)| (entity, transform.translation().truncate()));
+    let current = focused
+        .get_single()
+        .ok()
+        .and_then(|entity| buttons.get(entity).ok())
+        .filter(|(_, _, visibility)| visibility.get())
+        .map(|(entity, transform, _)| (entity, transform.translation().truncate()));
+
+    let next = match current {
+        Some((current, from)) => visible
+            .filter(|(entity, _)| *entity != current)
+            .filter_map(|(entity, position)| {
+                let offset = position - from;
+                let along = offset.dot(direction);
+                (along > 0.0).then(|| {
+                    let across = offset.perp_dot(direction).abs();
+                    (entity, along + across * OFF_AXIS_WEIGHT)
+                })
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(entity, _)| entity),
+        // start from the top left button
+        None => visible
+            .min_by(|a, b| (a.1.y, a.1.x).partial_cmp(&(b.1.y, b.1.x)).unwrap())
+            .map(|(entity, _)| entity),
+    };
+    if let Some(next) = next {
+        for entity in &focused {
+            commands.entity(entity).remove::<(Focused, Outline)>();
+        }
+        commands.entity(next).insert((
+            Focused,
+            Outline::new(Val::Px(3.0), Val::Px(2.0), Color::WHITE),
+        ));
+    }
+}
+
+/// Press the focused button with A, buttons react to the change of their interaction
+fn press_focused(
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    focused: Query<Entity, With<Focused>>,
+    mut interactions: Query<&mut Interaction>,
+    mut pressed: Local<Option<Entity>>,
+) {
+    // release the button pressed last frame
+    if let Some(entity) = pressed.take() {
+        if let Ok(mut interaction) = interactions.get_mut(entity) {
+            interaction.set_if_neq(Interaction::None);
+        }
+    }
+    if !just_pressed(&gamepads, &gamepad_buttons, GamepadButtonType::South) {
+        return;
+    }
+    if let Ok(entity) = focused.get_single() {
+        if let Ok(mut interaction) = interactions.get_mut(entity) {
+            *interaction = Interaction::Pressed;
+            *pressed = Some(entity);
+        }
+    }
+}
+
+/// Move the cursor with the right stick
+fn move_cursor(
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    time: Res<Time<Real>>,
+) {
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+    for gamepad in gamepads.iter() {
+        let tilt = Vec2::new(
+            axes.get(GamepadAxis::new(gamepad, GamepadAxisType::RightStickX))
+                .unwrap_or_default(),
+            axes.get(GamepadAxis::new(gamepad, GamepadAxisType::RightStickY))
+                .unwrap_or_default(),
+        );
+        if tilt.length() < STICK_DEAD_ZONE {
+            continue;
+        }
+        let size = window.size();
+        let from = window.cursor_position().unwrap_or(size / 2.0);
+        // window coordinates go down
+        let to = from + Vec2::new(tilt.x, -tilt.y) * CURSOR_SPEED * time.delta_seconds();
+        window.set_cursor_position(Some(to.clamp(Vec2::ZERO, size - Vec2::ONE)));
+    }
+}
+
+/// Click where the cursor is with the right trigger, like the left mouse button
+fn click_with_trigger(
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    mut mouse: ResMut<ButtonInput<MouseButton>>,
+) {
+    for gamepad in gamepads.iter() {
+        let trigger = GamepadButton::new(gamepad, GamepadButtonType::RightTrigger2);
+        if gamepad_buttons.just_pressed(trigger) {
+            mouse.press(MouseButton::Left);
+        }
+        if gamepad_buttons.just_released(trigger) {
+            mouse.release(MouseButton::Left);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Hints for players stuck on a level: after a few failures, the lost screen offers to play the
+//! level again with ghosts of obstacles where a winning layout puts them.
+
+use bevy::{color::palettes, prelude::*};
+
+use crate::{
+    game::ActiveLevel,
+    play::{obstacle_cell, GameInProgress, SpawnedObstacle},
+    GameState,
+};
+
+/// Failures on a level before a hint is offered
+pub const HINT_AFTER_FAILURES: u32 = 3;
+
+/// The hint of a level was asked for, and is shown every time it's played until going back to the
+/// menu
+#[derive(Resource)]
+pub struct Hint {
+    pub level: usize,
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Menu), |mut commands: Commands| {
+            commands.remove_resource::<Hint>()
+        })
+        .add_systems(
+            Update,
+            draw_hint.run_if(
+                in_state(GameState::InGame)
+                    .and_then(resource_exists::<Hint>)
+                    .and_then(resource_exists::<ActiveLevel>),
+            ),
+        );
+    }
+}
+
+/// Ghost obstacles on the cells of the hint that don't have one yet
+fn draw_hint(
+    hint: Res<Hint>,
+    game: Res<GameInProgress>,
+    level: Res<ActiveLevel>,
+    obstacles: Query<&Transform, With<SpawnedObstacle>>,
+    mut gizmos: Gizmos,
+) {
+    if hint.level != game.level {
+        return;
+    }
+    for cell in &level.0.hint {
+        if obstacles
+            .iter()
+            .any(|transform| obstacle_cell(transform) == *cell)
+        {
+            continue;
+        }
+        gizmos.cuboid(
+            Transform::from_xyz(cell.0 as f32 * 4.0, 1.5, cell.1 as f32 * 4.0)
+                .with_scale(Vec3::new(3.0, 3.0, 3.0)),
+            palettes::tailwind::SKY_300.with_alpha(0.6),
+        );
+    }
+}
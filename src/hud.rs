@@ -0,0 +1,162 @@
+//! Placement of the panels shown while playing. Percent positions made for a 16:9 window put panels
+//! at the far edges of ultrawide monitors and squeeze them on portrait screens, so panels are
+//! placed according to a layout picked from the aspect ratio of the window.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_easings::{EasingChainComponent, EasingComponent};
+
+/// Windows wider than this are ultrawide
+const ULTRAWIDE_RATIO: f32 = 2.0;
+/// Windows narrower than this are portrait
+const PORTRAIT_RATIO: f32 = 1.0;
+/// Aspect ratio of the safe area panels are kept in on ultrawide windows
+const SAFE_AREA_RATIO: f32 = 16.0 / 9.0;
+
+#[derive(Resource, Clone, Copy, PartialEq, Debug, Default)]
+pub enum HudLayout {
+    #[default]
+    Standard,
+    /// Panels are kept in a centered 16:9 area, `inset` being the percent of the width left empty
+    /// on each side
+    Ultrawide { inset: f32 },
+    /// Panels take the full width and are stacked from the top
+    Portrait,
+}
+
+impl HudLayout {
+    pub fn from_aspect_ratio(ratio: f32) -> Self {
+        if ratio > ULTRAWIDE_RATIO {
+            HudLayout::Ultrawide {
+                inset: ((1.0 - SAFE_AREA_RATIO / ratio) * 50.0).round(),
+            }
+        } else if ratio < PORTRAIT_RATIO {
+            HudLayout::Portrait
+        } else {
+            HudLayout::Standard
+        }
+    }
+
+    /// Style of the panel when shown, keeping everything else from `style`
+    pub fn place(&self, panel: HudPanel, style: &Style) -> Style {
+        // (left or right, top or bottom, width, height), in percent of the window
+        let (x, y, width, height) = match (self, panel) {
+            (HudLayout::Portrait, HudPanel::Message) => (0.0, 0.0, 100.0, 15.0),
+            (HudLayout::Portrait, HudPanel::MessageShown) => (5.0, 20.0, 90.0, 50.0),
+            (HudLayout::Portrait, HudPanel::Title) => (0.0, 0.0, 100.0, 3.0),
+            (HudLayout::Portrait, HudPanel::Progress) => (0.0, 15.0, 50.0, 35.0),
+            (HudLayout::Portrait, HudPanel::Blocked) => (0.0, 0.0, 50.0, 25.0),
+            (_, HudPanel::Message) => (30.0, 0.0, 70.0, 20.0),
+            (_, HudPanel::MessageShown) => (30.0, 20.0, 40.0, 60.0),
+            (_, HudPanel::Title) => (70.0, 0.0, 30.0, 3.0),
+            (_, HudPanel::Progress) => (0.0, 0.0, 20.0, 40.0),
+            (_, HudPanel::Blocked) => (0.0, 0.0, 30.0, 40.0),
+        };
+        let (x, width) = match self {
+            HudLayout::Ultrawide { inset } => {
+                let scale = 1.0 - inset * 2.0 / 100.0;
+                (inset + x * scale, width * scale)
+            }
+            _ => (x, width),
+        };
+        let mut placed = Style {
+            width: Val::Percent(width),
+            height: Val::Percent(height),
+            ..style.clone()
+        };
+        match panel {
+            HudPanel::Blocked => {
+                placed.right = Val::Percent(x);
+                placed.bottom = Val::Percent(y);
+            }
+            _ => {
+                placed.left = Val::Percent(x);
+                placed.top = Val::Percent(y);
+            }
+        }
+        placed
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HudPanel {
+    /// Level message, once it has been read
+    Message,
+    /// Level message, when it's first shown
+    MessageShown,
+    /// Level title, for levels without a message
+    Title,
+    Progress,
+    Blocked,
+}
+
+impl HudPanel {
+    /// Whether the panel is shown, panels are hidden by moving them out of the window
+    fn is_shown(&self, style: &Style) -> bool {
+        let slide = match self {
+            HudPanel::Message | HudPanel::MessageShown | HudPanel::Title => style.top,
+            HudPanel::Progress => style.left,
+            HudPanel::Blocked => style.right,
+        };
+        !matches!(slide, Val::Percent(offset) if offset < 0.0)
+    }
+
+    /// Keep the panel out of the window after placing it
+    fn keep_hidden(&self, placed: &mut Style, style: &Style) {
+        match self {
+            HudPanel::Message | HudPanel::MessageShown | HudPanel::Title => placed.top = style.top,
+            HudPanel::Progress => placed.left = style.left,
+            HudPanel::Blocked => placed.right = style.right,
+        }
+    }
+}
+
+/// A panel of the HUD, and the layout it was last placed for
+#[derive(Component)]
+pub struct Anchored {
+    pub panel: HudPanel,
+    pub layout: HudLayout,
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HudLayout>()
+            .add_systems(Update, (pick_layout, reflow).chain());
+    }
+}
+
+fn pick_layout(windows: Query<&Window, With<PrimaryWindow>>, mut layout: ResMut<HudLayout>) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    if window.height() > 0.0 {
+        layout.set_if_neq(HudLayout::from_aspect_ratio(
+            window.width() / window.height(),
+        ));
+    }
+}
+
+/// Place again panels placed for another layout, once they're done moving
+#[allow(clippy::type_complexity)]
+fn reflow(
+    layout: Res<HudLayout>,
+    mut panels: Query<
+        (&mut Anchored, &mut Style),
+        (
+            Without<EasingComponent<Style>>,
+            Without<EasingChainComponent<Style>>,
+        ),
+    >,
+) {
+    for (mut anchored, mut style) in &mut panels {
+        if anchored.layout == *layout {
+            continue;
+        }
+        anchored.layout = *layout;
+        let mut placed = layout.place(anchored.panel, &style);
+        if !anchored.panel.is_shown(&style) {
+            anchored.panel.keep_hidden(&mut placed, &style);
+        }
+        *style = placed;
+    }
+}
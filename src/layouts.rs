@@ -0,0 +1,297 @@
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+
+use crate::levels::{Level, Tile};
+
+const MAX_LAYOUTS: usize = 3;
+/// Levels of the campaign that can be skipped over the whole game
+pub const SKIP_TOKENS: u32 = 3;
+/// Failures on a level before it can be skipped
+pub const SKIP_AFTER_FAILURES: u32 = 5;
+/// Key of the store with the number of skip tokens used
+pub(crate) const SKIPS_USED_KEY: &str = "skips-used";
+
+/// An obstacle setup saved by the player for a level
+#[derive(Debug, Clone)]
+pub struct Layout {
+    pub name: String,
+    /// Hash of the level when the layout was saved
+    pub level_hash: u64,
+    pub cells: Vec<(usize, usize)>,
+}
+
+/// Obstacles in place when the last level was won, ready to be saved from the win screen
+#[derive(Resource)]
+pub struct LastLayout {
+    /// Where the layouts of the level are kept, see [`layouts_key`] and [`custom_layouts_key`]
+    pub layouts_key: String,
+    pub level_hash: u64,
+    pub cells: Vec<(usize, usize)>,
+}
+
+pub(crate) fn layouts_key(level: usize) -> String {
+    format!("layouts-{}", level)
+}
+
+/// Custom levels are identified by their file, their index changes when levels are added
+pub(crate) fn custom_layouts_key(file: &str) -> String {
+    format!("layouts-custom-{}", file)
+}
+
+pub fn load_layouts(store: &PkvStore, key: &str) -> Vec<Layout> {
+    store
+        .get::<Vec<(String, u64, Vec<(u32, u32)>)>>(key)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, level_hash, cells)| Layout {
+            name,
+            level_hash,
+            cells: cells
+                .into_iter()
+                .map(|(x, y)| (x as usize, y as usize))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Save a new layout for the level, replacing the oldest one if there are too many. Returns the
+/// name given to the layout.
+pub fn save_layout(store: &mut PkvStore, last_layout: &LastLayout) -> String {
+    let mut layouts = load_layouts(store, &last_layout.layouts_key);
+    if layouts.len() >= MAX_LAYOUTS {
+        layouts.remove(0);
+    }
+    let name = ('A'..='Z')
+        .map(|letter| format!("Setup {}", letter))
+        .find(|name| layouts.iter().all(|layout| &layout.name != name))
+        .unwrap();
+    layouts.push(Layout {
+        name: name.clone(),
+        level_hash: last_layout.level_hash,
+        cells: last_layout.cells.clone(),
+    });
+    let _ = set_layouts(store, &last_layout.layouts_key, &layouts);
+    name
+}
+
+/// Replace all the layouts saved under the key
+pub(crate) fn set_layouts(
+    store: &mut PkvStore,
+    key: &str,
+    layouts: &[Layout],
+) -> Result<(), bevy_pkv::SetError> {
+    store.set(
+        key,
+        &layouts
+            .iter()
+            .map(|layout| {
+                (
+                    layout.name.clone(),
+                    layout.level_hash,
+                    layout
+                        .cells
+                        .iter()
+                        .map(|(x, y)| (*x as u32, *y as u32))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+impl Layout {
+    /// The level changed since the layout was saved
+    pub fn is_stale(&self, level: &Level) -> bool {
+        self.level_hash != level.hash
+    }
+
+    /// A layout can only be used if the level still accepts all its obstacles
+    pub fn is_valid_for(&self, level: &Level) -> bool {
+        self.cells.len() <= level.bonus.len()
+            && self.cells.iter().all(|(x, y)| {
+                level.floors[0].get(*y).and_then(|row| row.get(*x)) == Some(&Tile::Floor)
+            })
+    }
+}
+
+/// Progress on a level of the campaign
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LevelRecord {
+    /// The level can be played
+    pub unlocked: bool,
+    /// Hash of the version of the level last won, if any
+    pub won: Option<u64>,
+    /// The next level was unlocked with a skip token, without winning this one
+    pub skipped: bool,
+}
+
+pub(crate) fn record_key(level: usize) -> String {
+    format!("level-{}", level)
+}
+
+impl LevelRecord {
+    pub fn load(store: &PkvStore, level: usize) -> Self {
+        let key = record_key(level);
+        store
+            .get::<(bool, Option<u64>, bool)>(&key)
+            // saved before levels could be skipped
+            .or_else(|_| {
+                store
+                    .get::<(bool, Option<u64>)>(&key)
+                    .map(|(unlocked, won)| (unlocked, won, false))
+            })
+            .map(|(unlocked, won, skipped)| LevelRecord {
+                unlocked,
+                won,
+                skipped,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, store: &mut PkvStore, level: usize) -> Result<(), bevy_pkv::SetError> {
+        store.set(&record_key(level), &(self.unlocked, self.won, self.skipped))
+    }
+}
+
+/// Remember which version of the level was won, and unlock the next one
+pub fn record_win(store: &mut PkvStore, level: usize, level_hash: u64) {
+    let _ = LevelRecord {
+        unlocked: true,
+        won: Some(level_hash),
+        skipped: false,
+    }
+    .save(store, level);
+    let mut next = LevelRecord::load(store, level + 1);
+    next.unlocked = true;
+    let _ = next.save(store, level + 1);
+}
+
+/// Skip tokens the player can still use
+pub fn skips_left(store: &PkvStore) -> u32 {
+    SKIP_TOKENS.saturating_sub(store.get::<u32>(SKIPS_USED_KEY).unwrap_or(0))
+}
+
+/// Use a skip token on the level: it stays incomplete, and the next one is unlocked
+pub fn skip_level(store: &mut PkvStore, level: usize) {
+    let used = store.get::<u32>(SKIPS_USED_KEY).unwrap_or(0);
+    let _ = store.set(SKIPS_USED_KEY, &(used + 1));
+    let mut record = LevelRecord::load(store, level);
+    record.unlocked = true;
+    record.skipped = true;
+    let _ = record.save(store, level);
+    let mut next = LevelRecord::load(store, level + 1);
+    next.unlocked = true;
+    let _ = next.save(store, level + 1);
+}
+
+/// Hash of the level version last won, if any
+pub fn last_won_version(store: &PkvStore, level: usize) -> Option<u64> {
+    LevelRecord::load(store, level).won
+}
+
+/// Custom levels are identified by their file, their index changes when levels are added
+fn custom_won_key(file: &str) -> String {
+    format!("won-custom-{}", file)
+}
+
+/// Remember which version of the custom level was won
+pub fn record_custom_win(store: &mut PkvStore, file: &str, level_hash: u64) {
+    let _ = store.set(&custom_won_key(file), &level_hash);
+}
+
+/// Hash of the custom level version last won, if any
+pub fn last_custom_won_version(store: &PkvStore, file: &str) -> Option<u64> {
+    store.get::<u64>(&custom_won_key(file)).ok()
+}
+
+/// Solutions are kept by level file, so that custom and downloaded levels have theirs too
+fn solution_key(file: &str) -> String {
+    format!("solution-{}", file)
+}
+
+/// Remember the obstacles in place when the level was won, shown in the level selector
+pub fn record_solution(store: &mut PkvStore, file: &str, last_layout: &LastLayout) {
+    let _ = store.set(
+        &solution_key(file),
+        &(
+            last_layout.level_hash,
+            last_layout
+                .cells
+                .iter()
+                .map(|(x, y)| (*x as u32, *y as u32))
+                .collect::<Vec<_>>(),
+        ),
+    );
+}
+
+/// Obstacles in place the last time the level was won, if any
+pub fn last_solution(store: &PkvStore, file: &str) -> Option<Layout> {
+    store
+        .get::<(u64, Vec<(u32, u32)>)>(&solution_key(file))
+        .ok()
+        .map(|(level_hash, cells)| Layout {
+            name: "Last solution".to_string(),
+            level_hash,
+            cells: cells
+                .into_iter()
+                .map(|(x, y)| (x as usize, y as usize))
+                .collect(),
+        })
+}
+
+/// How far the player went with a level, shown in the level selector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelSeen {
+    /// Never opened
+    Unseen,
+    /// Played without winning yet
+    Attempted,
+    Completed,
+}
+
+/// Attempts are kept by level file, like solutions
+fn attempted_key(file: &str) -> String {
+    format!("attempted-{}", file)
+}
+
+/// Remember the level was opened at least once
+pub fn record_attempt(store: &mut PkvStore, file: &str) {
+    if store.get::<bool>(&attempted_key(file)).is_err() {
+        let _ = store.set(&attempted_key(file), &true);
+    }
+}
+
+/// Count one more time the player left the level before the end
+pub fn record_abandoned(store: &mut PkvStore, file: &str) {
+    let key = format!("abandoned-{}", file);
+    let count = store.get::<u32>(&key).unwrap_or(0);
+    let _ = store.set(&key, &(count + 1));
+}
+
+fn failures_key(file: &str) -> String {
+    format!("failures-{}", file)
+}
+
+/// Count one more time the player lost the level, returns how many times they did
+pub fn record_failure(store: &mut PkvStore, file: &str) -> u32 {
+    let count = failures(store, file) + 1;
+    let _ = store.set(&failures_key(file), &count);
+    count
+}
+
+/// Times the player lost the level
+pub fn failures(store: &PkvStore, file: &str) -> u32 {
+    store.get::<u32>(&failures_key(file)).unwrap_or(0)
+}
+
+/// How far the player went with the level, `won` being its progress in the campaign or as a custom
+/// level
+pub fn level_seen(store: &PkvStore, file: &str, won: bool) -> LevelSeen {
+    if won {
+        LevelSeen::Completed
+    } else if store.get::<bool>(&attempted_key(file)).is_ok() {
+        LevelSeen::Attempted
+    } else {
+        LevelSeen::Unseen
+    }
+}
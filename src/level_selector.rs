@@ -1,14 +1,27 @@
-use std::time::Duration;
-
 use bevy::{color::palettes, prelude::*};
-use bevy_easings::{Ease, EaseFunction, EasingType};
+use bevy_pkv::PkvStore;
 use rand::Rng;
 
 use crate::{
-    assets::GameAssets, audio::AudioTrigger, menu::SwitchState, play::GameInProgress, GameProgress,
-    GameState,
+    accessibility::AccessibleName,
+    assets::GameAssets,
+    audio::AudioTrigger,
+    chapters::Chapter,
+    layouts::{last_custom_won_version, last_solution, last_won_version, level_seen, LevelSeen},
+    levels::{
+        generator::{generate, GeneratorSettings},
+        Level,
+    },
+    menu::SwitchState,
+    mutators::{Mutator, Mutators},
+    pictures::cell_color,
+    play::GameInProgress,
+    ui, GameProgress, GameState,
 };
 
+#[cfg(feature = "online")]
+use crate::online::{Download, DownloadLevelPacks, LevelPack, LevelPacks};
+
 const CURRENT_STATE: GameState = GameState::LevelSelect;
 
 pub struct Plugin;
@@ -17,54 +30,84 @@ impl bevy::prelude::Plugin for Plugin {
         app.add_systems(OnEnter(CURRENT_STATE), (spawn_level_selector,))
             .add_systems(
                 Update,
-                (button_system, crate::menu::change_state_after_event)
+                (
+                    button_system,
+                    toggle_mutators,
+                    show_page.run_if(page_outdated),
+                    crate::menu::change_state_after_event,
+                )
                     .run_if(in_state(CURRENT_STATE)),
             );
     }
 }
 
+/// Page displayed in the level selector
+#[derive(Resource, Clone, Copy, PartialEq, Eq)]
+enum SelectedPage {
+    /// A chapter of the campaign, by its index in [`GameAssets::chapters`]
+    Chapter(usize),
+    /// Levels made by the player
+    Custom,
+    /// A downloaded level pack, by its index in [`LevelPacks::packs`]
+    #[cfg(feature = "online")]
+    Online(usize),
+}
+
+/// The page must be shown again: another one was selected, or the level packs download progressed
+fn page_outdated(
+    selected: Option<Res<SelectedPage>>,
+    #[cfg(feature = "online")] packs: Res<LevelPacks>,
+) -> bool {
+    let Some(selected) = selected else {
+        return false;
+    };
+    #[cfg(feature = "online")]
+    if packs.is_changed() && matches!(*selected, SelectedPage::Online(_)) {
+        return true;
+    }
+    selected.is_changed()
+}
+
+/// Chapter of the level to play next
+fn current_chapter(assets: &GameAssets, progress: &GameProgress) -> usize {
+    assets
+        .chapters
+        .iter()
+        .rposition(|chapter| *chapter.levels.start() <= progress.current_level)
+        .unwrap_or_default()
+}
+
+/// Number of levels won in the chapter
+fn won_in(chapter: &Chapter, store: &PkvStore) -> usize {
+    chapter
+        .levels
+        .clone()
+        .filter(|level| last_won_version(store, *level).is_some())
+        .count()
+}
+
+/// Number of levels won in the chapter before this one
+fn won_in_previous(chapters: &[Chapter], index: usize, store: &PkvStore) -> usize {
+    index
+        .checked_sub(1)
+        .map(|previous| won_in(&chapters[previous], store))
+        .unwrap_or_default()
+}
+
 fn spawn_level_selector(
     mut commands: Commands,
     assets: Res<GameAssets>,
     progress: Res<GameProgress>,
+    mutators: Res<Mutators>,
+    store: Res<PkvStore>,
 ) {
     info!("Loading screen");
 
+    commands.insert_resource(SelectedPage::Chapter(current_chapter(&assets, &progress)));
+
     commands
         .spawn((
-            NodeBundle {
-                style: Style {
-                    width: Val::Percent(100.0),
-                    height: Val::Percent(100.0),
-                    left: Val::Percent(-100.0),
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::Start,
-                    ..default()
-                },
-                ..default()
-            },
-            Style {
-                width: Val::Percent(100.0),
-                height: Val::Percent(100.0),
-                left: Val::Percent(-100.0),
-                align_items: AlignItems::Center,
-                justify_content: JustifyContent::Start,
-                ..default()
-            }
-            .ease_to(
-                Style {
-                    width: Val::Percent(100.0),
-                    height: Val::Percent(100.0),
-                    left: Val::Percent(30.0),
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::Start,
-                    ..default()
-                },
-                EaseFunction::QuadraticOut,
-                EasingType::Once {
-                    duration: Duration::from_secs_f32(1.0),
-                },
-            ),
+            ui::screen_root(30.0),
             MenuItem::Root,
             StateScoped(CURRENT_STATE),
         ))
@@ -72,7 +115,6 @@ fn spawn_level_selector(
             parent
                 .spawn((
                     NodeBundle {
-                        background_color: palettes::tailwind::GREEN_400.into(),
                         border_radius: BorderRadius::all(Val::Percent(5.0)),
                         z_index: ZIndex::Global(1),
                         style: Style {
@@ -86,6 +128,7 @@ fn spawn_level_selector(
                         ..default()
                     },
                     MenuItem::Panel,
+                    ui::Panel,
                 ))
                 .with_children(|parent| {
                     parent.spawn(TextBundle {
@@ -104,170 +147,132 @@ fn spawn_level_selector(
                         ..default()
                     });
 
-                    let style = Style {
-                        width: Val::Px(50.0),
-                        height: Val::Px(50.0),
-                        border: UiRect::all(Val::Px(3.0)),
-                        align_items: AlignItems::Center,
-                        justify_content: JustifyContent::Center,
-                        margin: UiRect::all(Val::Px(10.0)),
-                        justify_self: JustifySelf::End,
-                        ..default()
-                    };
-
-                    let style_easing = style.clone().ease_to(
-                        Style {
-                            width: Val::Px(50.0),
-                            height: Val::Px(50.0),
-                            border: UiRect::all(Val::Px(5.0)),
-                            margin: UiRect::all(Val::Px(10.0)),
-                            justify_self: JustifySelf::End,
-
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                margin: UiRect::bottom(Val::Px(10.0)),
+                                column_gap: Val::Px(10.0),
+                                ..default()
+                            },
                             ..default()
-                        },
-                        EaseFunction::QuadraticInOut,
-                        EasingType::PingPong {
-                            duration: Duration::from_secs_f32(1.0),
-                            pause: Some(Duration::from_secs_f32(0.5)),
-                        },
-                    );
-                    let current_easing = style.clone().ease_to(
-                        Style {
-                            width: Val::Px(50.0),
-                            height: Val::Px(50.0),
-                            border: UiRect::all(Val::Px(6.0)),
-                            margin: UiRect::all(Val::Px(10.0)),
-                            justify_self: JustifySelf::End,
+                        })
+                        .with_children(|parent| {
+                            header_button(parent, "Campaign", 120.0, ButtonAction::Campaign);
+                            header_button(parent, "Custom", 120.0, ButtonAction::Custom);
+                            #[cfg(feature = "online")]
+                            header_button(parent, "Online", 120.0, ButtonAction::Online);
+                            header_button(parent, "Random", 120.0, ButtonAction::Random);
+                        });
 
+                    // mutators for the levels played from here
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                margin: UiRect::bottom(Val::Px(10.0)),
+                                column_gap: Val::Px(10.0),
+                                ..default()
+                            },
                             ..default()
-                        },
-                        EaseFunction::QuadraticInOut,
-                        EasingType::PingPong {
-                            duration: Duration::from_secs_f32(0.2),
-                            pause: Some(Duration::from_secs_f32(0.05)),
-                        },
-                    );
-                    let disabled_easing = style.ease_to(
-                        Style {
-                            width: Val::Px(50.0),
-                            height: Val::Px(50.0),
-                            border: UiRect::all(Val::Px(4.0)),
-                            margin: UiRect::all(Val::Px(10.0)),
-                            justify_self: JustifySelf::End,
+                        })
+                        .with_children(|parent| {
+                            for mutator in Mutator::all() {
+                                mutator_button(
+                                    parent,
+                                    mutator,
+                                    mutators.is_active(mutator),
+                                    mutator.has_badge(&store, &assets),
+                                );
+                            }
+                        });
 
-                            ..default()
-                        },
-                        EaseFunction::QuadraticInOut,
-                        EasingType::PingPong {
-                            duration: Duration::from_secs_f32(1.5),
-                            pause: Some(Duration::from_secs_f32(0.5)),
-                        },
-                    );
+                    // chapter header, with buttons to go through the chapters
                     parent
                         .spawn(NodeBundle {
                             style: Style {
-                                flex_wrap: FlexWrap::Wrap,
+                                align_items: AlignItems::Center,
                                 ..default()
                             },
                             ..default()
                         })
                         .with_children(|parent| {
-                            let start = if cfg!(feature = "debug") { 0 } else { 1 };
-                            for level in start..assets.levels.len() {
-                                parent
-                                    .spawn((
-                                        ButtonBundle {
-                                            background_color: if level <= progress.current_level {
-                                                palettes::tailwind::INDIGO_800.into()
-                                            } else {
-                                                palettes::tailwind::GRAY_400.into()
-                                            },
-                                            border_radius: BorderRadius::all(Val::Percent(10.0)),
-                                            border_color: BorderColor(
-                                                palettes::tailwind::INDIGO_400.into(),
-                                            ),
-                                            style: Style {
-                                                width: Val::Px(50.0),
-                                                height: Val::Px(50.0),
-                                                border: UiRect::all(Val::Px(3.0)),
-                                                align_items: AlignItems::Center,
-                                                justify_content: JustifyContent::Center,
-                                                margin: UiRect::all(Val::Px(10.0)),
-                                                justify_self: JustifySelf::End,
-                                                ..default()
-                                            },
+                            header_button(parent, "<", 40.0, ButtonAction::PreviousChapter);
+                            parent.spawn((
+                                TextBundle {
+                                    text: Text::from_section(
+                                        "",
+                                        TextStyle {
+                                            font_size: 30.0,
+                                            color: Color::WHITE,
                                             ..default()
                                         },
-                                        MenuItem::Button,
-                                        ButtonAction::Playlevel(level),
-                                        match level {
-                                            _ if level < progress.current_level => {
-                                                style_easing.clone()
-                                            }
-                                            _ if level == progress.current_level => {
-                                                current_easing.clone()
-                                            }
-                                            _ => disabled_easing.clone(),
-                                        }
-                                        .delay(
-                                            Duration::from_secs_f32(
-                                                rand::thread_rng().gen_range(0.0..1.5),
-                                            ),
-                                        ),
-                                    ))
-                                    .with_children(|p| {
-                                        p.spawn(TextBundle {
-                                            text: Text::from_section(
-                                                format!("{}", level),
-                                                TextStyle {
-                                                    font_size: 20.0,
-                                                    ..default()
-                                                },
-                                            ),
-                                            ..default()
-                                        });
-                                    });
-                            }
+                                    ),
+                                    style: Style {
+                                        margin: UiRect::horizontal(Val::Px(20.0)),
+                                        ..default()
+                                    },
+                                    ..default()
+                                },
+                                ChapterText::Title,
+                            ));
+                            header_button(parent, ">", 40.0, ButtonAction::NextChapter);
                         });
+                    parent.spawn((
+                        TextBundle {
+                            text: Text::from_section("", TextStyle::default()),
+                            ..default()
+                        },
+                        ChapterText::Summary,
+                    ));
 
-                    let button_height = 40.0;
-                    let style_easing = Style {
-                        width: Val::Px(200.0),
-                        height: Val::Px(button_height),
-                        border: UiRect::all(Val::Px(3.0)),
-                        align_items: AlignItems::Center,
-                        justify_content: JustifyContent::Center,
-                        margin: UiRect::top(Val::Percent(10.0)),
-                        justify_self: JustifySelf::End,
-                        ..default()
-                    };
-
-                    let style_easing = style_easing.ease_to(
-                        Style {
-                            width: Val::Px(200.0),
-                            height: Val::Px(button_height),
-                            border: UiRect::all(Val::Px(5.0)),
-                            margin: UiRect::top(Val::Percent(10.0)),
-                            justify_self: JustifySelf::End,
+                    // levels of the selected page, filled by `show_page`
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                flex_wrap: FlexWrap::Wrap,
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        ChapterPage,
+                    ));
 
+                    // title of the hovered level
+                    parent.spawn((
+                        TextBundle {
+                            text: Text::from_section("", TextStyle::default()),
+                            style: Style {
+                                margin: UiRect::top(Val::Percent(3.0)),
+                                ..default()
+                            },
                             ..default()
                         },
-                        EaseFunction::QuadraticInOut,
-                        EasingType::PingPong {
-                            duration: Duration::from_secs_f32(1.0),
-                            pause: Some(Duration::from_secs_f32(0.5)),
+                        LevelInfo,
+                    ));
+                    // obstacles of the hovered level when it was last won, filled by `show_solution`
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                align_items: AlignItems::Center,
+                                margin: UiRect::top(Val::Px(5.0)),
+                                ..default()
+                            },
+                            ..default()
                         },
-                    );
+                        LastSolution,
+                    ));
+
+                    let button_height = 40.0;
                     parent
                         .spawn((
                             ButtonBundle {
-                                background_color: palettes::tailwind::INDIGO_800.into(),
                                 border_radius: BorderRadius::all(Val::Percent(10.0)),
                                 border_color: BorderColor(palettes::tailwind::INDIGO_400.into()),
                                 style: Style {
                                     width: Val::Px(200.0),
                                     height: Val::Px(button_height),
-                                    border: UiRect::all(Val::Px(0.0)),
+                                    border: UiRect::all(Val::Px(3.0)),
                                     align_items: AlignItems::Center,
                                     justify_content: JustifyContent::Center,
                                     margin: UiRect::top(Val::Percent(10.0)),
@@ -276,8 +281,9 @@ fn spawn_level_selector(
                                 },
                                 ..default()
                             },
-                            style_easing,
+                            ui::Pulse::default(),
                             MenuItem::Button,
+                            ui::PaletteButton,
                             ButtonAction::Back,
                         ))
                         .with_children(|p| {
@@ -296,19 +302,471 @@ fn spawn_level_selector(
         });
 }
 
-#[derive(Component, Default, Clone)]
-struct ImageColor {
-    color: Srgba,
+fn header_button(parent: &mut ChildBuilder, label: &str, width: f32, action: ButtonAction) {
+    parent
+        .spawn((
+            ButtonBundle {
+                border_radius: BorderRadius::all(Val::Percent(20.0)),
+                style: Style {
+                    width: Val::Px(width),
+                    height: Val::Px(40.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            MenuItem::Button,
+            ui::PaletteButton,
+            action,
+        ))
+        .with_children(|p| {
+            p.spawn(TextBundle {
+                text: Text::from_section(
+                    label,
+                    TextStyle {
+                        font_size: 30.0,
+                        ..default()
+                    },
+                ),
+                ..default()
+            });
+        });
+}
+
+/// Border of the button of a mutator, lit while it's active
+fn mutator_border(active: bool) -> BorderColor {
+    if active {
+        BorderColor(palettes::tailwind::AMBER_400.into())
+    } else {
+        BorderColor(Color::NONE)
+    }
+}
+
+/// Toggle for a mutator, its label in gold once its badge is earned
+fn mutator_button(parent: &mut ChildBuilder, mutator: Mutator, active: bool, badge: bool) {
+    parent
+        .spawn((
+            ButtonBundle {
+                border_radius: BorderRadius::all(Val::Percent(20.0)),
+                border_color: mutator_border(active),
+                style: Style {
+                    width: Val::Px(130.0),
+                    height: Val::Px(30.0),
+                    border: UiRect::all(Val::Px(2.0)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            MenuItem::Button,
+            ui::PaletteButton,
+            ButtonAction::Mutator(mutator),
+        ))
+        .with_children(|p| {
+            p.spawn(TextBundle {
+                text: Text::from_section(
+                    mutator.label(),
+                    TextStyle {
+                        font_size: 18.0,
+                        color: if badge {
+                            palettes::tailwind::AMBER_400.into()
+                        } else {
+                            Color::WHITE
+                        },
+                        ..default()
+                    },
+                ),
+                ..default()
+            });
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn show_page(
+    mut commands: Commands,
+    selected: Res<SelectedPage>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+    progress: Res<GameProgress>,
+    store: Res<PkvStore>,
+    page: Query<Entity, With<ChapterPage>>,
+    mut texts: Query<(&mut Text, &ChapterText)>,
+    mut arrows: Query<(&ButtonAction, &mut Visibility)>,
+    #[cfg(feature = "online")] packs: Res<LevelPacks>,
+) {
+    let Ok(page) = page.get_single() else {
+        return;
+    };
+    for (action, mut visibility) in &mut arrows {
+        if matches!(
+            action,
+            ButtonAction::PreviousChapter | ButtonAction::NextChapter
+        ) {
+            *visibility = if *selected == SelectedPage::Custom {
+                Visibility::Hidden
+            } else {
+                Visibility::Inherited
+            };
+        }
+    }
+    let index = match *selected {
+        SelectedPage::Chapter(index) => index,
+        SelectedPage::Custom => {
+            show_custom_levels(&mut commands, page, &assets, &levels, &store, &mut texts);
+            return;
+        }
+        #[cfg(feature = "online")]
+        SelectedPage::Online(index) => {
+            show_online_levels(
+                &mut commands,
+                page,
+                packs.packs.get(index).map(|pack| (index, pack)),
+                &packs,
+                &assets,
+                &levels,
+                &store,
+                &mut texts,
+            );
+            return;
+        }
+    };
+    let Some(chapter) = assets.chapters.get(index) else {
+        return;
+    };
+    let won_in_previous = won_in_previous(&assets.chapters, index, &store);
+    let unlocked = chapter.is_unlocked(&progress, won_in_previous);
+
+    for (mut text, kind) in &mut texts {
+        text.sections[0].value = match kind {
+            ChapterText::Title => {
+                format!("{} ({}/{})", chapter.name, index + 1, assets.chapters.len())
+            }
+            ChapterText::Summary if !unlocked => match chapter.required_wins {
+                Some(required) => {
+                    format!("Locked - win {} levels of the previous chapter", required)
+                }
+                None => "Locked - finish the previous chapter".to_string(),
+            },
+            ChapterText::Summary => format!(
+                "Completed {} / {}",
+                won_in(chapter, &store),
+                chapter.levels.clone().count()
+            ),
+        };
+    }
+
+    // the menu background level is only reachable in debug
+    let indices = if cfg!(feature = "debug") && index == 0 {
+        0..=*chapter.levels.end()
+    } else {
+        chapter.levels.clone()
+    };
+
+    commands.entity(page).despawn_descendants();
+    commands.entity(page).with_children(|parent| {
+        for level in indices {
+            let playable = chapter.is_playable(level, &progress, won_in_previous)
+                || (level > 0 && last_won_version(&store, level - 1).is_some());
+            let pulse = match level {
+                _ if !playable => level_pulse(4.0, 1.5, 0.5),
+                _ if level == progress.current_level => level_pulse(6.0, 0.2, 0.05),
+                _ => level_pulse(5.0, 1.0, 0.5),
+            };
+            let seen = seen_state(level, &assets, &levels, &store);
+            level_button(parent, level, format!("{}", level), playable, seen, pulse);
+        }
+    });
+}
+
+fn show_custom_levels(
+    commands: &mut Commands,
+    page: Entity,
+    assets: &GameAssets,
+    levels: &Assets<Level>,
+    store: &PkvStore,
+    texts: &mut Query<(&mut Text, &ChapterText)>,
+) {
+    let won = won_by_file(assets.custom_levels.clone(), assets, levels, store);
+    for (mut text, kind) in texts.iter_mut() {
+        text.sections[0].value = match kind {
+            ChapterText::Title => "Custom Levels".to_string(),
+            ChapterText::Summary if assets.custom_levels.is_empty() => no_custom_levels(),
+            ChapterText::Summary => format!("Completed {} / {}", won, assets.custom_levels.len()),
+        };
+    }
+
+    commands.entity(page).despawn_descendants();
+    commands.entity(page).with_children(|parent| {
+        // custom levels are all playable, numbered from 1
+        for (number, level) in assets.custom_levels.clone().enumerate() {
+            level_button(
+                parent,
+                level,
+                format!("{}", number + 1),
+                true,
+                seen_state(level, assets, levels, store),
+                level_pulse(5.0, 1.0, 0.5),
+            );
+        }
+    });
 }
 
-impl bevy_easings::Lerp for ImageColor {
-    type Scalar = f32;
+#[cfg(feature = "online")]
+#[allow(clippy::too_many_arguments)]
+fn show_online_levels(
+    commands: &mut Commands,
+    page: Entity,
+    pack: Option<(usize, &LevelPack)>,
+    packs: &LevelPacks,
+    assets: &GameAssets,
+    levels: &Assets<Level>,
+    store: &PkvStore,
+    texts: &mut Query<(&mut Text, &ChapterText)>,
+) {
+    for (mut text, kind) in texts.iter_mut() {
+        text.sections[0].value = match (kind, pack) {
+            (ChapterText::Title, Some((index, pack))) => {
+                format!("{} ({}/{})", pack.name, index + 1, packs.packs.len())
+            }
+            (ChapterText::Title, None) => "Online Levels".to_string(),
+            (ChapterText::Summary, pack) => match &packs.download {
+                Download::NotStarted => "Connecting...".to_string(),
+                Download::InProgress { done, total } => {
+                    format!("Downloading {} / {}", done, total)
+                }
+                Download::Failed(err) => format!("Download failed: {}", err),
+                Download::Done => match pack {
+                    Some((_, pack)) => format!(
+                        "Completed {} / {}",
+                        won_by_file(pack.levels.iter().copied(), assets, levels, store),
+                        pack.levels.len()
+                    ),
+                    None => "No level packs available".to_string(),
+                },
+            },
+        };
+    }
 
-    fn lerp(&self, other: &Self, scalar: &Self::Scalar) -> Self {
-        ImageColor {
-            color: self.color.mix(&other.color, *scalar),
+    commands.entity(page).despawn_descendants();
+    let Some((_, pack)) = pack else {
+        return;
+    };
+    commands.entity(page).with_children(|parent| {
+        for (number, level) in pack.levels.iter().enumerate() {
+            level_button(
+                parent,
+                *level,
+                format!("{}", number + 1),
+                true,
+                seen_state(*level, assets, levels, store),
+                level_pulse(5.0, 1.0, 0.5),
+            );
         }
+    });
+}
+
+/// Number of levels won among levels outside of the campaign
+fn won_by_file(
+    indices: impl Iterator<Item = usize>,
+    assets: &GameAssets,
+    levels: &Assets<Level>,
+    store: &PkvStore,
+) -> usize {
+    indices
+        .filter(|level| {
+            levels
+                .get(&assets.levels[*level])
+                .and_then(|level| last_custom_won_version(store, &level.file))
+                .is_some()
+        })
+        .count()
+}
+
+/// How far the player went with a level. Campaign levels are won by index, others by file.
+fn seen_state(
+    level: usize,
+    assets: &GameAssets,
+    levels: &Assets<Level>,
+    store: &PkvStore,
+) -> LevelSeen {
+    let Some(file) = levels.get(&assets.levels[level]).map(|level| &level.file) else {
+        return LevelSeen::Unseen;
+    };
+    let won = if assets.is_campaign(level) {
+        last_won_version(store, level).is_some()
+    } else {
+        last_custom_won_version(store, file).is_some()
+    };
+    level_seen(store, file, won)
+}
+
+/// Where to put custom levels, when there are none yet
+fn no_custom_levels() -> String {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(dir) = crate::loading::custom_levels_dir() {
+        return format!("Add .level files to {}", dir.display());
     }
+    "No custom levels".to_string()
+}
+
+const SOLUTION_CELL_SIZE: f32 = 6.0;
+
+/// Top-down view of the level, with the obstacles in place the last time it was won
+fn show_solution(commands: &mut Commands, container: Entity, level: &Level, store: &PkvStore) {
+    let Some(solution) = last_solution(store, &level.file) else {
+        return;
+    };
+    if solution.is_stale(level) {
+        return;
+    }
+
+    commands.entity(container).with_children(|parent| {
+        parent.spawn(TextBundle {
+            text: Text::from_section(
+                "Your last solution",
+                TextStyle {
+                    font_size: 16.0,
+                    ..default()
+                },
+            ),
+            style: Style {
+                margin: UiRect::bottom(Val::Px(3.0)),
+                ..default()
+            },
+            ..default()
+        });
+        for (y, row) in level.floors[0].iter().enumerate() {
+            parent.spawn(NodeBundle::default()).with_children(|parent| {
+                for (x, tile) in row.iter().enumerate() {
+                    let color = cell_color(tile, solution.cells.contains(&(x, y)));
+                    parent.spawn(NodeBundle {
+                        background_color: color.into(),
+                        style: Style {
+                            width: Val::Px(SOLUTION_CELL_SIZE),
+                            height: Val::Px(SOLUTION_CELL_SIZE),
+                            ..default()
+                        },
+                        ..default()
+                    });
+                }
+            });
+        }
+    });
+}
+
+const LEVEL_BUTTON_STYLE: Style = Style {
+    width: Val::Px(50.0),
+    height: Val::Px(50.0),
+    border: UiRect::all(Val::Px(3.0)),
+    align_items: AlignItems::Center,
+    justify_content: JustifyContent::Center,
+    margin: UiRect::all(Val::Px(10.0)),
+    justify_self: JustifySelf::End,
+    ..Style::DEFAULT
+};
+
+fn level_pulse(border: f32, duration: f32, pause: f32) -> ui::Pulse {
+    ui::Pulse {
+        border,
+        duration,
+        pause: Some(pause),
+        desync: 1.5,
+    }
+}
+
+fn level_button(
+    parent: &mut ChildBuilder,
+    level: usize,
+    label: String,
+    playable: bool,
+    seen: LevelSeen,
+    pulse: ui::Pulse,
+) {
+    let mut button = parent.spawn((
+        ButtonBundle {
+            background_color: palettes::tailwind::GRAY_400.into(),
+            border_radius: BorderRadius::all(Val::Percent(10.0)),
+            border_color: BorderColor(palettes::tailwind::INDIGO_400.into()),
+            style: LEVEL_BUTTON_STYLE,
+            ..default()
+        },
+        MenuItem::Button,
+        ButtonAction::Playlevel(level),
+        AccessibleName(if playable {
+            format!("Level {}", label)
+        } else {
+            format!("Level {}, locked", label)
+        }),
+        pulse,
+    ));
+    if playable {
+        button.insert(ui::PaletteButton);
+    } else {
+        button.insert(Locked);
+    }
+    button.with_children(|p| {
+        p.spawn(TextBundle {
+            text: Text::from_section(
+                label,
+                TextStyle {
+                    font_size: 20.0,
+                    ..default()
+                },
+            ),
+            ..default()
+        });
+        if playable {
+            scroll_badge(p, seen);
+        }
+    });
+}
+
+/// Scroll in the corner of a level button: sealed for a level never opened, with its seal cracked
+/// for a level attempted but not won yet. Completed levels have none.
+fn scroll_badge(parent: &mut ChildBuilder, seen: LevelSeen) {
+    let cracked = match seen {
+        LevelSeen::Unseen => false,
+        LevelSeen::Attempted => true,
+        LevelSeen::Completed => return,
+    };
+    parent
+        .spawn(NodeBundle {
+            background_color: palettes::tailwind::AMBER_100.into(),
+            border_radius: BorderRadius::all(Val::Px(3.0)),
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(-8.0),
+                bottom: Val::Px(-8.0),
+                width: Val::Px(20.0),
+                height: Val::Px(14.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(if cracked { 2.0 } else { 0.0 }),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            // the seal, split in two halves once cracked
+            for (half, offset) in [
+                (BorderRadius::left(Val::Percent(50.0)), -1.0),
+                (BorderRadius::right(Val::Percent(50.0)), 1.0),
+            ] {
+                parent.spawn(NodeBundle {
+                    background_color: palettes::tailwind::RED_700.into(),
+                    border_radius: half,
+                    style: Style {
+                        width: Val::Px(4.0),
+                        height: Val::Px(8.0),
+                        top: Val::Px(if cracked { offset } else { 0.0 }),
+                        ..default()
+                    },
+                    ..default()
+                });
+            }
+        });
 }
 
 #[derive(Component, PartialEq, Eq)]
@@ -318,24 +776,83 @@ enum MenuItem {
     Button,
 }
 
+#[derive(Component)]
+struct LevelInfo;
+
+#[derive(Component)]
+struct LastSolution;
+
+#[derive(Component)]
+struct ChapterPage;
+
+#[derive(Component)]
+enum ChapterText {
+    Title,
+    Summary,
+}
+
+/// Level that can't be played yet
+#[derive(Component)]
+struct Locked;
+
 #[derive(Component, PartialEq, Eq)]
 enum ButtonAction {
     Back,
     Playlevel(usize),
+    PreviousChapter,
+    NextChapter,
+    Campaign,
+    Custom,
+    #[cfg(feature = "online")]
+    Online,
+    Random,
+    Mutator(Mutator),
+}
+
+fn play_level(
+    commands: &mut Commands,
+    level: usize,
+    next_state: &mut EventWriter<SwitchState>,
+    ui_items: &Query<(Entity, &MenuItem)>,
+) {
+    next_state.send(SwitchState(GameState::InGame));
+    commands.insert_resource(GameInProgress { level, ..default() });
+
+    for (entity, kind) in ui_items {
+        if *kind == MenuItem::Root {
+            ui::slide_out(commands, entity, 30.0);
+        }
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn button_system(
     mut commands: Commands,
     interaction_query: Query<
-        (Ref<Interaction>, &BackgroundColor, Entity, &ButtonAction),
+        (
+            Ref<Interaction>,
+            &BackgroundColor,
+            Entity,
+            &ButtonAction,
+            Has<Locked>,
+        ),
         Changed<Interaction>,
     >,
     mut next_state: EventWriter<SwitchState>,
     ui_items: Query<(Entity, &MenuItem)>,
+    mut selected: ResMut<SelectedPage>,
     progress: Res<GameProgress>,
+    #[cfg(feature = "online")] packs: Res<LevelPacks>,
+    #[cfg(feature = "online")] mut download: EventWriter<DownloadLevelPacks>,
     mut audio_trigger: EventWriter<AudioTrigger>,
+    mut assets: ResMut<GameAssets>,
+    mut levels: ResMut<Assets<Level>>,
+    mut level_info: Query<&mut Text, With<LevelInfo>>,
+    solution: Query<Entity, With<LastSolution>>,
+    store: Res<PkvStore>,
+    palette: Res<ui::Palette>,
 ) {
-    for (interaction, color, entity, action) in &interaction_query {
+    for (interaction, color, entity, action, locked) in &interaction_query {
         if interaction.is_added() {
             continue;
         }
@@ -348,122 +865,166 @@ fn button_system(
 
                     for (entity, kind) in &ui_items {
                         if *kind == MenuItem::Root {
-                            commands.entity(entity).insert(
-                                Style {
-                                    width: Val::Percent(100.0),
-                                    height: Val::Percent(100.0),
-                                    left: Val::Percent(30.0),
-                                    align_items: AlignItems::Center,
-                                    justify_content: JustifyContent::Start,
-                                    ..default()
-                                }
-                                .ease_to(
-                                    Style {
-                                        width: Val::Percent(100.0),
-                                        height: Val::Percent(100.0),
-                                        left: Val::Percent(-100.0),
-                                        align_items: AlignItems::Center,
-                                        justify_content: JustifyContent::Start,
-                                        ..default()
-                                    },
-                                    EaseFunction::QuadraticOut,
-                                    EasingType::Once {
-                                        duration: Duration::from_secs_f32(1.0),
-                                    },
-                                ),
+                            ui::slide_out(&mut commands, entity, 30.0);
+                        }
+                    }
+
+                    ui::ease_color(&mut commands, entity, color, palette.button_hovered);
+                }
+                ButtonAction::PreviousChapter => {
+                    audio_trigger.send(AudioTrigger::Click);
+                    match *selected {
+                        SelectedPage::Chapter(index) => {
+                            *selected = SelectedPage::Chapter(
+                                index.checked_sub(1).unwrap_or(assets.chapters.len() - 1),
                             );
                         }
+                        #[cfg(feature = "online")]
+                        SelectedPage::Online(index) if !packs.packs.is_empty() => {
+                            *selected = SelectedPage::Online(
+                                index.checked_sub(1).unwrap_or(packs.packs.len() - 1),
+                            );
+                        }
+                        _ => (),
+                    }
+                }
+                ButtonAction::NextChapter => {
+                    audio_trigger.send(AudioTrigger::Click);
+                    match *selected {
+                        SelectedPage::Chapter(index) => {
+                            *selected = SelectedPage::Chapter((index + 1) % assets.chapters.len());
+                        }
+                        #[cfg(feature = "online")]
+                        SelectedPage::Online(index) if !packs.packs.is_empty() => {
+                            *selected = SelectedPage::Online((index + 1) % packs.packs.len());
+                        }
+                        _ => (),
+                    }
+                }
+                ButtonAction::Campaign => {
+                    audio_trigger.send(AudioTrigger::Click);
+                    if !matches!(*selected, SelectedPage::Chapter(_)) {
+                        *selected = SelectedPage::Chapter(current_chapter(&assets, &progress));
+                    }
+                }
+                ButtonAction::Custom => {
+                    audio_trigger.send(AudioTrigger::Click);
+                    selected.set_if_neq(SelectedPage::Custom);
+                }
+                #[cfg(feature = "online")]
+                ButtonAction::Online => {
+                    audio_trigger.send(AudioTrigger::Click);
+                    // also retries a failed download
+                    download.send(DownloadLevelPacks);
+                    if !matches!(*selected, SelectedPage::Online(_)) {
+                        *selected = SelectedPage::Online(0);
                     }
+                }
+                ButtonAction::Random => {
+                    audio_trigger.send(AudioTrigger::Start);
 
-                    commands.entity(entity).insert(color.ease_to(
-                        BUTTON_HOVERED,
-                        EaseFunction::QuadraticInOut,
-                        EasingType::Once {
-                            duration: Duration::from_secs_f32(0.25),
-                        },
-                    ));
+                    let seed = rand::thread_rng().gen();
+                    let level = levels.add(generate(seed, &GeneratorSettings::default()));
+                    let level = assets.set_generated_level(level);
+                    play_level(&mut commands, level, &mut next_state, &ui_items);
                 }
+                // see `toggle_mutators`
+                ButtonAction::Mutator(_) => (),
                 ButtonAction::Playlevel(level) => {
                     audio_trigger.send(AudioTrigger::Start);
 
-                    if *level <= progress.current_level {
-                        next_state.send(SwitchState(GameState::InGame));
-                        commands.insert_resource(GameInProgress {
-                            level: *level,
-                            ..default()
-                        });
-
-                        for (entity, kind) in &ui_items {
-                            if *kind == MenuItem::Root {
-                                commands.entity(entity).insert(
-                                    Style {
-                                        width: Val::Percent(100.0),
-                                        height: Val::Percent(100.0),
-                                        left: Val::Percent(30.0),
-                                        align_items: AlignItems::Center,
-                                        justify_content: JustifyContent::Start,
-                                        ..default()
-                                    }
-                                    .ease_to(
-                                        Style {
-                                            width: Val::Percent(100.0),
-                                            height: Val::Percent(100.0),
-                                            left: Val::Percent(-100.0),
-                                            align_items: AlignItems::Center,
-                                            justify_content: JustifyContent::Start,
-                                            ..default()
-                                        },
-                                        EaseFunction::QuadraticOut,
-                                        EasingType::Once {
-                                            duration: Duration::from_secs_f32(1.0),
-                                        },
-                                    ),
-                                );
-                            }
-                        }
-
-                        commands.entity(entity).insert(color.ease_to(
-                            BUTTON_HOVERED,
-                            EaseFunction::QuadraticInOut,
-                            EasingType::Once {
-                                duration: Duration::from_secs_f32(0.25),
-                            },
-                        ));
+                    if !locked {
+                        play_level(&mut commands, *level, &mut next_state, &ui_items);
+                        ui::ease_color(&mut commands, entity, color, palette.button_hovered);
                     }
                 }
             },
             Interaction::Hovered => {
                 if let ButtonAction::Playlevel(level) = action {
-                    if progress.current_level < *level {
+                    if let Ok(mut text) = level_info.get_single_mut() {
+                        text.sections = if locked {
+                            vec![TextSection::new(
+                                "Locked",
+                                TextStyle {
+                                    font_size: 24.0,
+                                    ..default()
+                                },
+                            )]
+                        } else {
+                            levels
+                                .get(&assets.levels[*level])
+                                .map(|l| l.title_sections(*level, 24.0))
+                                .unwrap_or_default()
+                        };
+                    }
+                    if let Ok(solution) = solution.get_single() {
+                        commands.entity(solution).despawn_descendants();
+                        if let Some(level) = levels.get(&assets.levels[*level]).filter(|_| !locked)
+                        {
+                            show_solution(&mut commands, solution, level, &store);
+                        }
+                    }
+                    if locked {
                         continue;
                     }
                 }
-                commands.entity(entity).insert(color.ease_to(
-                    BUTTON_HOVERED,
-                    EaseFunction::QuadraticInOut,
-                    EasingType::Once {
-                        duration: Duration::from_secs_f32(0.25),
-                    },
-                ));
+                ui::ease_color(&mut commands, entity, color, palette.button_hovered);
             }
             Interaction::None => {
-                if let ButtonAction::Playlevel(level) = action {
-                    if progress.current_level < *level {
-                        continue;
-                    }
+                if locked {
+                    continue;
                 }
-                commands.entity(entity).insert(color.ease_to(
-                    BUTTON_IDLE,
-                    EaseFunction::QuadraticInOut,
-                    EasingType::Once {
-                        duration: Duration::from_secs_f32(0.25),
-                    },
-                ));
+                ui::ease_color(&mut commands, entity, color, palette.button_idle);
             }
         }
     }
 }
 
-const BUTTON_IDLE: BackgroundColor = BackgroundColor(Color::Srgba(palettes::tailwind::INDIGO_800));
-const BUTTON_HOVERED: BackgroundColor =
-    BackgroundColor(Color::Srgba(palettes::tailwind::AMBER_600));
+/// Toggle mutators when their button is pressed, and tell how many campaign levels were won with
+/// one when it's hovered
+fn toggle_mutators(
+    mut buttons: Query<(&Interaction, &ButtonAction, &mut BorderColor), Changed<Interaction>>,
+    mut mutators: ResMut<Mutators>,
+    mut level_info: Query<&mut Text, With<LevelInfo>>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+    assets: Res<GameAssets>,
+    store: Res<PkvStore>,
+) {
+    for (interaction, action, mut border) in &mut buttons {
+        let ButtonAction::Mutator(mutator) = action else {
+            continue;
+        };
+        match interaction {
+            Interaction::Pressed => {
+                audio_trigger.send(AudioTrigger::Click);
+                mutators.toggle(*mutator);
+                *border = mutator_border(mutators.is_active(*mutator));
+            }
+            Interaction::Hovered => {
+                if let Ok(mut text) = level_info.get_single_mut() {
+                    let won = mutator.won_levels(&store).len();
+                    let campaign = assets.custom_levels.start - 1;
+                    let badge = if mutator.has_badge(&store, &assets) {
+                        " - badge earned"
+                    } else {
+                        ""
+                    };
+                    text.sections = vec![TextSection::new(
+                        format!(
+                            "{}: {} of {} levels won{}",
+                            mutator.label(),
+                            won,
+                            campaign,
+                            badge
+                        ),
+                        TextStyle {
+                            font_size: 24.0,
+                            ..default()
+                        },
+                    )];
+                }
+            }
+            Interaction::None => (),
+        }
+    }
+}
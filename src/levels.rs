@@ -1,4 +1,9 @@
-use std::f32::consts::{FRAC_PI_2, FRAC_PI_3, FRAC_PI_4, FRAC_PI_8, PI};
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::{FRAC_PI_2, FRAC_PI_3, FRAC_PI_4, FRAC_PI_8, PI},
+    str::FromStr,
+    time::Duration,
+};
 
 use avian3d::{
     collision::Collider,
@@ -12,6 +17,7 @@ use bevy::{
     reflect::TypePath,
     scene::{SceneInstance, SceneInstanceReady},
 };
+use bevy_easings::{Ease, EaseFunction, EasingType};
 use bevy_firework::{
     bevy_utilitarian::{
         prelude::{Gradient, ParamCurve},
@@ -22,9 +28,25 @@ use bevy_firework::{
 };
 use bitflags::bitflags;
 use polyanya::Polygon;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{assets::GameAssets, game::ColliderKind};
+#[cfg(feature = "mods")]
+use crate::extensions::GameExtensions;
+use crate::{
+    assets::GameAssets,
+    game::{ActiveLevel, ColliderKind, GameEvent},
+};
+
+pub mod generator;
+
+/// Height of the walls, lowered in debug builds to see through them
+pub(crate) const WALL_HEIGHT: f32 = if cfg!(feature = "debug") { 0.4 } else { 2.0 };
+/// Hobbits carrying treasure back home are slightly slower
+const DEFAULT_TIRED_SPEED: f32 = 0.9;
+/// Fatigue never slows hobbits below this share of their speed
+const MIN_FATIGUE_SPEED: f32 = 0.5;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Tile {
@@ -36,31 +58,347 @@ pub enum Tile {
     Empty,
     Skeleton,
     OneWay(CompassQuadrant),
+    /// A tile registered by a mod, identified by its symbol
+    #[cfg(feature = "mods")]
+    Custom(char),
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub enum Bonus {
     Obstacle,
+    /// Blocks its cell like an obstacle, and lights the cells around it in night levels
+    Torch,
+    /// A bonus registered by a mod, identified by its registration index
+    #[cfg(feature = "mods")]
+    Custom(usize),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum HobbitKind {
+    #[default]
+    Regular,
+    Scout,
+    Porter,
 }
 
+impl HobbitKind {
+    pub fn speed(&self) -> f32 {
+        match self {
+            HobbitKind::Regular => 8.0,
+            HobbitKind::Scout => 11.0,
+            HobbitKind::Porter => 5.5,
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        match self {
+            HobbitKind::Regular => 0.5,
+            HobbitKind::Scout => 0.35,
+            HobbitKind::Porter => 0.6,
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        match self {
+            HobbitKind::Regular => 1.0,
+            HobbitKind::Scout => 0.8,
+            HobbitKind::Porter => 1.2,
+        }
+    }
+
+    /// Number of treasures this hobbit brings back home
+    pub fn capacity(&self) -> u32 {
+        match self {
+            HobbitKind::Regular | HobbitKind::Scout => 1,
+            HobbitKind::Porter => 2,
+        }
+    }
+}
+
+/// Hobbits going back and forth between a start and a chest. Most levels have a single team.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Team {
+    /// First spawn point of the team
+    pub start: (usize, usize, usize),
+    /// First chest of the team
+    pub end: (usize, usize, usize),
+}
+
+/// Where hobbits of a team go to get a treasure
+#[derive(Debug, Clone, Copy)]
+pub struct ChestPoint {
+    pub team: usize,
+    pub cell: (usize, usize, usize),
+    /// Number of treasures in the chest, unlimited if not set
+    pub treasures: Option<u32>,
+}
+
+/// Where hobbits of a team appear, and bring the treasure back to
+#[derive(Debug, Clone, Copy)]
+pub struct SpawnPoint {
+    pub team: usize,
+    pub cell: (usize, usize, usize),
+    /// Number of hobbits from this spawn point at the same time
+    pub hobbits: u32,
+    pub delay: f32,
+    /// Number of hobbits spawned at once
+    pub burst: u32,
+}
+
+/// Where the camera is at a moment of the level intro
+#[derive(Debug, Clone, Copy)]
+pub struct CameraKey {
+    /// Seconds since the start of the intro
+    pub at: f32,
+    pub position: Vec3,
+    /// Point the camera looks at
+    pub target: Vec3,
+}
+
+/// Skeletons added to a level remixed for New Game+
+const REMIX_SKELETONS: usize = 1;
+/// Spawn delays are multiplied by this in a level remixed for New Game+
+const REMIX_SPAWN_DELAY: f32 = 0.8;
+
+/// Seconds a page of the level message is shown when it doesn't set its own duration
+pub const DEFAULT_PAGE_DURATION: f32 = 6.0;
+
+/// A page of the message shown when a level starts
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessagePage {
+    pub text: String,
+    /// Image of the character speaking, as a path in the assets
+    pub portrait: Option<String>,
+    /// Seconds before turning to the next page, if the player doesn't click on it before
+    pub duration: f32,
+}
+
+impl MessagePage {
+    /// A page as `text`, optionally prefixed with `[portrait=path,duration=seconds]`
+    fn parse(page: &str) -> Result<Self, LevelError> {
+        let (options, text) = page
+            .strip_prefix('[')
+            .and_then(|page| page.split_once(']'))
+            .unwrap_or(("", page));
+        let mut parsed = MessagePage {
+            text: text.replace("\\n", "\n"),
+            portrait: None,
+            duration: DEFAULT_PAGE_DURATION,
+        };
+        for option in options.split(',').filter(|o| !o.is_empty()) {
+            match option.split_once('=') {
+                Some(("portrait", path)) => parsed.portrait = Some(path.to_string()),
+                Some(("duration", seconds)) => parsed.duration = parse_value("message", seconds)?,
+                _ => {
+                    return Err(LevelError::InvalidValue {
+                        key: "message",
+                        value: option.to_string(),
+                    })
+                }
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Area of the level where wind pushes hobbits and particles
+#[derive(Debug, Clone, Copy)]
+pub struct WindZone {
+    /// First cell of the zone
+    pub from: (usize, usize),
+    /// Last cell of the zone, included
+    pub to: (usize, usize),
+    pub direction: CompassQuadrant,
+    pub strength: f32,
+}
+
+impl WindZone {
+    pub fn contains(&self, position: Vec3) -> bool {
+        let (x, z) = ((position.x / 4.0).round(), (position.z / 4.0).round());
+        x >= self.from.0 as f32
+            && x <= self.to.0 as f32
+            && z >= self.from.1 as f32
+            && z <= self.to.1 as f32
+    }
+
+    /// Acceleration given by the wind
+    pub fn force(&self) -> Vec3 {
+        let direction = match self.direction {
+            CompassQuadrant::North => Vec3::NEG_Z,
+            CompassQuadrant::South => Vec3::Z,
+            CompassQuadrant::East => Vec3::X,
+            CompassQuadrant::West => Vec3::NEG_X,
+        };
+        direction * self.strength
+    }
+}
+
+/// Rule applied to hobbits inside a zone drawn by the player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ZoneRule {
+    /// Hobbits walk at half speed
+    Slow,
+    /// Hobbits stop for a moment when entering
+    Wait,
+    /// Hobbits keep to the left of their path
+    PreferLeft,
+}
+
+impl ZoneRule {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ZoneRule::Slow => "Slow",
+            ZoneRule::Wait => "Wait",
+            ZoneRule::PreferLeft => "Left",
+        }
+    }
+
+    pub fn color(&self) -> Srgba {
+        match self {
+            ZoneRule::Slow => palettes::tailwind::SKY_400,
+            ZoneRule::Wait => palettes::tailwind::AMBER_400,
+            ZoneRule::PreferLeft => palettes::tailwind::FUCHSIA_400,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+    Expert,
+}
+
+impl Difficulty {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+            Difficulty::Expert => "Expert",
+        }
+    }
+
+    pub fn color(&self) -> Srgba {
+        match self {
+            Difficulty::Easy => palettes::tailwind::GREEN_200,
+            Difficulty::Normal => palettes::tailwind::SKY_200,
+            Difficulty::Hard => palettes::tailwind::AMBER_300,
+            Difficulty::Expert => palettes::tailwind::RED_400,
+        }
+    }
+}
+
+/// Condition of a level. It is won once all its objectives are met, and lost as soon as one fails.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Objective {
+    /// Every team brings this many treasures back home
+    Treasures(u32),
+    /// Fails once this many hobbits are lost
+    MaxLosses(u32),
+    /// Keep the hobbits going for this many seconds
+    Survive(f32),
+    /// Fails if the other objectives are not met after this many seconds
+    TimeLimit(f32),
+    /// The hobbit spawned at this rank, starting from 1, must make it back home
+    Escort(u32),
+}
+
+/// Colors of each team in levels with more than one team
+pub const TEAM_COLORS: [Srgba; 2] = [palettes::tailwind::SKY_400, palettes::tailwind::ROSE_400];
+
+/// A level, loaded from a `.level` file by the asset server or with [`Level::parse`]
 #[derive(Asset, TypePath, Debug, Clone)]
 pub struct Level {
+    /// Tiles of each floor, by row then column
     pub floors: Vec<Vec<Vec<Tile>>>,
     pub neighbours: Vec<Vec<Vec<Flags>>>,
-    pub start: (usize, usize, usize),
-    pub end: (usize, usize, usize),
+    pub teams: Vec<Team>,
+    /// In reading order of the map
+    pub spawns: Vec<SpawnPoint>,
+    /// In reading order of the map
+    pub chests: Vec<ChestPoint>,
     pub nb_hobbits: u32,
     pub spawn_delay: f32,
-    pub message: Option<String>,
+    /// Pages of the message shown when the level starts, empty for no message
+    pub message: Vec<MessagePage>,
     pub goal: Option<String>,
-    pub treasures: u32,
-    pub losts: Option<u32>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub difficulty: Option<Difficulty>,
+    pub objectives: Vec<Objective>,
     pub bonus: Vec<Bonus>,
+    pub roster: Vec<HobbitKind>,
+    /// Hobbits only walk in lit cells: around starts, chests and torches
+    pub night: bool,
+    pub winds: Vec<WindZone>,
+    /// Path of the camera when the level starts, the default flyover when empty
+    pub intro: Vec<CameraKey>,
+    /// Hobbits can hop over an empty cell between two floors
+    pub jump: bool,
+    /// Spawns are held while hobbits crowd around their start
+    pub throttle: bool,
+    /// Speed multiplier of hobbits going back home
+    pub tired_speed: f32,
+    /// Share of their speed hobbits lose for every 100 units walked, 0 to disable fatigue
+    pub fatigue: f32,
+    /// Zones the player can draw, one rule each
+    pub zones: Vec<ZoneRule>,
+    /// Soundtrack played during the level, as a path in the assets. The default game track when
+    /// `None`.
+    pub music: Option<String>,
+    /// Tutorial played at the start of the level instead of showing its message, as the path of a
+    /// [`TutorialScript`](crate::tutorial::TutorialScript) in the assets
+    pub tutorial: Option<String>,
+    /// Cells of a layout of obstacles winning the level, shown to players stuck on it
+    pub hint: Vec<(usize, usize)>,
     pub file: String,
+    /// Hash of the level file content, changes every time the level is modified
+    pub hash: u64,
+    /// Navigation mesh without any cell removed, built once when loaded as an asset. See
+    /// [`Level::base_navmesh`].
+    pub navmesh: Option<polyanya::Mesh>,
+}
+
+/// Why a level file can't be played
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum LevelError {
+    #[error("missing header `{0}`")]
+    MissingHeader(&'static str),
+    #[error("invalid value `{value}` for `{key}`")]
+    InvalidValue { key: &'static str, value: String },
+    #[error("unknown bonus `{0}`")]
+    UnknownBonus(String),
+    #[error("unknown hobbit kind `{0}`")]
+    UnknownHobbitKind(String),
+    #[error("unknown difficulty `{0}`")]
+    UnknownDifficulty(String),
+    #[error("unknown objective `{0}`")]
+    UnknownObjective(String),
+    #[error("unknown zone `{0}`")]
+    UnknownZone(String),
+    #[error("unknown wind direction `{0}`")]
+    UnknownWindDirection(String),
+    #[error("unknown tile `{tile}` at ({x}, {y})")]
+    UnknownTile { tile: char, x: usize, y: usize },
+    #[error("no map after the headers")]
+    MissingMap,
+    #[error("no start for team {0}")]
+    NoStart(usize),
+    #[error("no chest for team {0}")]
+    NoChest(usize),
+    #[error("chest at ({x}, {y}) can't be reached from a start")]
+    UnreachableChest { x: usize, y: usize },
+    #[error("cells only touching by their corner at the top left of ({x}, {y})")]
+    DiagonalCorner { x: usize, y: usize },
 }
 
 #[derive(Default)]
-struct LevelAssetLoader;
+struct LevelAssetLoader {
+    #[cfg(feature = "mods")]
+    extensions: GameExtensions,
+}
 
 /// Possible errors that can be produced by [`BlobAssetLoader`]
 #[non_exhaustive]
@@ -69,6 +407,9 @@ enum LevelAssetLoaderError {
     /// An [IO](std::io) Error
     #[error("Could not load file: {0}")]
     Io(#[from] std::io::Error),
+    /// The file is not a level that can be played
+    #[error("Invalid level: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    Invalid(Vec<LevelError>),
 }
 
 impl AssetLoader for LevelAssetLoader {
@@ -84,83 +425,330 @@ impl AssetLoader for LevelAssetLoader {
     ) -> Result<Self::Asset, Self::Error> {
         let mut content = String::new();
         reader.read_to_string(&mut content).await?;
+
+        let mut level = Level::parse_with(
+            &content,
+            &load_context.path().to_string_lossy(),
+            #[cfg(feature = "mods")]
+            &self.extensions,
+        )
+        .map_err(|err| LevelAssetLoaderError::Invalid(vec![err]))?;
+        level.validate().map_err(LevelAssetLoaderError::Invalid)?;
+        // built while loading, so that starting the level doesn't pay for it every time
+        level.navmesh = Some(level.as_navmesh(vec![]));
+        Ok(level)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level"]
+    }
+}
+
+impl Level {
+    /// Read a level from the content of a `.level` file. `file` is only used to identify the
+    /// level.
+    ///
+    /// This only checks the syntax, use [`Level::validate`] to check the level can be played.
+    pub fn parse(content: &str, file: &str) -> Result<Level, LevelError> {
+        Level::parse_with(
+            content,
+            file,
+            #[cfg(feature = "mods")]
+            &GameExtensions::default(),
+        )
+    }
+
+    fn parse_with(
+        content: &str,
+        file: &str,
+        #[cfg(feature = "mods")] extensions: &GameExtensions,
+    ) -> Result<Level, LevelError> {
+        let hash = content_hash(content);
         let mut floor = Vec::new();
-        let mut start = (0, 0, 0);
-        let mut end = (0, 0, 0);
-
-        let mut lines = content.lines();
-        let line = lines.next().unwrap();
-        let nb_hobbits = line.split(':').last().unwrap().parse().unwrap();
-        let line = lines.next().unwrap();
-        let spawn_delay = line.split(':').last().unwrap().parse().unwrap();
-        let line = lines.next().unwrap();
-        let message = line
-            .split(':')
-            .skip(1)
-            .map(|s| s.to_string())
-            .collect::<Vec<String>>()
-            .join(":");
-        let message = if &message == "none" {
-            None
-        } else {
-            Some(message.replace("\\n", "\n"))
-        };
+        // second team is optional, with `Y` as start and `w`, `n`, `e`, `s` as chest
+        let mut teams = [Team::default(); 2];
+        let mut second_team = false;
+        let mut spawns: Vec<SpawnPoint> = vec![];
+        let mut chests: Vec<ChestPoint> = vec![];
 
-        let line = lines.next().unwrap();
-        let goal = match line.split(':').last().unwrap() {
+        let mut lines = content.lines().peekable();
+        let nb_hobbits = parse_value("hobbits", header(&mut lines, "hobbits")?)?;
+        let spawn_delay = parse_value("delay", header(&mut lines, "delay")?)?;
+        // pages separated by `|`
+        let message = match header(&mut lines, "message")? {
+            "none" => vec![],
+            message => message
+                .split('|')
+                .map(MessagePage::parse)
+                .collect::<Result<_, _>>()?,
+        };
+        let goal = match header(&mut lines, "goal")? {
             "none" => None,
             s => Some(s.to_string()),
         };
-        let line = lines.next().unwrap();
-        let treasures = line.split(':').last().unwrap().parse().unwrap();
-        let line = lines.next().unwrap();
-        let losts = line.split(':').last().unwrap().parse().ok();
-        let line = lines.next().unwrap();
-        let bonus = line
-            .split(':')
-            .last()
-            .unwrap()
+        let mut objectives = vec![Objective::Treasures(parse_value(
+            "treasures",
+            header(&mut lines, "treasures")?,
+        )?)];
+        if let Ok(losts) = header(&mut lines, "lost")?.parse() {
+            objectives.push(Objective::MaxLosses(losts));
+        }
+        let bonus = header(&mut lines, "bonus")?
             .split(',')
-            .flat_map(|s| match s {
-                "Obstacle" => Some(Bonus::Obstacle),
-                "" => None,
+            .filter(|s| !s.is_empty())
+            .map(|s| match s {
+                "Obstacle" => Ok(Bonus::Obstacle),
+                "Torch" => Ok(Bonus::Torch),
                 s => {
-                    error!("unknown bonus: {}", s);
-                    unimplemented!()
+                    #[cfg(feature = "mods")]
+                    if let Some(index) = extensions.bonus_index(s) {
+                        return Ok(Bonus::Custom(index));
+                    }
+                    Err(LevelError::UnknownBonus(s.to_string()))
                 }
             })
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // optional headers, until the map starts
+        let mut roster = vec![HobbitKind::Regular];
+        let mut title = None;
+        let mut author = None;
+        let mut difficulty = None;
+        let mut night = false;
+        let mut jump = false;
+        let mut throttle = false;
+        let mut tired_speed = DEFAULT_TIRED_SPEED;
+        let mut fatigue = 0.0;
+        let mut zones = vec![];
+        let mut music = None;
+        let mut tutorial = None;
+        let mut hint = vec![];
+        let mut winds = vec![];
+        let mut intro = vec![];
+        let mut spawn_settings: Vec<(u32, f32, u32)> = vec![];
+        let mut chest_treasures: Vec<Option<u32>> = vec![];
+        while let Some((key, value)) = lines.peek().and_then(|line| line.split_once(':')) {
+            match key {
+                "roster" => {
+                    roster = value
+                        .split(',')
+                        .filter(|s| !s.is_empty())
+                        .map(|s| match s {
+                            "Hobbit" => Ok(HobbitKind::Regular),
+                            "Scout" => Ok(HobbitKind::Scout),
+                            "Porter" => Ok(HobbitKind::Porter),
+                            s => Err(LevelError::UnknownHobbitKind(s.to_string())),
+                        })
+                        .collect::<Result<_, _>>()?;
+                    if roster.is_empty() {
+                        roster.push(HobbitKind::Regular);
+                    }
+                }
+                "title" => title = Some(value.to_string()),
+                "author" => author = Some(value.to_string()),
+                "difficulty" => {
+                    difficulty = Some(match value {
+                        "Easy" => Difficulty::Easy,
+                        "Normal" => Difficulty::Normal,
+                        "Hard" => Difficulty::Hard,
+                        "Expert" => Difficulty::Expert,
+                        s => return Err(LevelError::UnknownDifficulty(s.to_string())),
+                    })
+                }
+                "night" => night = value == "true",
+                "jump" => jump = value == "true",
+                "throttle" => throttle = value == "true",
+                "music" => music = Some(value.to_string()),
+                "tutorial" => tutorial = Some(value.to_string()),
+                "zones" => {
+                    zones = value
+                        .split(',')
+                        .filter(|s| !s.is_empty())
+                        .map(|s| match s {
+                            "Slow" => Ok(ZoneRule::Slow),
+                            "Wait" => Ok(ZoneRule::Wait),
+                            "Left" => Ok(ZoneRule::PreferLeft),
+                            s => Err(LevelError::UnknownZone(s.to_string())),
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                "tired" => tired_speed = parse_value("tired", value)?,
+                "fatigue" => fatigue = parse_value("fatigue", value)?,
+                // per spawn point in reading order, as `hobbits@delay` or `hobbits@delay*burst`
+                "spawns" => {
+                    spawn_settings = value
+                        .split(',')
+                        .filter(|spawn| !spawn.is_empty())
+                        .map(|spawn| {
+                            let (hobbits, timing) =
+                                spawn.split_once('@').ok_or(LevelError::InvalidValue {
+                                    key: "spawns",
+                                    value: spawn.to_string(),
+                                })?;
+                            let (delay, burst) = timing.split_once('*').unwrap_or((timing, "1"));
+                            Ok((
+                                parse_value("spawns", hobbits)?,
+                                parse_value("spawns", delay)?,
+                                parse_value("spawns", burst)?,
+                            ))
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                // added to the treasures and losts objectives, as `survive=seconds`, `escort=rank`
+                // or `time=seconds`
+                "objectives" => {
+                    for objective in value.split(',').filter(|o| !o.is_empty()) {
+                        objectives.push(match objective.split_once('=') {
+                            Some(("survive", seconds)) => {
+                                Objective::Survive(parse_value("objectives", seconds)?)
+                            }
+                            Some(("escort", rank)) => {
+                                Objective::Escort(parse_value("objectives", rank)?)
+                            }
+                            Some(("time", seconds)) => {
+                                Objective::TimeLimit(parse_value("objectives", seconds)?)
+                            }
+                            _ => return Err(LevelError::UnknownObjective(objective.to_string())),
+                        });
+                    }
+                }
+                // per chest in reading order, empty for an unlimited chest
+                "chests" => {
+                    chest_treasures = value
+                        .split(',')
+                        .map(|treasures| treasures.parse().ok())
+                        .collect();
+                }
+                // zones separated by `;`, each as `x,y,width,height,direction,strength`
+                "wind" => {
+                    winds = value
+                        .split(';')
+                        .filter(|zone| !zone.is_empty())
+                        .map(|zone| {
+                            let parts = zone.split(',').collect::<Vec<_>>();
+                            let [x, y, width, height, direction, strength] = parts[..] else {
+                                return Err(LevelError::InvalidValue {
+                                    key: "wind",
+                                    value: zone.to_string(),
+                                });
+                            };
+                            let x: usize = parse_value("wind", x)?;
+                            let y: usize = parse_value("wind", y)?;
+                            let width: usize = parse_value("wind", width)?;
+                            let height: usize = parse_value("wind", height)?;
+                            Ok(WindZone {
+                                from: (x, y),
+                                to: (x + width.max(1) - 1, y + height.max(1) - 1),
+                                direction: match direction {
+                                    "N" => CompassQuadrant::North,
+                                    "S" => CompassQuadrant::South,
+                                    "E" => CompassQuadrant::East,
+                                    "W" => CompassQuadrant::West,
+                                    s => {
+                                        return Err(LevelError::UnknownWindDirection(s.to_string()))
+                                    }
+                                },
+                                strength: parse_value("wind", strength)?,
+                            })
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                // cells separated by `;`, each as `x,y`
+                "hint" => {
+                    hint = value
+                        .split(';')
+                        .filter(|cell| !cell.is_empty())
+                        .map(|cell| {
+                            let (x, y) = cell.split_once(',').ok_or(LevelError::InvalidValue {
+                                key: "hint",
+                                value: cell.to_string(),
+                            })?;
+                            Ok((parse_value("hint", x)?, parse_value("hint", y)?))
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                // keys separated by `;`, each as `seconds,x,height,y,target_x,target_y` with `x`
+                // and `y` in cells, at least two keys in increasing time
+                "camera" => {
+                    intro = value
+                        .split(';')
+                        .filter(|key| !key.is_empty())
+                        .map(|key| {
+                            let parts = key.split(',').collect::<Vec<_>>();
+                            let [at, x, height, y, target_x, target_y] = parts[..] else {
+                                return Err(LevelError::InvalidValue {
+                                    key: "camera",
+                                    value: key.to_string(),
+                                });
+                            };
+                            let cell = |x: &str, y: &str| -> Result<(f32, f32), LevelError> {
+                                Ok((
+                                    parse_value::<f32>("camera", x)? * 4.0,
+                                    parse_value::<f32>("camera", y)? * 4.0,
+                                ))
+                            };
+                            let (x, z) = cell(x, y)?;
+                            let (target_x, target_z) = cell(target_x, target_y)?;
+                            Ok(CameraKey {
+                                at: parse_value("camera", at)?,
+                                position: Vec3::new(x, parse_value("camera", height)?, z),
+                                target: Vec3::new(target_x, 0.0, target_z),
+                            })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if intro.len() < 2 || intro.windows(2).any(|keys| keys[1].at <= keys[0].at) {
+                        return Err(LevelError::InvalidValue {
+                            key: "camera",
+                            value: value.to_string(),
+                        });
+                    }
+                }
+                _ => break,
+            }
+            lines.next();
+        }
 
         for (j, line) in lines.enumerate() {
             let mut row = Vec::new();
             for (i, char) in line.chars().enumerate() {
                 row.push(match char {
-                    'X' => {
-                        start.1 = i;
-                        start.2 = j;
+                    'X' | 'Y' => {
+                        let team = if char == 'X' { 0 } else { 1 };
+                        if spawns.iter().all(|spawn| spawn.team != team) {
+                            teams[team].start = (0, i, j);
+                        }
+                        second_team |= team == 1;
+                        let (hobbits, delay, burst) = spawn_settings
+                            .get(spawns.len())
+                            .copied()
+                            .unwrap_or((nb_hobbits, spawn_delay, 1));
+                        spawns.push(SpawnPoint {
+                            team,
+                            cell: (0, i, j),
+                            hobbits,
+                            delay,
+                            burst,
+                        });
                         Tile::Start
                     }
                     '#' => Tile::Floor,
 
-                    '<' => {
-                        end.1 = i;
-                        end.2 = j;
-                        Tile::Chest(CompassQuadrant::West)
-                    }
-                    '^' => {
-                        end.1 = i;
-                        end.2 = j;
-                        Tile::Chest(CompassQuadrant::North)
-                    }
-                    '>' => {
-                        end.1 = i;
-                        end.2 = j;
-                        Tile::Chest(CompassQuadrant::East)
-                    }
-                    'v' => {
-                        end.1 = i;
-                        end.2 = j;
-                        Tile::Chest(CompassQuadrant::South)
+                    '<' | '^' | '>' | 'v' | 'w' | 'n' | 'e' | 's' => {
+                        let team = if "<^>v".contains(char) { 0 } else { 1 };
+                        if chests.iter().all(|chest| chest.team != team) {
+                            teams[team].end = (0, i, j);
+                        }
+                        chests.push(ChestPoint {
+                            team,
+                            cell: (0, i, j),
+                            treasures: chest_treasures.get(chests.len()).copied().flatten(),
+                        });
+                        Tile::Chest(match char {
+                            '<' | 'w' => CompassQuadrant::West,
+                            '^' | 'n' => CompassQuadrant::North,
+                            '>' | 'e' => CompassQuadrant::East,
+                            _ => CompassQuadrant::South,
+                        })
                     }
                     'h' => Tile::OneWay(CompassQuadrant::East),
                     'H' => Tile::OneWay(CompassQuadrant::West),
@@ -170,11 +758,16 @@ impl AssetLoader for LevelAssetLoader {
                     'I' => Tile::In,
                     'O' => Tile::Out,
                     ' ' => Tile::Empty,
-                    _ => unimplemented!(),
+                    #[cfg(feature = "mods")]
+                    c if extensions.tile(c).is_some() => Tile::Custom(c),
+                    tile => return Err(LevelError::UnknownTile { tile, x: i, y: j }),
                 });
             }
             floor.push(row);
         }
+        if floor.is_empty() {
+            return Err(LevelError::MissingMap);
+        }
 
         let mut neighbours = Vec::new();
         for j in 0..floor.len() {
@@ -188,21 +781,450 @@ impl AssetLoader for LevelAssetLoader {
         Ok(Level {
             floors: vec![floor],
             neighbours: vec![neighbours],
-            start,
-            end,
+            teams: teams[..if second_team { 2 } else { 1 }].to_vec(),
+            spawns,
+            chests,
             nb_hobbits,
             spawn_delay,
-            message,
+            // the tutorial replaces the message
+            message: if tutorial.is_some() { vec![] } else { message },
             goal,
-            treasures,
-            losts,
+            title,
+            author,
+            difficulty,
+            objectives,
             bonus,
-            file: load_context.path().to_string_lossy().to_string(),
+            roster,
+            night,
+            winds,
+            intro,
+            jump,
+            throttle,
+            tired_speed,
+            fatigue,
+            zones,
+            music,
+            tutorial,
+            hint,
+            file: file.to_string(),
+            hash,
+            navmesh: None,
         })
     }
+}
 
-    fn extensions(&self) -> &[&str] {
-        &["level"]
+/// Value of a mandatory `key:value` header, on the next line
+fn header<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    key: &'static str,
+) -> Result<&'a str, LevelError> {
+    lines
+        .next()
+        .and_then(|line| line.split_once(':'))
+        .map(|(_, value)| value)
+        .ok_or(LevelError::MissingHeader(key))
+}
+
+fn parse_value<T: FromStr>(key: &'static str, value: &str) -> Result<T, LevelError> {
+    value.parse().map_err(|_| LevelError::InvalidValue {
+        key,
+        value: value.to_string(),
+    })
+}
+
+/// FNV-1a, stable across platforms and Rust versions so it can be persisted
+fn content_hash(content: &str) -> u64 {
+    content
+        .lines()
+        .flat_map(|line| line.trim_end().bytes().chain(std::iter::once(b'\n')))
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+impl Level {
+    /// Check the level can be played: every team has a start and a chest, no floor cells only
+    /// touch by a corner, and every chest can be reached from a start of its team before any
+    /// bonus is placed.
+    pub fn validate(&self) -> Result<(), Vec<LevelError>> {
+        let mut errors = vec![];
+        let teams = self
+            .spawns
+            .iter()
+            .map(|spawn| spawn.team)
+            .chain(self.chests.iter().map(|chest| chest.team))
+            .max()
+            .unwrap_or(0);
+        for team in 0..=teams {
+            if self.spawns.iter().all(|spawn| spawn.team != team) {
+                errors.push(LevelError::NoStart(team));
+            }
+            if self.chests.iter().all(|chest| chest.team != team) {
+                errors.push(LevelError::NoChest(team));
+            }
+        }
+        errors.extend(
+            self.diagonal_corners()
+                .into_iter()
+                .map(|(x, y)| LevelError::DiagonalCorner { x, y }),
+        );
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let navmesh = self.base_navmesh();
+        for chest in &self.chests {
+            let to = vec2(chest.cell.1 as f32 * 4.0, chest.cell.2 as f32 * 4.0);
+            let reachable = self
+                .spawns
+                .iter()
+                .filter(|spawn| spawn.team == chest.team)
+                .any(|spawn| {
+                    let from = vec2(spawn.cell.1 as f32 * 4.0, spawn.cell.2 as f32 * 4.0);
+                    navmesh
+                        .path_on_layers(from, to, HashSet::from([2]))
+                        .is_some()
+                });
+            if !reachable {
+                errors.push(LevelError::UnreachableChest {
+                    x: chest.cell.1,
+                    y: chest.cell.2,
+                });
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Distance in cells lit around a start, a chest or a torch in night levels
+pub const LIGHT_RADIUS: f32 = 2.5;
+
+impl Level {
+    /// Cells hobbits can't walk on because they are in the dark. Always empty for day levels.
+    pub fn dark_cells(&self, torches: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        if !self.night {
+            return vec![];
+        }
+        let lights = self
+            .chests
+            .iter()
+            .map(|chest| (chest.cell.1, chest.cell.2))
+            .chain(self.spawns.iter().map(|spawn| (spawn.cell.1, spawn.cell.2)))
+            .chain(torches.iter().copied())
+            .map(|(x, y)| vec2(x as f32, y as f32))
+            .collect::<Vec<_>>();
+        self.floors[0]
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, tile)| **tile != Tile::Empty)
+                    .map(move |(x, _)| (x, y))
+            })
+            .filter(|(x, y)| {
+                let cell = vec2(*x as f32, *y as f32);
+                lights
+                    .iter()
+                    .all(|light| light.distance(cell) > LIGHT_RADIUS)
+            })
+            .collect()
+    }
+
+    /// The level as it's played in New Game+: more skeletons, a bonus less and hobbits coming out
+    /// faster. Skeletons go in open areas away from starts and chests, so that the level can still
+    /// be won, on the same cells every time.
+    pub fn remix(&self) -> Level {
+        let mut level = self.clone();
+        let floor = &self.floors[0];
+        let is_floor =
+            |x: usize, y: usize| floor.get(y).and_then(|row| row.get(x)) == Some(&Tile::Floor);
+        let points = self
+            .spawns
+            .iter()
+            .map(|spawn| (spawn.cell.1, spawn.cell.2))
+            .chain(self.chests.iter().map(|chest| (chest.cell.1, chest.cell.2)))
+            .collect::<Vec<_>>();
+        let mut open = (1..floor.len())
+            .flat_map(|y| (1..floor[y].len()).map(move |x| (x, y)))
+            .filter(|&(x, y)| {
+                is_floor(x, y)
+                    && is_floor(x - 1, y)
+                    && is_floor(x + 1, y)
+                    && is_floor(x, y - 1)
+                    && is_floor(x, y + 1)
+                    && points
+                        .iter()
+                        .all(|point| point.0.abs_diff(x).max(point.1.abs_diff(y)) > 2)
+            })
+            .collect::<Vec<_>>();
+        open.shuffle(&mut StdRng::seed_from_u64(self.hash));
+        for (x, y) in open.into_iter().take(REMIX_SKELETONS) {
+            level.floors[0][y][x] = Tile::Skeleton;
+        }
+        if level.bonus.len() > 1 {
+            level.bonus.pop();
+        }
+        level.spawn_delay *= REMIX_SPAWN_DELAY;
+        for spawn in &mut level.spawns {
+            spawn.delay *= REMIX_SPAWN_DELAY;
+        }
+        // it solves the original level
+        level.hint.clear();
+        level
+    }
+
+    /// The level mirrored left to right. It keeps the hash of the level, its layouts and solutions
+    /// are mirrored with [`Level::mirror_cell`] and its autosave knows it was mirrored from the
+    /// mutators.
+    pub fn mirrored(&self) -> Level {
+        let mut level = self.clone();
+        let width = self.floors[0].iter().map(Vec::len).max().unwrap_or(0);
+        let mirror_x = |x: usize| width - 1 - x;
+        let mirror_direction = |direction: CompassQuadrant| match direction {
+            CompassQuadrant::East => CompassQuadrant::West,
+            CompassQuadrant::West => CompassQuadrant::East,
+            direction => direction,
+        };
+        for row in &mut level.floors[0] {
+            row.resize(width, Tile::Empty);
+            row.reverse();
+            for tile in row.iter_mut() {
+                *tile = match *tile {
+                    Tile::Chest(direction) => Tile::Chest(mirror_direction(direction)),
+                    Tile::OneWay(direction) => Tile::OneWay(mirror_direction(direction)),
+                    tile => tile,
+                };
+            }
+        }
+        let floor = &level.floors[0];
+        let neighbours = (0..floor.len())
+            .map(|j| {
+                (0..width)
+                    .map(|i| get_neighbours(0, i, j, &[floor]))
+                    .collect()
+            })
+            .collect();
+        level.neighbours = vec![neighbours];
+        for team in &mut level.teams {
+            team.start.1 = mirror_x(team.start.1);
+            team.end.1 = mirror_x(team.end.1);
+        }
+        for spawn in &mut level.spawns {
+            spawn.cell.1 = mirror_x(spawn.cell.1);
+        }
+        for chest in &mut level.chests {
+            chest.cell.1 = mirror_x(chest.cell.1);
+        }
+        for wind in &mut level.winds {
+            (wind.from.0, wind.to.0) = (mirror_x(wind.to.0), mirror_x(wind.from.0));
+            wind.direction = mirror_direction(wind.direction);
+        }
+        let mirror_position = |position: Vec3| {
+            Vec3::new(
+                mirror_x(0) as f32 * 4.0 - position.x,
+                position.y,
+                position.z,
+            )
+        };
+        for key in &mut level.intro {
+            key.position = mirror_position(key.position);
+            key.target = mirror_position(key.target);
+        }
+        for cell in &mut level.hint {
+            cell.0 = mirror_x(cell.0);
+        }
+        // built again for the mirrored floor
+        level.navmesh = None;
+        level
+    }
+
+    /// Cell of the mirrored level for a cell of this one, and the other way around
+    pub fn mirror_cell(&self, (x, y): (usize, usize)) -> (usize, usize) {
+        let width = self.floors[0].iter().map(Vec::len).max().unwrap_or(0);
+        // a cell outside of the level stays outside
+        (width.wrapping_sub(x + 1), y)
+    }
+
+    /// Seconds the message is shown when the level starts, if the player doesn't turn its pages
+    pub fn message_duration(&self) -> f32 {
+        self.message.iter().map(|page| page.duration).sum()
+    }
+
+    /// Title of the level, or its number if it doesn't have one
+    pub fn display_title(&self, index: usize) -> String {
+        self.title
+            .clone()
+            .unwrap_or_else(|| format!("Level {}", index))
+    }
+
+    /// Title, author and difficulty of the level, to display in a text
+    pub fn title_sections(&self, index: usize, font_size: f32) -> Vec<TextSection> {
+        let mut sections = vec![TextSection {
+            value: self.display_title(index),
+            style: TextStyle {
+                font_size,
+                color: Color::WHITE,
+                ..default()
+            },
+        }];
+        if let Some(author) = &self.author {
+            sections.push(TextSection {
+                value: format!("  by {}", author),
+                style: TextStyle {
+                    font_size: font_size * 0.7,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            });
+        }
+        if let Some(difficulty) = self.difficulty {
+            sections.push(TextSection {
+                value: format!("  {}", difficulty.label()),
+                style: TextStyle {
+                    font_size: font_size * 0.7,
+                    color: difficulty.color().into(),
+                    ..default()
+                },
+            });
+        }
+        sections
+    }
+
+    /// Empty cells hobbits can hop over, with a floor on both sides. Always empty if the level
+    /// doesn't allow jumps.
+    pub fn jump_gaps(&self) -> Vec<(usize, usize)> {
+        if !self.jump {
+            return vec![];
+        }
+        let floor = &self.floors[0];
+        let is_floor = |x: Option<usize>, y: Option<usize>| {
+            x.zip(y)
+                .and_then(|(x, y)| floor.get(y).and_then(|row| row.get(x)))
+                .is_some_and(|tile| *tile == Tile::Floor)
+        };
+        floor
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, tile)| **tile == Tile::Empty)
+                    .map(move |(x, _)| (x, y))
+            })
+            .filter(|(x, y)| {
+                (is_floor(x.checked_sub(1), Some(*y)) && is_floor(Some(x + 1), Some(*y)))
+                    || (is_floor(Some(*x), y.checked_sub(1)) && is_floor(Some(*x), Some(y + 1)))
+            })
+            .collect()
+    }
+
+    /// Neighbours of a cell of the ground floor, with the gaps hobbits jump over counted as floor
+    fn walkable_neighbours(&self, gaps: &[(usize, usize)], xi: usize, yi: usize) -> Flags {
+        let mut flag = self.neighbours[0][yi][xi];
+        for (side, dx, dy) in [
+            (Flags::TOPLEFT, -1, -1),
+            (Flags::TOP, 0, -1),
+            (Flags::TOPRIGHT, 1, -1),
+            (Flags::LEFT, -1, 0),
+            (Flags::CENTER, 0, 0),
+            (Flags::RIGHT, 1, 0),
+            (Flags::BOTTOMLEFT, -1, 1),
+            (Flags::BOTTOM, 0, 1),
+            (Flags::BOTTOMRIGHT, 1, 1),
+        ] {
+            if gaps.contains(&(xi.wrapping_add_signed(dx), yi.wrapping_add_signed(dy))) {
+                flag |= side;
+            }
+        }
+        flag
+    }
+
+    /// Cells whose top left corner is shared by two walkable cells touching only by that corner.
+    /// There is no navmesh for those.
+    fn diagonal_corners(&self) -> Vec<(usize, usize)> {
+        let floor = &self.floors[0];
+        let gaps = self.jump_gaps();
+        let mut corners = vec![];
+        for (yi, row) in floor.iter().enumerate() {
+            for xi in 0..row.len() {
+                let flag = self.walkable_neighbours(&gaps, xi, yi);
+                if matches!(
+                    (
+                        flag.contains(Flags::TOPLEFT),
+                        flag.contains(Flags::TOP),
+                        flag.contains(Flags::LEFT),
+                        flag.contains(Flags::CENTER),
+                    ),
+                    (true, false, false, true) | (false, true, true, false)
+                ) {
+                    corners.push((xi, yi));
+                }
+            }
+        }
+        corners
+    }
+
+    /// Rank of the hobbit to escort back home, if any
+    pub fn escort(&self) -> Option<u32> {
+        self.objectives
+            .iter()
+            .find_map(|objective| match objective {
+                Objective::Escort(rank) => Some(*rank),
+                _ => None,
+            })
+    }
+
+    /// Seconds to meet the objectives, if limited
+    pub fn time_limit(&self) -> Option<f32> {
+        self.objectives
+            .iter()
+            .find_map(|objective| match objective {
+                Objective::TimeLimit(seconds) => Some(*seconds),
+                _ => None,
+            })
+    }
+
+    /// Multiplier of the speed of a hobbit, slowed down going home and by the distance walked
+    pub fn speed_factor(&self, tired: bool, walked: f32) -> f32 {
+        let tired = if tired { self.tired_speed } else { 1.0 };
+        tired * (1.0 - self.fatigue * walked / 100.0).max(MIN_FATIGUE_SPEED)
+    }
+
+    /// Acceleration given by the wind at this position
+    pub fn wind_at(&self, position: Vec3) -> Vec3 {
+        self.winds
+            .iter()
+            .filter(|zone| zone.contains(position))
+            .map(WindZone::force)
+            .sum()
+    }
+
+    /// Color of the team using the given start or chest cell, only in levels with several teams
+    pub fn team_color(&self, cell: (usize, usize)) -> Option<Srgba> {
+        if self.teams.len() < 2 {
+            return None;
+        }
+        self.spawns
+            .iter()
+            .find(|spawn| (spawn.cell.1, spawn.cell.2) == cell)
+            .map(|spawn| spawn.team)
+            .or_else(|| {
+                self.chests
+                    .iter()
+                    .find(|chest| (chest.cell.1, chest.cell.2) == cell)
+                    .map(|chest| chest.team)
+            })
+            .map(|index| TEAM_COLORS[index])
+    }
+
+    /// Short version identifier of the level, to display
+    pub fn version(&self) -> String {
+        format!("{:08x}", self.hash >> 32)
     }
 }
 
@@ -287,9 +1309,27 @@ fn get_neighbours(floor: usize, i: usize, j: usize, data: &[&Vec<Vec<Tile>>]) ->
 pub struct Plugin;
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut App) {
-        app.init_asset::<Level>()
-            .init_asset_loader::<LevelAssetLoader>()
-            .add_systems(Update, (open_lid,));
+        app.init_asset::<Level>();
+        #[cfg(feature = "mods")]
+        {
+            let extensions = app
+                .world_mut()
+                .get_resource_or_insert_with(GameExtensions::default)
+                .clone();
+            app.register_asset_loader(LevelAssetLoader { extensions });
+        }
+        #[cfg(not(feature = "mods"))]
+        app.init_asset_loader::<LevelAssetLoader>();
+        app.add_systems(
+            Update,
+            (
+                open_lid,
+                close_chests,
+                reopen_chests.run_if(resource_exists_and_changed::<ActiveLevel>),
+                bevy_easings::custom_ease_system::<PointLightIntensity>,
+                fade_lights,
+            ),
+        );
     }
 }
 
@@ -383,6 +1423,22 @@ fn fix_indexes(
 }
 
 impl Level {
+    /// Navigation mesh of the level without any cell removed, built when the level was loaded as
+    /// an asset, or now for levels parsed directly
+    pub fn base_navmesh(&self) -> polyanya::Mesh {
+        self.navmesh
+            .clone()
+            .unwrap_or_else(|| self.as_navmesh(vec![]))
+    }
+
+    /// Navigation mesh of the level, without the given cells. Cells are 4 units wide, the center
+    /// of cell `(x, y)` is at `(x * 4, y * 4)`.
+    ///
+    /// Layer 1 has the `In` tiles, only walkable going to a chest, and layer 2 the `Out` tiles,
+    /// only walkable going home. Exclude the other layer when searching a path.
+    ///
+    /// To block or open cells while playing, start from [`Level::base_navmesh`] and use
+    /// [`NavMeshCells`].
     pub fn as_navmesh(&self, removed_cells: Vec<(usize, usize)>) -> polyanya::Mesh {
         info!("excluding cells from navmesh: {:?}", removed_cells);
         let floor = &self.floors[0];
@@ -392,11 +1448,13 @@ impl Level {
         let mut polygons_out = Vec::with_capacity(2);
         let mut polygons_ow = Vec::with_capacity(2);
         let mut one_way_stitches = Vec::with_capacity(4);
+        let gaps = self.jump_gaps();
 
         let floor = &self.floors[0];
         for (yi, row) in floor.iter().enumerate() {
             for (xi, tile) in row.iter().enumerate() {
-                let flag = self.neighbours[0][yi][xi];
+                // a gap lines up with the floors on each side, so the corridor keeps its width
+                let flag = self.walkable_neighbours(&gaps, xi, yi);
 
                 let (delta_x, delta_y) = match (
                     flag.contains(Flags::TOPLEFT),
@@ -481,7 +1539,8 @@ impl Level {
                                 .push(((topleft, bottomleft), (topright, bottomright))),
                         }
                     }
-                    Tile::Empty => (),
+                    // gaps are walkable, hobbits jump over them
+                    Tile::Empty if !gaps.contains(&(xi, yi)) => (),
                     _ => {
                         if removed_cells.contains(&(xi, yi)) {
                             continue;
@@ -513,7 +1572,7 @@ impl Level {
             ));
         }
         for xi in 0..floor[0].len() {
-            let flag = self.neighbours[0][floor.len() - 1][xi];
+            let flag = self.walkable_neighbours(&gaps, xi, floor.len() - 1);
             let mut delta_x = 0.0;
 
             if !flag.contains(Flags::CENTER) {
@@ -710,6 +1769,116 @@ impl Level {
     }
 }
 
+/// Walkable cells of a navmesh from [`Level::base_navmesh`]. Blocking or opening a cell only
+/// restitches the polygons around it, instead of building the navmesh again.
+#[derive(Debug, Clone, Default)]
+pub struct NavMeshCells {
+    /// Polygon of each walkable cell, on the first layer
+    polygons: HashMap<(usize, usize), u32>,
+    /// Polygons around each vertex of the first layer, with every cell open
+    around: Vec<Vec<u32>>,
+    /// Polygons of the blocked cells
+    blocked: HashSet<u32>,
+}
+
+impl NavMeshCells {
+    pub fn new(mesh: &polyanya::Mesh) -> Self {
+        let layer = &mesh.layers[0];
+        let polygons = layer
+            .polygons
+            .iter()
+            .enumerate()
+            .map(|(index, polygon)| {
+                let center = polygon
+                    .vertices
+                    .iter()
+                    .map(|vertex| layer.vertices[*vertex as usize].coords)
+                    .sum::<Vec2>()
+                    / polygon.vertices.len() as f32;
+                (
+                    (
+                        (center.x / 4.0).round() as usize,
+                        (center.y / 4.0).round() as usize,
+                    ),
+                    index as u32,
+                )
+            })
+            .collect();
+        NavMeshCells {
+            polygons,
+            around: layer
+                .vertices
+                .iter()
+                .map(|vertex| vertex.polygons.clone())
+                .collect(),
+            blocked: HashSet::new(),
+        }
+    }
+
+    pub fn is_blocked(&self, cell: (usize, usize)) -> bool {
+        self.polygons
+            .get(&cell)
+            .is_some_and(|polygon| self.blocked.contains(polygon))
+    }
+
+    /// Hobbits can't walk on the cell anymore. Cells that were never walkable are ignored.
+    pub fn block(&mut self, mesh: &mut polyanya::Mesh, cell: (usize, usize)) {
+        if let Some(polygon) = self.polygons.get(&cell).copied() {
+            if self.blocked.insert(polygon) {
+                self.restitch(mesh, polygon);
+            }
+        }
+    }
+
+    /// Hobbits can walk on the cell again
+    pub fn open(&mut self, mesh: &mut polyanya::Mesh, cell: (usize, usize)) {
+        if let Some(polygon) = self.polygons.get(&cell).copied() {
+            if self.blocked.remove(&polygon) {
+                self.restitch(mesh, polygon);
+            }
+        }
+    }
+
+    /// Block exactly these cells, only the cells that changed are updated
+    pub fn set_blocked(&mut self, mesh: &mut polyanya::Mesh, cells: &[(usize, usize)]) {
+        let blocked = cells
+            .iter()
+            .filter_map(|cell| self.polygons.get(cell))
+            .copied()
+            .collect::<HashSet<_>>();
+        let changed = self
+            .blocked
+            .symmetric_difference(&blocked)
+            .copied()
+            .collect::<Vec<_>>();
+        self.blocked = blocked;
+        for polygon in changed {
+            self.restitch(mesh, polygon);
+        }
+    }
+
+    /// Link the vertices of a polygon to their neighbours again, skipping blocked polygons so that
+    /// their edges become walls
+    fn restitch(&self, mesh: &mut polyanya::Mesh, polygon: u32) {
+        let layer = &mut mesh.layers[0];
+        for vertex in layer.polygons[polygon as usize].vertices.clone() {
+            let vertex = vertex as usize;
+            let polygons = self.around[vertex]
+                .iter()
+                .map(|around| {
+                    if self.blocked.contains(around) {
+                        u32::MAX
+                    } else {
+                        *around
+                    }
+                })
+                .collect::<Vec<_>>();
+            layer.vertices[vertex].is_corner = polygons.contains(&u32::MAX);
+            layer.vertices[vertex].polygons = polygons;
+        }
+    }
+}
+
 #[derive(Component, Debug)]
 pub enum AnimatedKind {
     Hobbit,
@@ -724,9 +1893,11 @@ pub fn spawn_level(
 ) -> ((usize, usize), polyanya::Mesh) {
     let floor = &level.floors[0];
 
-    let height = if cfg!(feature = "debug") { 0.1 } else { 0.5 };
+    // the wall scenes are 4 units high
+    let height = WALL_HEIGHT / 4.0;
     let wall_scale = vec3(1.0, height, 0.25);
     let corner_scale = vec3(0.25, height, 0.25);
+    let gaps = level.jump_gaps();
 
     commands
         .spawn((SpatialBundle::default(), tag))
@@ -739,7 +1910,8 @@ pub fn spawn_level(
                     let y = yi as f32 * 4.0;
 
                     if flag.contains(Flags::CENTER) {
-                        if !flag.contains(Flags::TOP) {
+                        // no wall on the side of a gap, hobbits jump from there
+                        if !flag.contains(Flags::TOP) && !gaps.contains(&(xi, yi.wrapping_sub(1))) {
                             parent.spawn((
                                 SceneBundle {
                                     scene: assets.wall.clone(),
@@ -756,7 +1928,7 @@ pub fn spawn_level(
                                 CollisionLayers::new(0b010, 0b100),
                             ));
                         }
-                        if !flag.contains(Flags::BOTTOM) {
+                        if !flag.contains(Flags::BOTTOM) && !gaps.contains(&(xi, yi + 1)) {
                             parent.spawn((
                                 SceneBundle {
                                     scene: assets.wall.clone(),
@@ -773,7 +1945,8 @@ pub fn spawn_level(
                                 CollisionLayers::new(0b010, 0b100),
                             ));
                         }
-                        if !flag.contains(Flags::LEFT) {
+                        if !flag.contains(Flags::LEFT) && !gaps.contains(&(xi.wrapping_sub(1), yi))
+                        {
                             parent.spawn((
                                 SceneBundle {
                                     scene: assets.wall.clone(),
@@ -791,7 +1964,7 @@ pub fn spawn_level(
                                 CollisionLayers::new(0b010, 0b100),
                             ));
                         }
-                        if !flag.contains(Flags::RIGHT) {
+                        if !flag.contains(Flags::RIGHT) && !gaps.contains(&(xi + 1, yi)) {
                             parent.spawn((
                                 SceneBundle {
                                     scene: assets.wall.clone(),
@@ -868,6 +2041,10 @@ pub fn spawn_level(
                                 transform: Transform::from_translation(Vec3::new(x, 5.0, y)),
                                 point_light: PointLight {
                                     intensity: 1_500_000.0,
+                                    color: level
+                                        .team_color((xi, yi))
+                                        .map(Color::from)
+                                        .unwrap_or(Color::WHITE),
                                     shadows_enabled: true,
                                     range: 20.0,
                                     ..default()
@@ -1054,16 +2231,30 @@ pub fn spawn_level(
                                 }));
                         }
                         Tile::Chest(direction) => {
-                            parent.spawn(PointLightBundle {
-                                transform: Transform::from_translation(Vec3::new(x, 5.0, y)),
-                                point_light: PointLight {
-                                    intensity: 1_000_000.0,
-                                    color: palettes::tailwind::YELLOW_800.into(),
-                                    shadows_enabled: true,
+                            let index = level
+                                .chests
+                                .iter()
+                                .position(|chest| (chest.cell.1, chest.cell.2) == (xi, yi))
+                                .unwrap();
+                            parent.spawn((
+                                PointLightBundle {
+                                    transform: Transform::from_translation(Vec3::new(x, 5.0, y)),
+                                    point_light: PointLight {
+                                        intensity: 1_000_000.0,
+                                        color: level
+                                            .team_color((xi, yi))
+                                            .unwrap_or(palettes::tailwind::YELLOW_800)
+                                            .into(),
+                                        shadows_enabled: true,
+                                        ..default()
+                                    },
                                     ..default()
                                 },
-                                ..default()
-                            });
+                                PointLightIntensity {
+                                    intensity: 1_000_000.0,
+                                },
+                                ChestLight(index),
+                            ));
                             parent.spawn((
                                 SceneBundle {
                                     scene: assets.floor.clone(),
@@ -1095,7 +2286,7 @@ pub fn spawn_level(
                                             scene: assets.chest.clone(),
                                             ..default()
                                         },
-                                        Chest,
+                                        Chest(index),
                                     ));
                                     parent.spawn(SceneBundle {
                                         scene: assets.coin_stack.clone(),
@@ -1142,20 +2333,146 @@ pub fn spawn_level(
                                     ));
                                 });
                         }
-                        Tile::Empty => {}
+                        #[cfg(feature = "mods")]
+                        Tile::Custom(symbol) => {
+                            parent.spawn((
+                                SceneBundle {
+                                    scene: assets.floor.clone(),
+                                    transform: Transform::from_translation(Vec3::new(x, 0.0, y)),
+                                    ..default()
+                                },
+                                RigidBody::Static,
+                                Collider::cuboid(4.0, 0.2, 4.0),
+                                CollisionLayers::new(0b010, 0b100),
+                            ));
+                            if let Some(behavior) = assets.extensions.tile(*symbol) {
+                                behavior.spawn(parent, Vec3::new(x, 0.0, y), assets);
+                            }
+                        }
+                        Tile::Empty => {
+                            if gaps.contains(&(xi, yi)) {
+                                // invisible bridge, only the hobbit model jumps
+                                parent.spawn((
+                                    SpatialBundle::from_transform(Transform::from_translation(
+                                        Vec3::new(x, 0.0, y),
+                                    )),
+                                    RigidBody::Static,
+                                    Collider::cuboid(4.0, 0.2, 4.0),
+                                    CollisionLayers::new(0b010, 0b100),
+                                ));
+                                // invisible walls along the sides without a floor to land on
+                                let walkable = level.walkable_neighbours(&gaps, xi, yi);
+                                for (side, offset, rotation) in [
+                                    (Flags::TOP, vec3(0.0, 0.0, -2.0), 0.0),
+                                    (Flags::BOTTOM, vec3(0.0, 0.0, 2.0), 0.0),
+                                    (Flags::LEFT, vec3(-2.0, 0.0, 0.0), FRAC_PI_2),
+                                    (Flags::RIGHT, vec3(2.0, 0.0, 0.0), FRAC_PI_2),
+                                ] {
+                                    if walkable.contains(side) {
+                                        continue;
+                                    }
+                                    parent.spawn((
+                                        SpatialBundle::from_transform(
+                                            Transform::from_translation(
+                                                Vec3::new(x, 0.0, y) + offset,
+                                            )
+                                            .with_rotation(Quat::from_rotation_y(rotation)),
+                                        ),
+                                        RigidBody::Static,
+                                        Collider::cuboid(4.0, 40.0, 0.2),
+                                        CollisionLayers::new(0b010, 0b100),
+                                    ));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            for zone in &level.winds {
+                for zx in zone.from.0..=zone.to.0 {
+                    for zy in zone.from.1..=zone.to.1 {
+                        parent
+                            .spawn(ParticleSpawnerBundle::from_settings(
+                                ParticleSpawnerSettings {
+                                    one_shot: false,
+                                    rate: 4.0,
+                                    emission_shape: EmissionShape::Circle {
+                                        normal: Vec3::Y,
+                                        radius: 2.0,
+                                    },
+                                    lifetime: RandF32::constant(1.5),
+                                    inherit_parent_velocity: false,
+                                    initial_velocity: RandVec3 {
+                                        magnitude: RandF32 {
+                                            min: zone.strength * 0.5,
+                                            max: zone.strength,
+                                        },
+                                        direction: zone.force().normalize_or_zero(),
+                                        spread: FRAC_PI_8,
+                                    },
+                                    initial_scale: RandF32 {
+                                        min: 0.08,
+                                        max: 0.15,
+                                    },
+                                    scale_curve: ParamCurve::constant(1.),
+                                    color: Gradient::linear(vec![
+                                        (0., palettes::tailwind::LIME_600.into()),
+                                        (0.7, palettes::tailwind::AMBER_700.into()),
+                                        (1., palettes::tailwind::AMBER_900.with_alpha(0.0).into()),
+                                    ]),
+                                    blend_mode: BlendMode::Blend,
+                                    linear_drag: 0.1,
+                                    pbr: true,
+                                    ..default()
+                                },
+                            ))
+                            .insert(Transform::from_translation(Vec3::new(
+                                zx as f32 * 4.0,
+                                1.0,
+                                zy as f32 * 4.0,
+                            )));
                     }
                 }
             }
         });
 
+    // nothing is blocked yet, dark cells are blocked with `NavMeshCells` by the caller
     (
         (level.floors[0].len() * 4, level.floors[0][0].len() * 4),
-        level.as_navmesh(vec![]),
+        level.base_navmesh(),
     )
 }
 
 #[derive(Component)]
-struct Chest;
+struct Chest(usize);
+
+/// Light above a chest, faded out once the chest is empty
+#[derive(Component)]
+struct ChestLight(usize);
+
+#[derive(Component, Default, Clone)]
+struct PointLightIntensity {
+    intensity: f32,
+}
+
+impl bevy_easings::Lerp for PointLightIntensity {
+    type Scalar = f32;
+
+    fn lerp(&self, other: &Self, scalar: &Self::Scalar) -> Self {
+        PointLightIntensity {
+            intensity: self.intensity + (other.intensity - self.intensity) * scalar,
+        }
+    }
+}
+
+fn fade_lights(
+    mut lights: Query<(&mut PointLight, &PointLightIntensity), Changed<PointLightIntensity>>,
+) {
+    for (mut light, intensity) in &mut lights {
+        light.intensity = intensity.intensity;
+    }
+}
 
 fn open_lid(
     mut scenes_loaded: EventReader<SceneInstanceReady>,
@@ -1179,3 +2496,80 @@ fn open_lid(
         }
     }
 }
+
+/// Close the lid and fade the light of emptied chests
+fn close_chests(
+    mut commands: Commands,
+    mut game_events: EventReader<GameEvent>,
+    chests: Query<(&Chest, &SceneInstance)>,
+    lights: Query<(Entity, &ChestLight, &PointLightIntensity)>,
+    transforms: Query<(&Name, &Transform)>,
+    scene_spawner: Res<SceneSpawner>,
+    has_material: Query<&Handle<StandardMaterial>>,
+) {
+    let lid_name = Name::new("chest_gold_lid");
+    for event in game_events.read() {
+        let GameEvent::ChestEmptied { chest: emptied } = event else {
+            continue;
+        };
+        for (chest, scene_instance) in &chests {
+            if chest.0 != *emptied {
+                continue;
+            }
+            scene_spawner
+                .iter_instance_entities(**scene_instance)
+                .for_each(|e| {
+                    if let Ok((name, transform)) = transforms.get(e) {
+                        if *name == lid_name && has_material.get(e).is_err() {
+                            commands.entity(e).insert(transform.ease_to(
+                                Transform {
+                                    rotation: Quat::IDENTITY,
+                                    ..*transform
+                                },
+                                EaseFunction::BounceOut,
+                                EasingType::Once {
+                                    duration: Duration::from_secs_f32(0.5),
+                                },
+                            ));
+                        }
+                    }
+                });
+        }
+        for (entity, light, intensity) in &lights {
+            if light.0 == *emptied {
+                commands.entity(entity).insert(intensity.ease_to(
+                    PointLightIntensity { intensity: 0.0 },
+                    EaseFunction::QuadraticOut,
+                    EasingType::Once {
+                        duration: Duration::from_secs_f32(1.5),
+                    },
+                ));
+            }
+        }
+    }
+}
+
+/// Chests are kept when retrying a level, open them again for the new game
+fn reopen_chests(
+    chests: Query<&SceneInstance, With<Chest>>,
+    mut lights: Query<&mut PointLightIntensity, With<ChestLight>>,
+    mut transforms: Query<(&Name, &mut Transform)>,
+    scene_spawner: Res<SceneSpawner>,
+    has_material: Query<&Handle<StandardMaterial>>,
+) {
+    let lid_name = Name::new("chest_gold_lid");
+    for scene_instance in &chests {
+        scene_spawner
+            .iter_instance_entities(**scene_instance)
+            .for_each(|e| {
+                if let Ok((name, mut transform)) = transforms.get_mut(e) {
+                    if *name == lid_name && has_material.get(e).is_err() {
+                        transform.rotation = Quat::from_rotation_x(-FRAC_PI_3 * 2.0);
+                    }
+                }
+            });
+    }
+    for mut light in &mut lights {
+        light.intensity = 1_000_000.0;
+    }
+}
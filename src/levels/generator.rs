@@ -0,0 +1,162 @@
+//! Random levels, always with a way there and back again.
+//!
+//! A path is carved from the start on the left to the chest on the right, then the rest of the
+//! map is filled randomly around it. Skeletons are only placed outside of the path.
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use super::Level;
+
+#[derive(Debug, Clone)]
+pub struct GeneratorSettings {
+    pub width: usize,
+    pub height: usize,
+    /// Share of the cells outside of the path that are left empty, between 0 and 1
+    pub density: f32,
+    pub skeletons: usize,
+    /// Obstacles given to the player
+    pub obstacles: usize,
+    pub hobbits: u32,
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> Self {
+        GeneratorSettings {
+            width: 14,
+            height: 7,
+            density: 0.5,
+            skeletons: 2,
+            obstacles: 3,
+            hobbits: 10,
+        }
+    }
+}
+
+/// Content of a level file, the same seed and settings always give the same level
+pub fn generate_content(seed: u32, settings: &GeneratorSettings) -> String {
+    let mut rng = StdRng::seed_from_u64(seed as u64);
+    let width = settings.width.max(3);
+    let height = settings.height.max(1);
+    let mut map = vec![vec![' '; width]; height];
+    let mut path = vec![];
+
+    // a path going right, wandering up and down
+    let mut y = rng.gen_range(0..height);
+    map[y][0] = 'X';
+    for x in 1..width - 1 {
+        map[y][x] = '#';
+        path.push((x, y));
+        if x % 2 == 0 {
+            let to = rng.gen_range(0..height);
+            while y != to {
+                y = if to > y { y + 1 } else { y - 1 };
+                map[y][x] = '#';
+                path.push((x, y));
+            }
+        }
+    }
+    map[y][width - 1] = '<';
+
+    // fill around the path
+    for row in map.iter_mut() {
+        for cell in row[1..width - 1].iter_mut() {
+            if *cell == ' ' && !rng.gen_bool(settings.density.clamp(0.0, 1.0) as f64) {
+                *cell = '#';
+            }
+        }
+    }
+    // two cells only touching by a corner can't be made into a navmesh, walk around them
+    while let Some(sides) = diagonal_corner(&map) {
+        let sides = sides
+            .into_iter()
+            .filter(|(x, _)| (1..width - 1).contains(x))
+            .collect::<Vec<_>>();
+        let (x, y) = *sides
+            .choose(&mut rng)
+            .expect("one side is away from the edges");
+        map[y][x] = '#';
+    }
+    let mut free = map
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, cell)| **cell == '#')
+                .map(move |(x, _)| (x, y))
+        })
+        .filter(|cell| !path.contains(cell))
+        .collect::<Vec<_>>();
+    free.shuffle(&mut rng);
+    for (x, y) in free.into_iter().take(settings.skeletons) {
+        map[y][x] = 'S';
+    }
+
+    format!(
+        "hobbits:{}\ndelay:3.0\nmessage:none\ngoal:none\ntreasures:{}\nlost:none\nbonus:{}\ntitle:Random #{}\n{}",
+        settings.hobbits,
+        (settings.hobbits / 2).max(1),
+        vec!["Obstacle"; settings.obstacles].join(","),
+        seed,
+        map.iter()
+            .map(|row| row.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+/// Empty sides of the first 2×2 block where only the two diagonal cells are walkable
+fn diagonal_corner(map: &[Vec<char>]) -> Option<[(usize, usize); 2]> {
+    let walkable = |x: usize, y: usize| map[y][x] != ' ';
+    for y in 1..map.len() {
+        for x in 1..map[y].len() {
+            match (
+                walkable(x - 1, y - 1),
+                walkable(x, y - 1),
+                walkable(x - 1, y),
+                walkable(x, y),
+            ) {
+                (true, false, false, true) => return Some([(x, y - 1), (x - 1, y)]),
+                (false, true, true, false) => return Some([(x - 1, y - 1), (x, y)]),
+                _ => (),
+            }
+        }
+    }
+    None
+}
+
+pub fn generate(seed: u32, settings: &GeneratorSettings) -> Level {
+    Level::parse(
+        &generate_content(seed, settings),
+        &format!("random-{}.level", seed),
+    )
+    .expect("generated levels are valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use bevy::math::vec2;
+
+    use super::*;
+
+    #[test]
+    fn generated_levels_have_a_navmesh() {
+        for seed in 0..200 {
+            let level = generate(seed, &GeneratorSettings::default());
+            let navmesh = level.base_navmesh();
+            let spawn = level.spawns[0].cell;
+            let chest = level.chests[0].cell;
+            let from = vec2(spawn.1 as f32 * 4.0, spawn.2 as f32 * 4.0);
+            let to = vec2(chest.1 as f32 * 4.0, chest.2 as f32 * 4.0);
+            assert!(
+                navmesh
+                    .path_on_layers(from, to, HashSet::from([2]))
+                    .is_some(),
+                "no path in level {}",
+                seed
+            );
+        }
+    }
+}
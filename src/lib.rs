@@ -1,23 +1,92 @@
+//! There And Back Again, a game about hobbits going on adventures and coming back home.
+//!
+//! Besides the game plugins, levels can be used by external tools:
+//! - [`levels::Level::parse`] reads a `.level` file, and [`levels::Level::validate`] checks it can
+//!   be played
+//! - [`levels::Level::as_navmesh`] builds the navigation mesh hobbits walk on
+//! - [`levels::NavMeshCells`] blocks and opens single cells of that navmesh while playing
+//! - [`simulation::simulate_level`] plays a level without rendering, to check it can be won
+//!
+//! See the `examples` folder for how to use them.
+
 use bevy::prelude::*;
 
+pub mod accessibility;
+pub mod achievements;
 pub mod assets;
+pub mod attract;
 pub mod audio;
+pub mod bindings;
+pub mod camera;
+pub mod captions;
+pub mod celebrations;
+pub mod chapters;
+pub mod colorblind;
+pub mod conditions;
+pub mod controls;
 pub mod credits;
+pub mod culling;
+pub mod daily;
+pub mod dialog;
+pub mod difficulty;
+pub mod display;
+pub mod effects;
+#[cfg(feature = "mods")]
+pub mod extensions;
 pub mod game;
+pub mod gamepad;
+pub mod hints;
+pub mod hud;
+pub mod layouts;
 pub mod level_selector;
 pub mod levels;
 pub mod loading;
 pub mod lost;
 pub mod menu;
+pub mod mutators;
+pub mod new_game_plus;
+#[cfg(feature = "online")]
+pub mod online;
+pub mod perf;
+pub mod pictures;
+#[cfg(feature = "steam")]
+pub mod platform;
 pub mod play;
+#[cfg(feature = "debug")]
+pub mod replay;
+pub mod resume;
+pub mod save;
+pub mod screenshot;
+pub mod settings;
+pub mod simulation;
+pub mod stats;
+#[cfg(feature = "debug")]
+pub mod telemetry;
+pub mod touch;
+pub mod tutorial;
+pub mod ui;
+pub mod voices;
 pub mod win;
+pub mod xray;
+pub mod zones;
+
+#[cfg(all(feature = "mods", target_arch = "wasm32"))]
+compile_error!("mods are only supported on native builds");
+#[cfg(all(feature = "steam", target_arch = "wasm32"))]
+compile_error!("Steam is only supported on native builds");
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 pub enum GameState {
     #[default]
     Loading,
     Menu,
+    /// A level won before replays on its own, after a while without input on the menu
+    Attract,
     Credits,
+    Achievements,
+    Statistics,
+    Settings,
+    Controls,
     LevelSelect,
     InGame,
     Win,
@@ -25,7 +94,26 @@ pub enum GameState {
     Reload,
 }
 
+/// Illuminance of the sun, dimmed in night levels
+pub const DAYLIGHT: f32 = light_consts::lux::OVERCAST_DAY * 2.0;
+
 #[derive(Resource)]
 pub struct GameProgress {
     pub current_level: usize,
 }
+
+impl GameProgress {
+    pub fn load(store: &bevy_pkv::PkvStore) -> Self {
+        GameProgress {
+            current_level: if cfg!(feature = "debug") {
+                usize::MAX
+            } else {
+                // the first two levels are always open, the next ones are unlocked one by one
+                (2..)
+                    .take_while(|level| layouts::LevelRecord::load(store, *level).unlocked)
+                    .last()
+                    .unwrap_or(1)
+            },
+        }
+    }
+}
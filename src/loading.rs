@@ -7,7 +7,14 @@ use std::{
     time::Duration,
 };
 
-use bevy::{asset::LoadedFolder, color::palettes, prelude::*, tasks::AsyncComputeTaskPool};
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::asset::io::AssetSourceBuilder;
+use bevy::{
+    asset::{LoadState, LoadedFolder},
+    color::palettes,
+    prelude::*,
+    tasks::AsyncComputeTaskPool,
+};
 use bevy_firework::{
     bevy_utilitarian::prelude::{Gradient, ParamCurve, RandF32, RandValue, RandVec3},
     core::{BlendMode, ParticleSpawnerBundle, ParticleSpawnerSettings},
@@ -19,7 +26,9 @@ use rand::Rng;
 
 use crate::{
     assets::{GameAssets, RawGameAssets},
+    chapters::{Chapter, Chapters},
     levels::Level,
+    voices::{Voice, VoiceBank},
     GameState,
 };
 
@@ -32,6 +41,49 @@ struct Screen {
 
 const NB_LEVELS: usize = 12;
 
+/// Asset source of the levels made by the player, in `levels` under the game data directory
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Resource)]
+struct CustomLevelsSource;
+
+/// Directory where the player can add their own levels
+#[cfg(not(target_arch = "wasm32"))]
+pub fn custom_levels_dir() -> Option<std::path::PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("ThereAndBackAgain").join("levels"))
+}
+
+/// Register the asset source for custom levels. Must be called before adding the `AssetPlugin`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn register_custom_levels(app: &mut App) {
+    let Some(dir) = custom_levels_dir() else {
+        return;
+    };
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        warn!("Couldn't create custom levels directory {:?}: {}", dir, err);
+        return;
+    }
+    let root = dir.parent().unwrap().to_string_lossy().to_string();
+    app.register_asset_source("custom", AssetSourceBuilder::platform_default(&root, None))
+        .insert_resource(CustomLevelsSource);
+}
+
+/// Files of the levels in the custom levels directory
+#[cfg(not(target_arch = "wasm32"))]
+fn custom_level_files() -> Vec<String> {
+    let Some(dir) = custom_levels_dir() else {
+        return vec![];
+    };
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        warn!("Couldn't read custom levels directory {:?}", dir);
+        return vec![];
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|file| file.ends_with(".level"))
+        .collect()
+}
+
 pub struct Plugin;
 impl bevy::prelude::Plugin for Plugin {
     fn build(&self, app: &mut App) {
@@ -50,6 +102,7 @@ fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     camera: Query<&Transform, With<Camera>>,
+    #[cfg(not(target_arch = "wasm32"))] custom_source: Option<Res<CustomLevelsSource>>,
 ) {
     info!("Loading screen");
     let vleue_logo = asset_server.load("embedded://there_and_back_again/branding/logo.png");
@@ -145,6 +198,13 @@ fn setup(
         levels: (0..=NB_LEVELS)
             .map(|i| asset_server.load_acquire(format!("levels/{:0>2}.level", i), guard.clone()))
             .collect(),
+        #[cfg(not(target_arch = "wasm32"))]
+        custom_levels: custom_source
+            .map(|_| custom_level_files())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|file| asset_server.load(format!("custom://levels/{}", file)))
+            .collect(),
         character: asset_server.load_acquire("characters/Rogue.glb", guard.clone()),
         traps_grate: asset_server.load_acquire(
             GltfAssetLabel::Scene(0).from_asset("ground/floor_tile_big_grate_open.gltf"),
@@ -175,6 +235,8 @@ fn setup(
             guard.clone(),
         ),
         icon_obstacle: asset_server.load_acquire("icons/obstacle.png", guard.clone()),
+        icon_torch: asset_server.load_acquire("icons/torch.png", guard.clone()),
+        chapters: asset_server.load_acquire("campaign.chapters", guard.clone()),
         skeleton: asset_server.load_acquire("traps/Skeleton_Warrior.glb", guard.clone()),
         skeleton_sword: asset_server.load_acquire(
             GltfAssetLabel::Scene(0).from_asset("traps/Skeleton_Blade.gltf"),
@@ -255,10 +317,12 @@ struct SplashGiggle(Timer);
 fn done(
     mut commands: Commands,
     gltfs: Res<Assets<Gltf>>,
-    folders: Res<Assets<LoadedFolder>>,
-    levels: Res<Assets<Level>>,
+    (folders, asset_server): (Res<Assets<LoadedFolder>>, Res<AssetServer>),
+    mut levels: ResMut<Assets<Level>>,
+    chapters: Res<Assets<Chapters>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut voices: ResMut<Assets<Voice>>,
     raw_assets: Res<RawGameAssets>,
     time: Res<Time>,
     mut screen: ResMut<Screen>,
@@ -266,8 +330,18 @@ fn done(
     loading_state: Res<AsyncLoadingState>,
     mut asset_ready: Local<bool>,
     camera: Query<&Transform, With<Camera>>,
+    #[cfg(feature = "mods")] extensions: Res<crate::extensions::GameExtensions>,
 ) {
     if !*asset_ready && loading_state.0.load(Ordering::Acquire) {
+        let Some(character) = gltfs.get(&raw_assets.character) else {
+            return;
+        };
+        let Some(skeleton) = gltfs.get(&raw_assets.skeleton) else {
+            return;
+        };
+        let Some(chapters) = chapters.get(&raw_assets.chapters) else {
+            return;
+        };
         let mut loaded_levels;
         #[cfg(not(target_arch = "wasm32"))]
         {
@@ -287,13 +361,57 @@ fn done(
         {
             loaded_levels = raw_assets.levels.clone();
         }
+        let campaign_levels = loaded_levels.len();
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if raw_assets
+                .custom_levels
+                .iter()
+                .any(|h| matches!(asset_server.load_state(h), LoadState::Loading))
+            {
+                return;
+            }
+            // a broken custom level is skipped, the others are still playable
+            let mut custom_levels = raw_assets
+                .custom_levels
+                .iter()
+                .filter(|h| match asset_server.load_state(*h) {
+                    LoadState::Failed(err) => {
+                        warn!("Skipping custom level: {}", err);
+                        false
+                    }
+                    _ => levels.contains(*h),
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            custom_levels.sort_by_key(|h| levels.get(h).unwrap().file.clone());
+            for handle in &custom_levels {
+                let level = levels.get_mut(handle).unwrap();
+                // custom levels are not numbered, name them after their file
+                if level.title.is_none() {
+                    level.title = std::path::Path::new(&level.file)
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().to_string());
+                }
+            }
+            info!("loaded {} custom levels", custom_levels.len());
+            loaded_levels.extend(custom_levels);
+        }
 
-        let Some(character) = gltfs.get(&raw_assets.character) else {
-            return;
-        };
-        let Some(skeleton) = gltfs.get(&raw_assets.skeleton) else {
-            return;
-        };
+        // levels not in a chapter, added by mods or the builder, get one of their own
+        let mut chapters = chapters.0.clone();
+        let covered = chapters
+            .iter()
+            .map(|chapter| *chapter.levels.end())
+            .max()
+            .unwrap_or(0);
+        if covered + 1 < campaign_levels {
+            chapters.push(Chapter {
+                name: "More Levels".to_string(),
+                levels: covered + 1..=campaign_levels - 1,
+                required_wins: None,
+            });
+        }
         *asset_ready = true;
 
         commands.insert_resource(GameAssets {
@@ -309,6 +427,8 @@ fn done(
             floor: raw_assets.floor.clone(),
             chest: raw_assets.chest.clone(),
             coin_stack: raw_assets.coin_stack.clone(),
+            custom_levels: campaign_levels..loaded_levels.len(),
+            generated_level: None,
             levels: loaded_levels,
             wall: raw_assets.wall.clone(),
             wall_corner: raw_assets.wall_corner.clone(),
@@ -330,7 +450,17 @@ fn done(
             undergrate_mesh: meshes.add(Rectangle::new(4.0, 4.0).mesh()),
             obstacle: raw_assets.obstacle.clone(),
             icon_obstacle: raw_assets.icon_obstacle.clone(),
+            icon_torch: raw_assets.icon_torch.clone(),
+            chapters,
+            torch_mesh: meshes.add(Cylinder::new(0.2, 2.4)),
+            torch_material: materials.add(StandardMaterial {
+                base_color: palettes::tailwind::AMBER_900.into(),
+                ..default()
+            }),
             skeleton_sword: raw_assets.skeleton_sword.clone(),
+            voices: VoiceBank::generate(&mut voices),
+            #[cfg(feature = "mods")]
+            extensions: extensions.clone(),
         });
 
         let mut camera_transform = *camera.single();
@@ -1,10 +1,16 @@
-use std::time::Duration;
-
-use bevy::{color::palettes, prelude::*};
-use bevy_easings::{Ease, EaseFunction, EasingType};
-use rand::Rng;
-
-use crate::{audio::AudioTrigger, menu::SwitchState, play::GameInProgress, GameState};
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+
+use crate::{
+    assets::GameAssets,
+    audio::AudioTrigger,
+    hints::{Hint, HINT_AFTER_FAILURES},
+    layouts::{record_failure, skip_level, skips_left, LevelRecord, SKIP_AFTER_FAILURES},
+    levels::Level,
+    menu::SwitchState,
+    play::GameInProgress,
+    ui, GameProgress, GameState,
+};
 
 const CURRENT_STATE: GameState = GameState::Lost;
 
@@ -20,203 +26,60 @@ impl bevy::prelude::Plugin for Plugin {
     }
 }
 
-fn spawn_win_screen(mut commands: Commands, mut audio_trigger: EventWriter<AudioTrigger>) {
+fn spawn_win_screen(
+    mut commands: Commands,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+    game: Res<GameInProgress>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+    mut store: ResMut<PkvStore>,
+) {
     info!("Loading screen");
     audio_trigger.send(AudioTrigger::Lost);
 
+    let level = levels.get(&assets.levels[game.level]).unwrap();
+    let failures = record_failure(&mut store, &level.file);
+    let mut actions = vec![
+        (ButtonAction::Back, "Back to Menu".to_string()),
+        (ButtonAction::Replay, "Restart Level".to_string()),
+    ];
+    if failures >= HINT_AFTER_FAILURES && !level.hint.is_empty() {
+        actions.push((ButtonAction::Hint, "Hint".to_string()));
+    }
+    // only campaign levels not won yet and followed by another one can be skipped
+    let skips = skips_left(&store);
+    if failures >= SKIP_AFTER_FAILURES
+        && skips > 0
+        && assets.is_campaign(game.level + 1)
+        && LevelRecord::load(&store, game.level).won.is_none()
+    {
+        actions.push((
+            ButtonAction::Skip,
+            format!("Skip this level ({} left)", skips),
+        ));
+    }
+    let margin = UiRect::top(Val::Percent(if actions.len() > 2 { 5.0 } else { 10.0 }));
+
     commands
         .spawn((
-            NodeBundle {
-                style: Style {
-                    width: Val::Percent(100.0),
-                    height: Val::Percent(100.0),
-                    left: Val::Percent(-100.0),
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::Start,
-                    ..default()
-                },
-                ..default()
-            },
-            Style {
-                width: Val::Percent(100.0),
-                height: Val::Percent(100.0),
-                left: Val::Percent(-100.0),
-                align_items: AlignItems::Center,
-                justify_content: JustifyContent::Start,
-                ..default()
-            }
-            .ease_to(
-                Style {
-                    width: Val::Percent(100.0),
-                    height: Val::Percent(100.0),
-                    left: Val::Percent(30.0),
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::Start,
-                    ..default()
-                },
-                EaseFunction::QuadraticOut,
-                EasingType::Once {
-                    duration: Duration::from_secs_f32(1.0),
-                },
-            ),
+            ui::screen_root(30.0),
             MenuItem::Root,
             StateScoped(CURRENT_STATE),
         ))
         .with_children(|parent| {
             parent
-                .spawn((
-                    NodeBundle {
-                        background_color: palettes::tailwind::GREEN_400.into(),
-                        border_radius: BorderRadius::all(Val::Percent(5.0)),
-                        z_index: ZIndex::Global(1),
-                        style: Style {
-                            flex_direction: FlexDirection::Column,
-                            justify_content: JustifyContent::Center,
-                            align_items: AlignItems::Center,
-                            width: Val::Percent(40.0),
-                            height: Val::Percent(60.0),
-                            ..default()
-                        },
-                        ..default()
-                    },
-                    MenuItem::Panel,
-                ))
+                .spawn((ui::panel(40.0, 60.0), MenuItem::Panel))
                 .with_children(|parent| {
-                    parent.spawn(TextBundle {
-                        text: Text::from_section(
-                            "You lost...!",
-                            TextStyle {
-                                font_size: 60.0,
-                                color: Color::WHITE,
-                                ..default()
-                            },
-                        ),
-                        style: Style {
-                            margin: UiRect::bottom(Val::Percent(5.0)),
-                            ..default()
-                        },
-                        ..default()
-                    });
-
-                    let button_height = 40.0;
-                    let style_easing = Style {
-                        width: Val::Px(200.0),
-                        height: Val::Px(button_height),
-                        border: UiRect::all(Val::Px(3.0)),
-                        align_items: AlignItems::Center,
-                        justify_content: JustifyContent::Center,
-                        margin: UiRect::top(Val::Percent(10.0)),
-                        justify_self: JustifySelf::End,
-                        ..default()
-                    };
+                    parent.spawn(ui::title("You lost...!"));
 
-                    let style_easing = style_easing.ease_to(
-                        Style {
-                            width: Val::Px(200.0),
-                            height: Val::Px(button_height),
-                            border: UiRect::all(Val::Px(5.0)),
-                            margin: UiRect::top(Val::Percent(10.0)),
-                            justify_self: JustifySelf::End,
-
-                            ..default()
-                        },
-                        EaseFunction::QuadraticInOut,
-                        EasingType::PingPong {
-                            duration: Duration::from_secs_f32(1.0),
-                            pause: Some(Duration::from_secs_f32(0.5)),
-                        },
-                    );
-                    parent
-                        .spawn((
-                            ButtonBundle {
-                                background_color: palettes::tailwind::INDIGO_800.into(),
-                                border_radius: BorderRadius::all(Val::Percent(10.0)),
-                                border_color: BorderColor(palettes::tailwind::INDIGO_400.into()),
-                                style: Style {
-                                    width: Val::Px(200.0),
-                                    height: Val::Px(button_height),
-                                    border: UiRect::all(Val::Px(0.0)),
-                                    align_items: AlignItems::Center,
-                                    justify_content: JustifyContent::Center,
-                                    margin: UiRect::top(Val::Percent(10.0)),
-                                    justify_self: JustifySelf::End,
-                                    ..default()
-                                },
-                                ..default()
-                            },
-                            style_easing.clone().delay(Duration::from_secs_f32(
-                                rand::thread_rng().gen_range(0.0..1.0),
-                            )),
-                            MenuItem::Button,
-                            ButtonAction::Back,
-                        ))
-                        .with_children(|p| {
-                            p.spawn(TextBundle {
-                                text: Text::from_section(
-                                    "Back to Menu",
-                                    TextStyle {
-                                        font_size: 20.0,
-                                        ..default()
-                                    },
-                                ),
-                                ..default()
-                            });
-                        });
-                    parent
-                        .spawn((
-                            ButtonBundle {
-                                background_color: palettes::tailwind::INDIGO_800.into(),
-                                border_radius: BorderRadius::all(Val::Percent(10.0)),
-                                border_color: BorderColor(palettes::tailwind::INDIGO_400.into()),
-                                style: Style {
-                                    width: Val::Px(200.0),
-                                    height: Val::Px(button_height),
-                                    border: UiRect::all(Val::Px(0.0)),
-                                    align_items: AlignItems::Center,
-                                    justify_content: JustifyContent::Center,
-                                    margin: UiRect::top(Val::Percent(10.0)),
-                                    justify_self: JustifySelf::End,
-                                    ..default()
-                                },
-                                ..default()
-                            },
-                            style_easing.delay(Duration::from_secs_f32(
-                                rand::thread_rng().gen_range(0.0..1.0),
-                            )),
-                            MenuItem::Button,
-                            ButtonAction::Replay,
-                        ))
-                        .with_children(|p| {
-                            p.spawn(TextBundle {
-                                text: Text::from_section(
-                                    "Restart Level",
-                                    TextStyle {
-                                        font_size: 20.0,
-                                        ..default()
-                                    },
-                                ),
-                                ..default()
-                            });
-                        });
+                    for (action, label) in actions {
+                        ui::spawn_button(parent, label, Vec2::new(200.0, 40.0), margin)
+                            .insert((MenuItem::Button, action));
+                    }
                 });
         });
 }
 
-#[derive(Component, Default, Clone)]
-struct ImageColor {
-    color: Srgba,
-}
-
-impl bevy_easings::Lerp for ImageColor {
-    type Scalar = f32;
-
-    fn lerp(&self, other: &Self, scalar: &Self::Scalar) -> Self {
-        ImageColor {
-            color: self.color.mix(&other.color, *scalar),
-        }
-    }
-}
-
 #[derive(Component, PartialEq, Eq)]
 enum MenuItem {
     Root,
@@ -228,134 +91,59 @@ enum MenuItem {
 enum ButtonAction {
     Back,
     Replay,
+    /// Restart the level with its hint shown
+    Hint,
+    /// Use a skip token and play the next level
+    Skip,
 }
 
 fn button_system(
     mut commands: Commands,
-    interaction_query: Query<
-        (Ref<Interaction>, &BackgroundColor, Entity, &ButtonAction),
-        Changed<Interaction>,
-    >,
+    interaction_query: Query<(&Interaction, &ButtonAction), Changed<Interaction>>,
     mut next_state: EventWriter<SwitchState>,
     ui_items: Query<(Entity, &MenuItem)>,
     game: Res<GameInProgress>,
+    mut progress: ResMut<GameProgress>,
+    mut store: ResMut<PkvStore>,
     mut audio_trigger: EventWriter<AudioTrigger>,
 ) {
-    for (interaction, color, entity, action) in &interaction_query {
-        if interaction.is_added() {
+    for (interaction, action) in &interaction_query {
+        if *interaction != Interaction::Pressed {
             continue;
         }
-        match *interaction {
-            Interaction::Pressed => match action {
-                ButtonAction::Back => {
-                    audio_trigger.send(AudioTrigger::Click);
-                    next_state.send(SwitchState(GameState::Menu));
-
-                    for (entity, kind) in &ui_items {
-                        if *kind == MenuItem::Root {
-                            commands.entity(entity).insert(
-                                Style {
-                                    width: Val::Percent(100.0),
-                                    height: Val::Percent(100.0),
-                                    left: Val::Percent(30.0),
-                                    align_items: AlignItems::Center,
-                                    justify_content: JustifyContent::Start,
-                                    ..default()
-                                }
-                                .ease_to(
-                                    Style {
-                                        width: Val::Percent(100.0),
-                                        height: Val::Percent(100.0),
-                                        left: Val::Percent(-100.0),
-                                        align_items: AlignItems::Center,
-                                        justify_content: JustifyContent::Start,
-                                        ..default()
-                                    },
-                                    EaseFunction::QuadraticOut,
-                                    EasingType::Once {
-                                        duration: Duration::from_secs_f32(1.0),
-                                    },
-                                ),
-                            );
-                        }
-                    }
-
-                    commands.entity(entity).insert(color.ease_to(
-                        BUTTON_HOVERED,
-                        EaseFunction::QuadraticInOut,
-                        EasingType::Once {
-                            duration: Duration::from_secs_f32(0.25),
-                        },
-                    ));
-                }
-                ButtonAction::Replay => {
-                    audio_trigger.send(AudioTrigger::Start);
-                    next_state.send(SwitchState(GameState::InGame));
-                    commands.insert_resource(GameInProgress {
-                        level: game.level,
-                        ..default()
-                    });
-
-                    for (entity, kind) in &ui_items {
-                        if *kind == MenuItem::Root {
-                            commands.entity(entity).insert(
-                                Style {
-                                    width: Val::Percent(100.0),
-                                    height: Val::Percent(100.0),
-                                    left: Val::Percent(30.0),
-                                    align_items: AlignItems::Center,
-                                    justify_content: JustifyContent::Start,
-                                    ..default()
-                                }
-                                .ease_to(
-                                    Style {
-                                        width: Val::Percent(100.0),
-                                        height: Val::Percent(100.0),
-                                        left: Val::Percent(-100.0),
-                                        align_items: AlignItems::Center,
-                                        justify_content: JustifyContent::Start,
-                                        ..default()
-                                    },
-                                    EaseFunction::QuadraticOut,
-                                    EasingType::Once {
-                                        duration: Duration::from_secs_f32(1.0),
-                                    },
-                                ),
-                            );
-                        }
-                    }
-
-                    commands.entity(entity).insert(color.ease_to(
-                        BUTTON_HOVERED,
-                        EaseFunction::QuadraticInOut,
-                        EasingType::Once {
-                            duration: Duration::from_secs_f32(0.25),
-                        },
-                    ));
+        match action {
+            ButtonAction::Back => {
+                audio_trigger.send(AudioTrigger::Click);
+                next_state.send(SwitchState(GameState::Menu));
+            }
+            ButtonAction::Replay | ButtonAction::Hint => {
+                if *action == ButtonAction::Hint {
+                    commands.insert_resource(Hint { level: game.level });
                 }
-            },
-            Interaction::Hovered => {
-                commands.entity(entity).insert(color.ease_to(
-                    BUTTON_HOVERED,
-                    EaseFunction::QuadraticInOut,
-                    EasingType::Once {
-                        duration: Duration::from_secs_f32(0.25),
-                    },
-                ));
+                audio_trigger.send(AudioTrigger::Start);
+                next_state.send(SwitchState(GameState::InGame));
+                commands.insert_resource(GameInProgress {
+                    level: game.level,
+                    ..default()
+                });
             }
-            Interaction::None => {
-                commands.entity(entity).insert(color.ease_to(
-                    BUTTON_IDLE,
-                    EaseFunction::QuadraticInOut,
-                    EasingType::Once {
-                        duration: Duration::from_secs_f32(0.25),
-                    },
-                ));
+            ButtonAction::Skip => {
+                info!("skipping level {}", game.level);
+                skip_level(&mut store, game.level);
+                progress.current_level = progress.current_level.max(game.level + 1);
+                audio_trigger.send(AudioTrigger::Start);
+                next_state.send(SwitchState(GameState::InGame));
+                commands.insert_resource(GameInProgress {
+                    level: game.level + 1,
+                    ..default()
+                });
+            }
+        }
+
+        for (entity, kind) in &ui_items {
+            if *kind == MenuItem::Root {
+                ui::slide_out(&mut commands, entity, 30.0);
             }
         }
     }
 }
-
-const BUTTON_IDLE: BackgroundColor = BackgroundColor(Color::Srgba(palettes::tailwind::INDIGO_800));
-const BUTTON_HOVERED: BackgroundColor =
-    BackgroundColor(Color::Srgba(palettes::tailwind::AMBER_600));
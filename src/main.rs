@@ -1,8 +1,6 @@
 #![windows_subsystem = "windows"]
 
 use avian3d::prelude::*;
-#[cfg(feature = "debug")]
-use bevy::window::PresentMode;
 use bevy::{
     asset::{embedded_asset, AssetMetaCheck},
     core_pipeline::bloom::BloomSettings,
@@ -13,8 +11,13 @@ use bevy_firework::plugin::ParticleSystemPlugin;
 
 use bevy_pkv::PkvStore;
 use there_and_back_again::{
-    audio, credits, game, level_selector, levels, loading, lost, menu, play, win, GameProgress,
-    GameState,
+    accessibility, achievements, achievements::Achievements, attract, audio, audio::Muted,
+    bindings, bindings::InputMap, camera, captions, celebrations, chapters, colorblind, conditions,
+    controls, credits, culling, dialog, difficulty, difficulty::DifficultyMode, display, effects,
+    game, gamepad, hints, hud, level_selector, levels, loading, lost, menu, mutators,
+    new_game_plus, new_game_plus::NewGamePlus, perf, play, resume, save, screenshot, settings,
+    settings::Settings, stats, stats::Stats, touch, tutorial, ui, voices, win, xray, zones,
+    GameProgress, GameState, DAYLIGHT,
 };
 
 fn main() {
@@ -23,21 +26,23 @@ fn main() {
     // needed for bevy_firework on web
     app.insert_resource(Msaa::Off);
 
-    let store = PkvStore::new("Vleue", "ThereAndBackAgain");
-    let game_progress = GameProgress {
-        current_level: if cfg!(feature = "debug") {
-            usize::MAX
-        } else {
-            store.get::<u32>("progress").unwrap_or(1) as usize
-        },
-    };
+    let mut store = PkvStore::new("Vleue", "ThereAndBackAgain");
+    save::migrate(&mut store);
+    let game_progress = GameProgress::load(&store);
+    let settings = Settings::load(&store);
+    let input_map = InputMap::load(&store);
+    let muted = Muted::load(&store);
+    let achievements = Achievements::load(&store);
+    let stats = Stats::load(&store);
+    let difficulty = DifficultyMode::load(&store).unwrap_or_default();
+    let new_game_plus = NewGamePlus::load(&store);
+    #[cfg(not(target_arch = "wasm32"))]
+    loading::register_custom_levels(&mut app);
     app.add_plugins(
         DefaultPlugins
             .set(WindowPlugin {
                 primary_window: Some(Window {
                     title: "There And Back Again".to_string(),
-                    #[cfg(feature = "debug")]
-                    present_mode: PresentMode::AutoNoVsync,
                     fit_canvas_to_parent: true,
                     ..default()
                 }),
@@ -60,20 +65,74 @@ fn main() {
         loading::Plugin,
         menu::Plugin,
         levels::Plugin,
+        chapters::Plugin,
+        conditions::Plugin,
+        effects::Plugin,
         credits::Plugin,
+        settings::Plugin,
         game::Plugin,
         level_selector::Plugin,
         play::Plugin,
         win::Plugin,
         lost::Plugin,
         audio::Plugin,
+        voices::Plugin,
+    ))
+    .add_plugins((
+        zones::Plugin,
+        celebrations::Plugin,
+        culling::Plugin,
+        camera::Plugin,
+        hud::Plugin,
+        xray::Plugin,
+        gamepad::Plugin,
+        touch::Plugin,
+        bindings::Plugin,
+        controls::Plugin,
+        dialog::Plugin,
+        ui::Plugin,
+        colorblind::Plugin,
+        accessibility::Plugin,
+        captions::Plugin,
+    ))
+    .add_plugins((
+        display::Plugin,
+        perf::Plugin,
+        screenshot::Plugin,
+        achievements::Plugin,
+        stats::Plugin,
+        resume::Plugin,
+        tutorial::Plugin,
+        hints::Plugin,
+        difficulty::Plugin,
+        new_game_plus::Plugin,
+        mutators::Plugin,
+        attract::Plugin,
     ))
     .add_systems(Startup, camera);
 
+    #[cfg(feature = "mods")]
+    app.add_plugins(there_and_back_again::extensions::Plugin);
+    #[cfg(feature = "online")]
+    app.add_plugins(there_and_back_again::online::Plugin);
+    #[cfg(feature = "steam")]
+    app.add_plugins(there_and_back_again::platform::Plugin);
+
     app.insert_resource(game_progress);
+    app.insert_resource(settings);
+    app.insert_resource(input_map);
+    app.insert_resource(muted);
+    app.insert_resource(achievements);
+    app.insert_resource(stats);
+    app.insert_resource(difficulty);
+    app.insert_resource(new_game_plus);
 
     #[cfg(feature = "debug")]
-    app.add_plugins(PhysicsDebugPlugin::default());
+    app.add_plugins((
+        PhysicsDebugPlugin::default(),
+        there_and_back_again::telemetry::Plugin,
+        there_and_back_again::replay::Plugin,
+    ));
 
     embedded_asset!(app, "branding/logo.png");
     embedded_asset!(app, "branding/bevy_logo_dark.png");
@@ -99,7 +158,7 @@ fn camera(mut commands: Commands) {
         transform: Transform::IDENTITY.looking_to(Vec3::new(1.0, -1.0, 1.0), Vec3::Y),
         directional_light: DirectionalLight {
             shadows_enabled: true,
-            illuminance: light_consts::lux::OVERCAST_DAY * 2.0,
+            illuminance: DAYLIGHT,
             ..default()
         },
         ..default()
@@ -2,14 +2,22 @@ use std::time::Duration;
 
 use bevy::{color::palettes, prelude::*, render::texture::TextureFormatPixelInfo};
 use bevy_easings::{CustomComponentEase, Ease, EaseFunction, EasingComponent, EasingType};
+use bevy_pkv::PkvStore;
 use rand::Rng;
 
 use crate::{
     assets::GameAssets,
     audio::AudioTrigger,
-    game::{ActiveLevel, NavMesh},
-    levels::{spawn_level, Level},
-    play::GameInProgress,
+    daily::{daily_level, daily_result, today, DailyChallenge},
+    dialog::{AskConfirmation, Confirmed, ConfirmedAction},
+    difficulty::DifficultyMode,
+    game::{ActiveLevel, Demo, NavMesh},
+    levels::{spawn_level, Level, NavMeshCells},
+    mutators::Mutators,
+    new_game_plus::NewGamePlus,
+    play::{GameInProgress, SessionSummary},
+    resume::{ResumedLevel, Snapshot},
+    ui::{self, ImageColor},
     GameProgress, GameState,
 };
 
@@ -19,7 +27,13 @@ pub struct Plugin;
 impl bevy::prelude::Plugin for Plugin {
     fn build(&self, app: &mut App) {
         app.add_event::<SwitchState>()
-            .add_systems(OnEnter(CURRENT_STATE), (spawn_menu, spawn_level_0))
+            .add_systems(
+                OnEnter(CURRENT_STATE),
+                (spawn_menu, spawn_level_0, show_session_summary),
+            )
+            .add_systems(OnExit(CURRENT_STATE), |mut commands: Commands| {
+                commands.remove_resource::<Demo>()
+            })
             .add_systems(
                 Update,
                 (
@@ -28,6 +42,8 @@ impl bevy::prelude::Plugin for Plugin {
                     animation_maintenance,
                     bevy_easings::custom_ease_system::<ImageColor>,
                     button_system,
+                    quit_when_confirmed,
+                    resume_when_confirmed,
                     spawn_reverse_title_points,
                     #[cfg(feature = "debug")]
                     display_navmesh,
@@ -46,9 +62,10 @@ fn spawn_level_0(
     assets: Res<GameAssets>,
     levels: Res<Assets<Level>>,
     camera_position: Query<(Entity, &Transform), With<Camera>>,
+    motion: Res<ui::MotionPreference>,
 ) {
     let level = levels.get(&assets.levels[0]).unwrap();
-    let (level_size, mesh) = spawn_level(
+    let (level_size, mut mesh) = spawn_level(
         &mut commands,
         level,
         assets.as_ref(),
@@ -72,55 +89,42 @@ fn spawn_level_0(
             ),
             EaseFunction::QuadraticInOut,
             EasingType::Once {
-                duration: Duration::from_secs_f32(2.0),
+                duration: motion.duration(2.0),
             },
         ),
     );
 
+    NavMeshCells::new(&mesh).set_blocked(&mut mesh, &level.dark_cells(&[]));
     commands.insert_resource(ActiveLevel(level.clone()));
     commands.insert_resource(NavMesh(mesh));
+    commands.insert_resource(Demo);
 }
 
-fn spawn_menu(mut commands: Commands, window: Query<&Window>) {
+fn spawn_menu(
+    mut commands: Commands,
+    window: Query<&Window>,
+    store: Res<PkvStore>,
+    motion: Res<ui::MotionPreference>,
+) {
     info!("Loading screen");
     let window_size = window.single().size();
+    let daily_done = daily_result(&store, today()).is_some();
 
     commands
         .spawn((
             NodeBundle {
-                style: Style {
-                    width: Val::Percent(100.0),
-                    height: Val::Percent(100.0),
-                    left: Val::Percent(-100.0),
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::Start,
-                    ..default()
-                },
+                style: ui::screen_style(-100.0),
                 ..default()
             },
-            Style {
-                width: Val::Percent(100.0),
-                height: Val::Percent(100.0),
-                left: Val::Percent(-100.0),
-                align_items: AlignItems::Center,
-                justify_content: JustifyContent::Start,
-                ..default()
-            }
-            .ease_to(
-                Style {
-                    width: Val::Percent(100.0),
-                    height: Val::Percent(100.0),
-                    left: Val::Percent(0.0),
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::Start,
-                    ..default()
-                },
-                EaseFunction::QuadraticOut,
-                EasingType::Once {
-                    duration: Duration::from_secs_f32(1.0),
-                },
-            )
-            .delay(Duration::from_secs_f32(0.5)),
+            ui::screen_style(-100.0)
+                .ease_to(
+                    ui::screen_style(0.0),
+                    EaseFunction::QuadraticOut,
+                    EasingType::Once {
+                        duration: motion.duration(1.0),
+                    },
+                )
+                .delay(Duration::from_secs_f32(0.5)),
             MenuItem::Root,
             StateScoped(CURRENT_STATE),
         ))
@@ -128,7 +132,6 @@ fn spawn_menu(mut commands: Commands, window: Query<&Window>) {
             parent
                 .spawn((
                     NodeBundle {
-                        background_color: palettes::tailwind::GREEN_400.into(),
                         border_radius: BorderRadius::right(Val::Percent(5.0)),
                         z_index: ZIndex::Global(1),
                         style: Style {
@@ -142,9 +145,10 @@ fn spawn_menu(mut commands: Commands, window: Query<&Window>) {
                         ..default()
                     },
                     MenuItem::Panel,
+                    ui::Panel,
                 ))
                 .with_children(|parent| {
-                    let nb_buttons = if cfg!(target_arch = "wasm32") { 3 } else { 4 };
+                    let nb_buttons = if cfg!(target_arch = "wasm32") { 7 } else { 8 };
                     let button_height = 65.0;
                     for i in 0..nb_buttons {
                         let style_easing = Style {
@@ -170,12 +174,14 @@ fn spawn_menu(mut commands: Commands, window: Query<&Window>) {
                             },
                             EaseFunction::BounceOut,
                             EasingType::Once {
-                                duration: Duration::from_secs_f32(1.2),
+                                duration: motion.duration(1.2),
                             },
                         )
                         .delay(Duration::from_secs_f32(0.5 + 0.2 * i as f32));
 
-                        let style_easing = if i == 0 {
+                        let style_easing = if motion.reduced() {
+                            style_easing
+                        } else if i == 0 {
                             style_easing.ease_to(
                                 Style {
                                     width: Val::Px(250.0),
@@ -207,7 +213,6 @@ fn spawn_menu(mut commands: Commands, window: Query<&Window>) {
                         parent
                             .spawn((
                                 ButtonBundle {
-                                    background_color: palettes::tailwind::INDIGO_800.into(),
                                     border_radius: BorderRadius::all(Val::Percent(10.0)),
                                     border_color: BorderColor(
                                         palettes::tailwind::INDIGO_400.into(),
@@ -230,11 +235,16 @@ fn spawn_menu(mut commands: Commands, window: Query<&Window>) {
                                 },
                                 style_easing,
                                 MenuItem::Button,
+                                ui::Highlight,
                                 match i {
                                     0 => MenuButton::Play,
                                     1 => MenuButton::LevelSelect,
-                                    2 => MenuButton::Credits,
-                                    3 => MenuButton::Quit,
+                                    2 => MenuButton::Daily,
+                                    3 => MenuButton::Settings,
+                                    4 => MenuButton::Achievements,
+                                    5 => MenuButton::Statistics,
+                                    6 => MenuButton::Credits,
+                                    7 => MenuButton::Quit,
                                     _ => unreachable!(),
                                 },
                             ))
@@ -244,8 +254,13 @@ fn spawn_menu(mut commands: Commands, window: Query<&Window>) {
                                         match i {
                                             0 => "Play",
                                             1 => "Select Level",
-                                            2 => "Credits",
-                                            3 => "Quit",
+                                            2 if daily_done => "Daily - Done",
+                                            2 => "Daily",
+                                            3 => "Settings",
+                                            4 => "Achievements",
+                                            5 => "Statistics",
+                                            6 => "Credits",
+                                            7 => "Quit",
                                             _ => unreachable!(),
                                         },
                                         TextStyle {
@@ -261,21 +276,6 @@ fn spawn_menu(mut commands: Commands, window: Query<&Window>) {
         });
 }
 
-#[derive(Component, Default, Clone)]
-struct ImageColor {
-    color: Srgba,
-}
-
-impl bevy_easings::Lerp for ImageColor {
-    type Scalar = f32;
-
-    fn lerp(&self, other: &Self, scalar: &Self::Scalar) -> Self {
-        ImageColor {
-            color: self.color.mix(&other.color, *scalar),
-        }
-    }
-}
-
 #[derive(Component)]
 struct SpawnedPoints;
 
@@ -286,6 +286,7 @@ fn spawn_title_points(
     mut commands: Commands,
     mut png: Local<Option<(Handle<Image>, Handle<Image>)>>,
     done: Query<Entity, With<SpawnedPoints>>,
+    motion: Res<ui::MotionPreference>,
 ) {
     if png.is_none() {
         *png = Some((
@@ -310,8 +311,8 @@ fn spawn_title_points(
     let window_size = window.single().size();
 
     let point_to_image_duration = Duration::from_secs_f32(0.5);
-    let point_placement_duration = Duration::from_secs_f32(1.5);
-    let second_image_delay = Duration::from_secs_f32(0.5);
+    let point_placement_duration = motion.duration(1.5);
+    let second_image_delay = motion.duration(0.5);
 
     let title_color = palettes::tailwind::GREEN_400;
 
@@ -525,7 +526,10 @@ fn spawn_title_points(
         }
     }
 
-    commands.spawn_batch(to_spawn);
+    // with reduced motion, the title only fades in
+    if !motion.reduced() {
+        commands.spawn_batch(to_spawn);
+    }
 
     commands.spawn((SpawnedPoints, StateScoped(CURRENT_STATE)));
 }
@@ -537,6 +541,7 @@ fn spawn_reverse_title_points(
     mut commands: Commands,
     image_query: Query<Entity, With<ImageColor>>,
     mut event_reader: EventReader<SwitchState>,
+    motion: Res<ui::MotionPreference>,
 ) {
     if event_reader.read().last().is_none() {
         return;
@@ -716,7 +721,10 @@ fn spawn_reverse_title_points(
         }
     }
 
-    commands.spawn_batch(to_spawn);
+    // with reduced motion, the title only fades out
+    if !motion.reduced() {
+        commands.spawn_batch(to_spawn);
+    }
 }
 
 #[derive(Component, PartialEq, Eq)]
@@ -729,17 +737,72 @@ enum MenuItem {
 #[derive(Component)]
 struct Dot;
 
-#[derive(Component)]
+#[derive(Component, PartialEq, Eq)]
 enum MenuButton {
     Play,
     LevelSelect,
+    /// Today's random level
+    Daily,
+    Settings,
+    Achievements,
+    Statistics,
     Credits,
     Quit,
+    /// Start the campaign in this difficulty
+    Difficulty(DifficultyMode),
 }
 
-fn update_text(mut text: Query<(&mut Text, &Parent)>, nodes: Query<&Node>) {
+/// Choice of the difficulty, shown when starting the campaign for the first time
+#[derive(Component)]
+struct DifficultyChoice;
+
+fn spawn_difficulty_choice(commands: &mut Commands) {
+    commands
+        .spawn((
+            ui::screen_root(30.0),
+            MenuItem::Root,
+            DifficultyChoice,
+            StateScoped(CURRENT_STATE),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((ui::panel(40.0, 60.0), MenuItem::Panel))
+                .with_children(|parent| {
+                    for difficulty in DifficultyMode::all() {
+                        ui::spawn_button(
+                            parent,
+                            difficulty.label(),
+                            Vec2::new(200.0, 40.0),
+                            UiRect::top(Val::Percent(8.0)),
+                        )
+                        .insert((MenuItem::Button, MenuButton::Difficulty(difficulty)));
+                    }
+                });
+        });
+}
+
+fn show_session_summary(mut commands: Commands, summary: Option<Res<SessionSummary>>) {
+    let Some(summary) = summary else {
+        return;
+    };
+    let seconds = summary.duration.as_secs();
+    let text = format!(
+        "Left after {}m {:02}s: {} treasures brought back, {} hobbits lost",
+        seconds / 60,
+        seconds % 60,
+        summary.treasures,
+        summary.lost_hobbits
+    );
+    commands.remove_resource::<SessionSummary>();
+
+    ui::spawn_toast(&mut commands, text).insert(StateScoped(CURRENT_STATE));
+}
+
+fn update_text(mut text: Query<(&mut Text, &Parent)>, nodes: Query<&Node, With<MenuButton>>) {
     for (mut text, parent) in text.iter_mut() {
-        let node = nodes.get(parent.get()).unwrap();
+        let Ok(node) = nodes.get(parent.get()) else {
+            continue;
+        };
         text.sections[0].style.font_size = (node.size().y / 4.0).floor() * 2.0;
     }
 }
@@ -771,196 +834,182 @@ fn animation_maintenance(
 #[allow(clippy::too_many_arguments)]
 fn button_system(
     mut commands: Commands,
-    interaction_query: Query<
-        (Ref<Interaction>, &BackgroundColor, &MenuButton, Entity),
-        Changed<Interaction>,
-    >,
-    mut exit: EventWriter<AppExit>,
+    interaction_query: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut ask_confirmation: EventWriter<AskConfirmation>,
     mut next_state: EventWriter<SwitchState>,
     ui_items: Query<(Entity, &MenuItem)>,
     camera_position: Query<(Entity, &Transform), With<Camera>>,
     progress: Res<GameProgress>,
     mut audio: EventWriter<AudioTrigger>,
-    assets: Res<GameAssets>,
+    mut assets: ResMut<GameAssets>,
+    mut levels: ResMut<Assets<Level>>,
+    mut store: ResMut<PkvStore>,
+    choices: Query<(), With<DifficultyChoice>>,
+    mut new_game_plus: ResMut<NewGamePlus>,
 ) {
-    for (interaction, color, button, entity) in &interaction_query {
-        if interaction.is_added() {
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Pressed {
             continue;
         }
-        match *interaction {
-            Interaction::Pressed => {
-                audio.send(AudioTrigger::Click);
-                match button {
-                    MenuButton::Play => {
-                        audio.send(AudioTrigger::Start);
-                        #[cfg(feature = "debug")]
-                        commands.insert_resource(GameInProgress {
-                            level: 1,
-                            ..default()
-                        });
-                        #[cfg(not(feature = "debug"))]
+        audio.send(AudioTrigger::Click);
+        match button {
+            MenuButton::Play if DifficultyMode::load(&store).is_none() => {
+                if choices.is_empty() {
+                    spawn_difficulty_choice(&mut commands);
+                }
+            }
+            MenuButton::Play | MenuButton::Daily | MenuButton::Difficulty(_) => {
+                if let MenuButton::Difficulty(difficulty) = button {
+                    difficulty.save(&mut store);
+                    commands.insert_resource(*difficulty);
+                }
+                audio.send(AudioTrigger::Start);
+                if *button == MenuButton::Daily {
+                    let day = today();
+                    let level = assets.set_generated_level(levels.add(daily_level(day)));
+                    commands.insert_resource(GameInProgress { level, ..default() });
+                    commands.insert_resource(DailyChallenge { day });
+                } else {
+                    #[cfg(feature = "debug")]
+                    commands.insert_resource(GameInProgress {
+                        level: 1,
+                        ..default()
+                    });
+                    #[cfg(not(feature = "debug"))]
+                    {
+                        // once the campaign is finished, Play goes on with New Game+
+                        let level = if progress.current_level >= assets.custom_levels.start {
+                            new_game_plus.start(&mut store)
+                        } else {
+                            progress.current_level
+                        };
                         commands.insert_resource(GameInProgress {
-                            level: progress.current_level.min(assets.levels.len() - 1),
+                            level: level.min(assets.custom_levels.start - 1),
                             ..default()
                         });
-                        next_state.send(SwitchState(GameState::InGame));
-                        let (entity, transform) = camera_position.single();
-                        commands.entity(entity).insert(transform.ease_to(
-                            Transform::from_translation(Vec3::new(0.0, 50.0, 0.0)),
-                            EaseFunction::QuadraticInOut,
-                            EasingType::Once {
-                                duration: Duration::from_secs_f32(1.0),
-                            },
-                        ));
-
-                        for (entity, kind) in &ui_items {
-                            if *kind == MenuItem::Root {
-                                commands.entity(entity).insert(
-                                    Style {
-                                        width: Val::Percent(100.0),
-                                        height: Val::Percent(100.0),
-                                        left: Val::Percent(0.0),
-                                        align_items: AlignItems::Center,
-                                        justify_content: JustifyContent::Start,
-                                        ..default()
-                                    }
-                                    .ease_to(
-                                        Style {
-                                            width: Val::Percent(100.0),
-                                            height: Val::Percent(100.0),
-                                            left: Val::Percent(-100.0),
-                                            align_items: AlignItems::Center,
-                                            justify_content: JustifyContent::Start,
-                                            ..default()
-                                        },
-                                        EaseFunction::QuadraticOut,
-                                        EasingType::Once {
-                                            duration: Duration::from_secs_f32(1.0),
-                                        },
-                                    ),
-                                );
-                            }
-                        }
-                    }
-                    MenuButton::LevelSelect => {
-                        next_state.send(SwitchState(GameState::LevelSelect));
-
-                        let (entity, transform) = camera_position.single();
-                        commands.entity(entity).insert(transform.ease_to(
-                            Transform::from_translation(Vec3::new(0.0, 50.0, 0.0)),
-                            EaseFunction::QuadraticInOut,
-                            EasingType::Once {
-                                duration: Duration::from_secs_f32(1.0),
-                            },
-                        ));
-
-                        for (entity, kind) in &ui_items {
-                            if *kind == MenuItem::Root {
-                                commands.entity(entity).insert(
-                                    Style {
-                                        width: Val::Percent(100.0),
-                                        height: Val::Percent(100.0),
-                                        left: Val::Percent(0.0),
-                                        align_items: AlignItems::Center,
-                                        justify_content: JustifyContent::Start,
-                                        ..default()
-                                    }
-                                    .ease_to(
-                                        Style {
-                                            width: Val::Percent(100.0),
-                                            height: Val::Percent(100.0),
-                                            left: Val::Percent(-100.0),
-                                            align_items: AlignItems::Center,
-                                            justify_content: JustifyContent::Start,
-                                            ..default()
-                                        },
-                                        EaseFunction::QuadraticOut,
-                                        EasingType::Once {
-                                            duration: Duration::from_secs_f32(1.0),
-                                        },
-                                    ),
-                                );
-                            }
-                        }
                     }
-                    MenuButton::Credits => {
-                        next_state.send(SwitchState(GameState::Credits));
-
-                        let (entity, transform) = camera_position.single();
-                        commands.entity(entity).insert(transform.ease_to(
-                            Transform::from_translation(Vec3::new(0.0, 50.0, 0.0)),
-                            EaseFunction::QuadraticInOut,
-                            EasingType::Once {
-                                duration: Duration::from_secs_f32(1.0),
-                            },
-                        ));
-
-                        for (entity, kind) in &ui_items {
-                            if *kind == MenuItem::Root {
-                                commands.entity(entity).insert(
-                                    Style {
-                                        width: Val::Percent(100.0),
-                                        height: Val::Percent(100.0),
-                                        left: Val::Percent(0.0),
-                                        align_items: AlignItems::Center,
-                                        justify_content: JustifyContent::Start,
-                                        ..default()
-                                    }
-                                    .ease_to(
-                                        Style {
-                                            width: Val::Percent(100.0),
-                                            height: Val::Percent(100.0),
-                                            left: Val::Percent(-100.0),
-                                            align_items: AlignItems::Center,
-                                            justify_content: JustifyContent::Start,
-                                            ..default()
-                                        },
-                                        EaseFunction::QuadraticOut,
-                                        EasingType::Once {
-                                            duration: Duration::from_secs_f32(1.0),
-                                        },
-                                    ),
-                                );
-                            }
-                        }
-                    }
-                    MenuButton::Quit => {
-                        exit.send_default();
-                    }
-                };
-                commands.entity(entity).insert(color.ease_to(
-                    BUTTON_HOVERED,
+                }
+                next_state.send(SwitchState(GameState::InGame));
+                let (entity, transform) = camera_position.single();
+                commands.entity(entity).insert(transform.ease_to(
+                    Transform::from_translation(Vec3::new(0.0, 50.0, 0.0)),
                     EaseFunction::QuadraticInOut,
                     EasingType::Once {
-                        duration: Duration::from_secs_f32(0.25),
+                        duration: Duration::from_secs_f32(1.0),
                     },
                 ));
+
+                for (entity, kind) in &ui_items {
+                    if *kind == MenuItem::Root {
+                        ui::slide_out(&mut commands, entity, 0.0);
+                    }
+                }
             }
-            Interaction::Hovered => {
-                commands.entity(entity).insert(color.ease_to(
-                    BUTTON_HOVERED,
+            MenuButton::LevelSelect => {
+                next_state.send(SwitchState(GameState::LevelSelect));
+
+                let (entity, transform) = camera_position.single();
+                commands.entity(entity).insert(transform.ease_to(
+                    Transform::from_translation(Vec3::new(0.0, 50.0, 0.0)),
                     EaseFunction::QuadraticInOut,
                     EasingType::Once {
-                        duration: Duration::from_secs_f32(0.25),
+                        duration: Duration::from_secs_f32(1.0),
                     },
                 ));
+
+                for (entity, kind) in &ui_items {
+                    if *kind == MenuItem::Root {
+                        ui::slide_out(&mut commands, entity, 0.0);
+                    }
+                }
             }
-            Interaction::None => {
-                commands.entity(entity).insert(color.ease_to(
-                    BUTTON_IDLE,
+            MenuButton::Settings
+            | MenuButton::Achievements
+            | MenuButton::Statistics
+            | MenuButton::Credits => {
+                next_state.send(SwitchState(match button {
+                    MenuButton::Settings => GameState::Settings,
+                    MenuButton::Achievements => GameState::Achievements,
+                    MenuButton::Statistics => GameState::Statistics,
+                    _ => GameState::Credits,
+                }));
+
+                let (entity, transform) = camera_position.single();
+                commands.entity(entity).insert(transform.ease_to(
+                    Transform::from_translation(Vec3::new(0.0, 50.0, 0.0)),
                     EaseFunction::QuadraticInOut,
                     EasingType::Once {
-                        duration: Duration::from_secs_f32(0.25),
+                        duration: Duration::from_secs_f32(1.0),
                     },
                 ));
+
+                for (entity, kind) in &ui_items {
+                    if *kind == MenuItem::Root {
+                        ui::slide_out(&mut commands, entity, 0.0);
+                    }
+                }
+            }
+            MenuButton::Quit => {
+                ask_confirmation.send(AskConfirmation {
+                    message: "Quit the game?".to_string(),
+                    confirm: "Quit".to_string(),
+                    action: ConfirmedAction::Quit,
+                });
             }
         }
     }
 }
 
-const BUTTON_IDLE: BackgroundColor = BackgroundColor(Color::Srgba(palettes::tailwind::INDIGO_800));
-const BUTTON_HOVERED: BackgroundColor =
-    BackgroundColor(Color::Srgba(palettes::tailwind::AMBER_600));
+fn quit_when_confirmed(mut confirmed: EventReader<Confirmed>, mut exit: EventWriter<AppExit>) {
+    if confirmed
+        .read()
+        .any(|confirmed| confirmed.0 == ConfirmedAction::Quit)
+    {
+        exit.send_default();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn resume_when_confirmed(
+    mut commands: Commands,
+    mut confirmed: EventReader<Confirmed>,
+    mut next_state: EventWriter<SwitchState>,
+    ui_items: Query<(Entity, &MenuItem)>,
+    camera_position: Query<(Entity, &Transform), With<Camera>>,
+    store: Res<PkvStore>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+    mut mutators: ResMut<Mutators>,
+    mut new_game_plus: ResMut<NewGamePlus>,
+) {
+    if !confirmed
+        .read()
+        .any(|confirmed| confirmed.0 == ConfirmedAction::ResumeLevel)
+    {
+        return;
+    }
+    let Some(snapshot) = Snapshot::load(&store, &assets, &levels) else {
+        return;
+    };
+    snapshot.restore_settings(&mut mutators, &mut new_game_plus);
+    commands.insert_resource(snapshot.game());
+    commands.insert_resource(ResumedLevel(snapshot));
+    next_state.send(SwitchState(GameState::InGame));
+
+    let (entity, transform) = camera_position.single();
+    commands.entity(entity).insert(transform.ease_to(
+        Transform::from_translation(Vec3::new(0.0, 50.0, 0.0)),
+        EaseFunction::QuadraticInOut,
+        EasingType::Once {
+            duration: Duration::from_secs_f32(1.0),
+        },
+    ));
+    for (entity, kind) in &ui_items {
+        if *kind == MenuItem::Root {
+            ui::slide_out(&mut commands, entity, 0.0);
+        }
+    }
+}
 
 #[derive(Event)]
 pub struct SwitchState(pub GameState);
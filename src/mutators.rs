@@ -0,0 +1,146 @@
+//! Mutators toggled in the level selector to replay levels differently. They are combined in
+//! [`Mutators`], and are all turned off when going back to the menu. Campaign levels won with a
+//! mutator are kept, and a badge is earned once all of them are won with it.
+
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+use serde::{Deserialize, Serialize};
+
+use crate::{assets::GameAssets, levels::Level, GameState};
+
+/// Speed of hobbits with [`Mutator::DoubleSpeed`]
+const DOUBLE_SPEED: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mutator {
+    /// Hobbits walk twice as fast, and come out twice as often
+    DoubleSpeed,
+    /// Paths are not previewed when placing a bonus
+    NoPathPreview,
+    /// Only the first bonus of the level can be placed
+    OneBonus,
+    /// The level is mirrored left to right
+    Mirrored,
+}
+
+impl Mutator {
+    pub fn all() -> [Mutator; 4] {
+        [
+            Mutator::DoubleSpeed,
+            Mutator::NoPathPreview,
+            Mutator::OneBonus,
+            Mutator::Mirrored,
+        ]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Mutator::DoubleSpeed => "Double Speed",
+            Mutator::NoPathPreview => "No Preview",
+            Mutator::OneBonus => "One Bonus",
+            Mutator::Mirrored => "Mirrored",
+        }
+    }
+
+    /// Key of the store with the campaign levels won with this mutator
+    pub(crate) fn key(&self) -> &'static str {
+        match self {
+            Mutator::DoubleSpeed => "mutator-double-speed",
+            Mutator::NoPathPreview => "mutator-no-path-preview",
+            Mutator::OneBonus => "mutator-one-bonus",
+            Mutator::Mirrored => "mutator-mirrored",
+        }
+    }
+
+    /// Campaign levels won with this mutator
+    pub fn won_levels(&self, store: &PkvStore) -> Vec<usize> {
+        store.get::<Vec<usize>>(self.key()).unwrap_or_default()
+    }
+
+    /// All the campaign levels were won with this mutator
+    pub fn has_badge(&self, store: &PkvStore, assets: &GameAssets) -> bool {
+        let won = self.won_levels(store);
+        (1..assets.custom_levels.start).all(|level| won.contains(&level))
+    }
+}
+
+/// Mutators currently active
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct Mutators(Vec<Mutator>);
+
+impl Mutators {
+    pub fn is_active(&self, mutator: Mutator) -> bool {
+        self.0.contains(&mutator)
+    }
+
+    pub fn active(&self) -> &[Mutator] {
+        &self.0
+    }
+
+    /// At least one mutator is active
+    pub fn any(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    pub fn toggle(&mut self, mutator: Mutator) {
+        if let Some(index) = self.0.iter().position(|active| *active == mutator) {
+            self.0.remove(index);
+        } else {
+            self.0.push(mutator);
+        }
+    }
+
+    /// The level as it's played with the active mutators
+    pub fn apply(&self, level: Level) -> Level {
+        let mut level = if self.is_active(Mutator::Mirrored) {
+            level.mirrored()
+        } else {
+            level
+        };
+        if self.is_active(Mutator::OneBonus) {
+            level.bonus.truncate(1);
+        }
+        level
+    }
+
+    /// Cell of the level as it's played for a cell of the level without mutators, and the other
+    /// way around. Layouts and solutions are kept for the level without mutators.
+    pub fn map_cell(&self, level: &Level, cell: (usize, usize)) -> (usize, usize) {
+        if self.is_active(Mutator::Mirrored) {
+            level.mirror_cell(cell)
+        } else {
+            cell
+        }
+    }
+
+    /// Multiplier of the speed of hobbits and of how often they come out
+    pub fn hobbit_speed(&self) -> f32 {
+        if self.is_active(Mutator::DoubleSpeed) {
+            DOUBLE_SPEED
+        } else {
+            1.0
+        }
+    }
+
+    /// Keep the campaign level as won with each active mutator
+    pub fn record_win(&self, store: &mut PkvStore, level: usize) {
+        for mutator in &self.0 {
+            let mut won = mutator.won_levels(store);
+            if !won.contains(&level) {
+                won.push(level);
+                let _ = store.set(mutator.key(), &won);
+            }
+        }
+    }
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        // levels are played as they are from the menu
+        app.add_systems(
+            OnEnter(GameState::Menu),
+            |mut mutators: ResMut<Mutators>| *mutators = Mutators::default(),
+        );
+    }
+}
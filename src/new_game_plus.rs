@@ -0,0 +1,59 @@
+//! New Game+, unlocked once the campaign is finished: its levels are played again remixed with
+//! [`Level::remix`](crate::levels::Level::remix). Progress in New Game+ is kept apart from the
+//! campaign's.
+
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+
+use crate::GameState;
+
+/// Key of the store with the level reached in New Game+
+pub(crate) const NEW_GAME_PLUS_KEY: &str = "new-game-plus";
+
+#[derive(Resource, Default)]
+pub struct NewGamePlus {
+    /// Campaign levels are remixed while playing New Game+
+    pub active: bool,
+    /// Level unlocked in New Game+, `None` until it's started
+    pub reached: Option<usize>,
+}
+
+impl NewGamePlus {
+    pub fn load(store: &PkvStore) -> Self {
+        NewGamePlus {
+            active: false,
+            reached: store
+                .get::<u32>(NEW_GAME_PLUS_KEY)
+                .ok()
+                .map(|level| level as usize),
+        }
+    }
+
+    /// Start New Game+, or continue it. Returns the level to play.
+    pub fn start(&mut self, store: &mut PkvStore) -> usize {
+        self.active = true;
+        if self.reached.is_none() {
+            self.reached = Some(1);
+            let _ = store.set(NEW_GAME_PLUS_KEY, &1u32);
+        }
+        self.reached.unwrap()
+    }
+
+    /// Unlock the level after the one won in New Game+
+    pub fn record_win(&mut self, store: &mut PkvStore, level: usize) {
+        let reached = self.reached.unwrap_or(1).max(level + 1);
+        self.reached = Some(reached);
+        let _ = store.set(NEW_GAME_PLUS_KEY, &(reached as u32));
+    }
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        // levels are played as they are from the level selector, Play goes back to New Game+
+        app.add_systems(
+            OnEnter(GameState::Menu),
+            |mut new_game_plus: ResMut<NewGamePlus>| new_game_plus.active = false,
+        );
+    }
+}
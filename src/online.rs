@@ -0,0 +1,202 @@
+//! Level packs made by the community, downloaded when opening the online tab of the level
+//! selector.
+//!
+//! The index lists one pack per line as `name:url,url,...`, with the url of each level of the
+//! pack. Its location is set at build time with the `LEVEL_PACKS_URL` environment variable.
+
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future, IoTaskPool, Task},
+};
+
+use crate::{assets::GameAssets, levels::Level};
+
+const INDEX_URL: Option<&str> = option_env!("LEVEL_PACKS_URL");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Download {
+    NotStarted,
+    InProgress { done: u32, total: u32 },
+    Failed(String),
+    Done,
+}
+
+pub struct LevelPack {
+    pub name: String,
+    /// Indices of the levels in [`GameAssets::levels`]
+    pub levels: Vec<usize>,
+}
+
+#[derive(Resource)]
+pub struct LevelPacks {
+    pub download: Download,
+    pub packs: Vec<LevelPack>,
+}
+
+/// Ask for the level packs to be downloaded, if they are not already
+#[derive(Event)]
+pub struct DownloadLevelPacks;
+
+/// Name of each pack, with the url and content of its levels
+type Downloaded = Vec<(String, Vec<(String, String)>)>;
+
+#[derive(Resource)]
+struct PackDownload {
+    task: Task<Result<Downloaded, String>>,
+    done: Arc<AtomicU32>,
+    total: Arc<AtomicU32>,
+}
+
+pub struct Plugin;
+impl bevy::prelude::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LevelPacks {
+            download: Download::NotStarted,
+            packs: vec![],
+        })
+        .add_event::<DownloadLevelPacks>()
+        .add_systems(
+            Update,
+            (
+                start_download,
+                poll_download.run_if(resource_exists::<PackDownload>),
+            ),
+        );
+    }
+}
+
+async fn fetch(url: &str) -> Result<String, String> {
+    let response = reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| format!("{}: {}", url, err))?;
+    response
+        .text()
+        .await
+        .map_err(|err| format!("{}: {}", url, err))
+}
+
+fn start_download(
+    mut commands: Commands,
+    mut events: EventReader<DownloadLevelPacks>,
+    mut packs: ResMut<LevelPacks>,
+) {
+    if events.is_empty() {
+        return;
+    }
+    events.clear();
+    if matches!(packs.download, Download::InProgress { .. } | Download::Done) {
+        return;
+    }
+    let Some(index_url) = INDEX_URL else {
+        packs.download = Download::Failed("no level pack server configured".to_string());
+        return;
+    };
+
+    let done = Arc::new(AtomicU32::new(0));
+    let total = Arc::new(AtomicU32::new(0));
+    let download = {
+        let done = done.clone();
+        let total = total.clone();
+        async move {
+            let index = fetch(index_url).await?;
+            let index = index
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .map(|(name, urls)| {
+                    (
+                        name.to_string(),
+                        urls.split(',')
+                            .filter(|url| !url.is_empty())
+                            .map(|url| url.trim().to_string())
+                            .collect::<Vec<_>>(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            total.store(
+                index.iter().map(|(_, urls)| urls.len() as u32).sum(),
+                Ordering::Release,
+            );
+
+            let mut downloaded = vec![];
+            for (name, urls) in index {
+                let mut levels = vec![];
+                for url in urls {
+                    done.fetch_add(1, Ordering::Release);
+                    // a level that can't be downloaded is skipped, the rest of its pack is kept
+                    match fetch(&url).await {
+                        Ok(content) => levels.push((url, content)),
+                        Err(err) => warn!("Couldn't download level {}", err),
+                    }
+                }
+                downloaded.push((name, levels));
+            }
+            Ok(downloaded)
+        }
+    };
+    // reqwest needs a tokio runtime on native
+    #[cfg(not(target_arch = "wasm32"))]
+    let download = async_compat::Compat::new(download);
+    let task = IoTaskPool::get().spawn(download);
+    commands.insert_resource(PackDownload { task, done, total });
+    packs.download = Download::InProgress { done: 0, total: 0 };
+}
+
+fn poll_download(
+    mut commands: Commands,
+    mut download: ResMut<PackDownload>,
+    mut packs: ResMut<LevelPacks>,
+    mut assets: ResMut<GameAssets>,
+    mut levels: ResMut<Assets<Level>>,
+) {
+    let progress = Download::InProgress {
+        done: download.done.load(Ordering::Acquire),
+        total: download.total.load(Ordering::Acquire),
+    };
+    if packs.download != progress {
+        packs.download = progress;
+    }
+
+    let Some(result) = block_on(future::poll_once(&mut download.task)) else {
+        return;
+    };
+    commands.remove_resource::<PackDownload>();
+    let downloaded = match result {
+        Ok(downloaded) => downloaded,
+        Err(err) => {
+            warn!("Couldn't download level packs: {}", err);
+            packs.download = Download::Failed(err);
+            return;
+        }
+    };
+
+    for (name, files) in downloaded {
+        let mut pack = LevelPack {
+            name,
+            levels: vec![],
+        };
+        for (url, content) in files {
+            let level = match Level::parse(&content, &url) {
+                Ok(level) => level,
+                Err(err) => {
+                    warn!("Invalid level {}: {}", url, err);
+                    continue;
+                }
+            };
+            if let Err(errors) = level.validate() {
+                warn!("Invalid level {}: {:?}", url, errors);
+                continue;
+            }
+            pack.levels.push(assets.levels.len());
+            assets.levels.push(levels.add(level));
+        }
+        packs.packs.push(pack);
+    }
+    info!("downloaded {} level packs", packs.packs.len());
+    packs.download = Download::Done;
+}
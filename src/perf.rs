@@ -0,0 +1,185 @@
+//! Performance overlay, toggled with F3, so that players can report performance issues with real
+//! numbers: a graph of the last frame times, the number of entities, the paths searched for
+//! hobbits each second, and the time the last update of the navmesh took.
+
+use std::time::Duration;
+
+use bevy::{
+    color::palettes,
+    diagnostic::{
+        Diagnostic, DiagnosticPath, Diagnostics, DiagnosticsStore, EntityCountDiagnosticsPlugin,
+        FrameTimeDiagnosticsPlugin, RegisterDiagnostic,
+    },
+    prelude::*,
+};
+
+use crate::play::BlockedCells;
+
+/// Paths searched for hobbits, measured at each gameplay step
+pub const PATH_QUERIES: DiagnosticPath = DiagnosticPath::const_new("game/path_queries");
+/// Time the last update of the navmesh took, in milliseconds
+pub const NAVMESH_UPDATE: DiagnosticPath = DiagnosticPath::const_new("game/navmesh_update");
+
+/// Frames shown in the graph
+const GRAPH_BARS: usize = 60;
+/// Frame time at the top of the graph, in milliseconds
+const GRAPH_MAX: f64 = 50.0;
+
+#[derive(Component)]
+struct PerfOverlay;
+
+#[derive(Component)]
+struct PerfText;
+
+/// Bar of the graph, showing the frame this many frames ago
+#[derive(Component)]
+struct FrameBar(usize);
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((FrameTimeDiagnosticsPlugin, EntityCountDiagnosticsPlugin))
+            .register_diagnostic(Diagnostic::new(PATH_QUERIES).with_max_history_length(200))
+            .register_diagnostic(Diagnostic::new(NAVMESH_UPDATE).with_suffix("ms"))
+            .add_systems(
+                Update,
+                (
+                    toggle_overlay,
+                    measure_navmesh_update.run_if(
+                        resource_exists::<BlockedCells>.and_then(resource_changed::<BlockedCells>),
+                    ),
+                    update_overlay.run_if(any_with_component::<PerfOverlay>),
+                ),
+            );
+    }
+}
+
+fn toggle_overlay(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    overlays: Query<Entity, With<PerfOverlay>>,
+) {
+    if !keyboard.just_pressed(KeyCode::F3) {
+        return;
+    }
+    if let Ok(overlay) = overlays.get_single() {
+        commands.entity(overlay).despawn_recursive();
+        return;
+    }
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    row_gap: Val::Px(6.0),
+                    ..default()
+                },
+                background_color: Color::BLACK.with_alpha(0.7).into(),
+                border_radius: BorderRadius::all(Val::Px(5.0)),
+                z_index: ZIndex::Global(20),
+                ..default()
+            },
+            PerfOverlay,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                PerfText,
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(GRAPH_BARS as f32 * 3.0),
+                        height: Val::Px(40.0),
+                        align_items: AlignItems::FlexEnd,
+                        column_gap: Val::Px(1.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    // oldest frame on the left
+                    for age in (0..GRAPH_BARS).rev() {
+                        parent.spawn((
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Px(2.0),
+                                    height: Val::Percent(0.0),
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                            FrameBar(age),
+                        ));
+                    }
+                });
+        });
+}
+
+fn measure_navmesh_update(cells: Res<BlockedCells>, mut diagnostics: Diagnostics) {
+    diagnostics.add_measurement(&NAVMESH_UPDATE, || cells.last_update.as_secs_f64() * 1000.0);
+}
+
+fn update_overlay(
+    store: Res<DiagnosticsStore>,
+    mut text: Query<&mut Text, With<PerfText>>,
+    mut bars: Query<(&FrameBar, &mut Style, &mut BackgroundColor)>,
+) {
+    let value = |path: &DiagnosticPath| {
+        store
+            .get(path)
+            .and_then(|diagnostic| diagnostic.smoothed())
+            .unwrap_or(0.0)
+    };
+    // measured at each gameplay step instead of each frame, count the last second of them
+    let path_queries = store.get(&PATH_QUERIES).map_or(0.0, |diagnostic| {
+        diagnostic
+            .measurements()
+            .filter(|measurement| measurement.time.elapsed() < Duration::from_secs(1))
+            .map(|measurement| measurement.value)
+            .sum::<f64>()
+    });
+    let navmesh_update = store
+        .get_measurement(&NAVMESH_UPDATE)
+        .map(|measurement| format!("{:.2} ms", measurement.value))
+        .unwrap_or_else(|| "-".to_string());
+    if let Ok(mut text) = text.get_single_mut() {
+        text.sections[0].value = format!(
+            "FPS: {:.0}\nFrame time: {:.1} ms\nEntities: {:.0}\nPath queries: {:.0}/s\n\
+            Navmesh update: {}",
+            value(&FrameTimeDiagnosticsPlugin::FPS),
+            value(&FrameTimeDiagnosticsPlugin::FRAME_TIME),
+            value(&EntityCountDiagnosticsPlugin::ENTITY_COUNT),
+            path_queries,
+            navmesh_update,
+        );
+    }
+
+    let frame_times = store
+        .get(&FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        .map(|diagnostic| diagnostic.values().rev().copied().collect::<Vec<_>>())
+        .unwrap_or_default();
+    for (bar, mut style, mut color) in &mut bars {
+        let frame_time = frame_times.get(bar.0).copied().unwrap_or(0.0);
+        style.height = Val::Percent((frame_time / GRAPH_MAX).min(1.0) as f32 * 100.0);
+        *color = if frame_time < 1000.0 / 60.0 {
+            palettes::tailwind::GREEN_500
+        } else if frame_time < 1000.0 / 30.0 {
+            palettes::tailwind::AMBER_500
+        } else {
+            palettes::tailwind::RED_500
+        }
+        .into();
+    }
+}
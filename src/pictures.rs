@@ -0,0 +1,145 @@
+//! Top-down pictures of a level with the obstacles placed, as PNG files to share puzzle setups
+//! outside of the game. The thumbnails of the level selector are UI nodes with the same
+//! [`cell_color`].
+
+use bevy::{
+    color::palettes,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+
+use crate::levels::{Level, Tile};
+
+/// Size in pixels of a cell in exported pictures
+const EXPORT_CELL_SIZE: u32 = 32;
+
+/// Color of a cell in a picture of the level
+pub fn cell_color(tile: &Tile, obstacle: bool) -> Srgba {
+    match tile {
+        _ if obstacle => palettes::tailwind::RED_700,
+        Tile::Empty => palettes::tailwind::GREEN_400,
+        Tile::Start => palettes::tailwind::INDIGO_600,
+        Tile::Chest(_) => palettes::tailwind::AMBER_400,
+        _ => palettes::tailwind::STONE_500,
+    }
+}
+
+/// Marker drawn over a cell, so that cells can be told apart without their color
+fn cell_marker(tile: &Tile, obstacle: bool) -> Option<(Marker, Srgba)> {
+    match tile {
+        _ if obstacle => Some((Marker::Cross, palettes::tailwind::RED_200)),
+        Tile::Start => Some((Marker::Disc, palettes::tailwind::INDIGO_200)),
+        Tile::Chest(_) => Some((Marker::Square, palettes::tailwind::AMBER_800)),
+        Tile::Skeleton => Some((Marker::Cross, palettes::tailwind::STONE_100)),
+        Tile::In => Some((Marker::Square, palettes::tailwind::GREEN_600)),
+        Tile::Out => Some((Marker::Square, palettes::tailwind::RED_500)),
+        Tile::OneWay(_) => Some((Marker::Disc, palettes::tailwind::STONE_300)),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Marker {
+    Disc,
+    Square,
+    Cross,
+}
+
+impl Marker {
+    /// Whether the pixel is part of the marker, `x` and `y` from -1 to 1 across the cell
+    fn covers(&self, x: f32, y: f32) -> bool {
+        match self {
+            Marker::Disc => x * x + y * y < 0.3,
+            Marker::Square => x.abs() < 0.5 && y.abs() < 0.5,
+            Marker::Cross => (x - y).abs() < 0.2 || (x + y).abs() < 0.2,
+        }
+    }
+}
+
+fn rgba8(color: Srgba) -> [u8; 4] {
+    [color.red, color.green, color.blue, color.alpha].map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8)
+}
+
+/// Picture of the level with obstacles on the given cells, each cell `cell_size` pixels wide
+pub fn render_layout(level: &Level, obstacles: &[(usize, usize)], cell_size: u32) -> Image {
+    let floor = &level.floors[0];
+    let (width, height) = (floor[0].len() as u32, floor.len() as u32);
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: width * cell_size,
+            height: height * cell_size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    for (y, row) in floor.iter().enumerate() {
+        for (x, tile) in row.iter().enumerate() {
+            let obstacle = obstacles.contains(&(x, y));
+            let color = cell_color(tile, obstacle);
+            let marker = cell_marker(tile, obstacle);
+            for py in 0..cell_size {
+                for px in 0..cell_size {
+                    // a thin darker line between cells, like the tiles of the floor
+                    let border = px == 0 || py == 0;
+                    let (mx, my) = (
+                        px as f32 / cell_size as f32 * 2.0 - 1.0,
+                        py as f32 / cell_size as f32 * 2.0 - 1.0,
+                    );
+                    let pixel = match marker {
+                        Some((marker, marker_color)) if marker.covers(mx, my) => marker_color,
+                        _ if border && *tile != Tile::Empty => Srgba {
+                            red: color.red * 0.8,
+                            green: color.green * 0.8,
+                            blue: color.blue * 0.8,
+                            ..color
+                        },
+                        _ => color,
+                    };
+                    let index = (((y as u32 * cell_size + py) * width * cell_size
+                        + x as u32 * cell_size
+                        + px)
+                        * 4) as usize;
+                    image.data[index..index + 4].copy_from_slice(&rgba8(pixel));
+                }
+            }
+        }
+    }
+    image
+}
+
+/// Directory where pictures of levels are exported
+#[cfg(not(target_arch = "wasm32"))]
+pub fn pictures_dir() -> Option<std::path::PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("ThereAndBackAgain").join("pictures"))
+}
+
+/// Save a picture of the level with obstacles on the given cells. Returns the path of the file.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_layout(
+    level: &Level,
+    obstacles: &[(usize, usize)],
+) -> Result<std::path::PathBuf, String> {
+    let dir = pictures_dir().ok_or("no data directory")?;
+    std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    let name = std::path::Path::new(&level.file)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "level".to_string());
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let path = dir.join(format!("{}-{}.png", name, timestamp));
+    render_layout(level, obstacles, EXPORT_CELL_SIZE)
+        .try_into_dynamic()
+        .map_err(|err| err.to_string())?
+        .save(&path)
+        .map_err(|err| err.to_string())?;
+    Ok(path)
+}
@@ -0,0 +1,148 @@
+//! Steam integration, with the `steam` feature: achievements are unlocked on Steam too, the
+//! progress is saved to the Steam cloud, and friends see the level being played.
+
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+use steamworks::{AppId, Client, SingleClient};
+
+use crate::{
+    achievements::{Achievement, Achievements},
+    assets::GameAssets,
+    levels::Level,
+    play::GameInProgress,
+    save, GameState,
+};
+
+/// Spacewar, the app Valve provides for testing, until the game has its own
+const STEAM_APP_ID: u32 = 480;
+/// File in the Steam cloud holding the progress
+const CLOUD_FILE: &str = "progress.json";
+/// Key of the store with the time of the last upload of the progress, in seconds since the epoch
+const CLOUD_UPLOADED_KEY: &str = "cloud-uploaded";
+
+#[derive(Resource, Deref)]
+struct Steam(Client);
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        let (client, single) = match Client::init_app(AppId(STEAM_APP_ID)) {
+            Ok(clients) => clients,
+            Err(err) => {
+                // the game works without Steam, just without what it brings
+                warn!("failed to connect to Steam: {}", err);
+                return;
+            }
+        };
+        app.insert_resource(Steam(client))
+            .insert_non_send_resource(single)
+            .add_systems(PreUpdate, |single: NonSend<SingleClient>| {
+                single.run_callbacks()
+            })
+            .add_systems(OnExit(GameState::Loading), download_progress)
+            .add_systems(
+                Update,
+                (
+                    unlock_achievements.run_if(resource_changed::<Achievements>),
+                    update_rich_presence.run_if(state_changed::<GameState>),
+                ),
+            )
+            .add_systems(OnExit(GameState::InGame), upload_progress)
+            .add_systems(Last, upload_progress.run_if(on_event::<AppExit>()));
+    }
+}
+
+/// Name of the achievement in the Steamworks settings of the game
+fn steam_name(achievement: Achievement) -> &'static str {
+    match achievement {
+        Achievement::FirstChapter => "FIRST_CHAPTER",
+        Achievement::Flawless => "FLAWLESS",
+        Achievement::Builder => "BUILDER",
+        Achievement::Swift => "SWIFT",
+    }
+}
+
+fn unlock_achievements(steam: Res<Steam>, achievements: Res<Achievements>) {
+    let stats = steam.user_stats();
+    for achievement in Achievement::all() {
+        if achievements.is_unlocked(achievement) {
+            let _ = stats.achievement(steam_name(achievement)).set();
+        }
+    }
+    let _ = stats.store_stats();
+}
+
+/// Rich presence uses the `#Playing` and `#InMenus` tokens from the localization file of the game
+/// on Steamworks
+fn update_rich_presence(
+    steam: Res<Steam>,
+    state: Res<State<GameState>>,
+    game: Option<Res<GameInProgress>>,
+) {
+    let friends = steam.friends();
+    match (state.get(), game) {
+        (GameState::InGame, Some(game)) => {
+            friends.set_rich_presence("level", Some(&(game.level + 1).to_string()));
+            friends.set_rich_presence("steam_display", Some("#Playing"));
+        }
+        _ => {
+            friends.set_rich_presence("level", None);
+            friends.set_rich_presence("steam_display", Some("#InMenus"));
+        }
+    }
+}
+
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Replace the local progress by the one from the cloud when another device uploaded it since
+fn download_progress(mut commands: Commands, steam: Res<Steam>, mut store: ResMut<PkvStore>) {
+    let file = steam.remote_storage().file(CLOUD_FILE);
+    let uploaded = store.get::<i64>(CLOUD_UPLOADED_KEY).unwrap_or(0);
+    if !file.exists() || file.timestamp() <= uploaded {
+        return;
+    }
+    let mut json = String::new();
+    if let Err(err) = std::io::Read::read_to_string(&mut file.read(), &mut json) {
+        warn!("failed to read the progress from the Steam cloud: {}", err);
+        return;
+    }
+    match save::import(&mut store, &json) {
+        Ok(()) => {
+            info!("progress restored from the Steam cloud");
+            let _ = store.set(CLOUD_UPLOADED_KEY, &file.timestamp());
+            save::reload(&mut commands, &store);
+        }
+        Err(err) => warn!(
+            "failed to restore the progress from the Steam cloud: {}",
+            err
+        ),
+    }
+}
+
+fn upload_progress(
+    steam: Res<Steam>,
+    mut store: ResMut<PkvStore>,
+    assets: Option<Res<GameAssets>>,
+    levels: Res<Assets<Level>>,
+) {
+    let Some(assets) = assets else {
+        return;
+    };
+    let json = save::export(&store, &save::progress_keys(&assets, &levels));
+    // the file is committed to the cloud once the writer is dropped
+    let written = {
+        let mut writer = steam.remote_storage().file(CLOUD_FILE).write();
+        std::io::Write::write_all(&mut writer, json.as_bytes())
+    };
+    match written {
+        Ok(()) => {
+            let _ = store.set(CLOUD_UPLOADED_KEY, &now());
+        }
+        Err(err) => warn!("failed to save the progress to the Steam cloud: {}", err),
+    }
+}
@@ -1,77 +1,681 @@
-use std::{f32::consts::PI, time::Duration};
+use std::{
+    collections::HashSet,
+    f32::consts::{FRAC_PI_2, FRAC_PI_8, PI},
+    time::Duration,
+};
 
 use avian3d::{collision::Collider, prelude::RigidBody};
-use bevy::{color::palettes, prelude::*};
-use bevy_easings::{Ease, EaseFunction, EaseMethod, EasingType};
+use bevy::{
+    color::palettes,
+    math::{vec2, vec3},
+    prelude::*,
+    ui::UiSystem,
+    utils::Instant,
+};
+use bevy_easings::{
+    Ease, EaseFunction, EaseMethod, EasingChainComponent, EasingComponent, EasingType,
+};
+use bevy_firework::{
+    bevy_utilitarian::{
+        prelude::{Gradient, ParamCurve},
+        randomized_values::{RandF32, RandValue, RandVec3},
+    },
+    core::{BlendMode, ParticleSpawnerBundle, ParticleSpawnerSettings},
+    emission_shape::EmissionShape,
+};
 use bevy_pkv::PkvStore;
-use rand::Rng;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::{
+    accessibility::AccessibleName,
     assets::GameAssets,
     audio::AudioTrigger,
-    game::{ActiveLevel, GameEvent, NavMesh, PathStatus},
-    levels::{spawn_level, Bonus, Level, Tile},
+    bindings::{Action, InputMap, BONUS_SHORTCUTS},
+    camera::play_view,
+    celebrations::{Milestone, MILESTONE_EVERY},
+    conditions::Conditions,
+    daily::{daily_file, record_daily, DailyChallenge},
+    dialog::{no_dialog, AskConfirmation, Confirmed, ConfirmedAction},
+    difficulty::DifficultyMode,
+    game::{
+        ActiveLevel, GameEvent, GameRng, HeldSpawns, Hobbit, NavMesh, PathStatus, PlayLoop,
+        SimulationSpeed, SkipIntro, Spawner,
+    },
+    hud::{Anchored, HudLayout, HudPanel},
+    layouts::{
+        custom_layouts_key, last_custom_won_version, last_won_version, layouts_key, load_layouts,
+        record_abandoned, record_attempt, record_custom_win, record_solution, record_win,
+        LastLayout, Layout,
+    },
+    levels::{
+        spawn_level, Bonus, CameraKey, Level, MessagePage, NavMeshCells, Objective, Tile,
+        LIGHT_RADIUS,
+    },
     menu::SwitchState,
-    GameProgress, GameState,
+    mutators::{Mutator, Mutators},
+    new_game_plus::NewGamePlus,
+    resume::ResumedLevel,
+    touch::Tap,
+    ui, GameProgress, GameState, DAYLIGHT,
 };
 
 const CURRENT_STATE: GameState = GameState::InGame;
+/// Buttons are bigger on the web, so that they can be tapped on tablets
+const BUTTON_SCALE: f32 = if cfg!(target_arch = "wasm32") {
+    1.4
+} else {
+    1.0
+};
 
 pub struct Plugin;
 impl bevy::prelude::Plugin for Plugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(CURRENT_STATE), spawn_message)
+        app.add_systems(OnEnter(CURRENT_STATE), (spawn_message, set_daylight))
+            .add_systems(OnEnter(CURRENT_STATE), (seed_rng, start_session))
+            .add_systems(OnExit(CURRENT_STATE), (restore_daylight, stop_camera_intro))
+            .add_systems(OnEnter(GameState::Menu), unload_level)
+            .add_systems(OnEnter(GameState::LevelSelect), unload_level)
+            .add_systems(OnEnter(GameState::Reload), (unload_level, restart_level))
             .add_systems(
                 Update,
                 (
                     button_system,
+                    leave_level,
+                    summarize_session,
+                    follow_camera_intro,
+                    skip_intro.run_if(resource_exists::<IntroPlaying>),
+                    turn_message_pages,
+                    simulation_speed_buttons,
+                    #[cfg(not(target_arch = "wasm32"))]
+                    export_picture,
                     update_progress,
+                    update_hobbit_counter,
                     display_and_check_conditions,
                     draw_cursor,
-                    update_navmesh,
+                    draw_placement_grid,
+                    preview_obstacle_path.run_if(|mutators: Res<Mutators>| {
+                        !mutators.is_active(Mutator::NoPathPreview)
+                    }),
                     info_about_blockage,
+                    display_held_spawns.run_if(resource_changed::<HeldSpawns>),
+                    display_upcoming_spawns.run_if(resource_exists::<ActiveLevel>),
                     #[cfg(feature = "debug")]
                     crate::menu::display_navmesh,
                 )
                     .run_if(in_state(CURRENT_STATE)),
             )
+            .add_systems(
+                PreUpdate,
+                (
+                    place_selected_bonus.run_if(no_dialog),
+                    apply_layout,
+                    update_navmesh,
+                )
+                    .in_set(PlayLoop::Placement)
+                    .run_if(in_state(CURRENT_STATE)),
+            )
+            // with the gameplay, so that time objectives are decided at the same step every time
+            .add_systems(
+                FixedUpdate,
+                track_elapsed
+                    .run_if(in_state(CURRENT_STATE).and_then(resource_exists::<ActiveLevel>)),
+            )
             .add_systems(
                 PreUpdate,
                 change_state_after_event.run_if(in_state(CURRENT_STATE)),
+            )
+            .add_systems(
+                PreUpdate,
+                (keyboard_shortcuts, right_click)
+                    .after(UiSystem::Focus)
+                    .before(PlayLoop::Placement)
+                    .run_if(
+                        in_state(CURRENT_STATE)
+                            .and_then(resource_exists::<ActiveLevel>)
+                            .and_then(no_dialog),
+                    ),
             );
     }
 }
 
-#[derive(Resource, Default)]
+#[derive(Resource, Default, Clone)]
 pub struct GameInProgress {
     pub level: usize,
-    pub score: u32,
+    /// Treasures brought back by each team
+    pub scores: Vec<u32>,
     pub lost_hobbits: u32,
+    /// Hobbits that came out of a spawn point since the start
+    pub spawned_hobbits: u32,
     pub bonus: Vec<Bonus>,
+    /// Seconds the hobbits have been going, until the level is decided
+    pub elapsed: f32,
+    /// Whether the hobbit to escort made it back home, once it's known
+    pub escorted: Option<bool>,
+}
+
+impl GameInProgress {
+    pub fn score(&self, team: usize) -> u32 {
+        self.scores.get(team).copied().unwrap_or(0)
+    }
 }
 
+/// The level at `index` as it's played: campaign levels are adjusted to the difficulty, and
+/// remixed in New Game+, then the active mutators change any level
+pub(crate) fn played_level(
+    level: &Level,
+    index: usize,
+    assets: &GameAssets,
+    difficulty: DifficultyMode,
+    new_game_plus: &NewGamePlus,
+    mutators: &Mutators,
+) -> Level {
+    if !assets.is_campaign(index) {
+        return mutators.apply(level.clone());
+    }
+    let level = difficulty.apply(level);
+    let level = if new_game_plus.active {
+        level.remix()
+    } else {
+        level
+    };
+    mutators.apply(level)
+}
+
+/// Time to go from the last key of a level intro to the view used while playing
+const INTRO_SETTLE_DURATION: f32 = 1.5;
+
+/// Camera following the intro path of a level, before settling on the view used while playing
+#[derive(Component)]
+pub(crate) struct CameraIntro {
+    positions: CubicCurve<Vec3>,
+    targets: CubicCurve<Vec3>,
+    /// Time of each key
+    times: Vec<f32>,
+    elapsed: f32,
+    end: Transform,
+}
+
+impl CameraIntro {
+    // the builder starts on the view used while playing
+    #[cfg_attr(feature = "builder", allow(dead_code))]
+    fn new(keys: &[CameraKey], end: Transform) -> Option<Self> {
+        if keys.len() < 2 {
+            return None;
+        }
+        Some(CameraIntro {
+            positions: CubicCardinalSpline::new_catmull_rom(
+                keys.iter().map(|key| key.position).collect::<Vec<_>>(),
+            )
+            .to_curve(),
+            targets: CubicCardinalSpline::new_catmull_rom(
+                keys.iter().map(|key| key.target).collect::<Vec<_>>(),
+            )
+            .to_curve(),
+            times: keys.iter().map(|key| key.at).collect(),
+            elapsed: 0.0,
+            end,
+        })
+    }
+
+    fn duration(&self) -> f32 {
+        *self.times.last().unwrap()
+    }
+
+    fn transform_at(&self, time: f32) -> Transform {
+        // the curves go through one key per unit, find between which keys `time` is
+        let segment = self
+            .times
+            .windows(2)
+            .position(|keys| time < keys[1])
+            .unwrap_or(self.times.len() - 2);
+        let (from, to) = (self.times[segment], self.times[segment + 1]);
+        let t = segment as f32 + ((time - from) / (to - from)).clamp(0.0, 1.0);
+        Transform::from_translation(self.positions.position(t))
+            .looking_at(self.targets.position(t), Vec3::Y)
+    }
+}
+
+fn follow_camera_intro(
+    mut commands: Commands,
+    mut cameras: Query<(Entity, &mut Transform, &mut CameraIntro)>,
+    time: Res<Time<Real>>,
+) {
+    for (entity, mut transform, mut intro) in &mut cameras {
+        intro.elapsed += time.delta_seconds();
+        *transform = intro.transform_at(intro.elapsed);
+        if intro.elapsed >= intro.duration() {
+            commands
+                .entity(entity)
+                .remove::<CameraIntro>()
+                .insert(transform.ease_to(
+                    intro.end,
+                    EaseFunction::QuadraticInOut,
+                    EasingType::Once {
+                        duration: Duration::from_secs_f32(INTRO_SETTLE_DURATION),
+                    },
+                ));
+        }
+    }
+}
+
+fn stop_camera_intro(mut commands: Commands, cameras: Query<Entity, With<CameraIntro>>) {
+    for entity in &cameras {
+        commands.entity(entity).remove::<CameraIntro>();
+    }
+}
+
+/// The camera and panels are still moving in at the start of the level
+#[derive(Resource)]
+struct IntroPlaying;
+
+/// Fast-forward the intro to the view used while playing with a click or space, and start spawning
+/// hobbits right away
+#[allow(clippy::type_complexity)]
+fn skip_intro(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut cameras: Query<
+        (Entity, &mut Transform),
+        (
+            With<Camera3d>,
+            Or<(
+                With<CameraIntro>,
+                With<EasingComponent<Transform>>,
+                With<EasingChainComponent<Transform>>,
+            )>,
+        ),
+    >,
+    mut panels: Query<
+        (Entity, &Anchored, &mut Style),
+        Or<(
+            With<EasingComponent<Style>>,
+            With<EasingChainComponent<Style>>,
+        )>,
+    >,
+    mut message: Query<(&Interaction, &mut MessagePages)>,
+    level: Res<ActiveLevel>,
+    hud_layout: Res<HudLayout>,
+    mut skip: EventWriter<SkipIntro>,
+) {
+    let panels_moving = panels
+        .iter()
+        .any(|(_, anchored, _)| anchored.panel != HudPanel::Blocked);
+    if cameras.is_empty() && !panels_moving {
+        commands.remove_resource::<IntroPlaying>();
+        return;
+    }
+    // clicks on the message turn its pages instead
+    let clicked = mouse.just_pressed(MouseButton::Left)
+        && message
+            .iter()
+            .all(|(interaction, _)| *interaction == Interaction::None);
+    if !keyboard.just_pressed(KeyCode::Space) && !clicked {
+        return;
+    }
+
+    let floor = &level.0.floors[0];
+    for (entity, mut transform) in &mut cameras {
+        *transform = play_view((floor.len() * 4, floor[0].len() * 4));
+        commands.entity(entity).remove::<(
+            CameraIntro,
+            EasingComponent<Transform>,
+            EasingChainComponent<Transform>,
+        )>();
+    }
+    for (entity, anchored, mut style) in &mut panels {
+        if anchored.panel == HudPanel::Blocked {
+            continue;
+        }
+        *style = hud_layout.place(anchored.panel, &style);
+        commands
+            .entity(entity)
+            .remove::<(EasingComponent<Style>, EasingChainComponent<Style>)>();
+    }
+    for (_, mut pages) in &mut message {
+        pages.skip();
+    }
+    skip.send(SkipIntro);
+    commands.remove_resource::<IntroPlaying>();
+}
+
+/// Pages of the level message, on its panel while they are being read
+#[derive(Component)]
+struct MessagePages {
+    pages: Vec<MessagePage>,
+    current: usize,
+    timer: Timer,
+}
+
+impl MessagePages {
+    /// Leave the message on its current page, to be moved out of the way right away
+    fn skip(&mut self) {
+        self.current = self.pages.len() - 1;
+        let duration = self.timer.duration();
+        self.timer.set_elapsed(duration);
+    }
+}
+
+#[derive(Component)]
+struct MessageText;
+
+#[derive(Component)]
+struct MessagePortrait;
+
+/// Bar counting down the time left on the current page of the message
+#[derive(Component)]
+struct MessageCountdown;
+
+fn page_countdown(seconds: f32) -> EasingComponent<Style> {
+    Style {
+        width: Val::Percent(100.0),
+        height: Val::Px(5.0),
+        ..default()
+    }
+    .ease_to(
+        Style {
+            width: Val::Percent(0.0),
+            height: Val::Px(5.0),
+            ..default()
+        },
+        EaseMethod::Linear,
+        EasingType::Once {
+            duration: Duration::from_secs_f32(seconds),
+        },
+    )
+}
+
+/// Turn the pages of the level message with a click or once they were shown long enough, then move
+/// the message out of the way
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn turn_message_pages(
+    mut commands: Commands,
+    mut panels: Query<(Entity, &mut MessagePages, Ref<Interaction>, &Style)>,
+    mut texts: Query<&mut Text, With<MessageText>>,
+    mut portraits: Query<
+        (&mut UiImage, &mut Style),
+        (With<MessagePortrait>, Without<MessagePages>),
+    >,
+    countdowns: Query<Entity, With<MessageCountdown>>,
+    asset_server: Res<AssetServer>,
+    hud_layout: Res<HudLayout>,
+    motion: Res<ui::MotionPreference>,
+    time: Res<Time<Real>>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+) {
+    for (entity, mut pages, interaction, style) in &mut panels {
+        let clicked = interaction.is_changed() && *interaction == Interaction::Pressed;
+        if !pages.timer.tick(time.delta()).finished() && !clicked {
+            continue;
+        }
+        if clicked {
+            audio_trigger.send(AudioTrigger::Click);
+        }
+        pages.current += 1;
+        let Some(page) = pages.pages.get(pages.current).cloned() else {
+            commands
+                .entity(entity)
+                .remove::<(MessagePages, Interaction)>()
+                .insert(style.clone().ease_to(
+                    hud_layout.place(HudPanel::Message, style),
+                    EaseFunction::QuadraticOut,
+                    EasingType::Once {
+                        duration: motion.duration(1.0),
+                    },
+                ));
+            continue;
+        };
+        pages.timer = Timer::from_seconds(page.duration, TimerMode::Once);
+        for mut text in &mut texts {
+            text.sections[0].value.clone_from(&page.text);
+        }
+        for (mut image, mut style) in &mut portraits {
+            match &page.portrait {
+                Some(path) => {
+                    image.texture = asset_server.load(path);
+                    style.display = Display::Flex;
+                }
+                None => style.display = Display::None,
+            }
+        }
+        for countdown in &countdowns {
+            commands
+                .entity(countdown)
+                .insert(page_countdown(page.duration));
+        }
+    }
+}
+
+/// When the level was started, in real time
+#[derive(Resource)]
+struct SessionStart(Duration);
+
+/// What happened in a level the player left before the end, shown back on the menu
+#[derive(Resource)]
+pub struct SessionSummary {
+    pub duration: Duration,
+    pub treasures: u32,
+    pub lost_hobbits: u32,
+}
+
+/// Marks the static geometry of the level being played. It is kept alive when retrying the same
+/// level, and only despawned when switching level or leaving the game.
+#[derive(Component)]
+struct LevelWorld;
+
+#[derive(Resource)]
+struct RetainedLevel {
+    level: usize,
+    size: (usize, usize),
+    navmesh: polyanya::Mesh,
+}
+
+fn unload_level(mut commands: Commands, worlds: Query<Entity, With<LevelWorld>>) {
+    for entity in &worlds {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<RetainedLevel>();
+}
+
+/// The level is reloaded by going through [`GameState::Reload`]
+fn restart_level(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::InGame);
+}
+
+/// Shortcuts from the key bindings: select a bonus, unselect it, pause, and restart the level
+#[allow(clippy::too_many_arguments)]
+fn keyboard_shortcuts(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    input_map: Res<InputMap>,
+    panels: Query<(&MenuItem, &Children)>,
+    buttons: Query<(Entity, &ButtonAction)>,
+    mut interactions: Query<&mut Interaction>,
+    selected: Query<(Entity, &BackgroundColor), With<SelectedBonus>>,
+    mut pressed: Local<Option<Entity>>,
+    mut next_state: EventWriter<SwitchState>,
+    game: Res<GameInProgress>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+    palette: Res<ui::Palette>,
+) {
+    // buttons react to the change of their interaction, release the one pressed last frame
+    if let Some(entity) = pressed.take() {
+        if let Ok(mut interaction) = interactions.get_mut(entity) {
+            interaction.set_if_neq(Interaction::None);
+        }
+    }
+
+    if input_map.just_pressed(&keyboard, Action::CancelBonus) {
+        for (entity, color) in &selected {
+            unselect_bonus(&mut commands, entity, color, &palette);
+        }
+    }
+
+    if let Some(button) = (0..BONUS_SHORTCUTS)
+        .find(|index| input_map.just_pressed(&keyboard, Action::SelectBonus(*index)))
+        .and_then(|index| {
+            panels
+                .iter()
+                .find(|(kind, _)| **kind == MenuItem::BonusPanel)
+                .and_then(|(_, children)| children.get(index).copied())
+        })
+        // placed bonus can only be removed with the mouse
+        .filter(|button| matches!(buttons.get(*button), Ok((_, ButtonAction::Bonus(_)))))
+    {
+        if let Ok(mut interaction) = interactions.get_mut(button) {
+            *interaction = Interaction::Pressed;
+            *pressed = Some(button);
+        }
+    }
+
+    if input_map.just_pressed(&keyboard, Action::Pause) {
+        if let Some((button, _)) = buttons
+            .iter()
+            .find(|(_, action)| **action == ButtonAction::Pause)
+        {
+            if let Ok(mut interaction) = interactions.get_mut(button) {
+                *interaction = Interaction::Pressed;
+                *pressed = Some(button);
+            }
+        }
+    }
+
+    if input_map.just_pressed(&keyboard, Action::Restart) {
+        audio_trigger.send(AudioTrigger::Start);
+        next_state.send(SwitchState(GameState::Reload));
+        commands.insert_resource(GameInProgress {
+            level: game.level,
+            ..default()
+        });
+    }
+}
+
+/// Right click unselects the selected bonus, or removes the obstacle under the cursor
+#[allow(clippy::too_many_arguments)]
+fn right_click(
+    mut commands: Commands,
+    mouse: Res<ButtonInput<MouseButton>>,
+    selected: Query<(Entity, &BackgroundColor), With<SelectedBonus>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    windows: Query<&Window>,
+    obstacles: Query<(Entity, &Transform), With<SpawnedObstacle>>,
+    buttons: Query<(Entity, &ButtonAction)>,
+    assets: Res<GameAssets>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+    palette: Res<ui::Palette>,
+) {
+    if !mouse.just_pressed(MouseButton::Right) {
+        return;
+    }
+    if !selected.is_empty() {
+        for (entity, color) in &selected {
+            unselect_bonus(&mut commands, entity, color, &palette);
+        }
+        return;
+    }
+
+    let (camera, camera_transform) = camera_query.single();
+    let Some(point) = cursor_cell(camera, camera_transform, windows.single()) else {
+        return;
+    };
+    if point.x < 0.0 || point.z < 0.0 {
+        return;
+    }
+    let cell = (point.x as usize, point.z as usize);
+    let Some(obstacle) = obstacles
+        .iter()
+        .find(|(_, transform)| obstacle_cell(transform) == cell)
+        .map(|(entity, _)| entity)
+    else {
+        return;
+    };
+    let Some((button, bonus)) = buttons.iter().find_map(|(button, action)| match action {
+        ButtonAction::RemoveBonus(bonus, placed) if *placed == obstacle => Some((button, *bonus)),
+        _ => None,
+    }) else {
+        return;
+    };
+    audio_trigger.send(AudioTrigger::ObstacleRemoved);
+    remove_obstacle(&mut commands, &assets, button, bonus, obstacle);
+}
+
+#[allow(clippy::too_many_arguments)]
 fn spawn_message(
     mut commands: Commands,
     assets: Res<GameAssets>,
     mut game: ResMut<GameInProgress>,
     levels: Res<Assets<Level>>,
     mut camera_position: Query<(Entity, &mut Transform), With<Camera>>,
+    retained: Option<Res<RetainedLevel>>,
+    worlds: Query<Entity, With<LevelWorld>>,
+    mut progress_storage: ResMut<PkvStore>,
+    hud_layout: Res<HudLayout>,
+    motion: Res<ui::MotionPreference>,
+    asset_server: Res<AssetServer>,
+    difficulty: Res<DifficultyMode>,
+    new_game_plus: Res<NewGamePlus>,
+    mutators: Res<Mutators>,
 ) {
     info!("Loading screen");
 
-    let level: &Level = levels.get(&assets.levels[game.level]).unwrap();
+    let level = &played_level(
+        levels.get(&assets.levels[game.level]).unwrap(),
+        game.level,
+        &assets,
+        *difficulty,
+        &new_game_plus,
+        &mutators,
+    );
     game.bonus.clone_from(&level.bonus);
+    record_attempt(&mut progress_storage, &level.file);
 
-    let (level_size, mesh) = spawn_level(
-        &mut commands,
-        level,
-        assets.as_ref(),
-        StateScoped(CURRENT_STATE),
-    );
-    let camera_distance = (level_size.0 as f32 * 1.8).max(level_size.1 as f32);
+    let layouts = load_layouts(
+        &progress_storage,
+        &level_layouts_key(&assets, game.level, &level.file),
+    )
+    .into_iter()
+    .map(|mut layout| {
+        for cell in &mut layout.cells {
+            *cell = mutators.map_cell(level, *cell);
+        }
+        layout
+    })
+    .filter(|layout| layout.is_valid_for(level))
+    .collect::<Vec<_>>();
+    let won_version = if !assets.is_campaign(game.level) {
+        last_custom_won_version(&progress_storage, &level.file)
+    } else {
+        last_won_version(&progress_storage, game.level)
+    };
+    let changed_since_win = won_version.map(|hash| hash != level.hash).unwrap_or(false);
+
+    let (level_size, mut mesh) = match retained {
+        Some(retained) if retained.level == game.level => {
+            info!("retrying level {}, keeping it loaded", game.level);
+            (retained.size, retained.navmesh.clone())
+        }
+        _ => {
+            for entity in &worlds {
+                commands.entity(entity).despawn_recursive();
+            }
+            let (level_size, mesh) = spawn_level(&mut commands, level, assets.as_ref(), LevelWorld);
+            commands.insert_resource(RetainedLevel {
+                level: game.level,
+                size: level_size,
+                navmesh: mesh.clone(),
+            });
+            (level_size, mesh)
+        }
+    };
     let (entity, mut transform) = camera_position.single_mut();
     #[cfg(not(feature = "builder"))]
-    if level.message.is_some() {
+    if motion.reduced() {
+        // no fly-in, the level starts on the view used while playing
+        *transform = play_view(level_size);
+    } else if let Some(intro) = CameraIntro::new(&level.intro, play_view(level_size)) {
+        *transform = intro.transform_at(0.0);
+        commands.entity(entity).insert(intro);
+    } else if !level.message.is_empty() {
         *transform = Transform::from_translation(Vec3::new(
             level_size.1 as f32 / 2.0,
             4000.0,
@@ -84,15 +688,7 @@ fn spawn_message(
         commands.entity(entity).insert(
             transform
                 .ease_to(
-                    Transform::from_translation(Vec3::new(
-                        level_size.1 as f32 / 2.0,
-                        camera_distance,
-                        level_size.0 as f32 * 1.2,
-                    ))
-                    .looking_at(
-                        Vec3::new(level_size.1 as f32 / 2.0, 0.0, level_size.0 as f32 / 4.0),
-                        Vec3::Y,
-                    ),
+                    play_view(level_size),
                     EaseFunction::QuadraticInOut,
                     EasingType::Once {
                         duration: Duration::from_secs_f32(8.0),
@@ -110,39 +706,24 @@ fn spawn_message(
             Vec3::new(level_size.1 as f32 / 2.0, 0.0, level_size.0 as f32 / 2.0),
             Vec3::Y,
         );
-        commands.entity(entity).insert(
-            transform.ease_to(
-                Transform::from_translation(Vec3::new(
-                    level_size.1 as f32 / 2.0,
-                    camera_distance,
-                    level_size.0 as f32 * 1.2,
-                ))
-                .looking_at(
-                    Vec3::new(level_size.1 as f32 / 2.0, 0.0, level_size.0 as f32 / 4.0),
-                    Vec3::Y,
-                ),
-                EaseFunction::QuadraticInOut,
-                EasingType::Once {
-                    duration: Duration::from_secs_f32(4.0),
-                },
-            ),
-        );
+        commands.entity(entity).insert(transform.ease_to(
+            play_view(level_size),
+            EaseFunction::QuadraticInOut,
+            EasingType::Once {
+                duration: Duration::from_secs_f32(4.0),
+            },
+        ));
     }
     #[cfg(feature = "builder")]
     {
-        *transform = Transform::from_translation(Vec3::new(
-            level_size.1 as f32 / 2.0,
-            camera_distance,
-            level_size.0 as f32 * 1.2,
-        ))
-        .looking_at(
-            Vec3::new(level_size.1 as f32 / 2.0, 0.0, level_size.0 as f32 / 4.0),
-            Vec3::Y,
-        );
+        *transform = play_view(level_size);
     }
 
     commands.insert_resource(ActiveLevel(level.clone()));
+    commands.insert_resource(IntroPlaying);
+    commands.insert_resource(BlockedCells::new(&mut mesh, level));
     commands.insert_resource(NavMesh(mesh));
+    commands.insert_resource(SavedLayouts(layouts.clone()));
 
     commands
         .spawn((
@@ -172,281 +753,365 @@ fn spawn_message(
                 left: Val::Percent(30.0),
                 ..default()
             };
-            parent
-                .spawn((
-                    NodeBundle {
-                        background_color: palettes::tailwind::GREEN_400.into(),
-                        border_radius: BorderRadius::all(Val::Percent(5.0)),
-                        z_index: ZIndex::Global(1),
-                        style: message_panel_style.clone(),
-                        ..default()
-                    },
-                    if level.message.is_some() {
-                        message_panel_style
-                            .clone()
-                            .ease_to(
-                                Style {
-                                    top: Val::Percent(20.0),
-                                    left: Val::Percent(30.0),
-                                    ..message_panel_style.clone()
-                                },
-                                EaseFunction::QuadraticOut,
-                                EasingType::Once {
-                                    duration: Duration::from_secs_f32(1.0),
-                                },
-                            )
-                            .ease_to(
-                                Style {
-                                    width: Val::Percent(70.0),
-                                    height: Val::Percent(20.0),
-                                    top: Val::Percent(0.0),
-                                    left: Val::Percent(30.0),
-                                    ..message_panel_style.clone()
-                                },
-                                EaseFunction::QuadraticOut,
-                                EasingType::Once {
-                                    duration: Duration::from_secs_f32(1.0),
-                                },
-                            )
-                            .delay(Duration::from_secs_f32(6.0))
+            let mut panel = parent.spawn((
+                NodeBundle {
+                    border_radius: BorderRadius::all(Val::Percent(5.0)),
+                    z_index: ZIndex::Global(1),
+                    style: message_panel_style.clone(),
+                    ..default()
+                },
+                ui::Panel,
+                if level.message.is_empty() {
+                    Style {
+                        top: Val::Percent(-100.0),
+                        ..hud_layout.place(HudPanel::Title, &message_panel_style)
+                    }
+                    .ease_to(
+                        hud_layout.place(HudPanel::Title, &message_panel_style),
+                        EaseFunction::QuadraticOut,
+                        EasingType::Once {
+                            duration: motion.duration(1.0),
+                        },
+                    )
+                } else {
+                    // moved out of the way once its pages are turned
+                    Style {
+                        top: Val::Percent(-100.0),
+                        ..hud_layout.place(HudPanel::MessageShown, &message_panel_style)
+                    }
+                    .ease_to(
+                        hud_layout.place(HudPanel::MessageShown, &message_panel_style),
+                        EaseFunction::QuadraticOut,
+                        EasingType::Once {
+                            duration: motion.duration(1.0),
+                        },
+                    )
+                },
+                Anchored {
+                    panel: if level.message.is_empty() {
+                        HudPanel::Title
                     } else {
-                        Style {
-                            width: Val::Percent(30.0),
-                            height: Val::Percent(3.0),
-                            left: Val::Percent(70.0),
-                            ..message_panel_style.clone()
-                        }
-                        .ease_to(
-                            Style {
-                                top: Val::Percent(0.0),
-                                left: Val::Percent(70.0),
-                                width: Val::Percent(30.0),
-                                height: Val::Percent(3.0),
-                                ..message_panel_style.clone()
-                            },
-                            EaseFunction::QuadraticOut,
-                            EasingType::Once {
-                                duration: Duration::from_secs_f32(1.0),
-                            },
-                        )
-                        .ease_to(
-                            Style {
-                                width: Val::Percent(30.0),
-                                height: Val::Percent(3.0),
-                                top: Val::Percent(0.0),
-                                left: Val::Percent(70.0),
-                                ..message_panel_style.clone()
-                            },
-                            EaseFunction::QuadraticOut,
-                            EasingType::Once {
-                                duration: Duration::from_secs_f32(1.0),
-                            },
-                        )
+                        HudPanel::Message
                     },
-                    MenuItem::Panel,
-                ))
-                .with_children(|parent| {
-                    if let Some(message) = level.message.as_ref() {
-                        parent.spawn(TextBundle {
-                            text: Text::from_section(
-                                message.clone(),
-                                TextStyle {
-                                    font_size: 20.0,
-                                    color: Color::WHITE,
+                    layout: *hud_layout,
+                },
+                MenuItem::Panel,
+            ));
+            if let Some(page) = level.message.first() {
+                panel.insert((
+                    MessagePages {
+                        pages: level.message.clone(),
+                        current: 0,
+                        timer: Timer::from_seconds(page.duration, TimerMode::Once),
+                    },
+                    // to turn pages with a click
+                    Interaction::default(),
+                ));
+            }
+            panel.with_children(|parent| {
+                parent.spawn(TextBundle {
+                    text: Text::from_sections(level.title_sections(game.level, 20.0)),
+                    ..default()
+                });
+                if let Some(page) = level.message.first() {
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                align_items: AlignItems::Center,
+                                column_gap: Val::Px(15.0),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            parent.spawn((
+                                ImageBundle {
+                                    style: Style {
+                                        width: Val::Px(96.0),
+                                        height: Val::Px(96.0),
+                                        flex_shrink: 0.0,
+                                        display: if page.portrait.is_some() {
+                                            Display::Flex
+                                        } else {
+                                            Display::None
+                                        },
+                                        ..default()
+                                    },
+                                    image: page
+                                        .portrait
+                                        .as_ref()
+                                        .map(|path| UiImage::new(asset_server.load(path)))
+                                        .unwrap_or_default(),
                                     ..default()
                                 },
-                            ),
-                            ..default()
-                        });
-
-                        parent.spawn((
-                            NodeBundle {
-                                style: Style {
-                                    width: Val::Percent(100.0),
-                                    height: Val::Px(5.0),
+                                MessagePortrait,
+                            ));
+                            parent.spawn((
+                                TextBundle {
+                                    text: Text::from_section(
+                                        page.text.clone(),
+                                        TextStyle {
+                                            font_size: 20.0,
+                                            color: Color::WHITE,
+                                            ..default()
+                                        },
+                                    ),
                                     ..default()
                                 },
-                                background_color: palettes::tailwind::INDIGO_800.into(),
-                                ..default()
-                            },
-                            Style {
+                                MessageText,
+                            ));
+                        });
+
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
                                 width: Val::Percent(100.0),
                                 height: Val::Px(5.0),
                                 ..default()
-                            }
-                            .ease_to(
-                                Style {
-                                    width: Val::Percent(0.0),
-                                    height: Val::Px(5.0),
-                                    ..default()
-                                },
-                                EaseMethod::Linear,
-                                EasingType::Once {
-                                    duration: Duration::from_secs_f32(6.0),
-                                },
-                            ),
-                        ));
-                    }
+                            },
+                            background_color: palettes::tailwind::INDIGO_800.into(),
+                            ..default()
+                        },
+                        page_countdown(page.duration),
+                        MessageCountdown,
+                    ));
+                }
 
-                    let button_style = Style {
-                        width: Val::Px(150.0),
-                        height: Val::Px(30.0),
-                        border: UiRect::all(Val::Px(3.0)),
-                        align_items: AlignItems::Center,
-                        justify_content: JustifyContent::Center,
-                        margin: UiRect::top(Val::Px(20.0)),
-                        justify_self: JustifySelf::End,
-                        position_type: PositionType::Absolute,
-                        bottom: Val::Px(10.0),
-                        right: Val::Px(10.0),
-                        ..default()
-                    };
+                let button_style = Style {
+                    width: Val::Px(150.0 * BUTTON_SCALE),
+                    height: Val::Px(30.0 * BUTTON_SCALE),
+                    border: UiRect::all(Val::Px(3.0)),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    margin: UiRect::top(Val::Px(20.0)),
+                    justify_self: JustifySelf::End,
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..default()
+                };
 
-                    parent
-                        .spawn((
-                            ButtonBundle {
-                                background_color: palettes::tailwind::INDIGO_800.into(),
-                                border_radius: BorderRadius::all(Val::Percent(10.0)),
-                                border_color: BorderColor(palettes::tailwind::INDIGO_400.into()),
-                                style: button_style.clone(),
-                                ..default()
-                            },
-                            button_style.clone().ease_to(
-                                Style {
-                                    border: UiRect::all(Val::Px(5.0)),
-                                    ..button_style.clone()
-                                },
-                                EaseFunction::QuadraticInOut,
-                                EasingType::PingPong {
-                                    duration: Duration::from_secs_f32(1.0),
-                                    pause: Some(Duration::from_secs_f32(0.5)),
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            border_radius: BorderRadius::all(Val::Percent(10.0)),
+                            border_color: BorderColor(palettes::tailwind::INDIGO_400.into()),
+                            style: button_style,
+                            ..default()
+                        },
+                        ui::Pulse::default(),
+                        MenuItem::Button,
+                        ui::PaletteButton,
+                        ButtonAction::Back,
+                    ))
+                    .with_children(|p| {
+                        p.spawn(TextBundle {
+                            text: Text::from_section(
+                                "Back to Menu",
+                                TextStyle {
+                                    font_size: 18.0,
+                                    ..default()
                                 },
                             ),
-                            MenuItem::Button,
-                            ButtonAction::Back,
-                        ))
-                        .with_children(|p| {
-                            p.spawn(TextBundle {
-                                text: Text::from_section(
-                                    "Back to Menu",
-                                    TextStyle {
-                                        font_size: 18.0,
-                                        ..default()
-                                    },
-                                ),
-                                ..default()
-                            });
+                            ..default()
                         });
-                });
+                    });
+            });
 
             {
                 let progress_panel_style = Style {
-                    flex_direction: FlexDirection::Column,
-                    justify_content: JustifyContent::Center,
-                    align_items: AlignItems::Center,
-                    padding: UiRect::all(Val::Percent(5.0)),
-                    width: Val::Percent(20.0),
-                    height: Val::Percent(40.0),
-                    position_type: PositionType::Absolute,
-                    top: Val::Percent(0.0),
                     left: Val::Percent(-50.0),
-                    ..default()
+                    ..hud_layout.place(
+                        HudPanel::Progress,
+                        &Style {
+                            flex_direction: FlexDirection::Column,
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            padding: UiRect::all(Val::Percent(5.0)),
+                            position_type: PositionType::Absolute,
+                            ..default()
+                        },
+                    )
                 };
                 parent
                     .spawn((
                         NodeBundle {
-                            background_color: palettes::tailwind::GREEN_400.into(),
                             border_radius: BorderRadius::all(Val::Percent(5.0)),
                             z_index: ZIndex::Global(1),
                             style: progress_panel_style.clone(),
                             ..default()
                         },
+                        ui::Panel,
                         progress_panel_style
                             .clone()
                             .ease_to(
-                                Style {
-                                    left: Val::Percent(0.0),
-                                    ..progress_panel_style.clone()
-                                },
+                                hud_layout.place(HudPanel::Progress, &progress_panel_style),
                                 EaseFunction::QuadraticOut,
                                 EasingType::Once {
-                                    duration: Duration::from_secs_f32(1.0),
+                                    duration: motion.duration(1.0),
                                 },
                             )
-                            .delay(Duration::from_secs_f32(if level.message.is_some() {
-                                6.0
-                            } else {
-                                0.0
-                            })),
+                            .delay(Duration::from_secs_f32(level.message_duration())),
+                        Anchored {
+                            panel: HudPanel::Progress,
+                            layout: *hud_layout,
+                        },
                         MenuItem::Panel,
                     ))
                     .with_children(|parent| {
+                        let capacity = level.spawns.iter().map(|spawn| spawn.hobbits).sum::<u32>();
                         parent.spawn((
-                            TextBundle {
-                                text: Text::from_sections([
-                                    TextSection {
-                                        value: "Treasures: ".to_string(),
-                                        style: TextStyle {
-                                            font_size: 20.0,
-                                            color: Color::WHITE,
-                                            ..default()
-                                        },
-                                    },
-                                    TextSection {
-                                        value: "0".to_string(),
-                                        style: TextStyle {
-                                            font_size: 20.0,
-                                            color: Color::WHITE,
-                                            ..default()
-                                        },
-                                    },
-                                    TextSection {
-                                        value: format!(" / {}", level.treasures),
-                                        style: TextStyle {
-                                            font_size: 20.0,
-                                            color: Color::WHITE,
-                                            ..default()
-                                        },
-                                    },
-                                ]),
-                                ..default()
-                            },
-                            StatusText::Treasures,
+                            status_text(
+                                "Hobbits: ",
+                                Color::WHITE,
+                                "0".to_string(),
+                                format!(" / {}", capacity),
+                            ),
+                            StatusText::HobbitsAlive,
+                        ));
+                        parent.spawn((
+                            status_text("Spawned: ", Color::WHITE, "0".to_string(), String::new()),
+                            StatusText::HobbitsSpawned,
+                        ));
+                        parent.spawn((
+                            status_text(
+                                "Next: ",
+                                Color::WHITE,
+                                upcoming_spawns(&[]),
+                                String::new(),
+                            ),
+                            StatusText::NextSpawns,
                         ));
-                        if let Some(max_lost) = level.losts {
+                        for objective in &level.objectives {
+                            match objective {
+                                Objective::Treasures(treasures) => {
+                                    for (index, team) in level.teams.iter().enumerate() {
+                                        let color = level
+                                            .team_color((team.start.1, team.start.2))
+                                            .map(Color::from)
+                                            .unwrap_or(Color::WHITE);
+                                        parent.spawn((
+                                            status_text(
+                                                "Treasures: ",
+                                                color,
+                                                "0".to_string(),
+                                                format!(" / {}", treasures),
+                                            ),
+                                            StatusText::Treasures(index),
+                                        ));
+                                        parent
+                                            .spawn(NodeBundle {
+                                                style: Style {
+                                                    width: Val::Percent(90.0),
+                                                    height: Val::Px(6.0),
+                                                    margin: UiRect::bottom(Val::Px(4.0)),
+                                                    ..default()
+                                                },
+                                                background_color: palettes::tailwind::GREEN_700
+                                                    .into(),
+                                                border_radius: BorderRadius::all(Val::Px(3.0)),
+                                                ..default()
+                                            })
+                                            .with_children(|parent| {
+                                                parent.spawn((
+                                                    NodeBundle {
+                                                        style: Style {
+                                                            width: Val::Percent(0.0),
+                                                            height: Val::Percent(100.0),
+                                                            ..default()
+                                                        },
+                                                        background_color: color.into(),
+                                                        border_radius: BorderRadius::all(Val::Px(
+                                                            3.0,
+                                                        )),
+                                                        ..default()
+                                                    },
+                                                    TreasureBar {
+                                                        team: index,
+                                                        target: *treasures,
+                                                    },
+                                                ));
+                                            });
+                                    }
+                                }
+                                Objective::MaxLosses(max_lost) => {
+                                    parent.spawn((
+                                        status_text(
+                                            "Lost Hobbits: ",
+                                            Color::WHITE,
+                                            "0".to_string(),
+                                            format!(" / {}", max_lost),
+                                        ),
+                                        StatusText::HobbitsLost,
+                                    ));
+                                }
+                                Objective::Survive(seconds) => {
+                                    parent.spawn((
+                                        status_text(
+                                            "Survive: ",
+                                            Color::WHITE,
+                                            "0".to_string(),
+                                            format!(" / {}s", seconds),
+                                        ),
+                                        StatusText::Elapsed,
+                                    ));
+                                }
+                                Objective::TimeLimit(seconds) => {
+                                    parent.spawn((
+                                        status_text(
+                                            "Time left: ",
+                                            Color::WHITE,
+                                            format!("{}", seconds.ceil() as u32),
+                                            "s".to_string(),
+                                        ),
+                                        StatusText::TimeLeft,
+                                    ));
+                                }
+                                Objective::Escort(rank) => {
+                                    parent.spawn((
+                                        status_text(
+                                            &format!("Escort hobbit #{}: ", rank),
+                                            palettes::tailwind::AMBER_300.into(),
+                                            escort_status(None).to_string(),
+                                            String::new(),
+                                        ),
+                                        StatusText::Escort,
+                                    ));
+                                }
+                            }
+                        }
+                        if level.throttle {
                             parent.spawn((
                                 TextBundle {
-                                    text: Text::from_sections([
-                                        TextSection {
-                                            value: "Lost Hobbits: ".to_string(),
-                                            style: TextStyle {
-                                                font_size: 20.0,
-                                                color: Color::WHITE,
-                                                ..default()
-                                            },
-                                        },
-                                        TextSection {
-                                            value: "0".to_string(),
-                                            style: TextStyle {
-                                                font_size: 20.0,
-                                                color: Color::WHITE,
-                                                ..default()
-                                            },
-                                        },
-                                        TextSection {
-                                            value: format!(" / {}", max_lost),
-                                            style: TextStyle {
-                                                font_size: 20.0,
-                                                color: Color::WHITE,
-                                                ..default()
-                                            },
+                                    text: Text::from_section(
+                                        "Holding spawns",
+                                        TextStyle {
+                                            font_size: 16.0,
+                                            color: palettes::tailwind::AMBER_300.into(),
+                                            ..default()
                                         },
-                                    ]),
+                                    ),
+                                    visibility: Visibility::Hidden,
                                     ..default()
                                 },
-                                StatusText::HobbitsLost,
+                                HeldSpawnsText,
                             ));
                         }
+                        parent.spawn(TextBundle {
+                            text: Text::from_section(
+                                if changed_since_win {
+                                    format!("Version {} (changed since your win)", level.version())
+                                } else {
+                                    format!("Version {}", level.version())
+                                },
+                                TextStyle {
+                                    font_size: 12.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                },
+                            ),
+                            ..default()
+                        });
                         if let Some(goal) = &level.goal {
                             parent.spawn(TextBundle {
                                 text: Text::from_section(
@@ -475,8 +1140,8 @@ fn spawn_message(
                             .with_children(|parent| {
                                 for bonus in &game.bonus {
                                     let button_style = Style {
-                                        width: Val::Px(50.0),
-                                        height: Val::Px(50.0),
+                                        width: Val::Px(50.0 * BUTTON_SCALE),
+                                        height: Val::Px(50.0 * BUTTON_SCALE),
                                         border: UiRect::all(Val::Px(2.0)),
                                         align_items: AlignItems::Center,
                                         justify_content: JustifyContent::Center,
@@ -487,42 +1152,153 @@ fn spawn_message(
                                     parent
                                         .spawn((
                                             ButtonBundle {
-                                                background_color: palettes::tailwind::INDIGO_800
-                                                    .into(),
                                                 border_radius: BorderRadius::all(Val::Percent(
                                                     10.0,
                                                 )),
                                                 border_color: BorderColor(
                                                     palettes::tailwind::INDIGO_400.into(),
                                                 ),
-                                                style: button_style.clone(),
+                                                style: button_style,
+                                                ..default()
+                                            },
+                                            ui::Pulse {
+                                                border: 6.0,
+                                                duration: 0.5,
+                                                pause: None,
                                                 ..default()
                                             },
-                                            button_style.clone().ease_to(
-                                                Style {
-                                                    border: UiRect::all(Val::Px(6.0)),
-                                                    ..button_style.clone()
-                                                },
-                                                EaseFunction::QuadraticInOut,
-                                                EasingType::PingPong {
-                                                    duration: Duration::from_secs_f32(0.5),
-                                                    pause: None,
-                                                },
-                                            ),
                                             MenuItem::Button,
+                                            ui::PaletteButton,
                                             ButtonAction::Bonus(*bonus),
+                                            AccessibleName(format!(
+                                                "Place {}",
+                                                assets.bonus_name(*bonus)
+                                            )),
                                         ))
                                         .with_children(|p| {
                                             p.spawn(ImageBundle {
-                                                image: UiImage::new(match bonus {
-                                                    Bonus::Obstacle => assets.icon_obstacle.clone(),
-                                                }),
+                                                image: UiImage::new(assets.bonus_icon(*bonus)),
                                                 style: Style {
-                                                    width: Val::Px(40.0),
-                                                    height: Val::Px(40.0),
+                                                    width: Val::Px(40.0 * BUTTON_SCALE),
+                                                    height: Val::Px(40.0 * BUTTON_SCALE),
                                                     ..default()
                                                 },
 
+                                                ..default()
+                                            });
+                                        });
+                                }
+                            });
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::Row,
+                                    flex_wrap: FlexWrap::Wrap,
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                for (index, layout) in layouts.iter().enumerate() {
+                                    parent
+                                        .spawn((
+                                            ButtonBundle {
+                                                border_radius: BorderRadius::all(Val::Percent(
+                                                    10.0,
+                                                )),
+                                                border_color: BorderColor(
+                                                    palettes::tailwind::INDIGO_400.into(),
+                                                ),
+                                                style: Style {
+                                                    height: Val::Px(30.0 * BUTTON_SCALE),
+                                                    border: UiRect::all(Val::Px(2.0)),
+                                                    padding: UiRect::horizontal(Val::Px(5.0)),
+                                                    align_items: AlignItems::Center,
+                                                    justify_content: JustifyContent::Center,
+                                                    margin: UiRect::all(Val::Px(5.0)),
+                                                    ..default()
+                                                },
+                                                ..default()
+                                            },
+                                            MenuItem::Button,
+                                            ui::PaletteButton,
+                                            ButtonAction::ApplyLayout(index),
+                                        ))
+                                        .with_children(|p| {
+                                            p.spawn(TextBundle {
+                                                text: Text::from_section(
+                                                    if layout.is_stale(level) {
+                                                        format!("{} (old)", layout.name)
+                                                    } else {
+                                                        layout.name.clone()
+                                                    },
+                                                    TextStyle {
+                                                        font_size: 16.0,
+                                                        ..default()
+                                                    },
+                                                ),
+                                                ..default()
+                                            });
+                                        });
+                                }
+                            });
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::Row,
+                                    margin: UiRect::top(Val::Px(10.0)),
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                for (action, label) in [
+                                    (ButtonAction::Pause, "Pause"),
+                                    (ButtonAction::Speed(1), "1x"),
+                                    (ButtonAction::Speed(2), "2x"),
+                                    (ButtonAction::Speed(4), "4x"),
+                                    #[cfg(not(target_arch = "wasm32"))]
+                                    (ButtonAction::ExportPicture, "Picture"),
+                                ] {
+                                    let color = if action == ButtonAction::Speed(1) {
+                                        palettes::tailwind::AMBER_300.into()
+                                    } else {
+                                        Color::WHITE
+                                    };
+                                    parent
+                                        .spawn((
+                                            ButtonBundle {
+                                                border_radius: BorderRadius::all(Val::Percent(
+                                                    10.0,
+                                                )),
+                                                border_color: BorderColor(
+                                                    palettes::tailwind::INDIGO_400.into(),
+                                                ),
+                                                style: Style {
+                                                    height: Val::Px(30.0 * BUTTON_SCALE),
+                                                    border: UiRect::all(Val::Px(2.0)),
+                                                    padding: UiRect::horizontal(Val::Px(5.0)),
+                                                    align_items: AlignItems::Center,
+                                                    justify_content: JustifyContent::Center,
+                                                    margin: UiRect::all(Val::Px(3.0)),
+                                                    ..default()
+                                                },
+                                                ..default()
+                                            },
+                                            MenuItem::Button,
+                                            ui::PaletteButton,
+                                            action,
+                                        ))
+                                        .with_children(|p| {
+                                            p.spawn(TextBundle {
+                                                text: Text::from_section(
+                                                    label,
+                                                    TextStyle {
+                                                        font_size: 16.0,
+                                                        color,
+                                                        ..default()
+                                                    },
+                                                ),
                                                 ..default()
                                             });
                                         });
@@ -533,27 +1309,33 @@ fn spawn_message(
 
             {
                 let blocked_panel_style = Style {
-                    flex_direction: FlexDirection::Column,
-                    justify_content: JustifyContent::Center,
-                    align_items: AlignItems::Center,
-                    padding: UiRect::all(Val::Percent(5.0)),
-                    width: Val::Percent(30.0),
-                    height: Val::Percent(40.0),
-                    position_type: PositionType::Absolute,
-                    bottom: Val::Percent(0.0),
                     right: Val::Percent(-50.0),
-                    ..default()
+                    ..hud_layout.place(
+                        HudPanel::Blocked,
+                        &Style {
+                            flex_direction: FlexDirection::Column,
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            padding: UiRect::all(Val::Percent(5.0)),
+                            position_type: PositionType::Absolute,
+                            ..default()
+                        },
+                    )
                 };
 
                 parent
                     .spawn((
                         NodeBundle {
-                            background_color: palettes::tailwind::GREEN_400.into(),
                             border_radius: BorderRadius::all(Val::Percent(5.0)),
                             z_index: ZIndex::Global(1),
                             style: blocked_panel_style.clone(),
                             ..default()
                         },
+                        ui::Panel,
+                        Anchored {
+                            panel: HudPanel::Blocked,
+                            layout: *hud_layout,
+                        },
                         MenuItem::BlockedPanel,
                     ))
                     .with_children(|parent| {
@@ -583,21 +1365,6 @@ fn spawn_message(
         });
 }
 
-#[derive(Component, Default, Clone)]
-struct ImageColor {
-    color: Srgba,
-}
-
-impl bevy_easings::Lerp for ImageColor {
-    type Scalar = f32;
-
-    fn lerp(&self, other: &Self, scalar: &Self::Scalar) -> Self {
-        ImageColor {
-            color: self.color.mix(&other.color, *scalar),
-        }
-    }
-}
-
 #[derive(Component, PartialEq, Eq)]
 enum MenuItem {
     Root,
@@ -608,10 +1375,17 @@ enum MenuItem {
 }
 
 #[derive(Component, PartialEq, Eq)]
-enum ButtonAction {
+pub(crate) enum ButtonAction {
     Back,
     Bonus(Bonus),
     RemoveBonus(Bonus, Entity),
+    ApplyLayout(usize),
+    /// Run the simulation this many times faster
+    Speed(u32),
+    Pause,
+    /// Save a picture of the level with the placed obstacles
+    #[cfg(not(target_arch = "wasm32"))]
+    ExportPicture,
 }
 
 #[allow(clippy::type_complexity)]
@@ -624,11 +1398,10 @@ fn button_system(
         &ButtonAction,
         Option<&SelectedBonus>,
     )>,
-    mut next_state: EventWriter<SwitchState>,
-    ui_items: Query<(Entity, &MenuItem, &Style)>,
-    camera_position: Query<(Entity, &Transform), With<Camera>>,
+    mut ask_confirmation: EventWriter<AskConfirmation>,
     assets: Res<GameAssets>,
     mut audio_trigger: EventWriter<AudioTrigger>,
+    palette: Res<ui::Palette>,
 ) {
     for (interaction, color, entity, action, selected) in &interaction_query {
         if !interaction.is_changed() {
@@ -642,164 +1415,162 @@ fn button_system(
                 ButtonAction::Back => {
                     audio_trigger.send(AudioTrigger::Click);
 
-                    next_state.send(SwitchState(GameState::Menu));
-
-                    let (entity, transform) = camera_position.single();
-                    commands.entity(entity).insert(transform.ease_to(
-                        Transform::from_translation(Vec3::new(0.0, 50.0, 0.0)),
-                        EaseFunction::QuadraticInOut,
-                        EasingType::Once {
-                            duration: Duration::from_secs_f32(1.0),
-                        },
-                    ));
-
-                    for (entity, kind, style) in &ui_items {
-                        if *kind == MenuItem::Panel {
-                            commands.entity(entity).insert(style.clone().ease_to(
-                                Style {
-                                    top: Val::Percent(-50.0),
-                                    ..style.clone()
-                                },
-                                EaseFunction::QuadraticOut,
-                                EasingType::Once {
-                                    duration: Duration::from_secs_f32(1.0),
-                                },
-                            ));
-                        }
-                        if *kind == MenuItem::BlockedPanel {
-                            commands.entity(entity).insert(style.clone().ease_to(
-                                Style {
-                                    right: Val::Percent(-50.0),
-                                    ..style.clone()
-                                },
-                                EaseFunction::QuadraticOut,
-                                EasingType::Once {
-                                    duration: Duration::from_secs_f32(1.0),
-                                },
-                            ));
-                        }
-                    }
+                    ask_confirmation.send(AskConfirmation {
+                        message: "Progress in this level will be lost".to_string(),
+                        confirm: "Back to Menu".to_string(),
+                        action: ConfirmedAction::LeaveLevel,
+                    });
 
-                    commands.entity(entity).insert(color.ease_to(
-                        BUTTON_HOVERED,
-                        EaseFunction::QuadraticInOut,
-                        EasingType::Once {
-                            duration: Duration::from_secs_f32(0.25),
-                        },
-                    ));
+                    ui::ease_color(&mut commands, entity, color, palette.button_hovered);
                 }
                 ButtonAction::Bonus(_) => {
                     audio_trigger.send(AudioTrigger::Click);
 
                     if selected.is_none() {
-                        commands.entity(entity).insert((
-                            color.ease_to(
-                                BUTTON_SELECTED,
-                                EaseFunction::QuadraticInOut,
-                                EasingType::Once {
-                                    duration: Duration::from_secs_f32(0.25),
-                                },
-                            ),
-                            SelectedBonus,
-                        ));
-                        for (_, _, entity, _, selected) in &interaction_query {
+                        ui::ease_color(&mut commands, entity, color, palette.button_selected);
+                        commands.entity(entity).insert(SelectedBonus);
+                        for (_, color, entity, _, selected) in &interaction_query {
                             if selected.is_some() {
-                                commands
-                                    .entity(entity)
-                                    .insert(color.ease_to(
-                                        BUTTON_IDLE,
-                                        EaseFunction::QuadraticInOut,
-                                        EasingType::Once {
-                                            duration: Duration::from_secs_f32(0.25),
-                                        },
-                                    ))
-                                    .remove::<SelectedBonus>();
+                                unselect_bonus(&mut commands, entity, color, &palette);
                             }
                         }
                     } else {
-                        commands
-                            .entity(entity)
-                            .insert(color.ease_to(
-                                BUTTON_HOVERED,
-                                EaseFunction::QuadraticInOut,
-                                EasingType::Once {
-                                    duration: Duration::from_secs_f32(0.25),
-                                },
-                            ))
-                            .remove::<SelectedBonus>();
+                        ui::ease_color(&mut commands, entity, color, palette.button_hovered);
+                        commands.entity(entity).remove::<SelectedBonus>();
                     }
                 }
-                ButtonAction::RemoveBonus(original_bonus, to_remove) => {
+                ButtonAction::ApplyLayout(_) | ButtonAction::Speed(_) | ButtonAction::Pause => {
                     audio_trigger.send(AudioTrigger::Click);
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                ButtonAction::ExportPicture => {
+                    audio_trigger.send(AudioTrigger::Click);
+                }
+                ButtonAction::RemoveBonus(original_bonus, to_remove) => {
+                    audio_trigger.send(AudioTrigger::ObstacleRemoved);
 
-                    commands.entity(*to_remove).despawn_recursive();
-                    commands
-                        .entity(entity)
-                        .despawn_descendants()
-                        .insert(ButtonAction::Bonus(*original_bonus))
-                        .with_children(|p| {
-                            p.spawn(ImageBundle {
-                                image: UiImage::new(match original_bonus {
-                                    Bonus::Obstacle => assets.icon_obstacle.clone(),
-                                }),
-                                style: Style {
-                                    width: Val::Px(40.0),
-                                    height: Val::Px(40.0),
-                                    ..default()
-                                },
-
-                                ..default()
-                            });
-                        });
+                    remove_obstacle(&mut commands, &assets, entity, *original_bonus, *to_remove);
                 }
             },
             Interaction::Hovered => {
                 if selected.is_some() {
                     continue;
                 }
-                commands.entity(entity).insert(color.ease_to(
-                    BUTTON_HOVERED,
-                    EaseFunction::QuadraticInOut,
-                    EasingType::Once {
-                        duration: Duration::from_secs_f32(0.25),
-                    },
-                ));
+                ui::ease_color(&mut commands, entity, color, palette.button_hovered);
             }
             Interaction::None => {
                 if selected.is_some() {
                     continue;
                 }
-                if matches!(action, ButtonAction::RemoveBonus(_, _)) {
-                    commands.entity(entity).insert(color.ease_to(
-                        BUTTON_IDLE_REMOVE,
-                        EaseFunction::QuadraticInOut,
-                        EasingType::Once {
-                            duration: Duration::from_secs_f32(0.25),
-                        },
-                    ));
+                let idle = if matches!(action, ButtonAction::RemoveBonus(_, _)) {
+                    BUTTON_IDLE_REMOVE
                 } else {
-                    commands.entity(entity).insert(color.ease_to(
-                        BUTTON_IDLE,
-                        EaseFunction::QuadraticInOut,
-                        EasingType::Once {
-                            duration: Duration::from_secs_f32(0.25),
-                        },
-                    ));
-                }
+                    palette.button_idle
+                };
+                ui::ease_color(&mut commands, entity, color, idle);
             }
         }
     }
 }
 
+/// Go back to the menu once the player confirmed leaving the level
+fn leave_level(
+    mut commands: Commands,
+    mut confirmed: EventReader<Confirmed>,
+    mut next_state: EventWriter<SwitchState>,
+    ui_items: Query<(Entity, &MenuItem, &Style)>,
+    camera_position: Query<(Entity, &Transform), With<Camera>>,
+) {
+    if !confirmed
+        .read()
+        .any(|confirmed| confirmed.0 == ConfirmedAction::LeaveLevel)
+    {
+        return;
+    }
+    next_state.send(SwitchState(GameState::Menu));
+
+    let (entity, transform) = camera_position.single();
+    commands.entity(entity).insert(transform.ease_to(
+        Transform::from_translation(Vec3::new(0.0, 50.0, 0.0)),
+        EaseFunction::QuadraticInOut,
+        EasingType::Once {
+            duration: Duration::from_secs_f32(1.0),
+        },
+    ));
+
+    for (entity, kind, style) in &ui_items {
+        if *kind == MenuItem::Panel {
+            commands.entity(entity).insert(style.clone().ease_to(
+                Style {
+                    top: Val::Percent(-50.0),
+                    ..style.clone()
+                },
+                EaseFunction::QuadraticOut,
+                EasingType::Once {
+                    duration: Duration::from_secs_f32(1.0),
+                },
+            ));
+        }
+        if *kind == MenuItem::BlockedPanel {
+            commands.entity(entity).insert(style.clone().ease_to(
+                Style {
+                    right: Val::Percent(-50.0),
+                    ..style.clone()
+                },
+                EaseFunction::QuadraticOut,
+                EasingType::Once {
+                    duration: Duration::from_secs_f32(1.0),
+                },
+            ));
+        }
+    }
+}
+
 #[derive(Component)]
-struct SelectedBonus;
+pub(crate) struct SelectedBonus;
+
+fn unselect_bonus(
+    commands: &mut Commands,
+    button: Entity,
+    color: &BackgroundColor,
+    palette: &ui::Palette,
+) {
+    ui::ease_color(commands, button, color, palette.button_idle);
+    commands.entity(button).remove::<SelectedBonus>();
+}
+
+/// Remove a placed obstacle, giving the bonus back to its button
+fn remove_obstacle(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    button: Entity,
+    bonus: Bonus,
+    obstacle: Entity,
+) {
+    commands.entity(obstacle).despawn_recursive();
+    commands
+        .entity(button)
+        .despawn_descendants()
+        .insert((
+            ButtonAction::Bonus(bonus),
+            AccessibleName(format!("Place {}", assets.bonus_name(bonus))),
+        ))
+        .with_children(|p| {
+            p.spawn(ImageBundle {
+                image: UiImage::new(assets.bonus_icon(bonus)),
+                style: Style {
+                    width: Val::Px(40.0 * BUTTON_SCALE),
+                    height: Val::Px(40.0 * BUTTON_SCALE),
+                    ..default()
+                },
+
+                ..default()
+            });
+        });
+}
 
-const BUTTON_IDLE: BackgroundColor = BackgroundColor(Color::Srgba(palettes::tailwind::INDIGO_800));
 const BUTTON_IDLE_REMOVE: BackgroundColor =
     BackgroundColor(Color::Srgba(palettes::tailwind::GRAY_600));
-const BUTTON_HOVERED: BackgroundColor =
-    BackgroundColor(Color::Srgba(palettes::tailwind::AMBER_600));
-const BUTTON_SELECTED: BackgroundColor = BackgroundColor(Color::Srgba(palettes::tailwind::SKY_300));
 
 pub fn change_state_after_event(
     mut commands: Commands,
@@ -821,19 +1592,226 @@ pub fn change_state_after_event(
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Component)]
 enum StatusText {
-    Treasures,
+    Treasures(usize),
+    /// Hobbits currently in the level
+    HobbitsAlive,
+    HobbitsSpawned,
+    /// Hobbits about to come out, and when
+    NextSpawns,
     HobbitsLost,
+    Elapsed,
+    TimeLeft,
+    Escort,
+}
+
+/// Bar filling up as a team brings treasures back home, toward the treasures objective
+#[derive(Component)]
+struct TreasureBar {
+    team: usize,
+    target: u32,
+}
+
+/// A line of the progress panel, with a label, a value updated during the game and a target
+fn status_text(label: &str, label_color: Color, value: String, target: String) -> TextBundle {
+    TextBundle {
+        text: Text::from_sections([
+            TextSection {
+                value: label.to_string(),
+                style: TextStyle {
+                    font_size: 20.0,
+                    color: label_color,
+                    ..default()
+                },
+            },
+            TextSection {
+                value,
+                style: TextStyle {
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            },
+            TextSection {
+                value: target,
+                style: TextStyle {
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            },
+        ]),
+        ..default()
+    }
+}
+
+/// Upcoming spawns shown in the progress panel
+const UPCOMING_SPAWNS: usize = 3;
+
+/// The next spawns as `hobbits in seconds`, soonest first
+fn upcoming_spawns(upcoming: &[(u32, f32)]) -> String {
+    if upcoming.is_empty() {
+        return "-".to_string();
+    }
+    upcoming
+        .iter()
+        .take(UPCOMING_SPAWNS)
+        .map(|(hobbits, seconds)| format!("{} in {}s", hobbits, seconds.ceil() as u32))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Hobbits waiting at each spawn point, with the bursts they will come out in
+fn display_upcoming_spawns(
+    level: Res<ActiveLevel>,
+    spawner: Res<Spawner>,
+    hobbits: Query<&Hobbit>,
+    mut texts: Query<(&mut Text, &StatusText)>,
+) {
+    let mut upcoming = level
+        .0
+        .spawns
+        .iter()
+        .enumerate()
+        .filter_map(|(index, spawn)| {
+            let timer = spawner.timers.get(index)?.as_ref()?;
+            let alive = hobbits
+                .iter()
+                .filter(|hobbit| hobbit.spawn == index)
+                .count() as u32;
+            let burst = spawn.burst.min(spawn.hobbits.saturating_sub(alive)).max(1);
+            Some((burst, timer.remaining_secs()))
+        })
+        .collect::<Vec<_>>();
+    upcoming.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let upcoming = upcoming_spawns(&upcoming);
+    for (mut text, kind) in &mut texts {
+        if *kind == StatusText::NextSpawns && text.sections[1].value != upcoming {
+            text.sections[1].value.clone_from(&upcoming);
+        }
+    }
+}
+
+/// Shown while spawns are held because hobbits crowd around a start
+#[derive(Component)]
+struct HeldSpawnsText;
+
+fn display_held_spawns(
+    held: Res<HeldSpawns>,
+    mut texts: Query<&mut Visibility, With<HeldSpawnsText>>,
+) {
+    for mut visibility in &mut texts {
+        visibility.set_if_neq(if held.any() {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
+
+/// Seconds left when the countdown starts to tick
+const COUNTDOWN_WARNING: f32 = 10.0;
+
+fn escort_status(escorted: Option<bool>) -> &'static str {
+    match escorted {
+        Some(true) => "home",
+        Some(false) => "lost",
+        None => "on the way",
+    }
+}
+
+/// Start each attempt from the same randomness, the level hash as the seed
+fn seed_rng(
+    mut rng: ResMut<GameRng>,
+    game: Res<GameInProgress>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+) {
+    let level = levels.get(&assets.levels[game.level]).unwrap();
+    rng.0 = StdRng::seed_from_u64(level.hash);
+}
+
+/// Time follows the simulation speed, and stops once the level is decided
+fn track_elapsed(
+    time: Res<Time>,
+    mut game: ResMut<GameInProgress>,
+    level: Res<ActiveLevel>,
+    conditions: Res<Conditions>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+) {
+    let level = &level.0;
+    // counted on every level, the replay places obstacles when they were placed
+    if conditions.is_won(&game, level) || conditions.is_lost(&game, level) {
+        return;
+    }
+    let before = game.elapsed;
+    game.elapsed += time.delta_seconds();
+
+    if let Some(limit) = level.time_limit() {
+        let (before, after) = (limit - before, limit - game.elapsed);
+        if after > 0.0 && after <= COUNTDOWN_WARNING && before.ceil() != after.ceil() {
+            audio_trigger.send(AudioTrigger::Countdown);
+        }
+    }
 }
 
-fn update_progress(mut game_events: EventReader<GameEvent>, mut game: ResMut<GameInProgress>) {
+/// Scores, losses and the escort as the gameplay reports them
+pub(crate) fn update_progress(
+    mut game_events: EventReader<GameEvent>,
+    mut game: ResMut<GameInProgress>,
+    mut milestones: EventWriter<Milestone>,
+) {
     for event in game_events.read() {
         match event {
-            GameEvent::HomeWithTreasure => {
-                game.score += 1;
+            GameEvent::HomeWithTreasure { team } => {
+                if game.scores.len() <= *team {
+                    game.scores.resize(*team + 1, 0);
+                }
+                game.scores[*team] += 1;
+                if game.scores[*team] % MILESTONE_EVERY == 0 {
+                    milestones.send(Milestone {
+                        team: *team,
+                        treasures: game.scores[*team],
+                    });
+                }
             }
-            GameEvent::CollidedWithHobbit => {
+            GameEvent::CollidedWithHobbit { .. } => {
                 game.lost_hobbits += 1;
             }
+            GameEvent::EscortedHome => {
+                game.escorted.get_or_insert(true);
+            }
+            GameEvent::EscortedLost => {
+                game.escorted.get_or_insert(false);
+            }
+            GameEvent::Spawned { hobbits, .. } => {
+                game.spawned_hobbits += hobbits;
+            }
+            GameEvent::ChestEmptied { .. }
+            | GameEvent::Home { .. }
+            | GameEvent::TreasureTaken { .. }
+            | GameEvent::NearMiss => {}
+        }
+    }
+}
+
+/// Hobbits currently in the level and progress toward the treasures, which change without
+/// [`GameInProgress`] always changing
+fn update_hobbit_counter(
+    game: Res<GameInProgress>,
+    hobbits: Query<(), With<Hobbit>>,
+    mut texts: Query<(&mut Text, &StatusText)>,
+    mut bars: Query<(&mut Style, &TreasureBar)>,
+) {
+    let alive = hobbits.iter().count().to_string();
+    for (mut text, kind) in &mut texts {
+        if *kind == StatusText::HobbitsAlive && text.sections[1].value != alive {
+            text.sections[1].value.clone_from(&alive);
+        }
+    }
+    if game.is_changed() {
+        for (mut style, bar) in &mut bars {
+            let progress = game.score(bar.team) as f32 / bar.target.max(1) as f32;
+            style.width = Val::Percent(progress.min(1.0) * 100.0);
         }
     }
 }
@@ -850,23 +1828,76 @@ fn display_and_check_conditions(
     levels: Res<Assets<Level>>,
     mut texts: Query<(&mut Text, &StatusText)>,
     mut progress_storage: ResMut<PkvStore>,
+    obstacles: Query<&Transform, With<SpawnedObstacle>>,
+    daily: Option<Res<DailyChallenge>>,
+    conditions: Res<Conditions>,
+    mut new_game_plus: ResMut<NewGamePlus>,
+    mutators: Res<Mutators>,
 ) {
     if game.is_changed() {
+        let level = levels.get(&assets.levels[game.level]).unwrap();
         for (mut text, kind) in &mut texts {
             match kind {
-                StatusText::Treasures => {
-                    text.sections[1].value = game.score.to_string();
+                StatusText::Treasures(team) => {
+                    text.sections[1].value = game.score(*team).to_string();
                 }
                 StatusText::HobbitsLost => {
                     text.sections[1].value = game.lost_hobbits.to_string();
                 }
+                StatusText::HobbitsSpawned => {
+                    text.sections[1].value = game.spawned_hobbits.to_string();
+                }
+                // changes with the hobbits, see `update_hobbit_counter`
+                StatusText::HobbitsAlive => {}
+                // changes with the spawn timers, see `display_upcoming_spawns`
+                StatusText::NextSpawns => {}
+                StatusText::Elapsed => {
+                    text.sections[1].value = format!("{}", game.elapsed as u32);
+                }
+                StatusText::TimeLeft => {
+                    let left = (level.time_limit().unwrap_or(0.0) - game.elapsed).max(0.0);
+                    text.sections[1].value = format!("{}", left.ceil() as u32);
+                    if left <= COUNTDOWN_WARNING {
+                        text.sections[1].style.color = palettes::tailwind::RED_500.into();
+                    }
+                }
+                StatusText::Escort => {
+                    text.sections[1].value = escort_status(game.escorted).to_string();
+                }
             }
         }
 
-        let level = levels.get(&assets.levels[game.level]).unwrap();
-        if game.score == level.treasures {
-            progress.current_level = game.level + 1;
-            let _ = progress_storage.set("progress", &(progress.current_level as u32));
+        if conditions.is_won(&game, level) {
+            // custom and downloaded levels have their own progress
+            if mutators.any() {
+                // replays with mutators only count towards their badges
+                if assets.is_campaign(game.level) {
+                    mutators.record_win(&mut progress_storage, game.level);
+                }
+            } else if !assets.is_campaign(game.level) {
+                record_custom_win(&mut progress_storage, &level.file, level.hash);
+                if let Some(daily) = daily
+                    .as_ref()
+                    .filter(|daily| level.file == daily_file(daily.day))
+                {
+                    record_daily(&mut progress_storage, daily.day, game.lost_hobbits);
+                }
+            } else if new_game_plus.active {
+                new_game_plus.record_win(&mut progress_storage, game.level);
+            } else {
+                progress.current_level = progress.current_level.max(game.level + 1);
+                record_win(&mut progress_storage, game.level, level.hash);
+            }
+            let last_layout = LastLayout {
+                layouts_key: level_layouts_key(&assets, game.level, &level.file),
+                level_hash: level.hash,
+                cells: obstacles
+                    .iter()
+                    .map(|obstacle| mutators.map_cell(level, obstacle_cell(obstacle)))
+                    .collect(),
+            };
+            record_solution(&mut progress_storage, &level.file, &last_layout);
+            commands.insert_resource(last_layout);
             next_state.send(SwitchState(GameState::Win));
 
             let (entity, transform) = camera_position.single();
@@ -905,7 +1936,7 @@ fn display_and_check_conditions(
                 }
             }
         }
-        if Some(game.lost_hobbits) == level.losts {
+        if conditions.is_lost(&game, level) {
             next_state.send(SwitchState(GameState::Lost));
 
             let (entity, transform) = camera_position.single();
@@ -948,174 +1979,720 @@ fn display_and_check_conditions(
 }
 
 #[derive(Component)]
-struct SpawnedObstacle;
+pub(crate) struct SpawnedObstacle(Bonus);
+
+/// Where the layouts of a level are kept, custom levels have theirs by file
+fn level_layouts_key(assets: &GameAssets, level: usize, file: &str) -> String {
+    if assets.is_campaign(level) {
+        layouts_key(level)
+    } else {
+        custom_layouts_key(file)
+    }
+}
+
+pub(crate) fn obstacle_cell(transform: &Transform) -> (usize, usize) {
+    (
+        transform.translation.x as usize / 4,
+        transform.translation.z as usize / 4,
+    )
+}
+
+pub(crate) fn placed_bonus(
+    (transform, obstacle): (&Transform, &SpawnedObstacle),
+) -> ((usize, usize), Bonus) {
+    (obstacle_cell(transform), obstacle.0)
+}
+
+/// Cells hobbits can't walk on with bonuses placed: they block their cell, and torches light the
+/// cells around them in night levels
+fn blocked_cells(level: &Level, placed: &[((usize, usize), Bonus)]) -> Vec<(usize, usize)> {
+    let torches = placed
+        .iter()
+        .filter(|(_, bonus)| *bonus == Bonus::Torch)
+        .map(|(cell, _)| *cell)
+        .collect::<Vec<_>>();
+    let mut removed_cells = placed.iter().map(|(cell, _)| *cell).collect::<Vec<_>>();
+    removed_cells.extend(level.dark_cells(&torches));
+    removed_cells
+}
+
+/// Cells blocked on the [`NavMesh`]. When bonuses are placed or removed, only the cells that
+/// changed are updated, so hobbits path around them in the same frame.
+#[derive(Resource)]
+pub struct BlockedCells {
+    cells: NavMeshCells,
+    /// Time the last update of the navmesh took
+    pub(crate) last_update: Duration,
+}
+
+impl BlockedCells {
+    /// Block the dark cells of the level on its navmesh, before any bonus is placed
+    pub(crate) fn new(mesh: &mut polyanya::Mesh, level: &Level) -> Self {
+        let mut cells = NavMeshCells::new(mesh);
+        cells.set_blocked(mesh, &level.dark_cells(&[]));
+        BlockedCells {
+            cells,
+            last_update: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn update(
+        &mut self,
+        navmesh: &mut NavMesh,
+        level: &Level,
+        placed: &[((usize, usize), Bonus)],
+    ) {
+        let start = Instant::now();
+        self.cells
+            .set_blocked(&mut navmesh.0, &blocked_cells(level, placed));
+        self.last_update = start.elapsed();
+    }
+}
+
+/// Radius of the collider of placed bonuses
+const OBSTACLE_RADIUS: f32 = 1.0;
+/// Height of the collider of placed bonuses
+const OBSTACLE_HEIGHT: f32 = 2.0;
+
+/// Where the model of a bonus placed on a cell stands, its collider is scaled with it
+fn obstacle_transform(assets: &GameAssets, bonus: Bonus, cell: (usize, usize)) -> Transform {
+    let transform =
+        Transform::from_translation(Vec3::new(cell.0 as f32 * 4.0, 0.4, cell.1 as f32 * 4.0));
+    if assets.bonus_scene(bonus).is_some() {
+        transform.with_scale(Vec3::splat(1.5))
+    } else {
+        transform.with_translation(transform.translation.with_y(1.2))
+    }
+}
+
+/// A bonus placed on a cell as hobbits bump into it, without its model
+pub(crate) fn obstacle_body(
+    assets: &GameAssets,
+    bonus: Bonus,
+    cell: (usize, usize),
+) -> (SpawnedObstacle, RigidBody, Collider, SpatialBundle) {
+    (
+        SpawnedObstacle(bonus),
+        RigidBody::Static,
+        Collider::cylinder(OBSTACLE_RADIUS, OBSTACLE_HEIGHT),
+        SpatialBundle::from_transform(obstacle_transform(assets, bonus, cell)),
+    )
+}
+
+/// Spawn an obstacle on a cell, and turn the bonus button that was used into a button to remove it
+fn place_obstacle(
+    commands: &mut Commands,
+    assets: &GameAssets,
+    rng: &mut GameRng,
+    bonus: Bonus,
+    cell: (usize, usize),
+    button: Entity,
+) {
+    let transform = obstacle_transform(assets, bonus, cell);
+    let mut obstacle = commands.spawn((
+        obstacle_body(assets, bonus, cell),
+        StateScoped(CURRENT_STATE),
+    ));
+    if let Some(scene) = assets.bonus_scene(bonus) {
+        obstacle.insert(SceneBundle {
+            scene,
+            transform: transform
+                .with_rotation(Quat::from_rotation_y(rng.0.gen_range(0.0..(2.0 * PI)))),
+            ..default()
+        });
+    } else {
+        obstacle
+            .insert(PbrBundle {
+                mesh: assets.torch_mesh.clone(),
+                material: assets.torch_material.clone(),
+                transform,
+                ..default()
+            })
+            .with_children(|parent| {
+                parent.spawn(PointLightBundle {
+                    transform: Transform::from_translation(Vec3::Y * 2.0),
+                    point_light: PointLight {
+                        intensity: 800_000.0,
+                        color: palettes::tailwind::ORANGE_400.into(),
+                        shadows_enabled: true,
+                        range: (LIGHT_RADIUS + 1.0) * 4.0,
+                        ..default()
+                    },
+                    ..default()
+                });
+                parent
+                    .spawn(ParticleSpawnerBundle::from_settings(
+                        ParticleSpawnerSettings {
+                            one_shot: false,
+                            rate: 80.0,
+                            emission_shape: EmissionShape::Circle {
+                                normal: Vec3::Y,
+                                radius: 0.15,
+                            },
+                            lifetime: RandF32::constant(0.4),
+                            inherit_parent_velocity: false,
+                            initial_velocity: RandVec3 {
+                                magnitude: RandF32 { min: 1., max: 3. },
+                                direction: Vec3::Y,
+                                spread: FRAC_PI_8,
+                            },
+                            initial_scale: RandF32 {
+                                min: 0.05,
+                                max: 0.15,
+                            },
+                            scale_curve: ParamCurve::constant(1.),
+                            color: Gradient::constant(
+                                (palettes::tailwind::ORANGE_500 * 4.0).into(),
+                            ),
+                            blend_mode: BlendMode::Blend,
+                            linear_drag: 0.1,
+                            pbr: false,
+                            ..default()
+                        },
+                    ))
+                    .insert(Transform::from_translation(Vec3::Y * 1.2));
+            });
+    }
+    let obstacle_entity = obstacle.id();
+    commands
+        .entity(button)
+        .insert((
+            BUTTON_IDLE_REMOVE,
+            ButtonAction::RemoveBonus(bonus, obstacle_entity),
+            AccessibleName(format!("Remove {}", assets.bonus_name(bonus))),
+        ))
+        .remove::<SelectedBonus>()
+        .with_children(|p| {
+            p.spawn(TextBundle {
+                text: Text::from_section(
+                    "X",
+                    TextStyle {
+                        font_size: 30.0,
+                        color: palettes::tailwind::RED_600.into(),
+                        ..default()
+                    },
+                ),
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    position_type: PositionType::Absolute,
+                    justify_content: JustifyContent::Center,
+                    align_self: AlignSelf::Center,
+                    ..default()
+                },
+                background_color: BackgroundColor(
+                    palettes::tailwind::GRAY_400.with_alpha(0.5).into(),
+                ),
+                ..default()
+            });
+        });
+}
+
+#[derive(Resource)]
+struct SavedLayouts(Vec<Layout>);
 
 #[allow(clippy::too_many_arguments)]
-fn draw_cursor(
+fn apply_layout(
+    mut commands: Commands,
+    interactions: Query<(&Interaction, &ButtonAction), Changed<Interaction>>,
+    bonus_buttons: Query<(Entity, &ButtonAction)>,
+    layouts: Res<SavedLayouts>,
+    obstacles: Query<(&Transform, &SpawnedObstacle)>,
+    assets: Res<GameAssets>,
+    mut rng: ResMut<GameRng>,
+    level: Res<ActiveLevel>,
+    mut cells: ResMut<BlockedCells>,
+    mut navmesh: ResMut<NavMesh>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+) {
+    for (interaction, action) in &interactions {
+        let (Interaction::Pressed, ButtonAction::ApplyLayout(index)) = (interaction, action) else {
+            continue;
+        };
+        let mut placed = obstacles.iter().map(placed_bonus).collect::<Vec<_>>();
+        let mut free_bonus = bonus_buttons
+            .iter()
+            .filter_map(|(entity, action)| match action {
+                ButtonAction::Bonus(bonus) => Some((entity, *bonus)),
+                _ => None,
+            });
+        for cell in &layouts.0[*index].cells {
+            if placed.iter().any(|(placed_cell, _)| placed_cell == cell) {
+                continue;
+            }
+            let Some((button, bonus)) = free_bonus.next() else {
+                break;
+            };
+            place_obstacle(&mut commands, &assets, &mut rng, bonus, *cell, button);
+            placed.push((*cell, bonus));
+        }
+        cells.update(&mut navmesh, &level.0, &placed);
+        audio_trigger.send(AudioTrigger::Obstacle);
+    }
+}
+
+/// Place the obstacles of an autosave being resumed, with the bonus buttons of the same kind
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn restore_obstacles(
+    mut commands: Commands,
+    resumed: Res<ResumedLevel>,
+    bonus_buttons: Query<(Entity, &ButtonAction)>,
+    assets: Res<GameAssets>,
+    mut rng: ResMut<GameRng>,
+    level: Res<ActiveLevel>,
+    mut cells: ResMut<BlockedCells>,
+    mut navmesh: ResMut<NavMesh>,
+) {
+    let mut free_bonus = bonus_buttons
+        .iter()
+        .filter_map(|(entity, action)| match action {
+            ButtonAction::Bonus(bonus) => Some((entity, *bonus)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let mut placed = vec![];
+    for (cell, bonus) in resumed.obstacles() {
+        let Some(index) = free_bonus.iter().position(|(_, free)| free == bonus) else {
+            continue;
+        };
+        let (button, _) = free_bonus.remove(index);
+        place_obstacle(&mut commands, &assets, &mut rng, *bonus, *cell, button);
+        placed.push((*cell, *bonus));
+    }
+    cells.update(&mut navmesh, &level.0, &placed);
+}
+
+fn start_session(mut commands: Commands, time: Res<Time<Real>>) {
+    commands.insert_resource(SessionStart(time.elapsed()));
+}
+
+fn summarize_session(
     mut commands: Commands,
+    mut confirmed: EventReader<Confirmed>,
+    game: Res<GameInProgress>,
+    level: Res<ActiveLevel>,
+    start: Res<SessionStart>,
+    time: Res<Time<Real>>,
+    mut store: ResMut<PkvStore>,
+) {
+    for confirmed in confirmed.read() {
+        if confirmed.0 != ConfirmedAction::LeaveLevel {
+            continue;
+        }
+        record_abandoned(&mut store, &level.0.file);
+        commands.insert_resource(SessionSummary {
+            duration: time.elapsed().saturating_sub(start.0),
+            treasures: game.scores.iter().sum(),
+            lost_hobbits: game.lost_hobbits,
+        });
+    }
+}
+
+fn simulation_speed_buttons(
+    interactions: Query<(&Interaction, &ButtonAction), Changed<Interaction>>,
+    buttons: Query<(&ButtonAction, &Children)>,
+    mut speed: ResMut<SimulationSpeed>,
+    mut texts: Query<&mut Text>,
+) {
+    for (interaction, action) in &interactions {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match action {
+            ButtonAction::Speed(factor) => {
+                speed.factor = *factor as f32;
+                speed.paused = false;
+            }
+            ButtonAction::Pause => speed.paused = !speed.paused,
+            _ => continue,
+        }
+        for (action, children) in &buttons {
+            let Ok(mut text) = texts.get_mut(children[0]) else {
+                continue;
+            };
+            match action {
+                ButtonAction::Speed(factor) => {
+                    text.sections[0].style.color =
+                        if !speed.paused && speed.factor == *factor as f32 {
+                            palettes::tailwind::AMBER_300.into()
+                        } else {
+                            Color::WHITE
+                        };
+                }
+                ButtonAction::Pause => {
+                    text.sections[0].value =
+                        if speed.paused { "Resume" } else { "Pause" }.to_string();
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn export_picture(
+    interactions: Query<(&Interaction, &ButtonAction, &Children), Changed<Interaction>>,
+    obstacles: Query<(&Transform, &SpawnedObstacle)>,
+    level: Res<ActiveLevel>,
+    mut texts: Query<&mut Text>,
+) {
+    for (interaction, action, children) in &interactions {
+        let (Interaction::Pressed, ButtonAction::ExportPicture) = (interaction, action) else {
+            continue;
+        };
+        let cells = obstacles
+            .iter()
+            .map(|(transform, _)| obstacle_cell(transform))
+            .collect::<Vec<_>>();
+        let label = match crate::pictures::export_layout(&level.0, &cells) {
+            Ok(path) => {
+                info!("exported a picture of the level to {}", path.display());
+                "Saved"
+            }
+            Err(err) => {
+                warn!("failed to export a picture of the level: {}", err);
+                "Failed"
+            }
+        };
+        if let Ok(mut text) = texts.get_mut(children[0]) {
+            text.sections[0].value = label.to_string();
+        }
+    }
+}
+
+/// Floor cell under the cursor, if it's free to place a bonus
+fn free_floor_cell(
+    level: &Level,
+    point: Vec3,
+    obstacles: &Query<(&Transform, &SpawnedObstacle)>,
+) -> Option<(usize, usize)> {
+    if point.x < 0.0 || point.z < 0.0 {
+        return None;
+    }
+    let cell = (point.x as usize, point.z as usize);
+    (level.floors[0].get(cell.1).and_then(|row| row.get(cell.0)) == Some(&Tile::Floor)
+        && obstacles
+            .iter()
+            .all(|(transform, _)| obstacle_cell(transform) != cell))
+    .then_some(cell)
+}
+
+/// Preview of the selected bonus under the cursor: the footprint of its collider, and the cells of
+/// the navmesh it would block
+fn draw_cursor(
     camera_query: Query<(&Camera, &GlobalTransform)>,
     windows: Query<&Window>,
     mut gizmos: Gizmos,
-    game: Res<GameInProgress>,
+    level: Res<ActiveLevel>,
+    selected: Query<&ButtonAction, With<SelectedBonus>>,
+    obstacles: Query<(&Transform, &SpawnedObstacle)>,
+) {
+    let Ok(ButtonAction::Bonus(bonus)) = selected.get_single() else {
+        return;
+    };
+    let (camera, camera_transform) = camera_query.single();
+    let ground = GlobalTransform::default();
+    let Some(point) = cursor_cell(camera, camera_transform, windows.single()) else {
+        return;
+    };
+    let Some(cell) = free_floor_cell(&level.0, point, &obstacles) else {
+        return;
+    };
+
+    let mut placed = obstacles.iter().map(placed_bonus).collect::<Vec<_>>();
+    let blocked = blocked_cells(&level.0, &placed);
+    placed.push((cell, *bonus));
+    let flat = Quat::from_rotation_x(FRAC_PI_2);
+    for (x, z) in blocked_cells(&level.0, &placed)
+        .into_iter()
+        .filter(|cell| !blocked.contains(cell))
+    {
+        let center = Vec3::new(x as f32 * 4.0, 0.4, z as f32 * 4.0);
+        for size in [3.8, 3.0, 2.2] {
+            gizmos.rect(
+                center,
+                flat,
+                Vec2::splat(size),
+                palettes::tailwind::RED_500.with_alpha(0.3),
+            );
+        }
+    }
+
+    let center = point * 4.0;
+    gizmos.circle(
+        center,
+        ground.up(),
+        OBSTACLE_RADIUS,
+        palettes::tailwind::GREEN_400,
+    );
+    gizmos.circle(
+        // the collider is centered on the obstacle
+        center + Vec3::Y * OBSTACLE_HEIGHT / 2.0,
+        ground.up(),
+        OBSTACLE_RADIUS,
+        palettes::tailwind::GREEN_400.with_alpha(0.5),
+    );
+}
+
+/// While a bonus is selected, draw the grid of the level and highlight every cell where it can be
+/// placed. The grid can be hidden with its shortcut.
+fn draw_placement_grid(
+    mut gizmos: Gizmos,
+    level: Res<ActiveLevel>,
+    selected: Query<&ButtonAction, With<SelectedBonus>>,
+    obstacles: Query<(&Transform, &SpawnedObstacle)>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    input_map: Res<InputMap>,
+    mut hidden: Local<bool>,
+) {
+    if input_map.just_pressed(&keyboard, Action::ToggleGrid) {
+        *hidden = !*hidden;
+    }
+    let Ok(ButtonAction::Bonus(_)) = selected.get_single() else {
+        return;
+    };
+    if *hidden {
+        return;
+    }
+    let floor = &level.0.floors[0];
+    let (depth, width) = (floor.len(), floor[0].len());
+    // gizmo grids are drawn in the XY plane
+    let flat = Quat::from_rotation_x(FRAC_PI_2);
+    gizmos.grid(
+        Vec3::new((width as f32 - 1.0) * 2.0, 0.3, (depth as f32 - 1.0) * 2.0),
+        flat,
+        UVec2::new(width as u32, depth as u32),
+        Vec2::splat(4.0),
+        Color::WHITE.with_alpha(0.1),
+    );
+    for (z, row) in floor.iter().enumerate() {
+        for x in 0..row.len() {
+            let point = Vec3::new(x as f32, 0.1, z as f32);
+            if free_floor_cell(&level.0, point, &obstacles).is_some() {
+                gizmos.rect(
+                    point * 4.0,
+                    flat,
+                    Vec2::splat(3.4),
+                    palettes::tailwind::GREEN_400.with_alpha(0.4),
+                );
+            }
+        }
+    }
+}
+
+/// Place the selected bonus where the player clicked or tapped. Runs in `PreUpdate` so that hobbits path
+/// around it in the same frame.
+#[allow(clippy::too_many_arguments)]
+fn place_selected_bonus(
+    mut commands: Commands,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    windows: Query<&Window>,
     assets: Res<GameAssets>,
-    levels: Res<Assets<Level>>,
+    mut rng: ResMut<GameRng>,
+    level: Res<ActiveLevel>,
     selected: Query<(Entity, &ButtonAction), With<SelectedBonus>>,
     mouse_input: Res<ButtonInput<MouseButton>>,
-    obstacles: Query<&Transform, With<SpawnedObstacle>>,
+    obstacles: Query<(&Transform, &SpawnedObstacle)>,
+    mut cells: ResMut<BlockedCells>,
     mut navmesh: ResMut<NavMesh>,
     mut audio_trigger: EventWriter<AudioTrigger>,
+    mut taps: EventReader<Tap>,
 ) {
-    if let Ok((entity, button)) = selected.get_single() {
-        let (camera, camera_transform) = camera_query.single();
-        let ground = GlobalTransform::default();
+    let tap = taps.read().last().map(|tap| tap.0);
+    if !mouse_input.just_pressed(MouseButton::Left) && tap.is_none() {
+        return;
+    }
+    let Ok((entity, ButtonAction::Bonus(bonus))) = selected.get_single() else {
+        return;
+    };
+    let (camera, camera_transform) = camera_query.single();
+    let Some(cell) = tap
+        .or_else(|| windows.single().cursor_position())
+        .and_then(|position| screen_cell(camera, camera_transform, position))
+        .and_then(|point| free_floor_cell(&level.0, point, &obstacles))
+    else {
+        return;
+    };
+    place_obstacle(&mut commands, &assets, &mut rng, *bonus, cell, entity);
+    cells.update(
+        &mut navmesh,
+        &level.0,
+        &obstacles
+            .iter()
+            .map(placed_bonus)
+            .chain(std::iter::once((cell, *bonus)))
+            .collect::<Vec<_>>(),
+    );
+    audio_trigger.send(AudioTrigger::Obstacle);
+}
 
-        let level = levels.get(&assets.levels[game.level]).unwrap();
+fn set_daylight(
+    game: Res<GameInProgress>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+    mut lights: Query<&mut DirectionalLight>,
+    mut ambient: ResMut<AmbientLight>,
+) {
+    if levels.get(&assets.levels[game.level]).unwrap().night {
+        for mut light in &mut lights {
+            light.illuminance = light_consts::lux::FULL_MOON_NIGHT;
+        }
+        ambient.brightness = 5.0;
+    }
+}
 
-        let Some(cursor_position) = windows.single().cursor_position() else {
-            return;
-        };
+fn restore_daylight(mut lights: Query<&mut DirectionalLight>, mut ambient: ResMut<AmbientLight>) {
+    for mut light in &mut lights {
+        light.illuminance = DAYLIGHT;
+    }
+    ambient.brightness = AmbientLight::default().brightness;
+}
 
-        // Calculate a ray pointing from the camera into the world based on the cursor's position.
-        let Some(ray) = camera.viewport_to_world(camera_transform, cursor_position) else {
-            return;
-        };
+/// Cell under the cursor, in level coordinates. Can be outside of the level.
+pub(crate) fn cursor_cell(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    window: &Window,
+) -> Option<Vec3> {
+    screen_cell(camera, camera_transform, window.cursor_position()?)
+}
 
-        // Calculate if and where the ray is hitting the ground plane.
-        let Some(distance) =
-            ray.intersect_plane(ground.translation(), InfinitePlane3d::new(ground.up()))
-        else {
-            return;
-        };
-        let point = ray.get_point(distance);
-        let normalized_point = Vec3::new((point.x / 4.0).round(), 0.1, (point.z / 4.0).round());
+/// Cell at a position in the window, in level coordinates. Can be outside of the level.
+pub(crate) fn screen_cell(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    position: Vec2,
+) -> Option<Vec3> {
+    let ground = GlobalTransform::default();
+
+    // Calculate a ray pointing from the camera into the world based on the position.
+    let ray = camera.viewport_to_world(camera_transform, position)?;
+
+    // Calculate if and where the ray is hitting the ground plane.
+    let distance = ray.intersect_plane(ground.translation(), InfinitePlane3d::new(ground.up()))?;
+    let point = ray.get_point(distance);
+    Some(Vec3::new(
+        (point.x / 4.0).round(),
+        0.1,
+        (point.z / 4.0).round(),
+    ))
+}
+
+/// Routes from each spawn point if `bonus` was placed on `cell`, `None` for a spawn point that
+/// would be blocked
+struct PathPreview {
+    cell: (usize, usize),
+    bonus: Bonus,
+    obstacles: usize,
+    routes: Vec<Option<Vec<Vec3>>>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn preview_obstacle_path(
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    windows: Query<&Window>,
+    mut gizmos: Gizmos,
+    level: Res<ActiveLevel>,
+    selected: Query<&ButtonAction, With<SelectedBonus>>,
+    obstacles: Query<(&Transform, &SpawnedObstacle)>,
+    current_navmesh: Res<NavMesh>,
+    cells: Res<BlockedCells>,
+    mut preview: Local<Option<PathPreview>>,
+) {
+    let Ok(ButtonAction::Bonus(bonus)) = selected.get_single() else {
+        *preview = None;
+        return;
+    };
+    let (camera, camera_transform) = camera_query.single();
+    let Some(normalized_point) = cursor_cell(camera, camera_transform, windows.single()) else {
+        return;
+    };
+    if normalized_point.x < 0.0 || normalized_point.z < 0.0 {
+        return;
+    }
+    let cell = (normalized_point.x as usize, normalized_point.z as usize);
+    let level = &level.0;
+    let mut placed = obstacles.iter().map(placed_bonus).collect::<Vec<_>>();
+    if level.floors[0].get(cell.1).and_then(|row| row.get(cell.0)) != Some(&Tile::Floor)
+        || placed.iter().any(|(placed_cell, _)| *placed_cell == cell)
+    {
+        return;
+    }
 
-        let existing_obstacles = obstacles
+    if preview
+        .as_ref()
+        .map(|preview| (preview.cell, preview.bonus, preview.obstacles))
+        != Some((cell, *bonus, placed.len()))
+    {
+        let obstacles = placed.len();
+        placed.push((cell, *bonus));
+        let mut navmesh = current_navmesh.0.clone();
+        cells
+            .cells
+            .clone()
+            .set_blocked(&mut navmesh, &blocked_cells(level, &placed));
+        let routes = level
+            .spawns
             .iter()
-            .map(|t| (t.translation.x as usize / 4, t.translation.z as usize / 4))
-            .collect::<Vec<_>>();
+            .map(|spawn| {
+                let team = &level.teams[spawn.team];
+                let start = vec2(spawn.cell.1 as f32 * 4.0, spawn.cell.2 as f32 * 4.0);
+                let end = vec2(team.end.1 as f32 * 4.0, team.end.2 as f32 * 4.0);
+                let mut exclusion = HashSet::new();
+                exclusion.insert(2);
+                let there = navmesh.path_on_layers(start, end, exclusion)?;
+                let mut exclusion = HashSet::new();
+                exclusion.insert(1);
+                let back = navmesh.path_on_layers(end, start, exclusion)?;
+                Some(
+                    std::iter::once(start)
+                        .chain(there.path)
+                        .chain(back.path)
+                        .map(|point| vec3(point.x, 0.15, point.y))
+                        .collect(),
+                )
+            })
+            .collect();
+        *preview = Some(PathPreview {
+            cell,
+            bonus: *bonus,
+            obstacles,
+            routes,
+        });
+    }
 
-        if Some(&Tile::Floor)
-            == level.floors[0]
-                .get(if normalized_point.z < 0.0 {
-                    usize::MAX
-                } else {
-                    normalized_point.z as usize
-                })
-                .and_then(|r| {
-                    r.get(if normalized_point.x < 0.0 {
-                        usize::MAX
-                    } else {
-                        normalized_point.x as usize
-                    })
-                })
-        {
-            if let ButtonAction::Bonus(bonus_to_add) = button {
-                if existing_obstacles
-                    .contains(&(normalized_point.x as usize, normalized_point.z as usize))
-                {
-                    return;
-                }
-                gizmos.circle(
-                    normalized_point * 4.0,
-                    ground.up(),
-                    1.3,
-                    palettes::tailwind::GREEN_400,
-                );
-                gizmos.circle(
-                    normalized_point * 4.0,
-                    ground.up(),
-                    1.2,
-                    palettes::tailwind::GREEN_500,
-                );
+    for (spawn, route) in level.spawns.iter().zip(&preview.as_ref().unwrap().routes) {
+        match route {
+            Some(route) => {
+                let color = level
+                    .team_color((spawn.cell.1, spawn.cell.2))
+                    .unwrap_or(palettes::tailwind::GRAY_100);
+                gizmos.linestrip(route.iter().copied(), color.with_alpha(0.5));
+            }
+            None => {
                 gizmos.circle(
                     normalized_point * 4.0,
-                    ground.up(),
-                    1.1,
-                    palettes::tailwind::GREEN_600,
+                    Dir3::Y,
+                    1.5,
+                    palettes::tailwind::RED_500,
                 );
-                if mouse_input.just_pressed(MouseButton::Left) {
-                    let obstacle_entity = commands
-                        .spawn((
-                            SceneBundle {
-                                scene: match bonus_to_add {
-                                    Bonus::Obstacle => assets.obstacle.clone(),
-                                },
-                                transform: Transform::from_translation(normalized_point * 4.0)
-                                    .with_rotation(Quat::from_rotation_y(
-                                        rand::thread_rng().gen_range(0.0..(2.0 * PI)),
-                                    ))
-                                    .with_scale(Vec3::splat(1.5)),
-                                ..default()
-                            },
-                            SpawnedObstacle,
-                            RigidBody::Static,
-                            Collider::cylinder(1.0, 2.0),
-                            StateScoped(CURRENT_STATE),
-                        ))
-                        .id();
-                    commands
-                        .entity(entity)
-                        .insert((
-                            BUTTON_IDLE_REMOVE,
-                            ButtonAction::RemoveBonus(*bonus_to_add, obstacle_entity),
-                        ))
-                        .remove::<SelectedBonus>()
-                        .with_children(|p| {
-                            p.spawn(TextBundle {
-                                text: Text::from_section(
-                                    "X",
-                                    TextStyle {
-                                        font_size: 30.0,
-                                        color: palettes::tailwind::RED_600.into(),
-                                        ..default()
-                                    },
-                                ),
-                                style: Style {
-                                    width: Val::Percent(100.0),
-                                    height: Val::Percent(100.0),
-                                    position_type: PositionType::Absolute,
-                                    justify_content: JustifyContent::Center,
-                                    align_self: AlignSelf::Center,
-                                    ..default()
-                                },
-                                background_color: BackgroundColor(
-                                    palettes::tailwind::GRAY_400.with_alpha(0.5).into(),
-                                ),
-                                ..default()
-                            });
-                        });
-                    navmesh.0 = level.as_navmesh(
-                        obstacles
-                            .iter()
-                            .map(|t| (t.translation.x as usize / 4, t.translation.z as usize / 4))
-                            .chain(std::iter::once((
-                                normalized_point.x as usize,
-                                normalized_point.z as usize,
-                            )))
-                            .collect(),
-                    );
-                    audio_trigger.send(AudioTrigger::Obstacle);
-                }
             }
         }
     }
 }
 
 fn update_navmesh(
-    game: Res<GameInProgress>,
-    assets: Res<GameAssets>,
-    levels: Res<Assets<Level>>,
-    obstacles: Query<&Transform, With<SpawnedObstacle>>,
+    level: Res<ActiveLevel>,
+    obstacles: Query<(&Transform, &SpawnedObstacle)>,
+    mut cells: ResMut<BlockedCells>,
     mut navmesh: ResMut<NavMesh>,
     removed_obstacles: RemovedComponents<SpawnedObstacle>,
 ) {
     if !removed_obstacles.is_empty() {
-        let level = levels.get(&assets.levels[game.level]).unwrap();
-        navmesh.0 = level.as_navmesh(
-            obstacles
-                .iter()
-                .map(|t| (t.translation.x as usize / 4, t.translation.z as usize / 4))
-                .collect(),
+        cells.update(
+            &mut navmesh,
+            &level.0,
+            &obstacles.iter().map(placed_bonus).collect::<Vec<_>>(),
         );
     }
 }
@@ -1124,16 +2701,14 @@ fn info_about_blockage(
     mut commands: Commands,
     panels: Query<(Entity, &MenuItem, &Style)>,
     path_status: Res<PathStatus>,
+    hud_layout: Res<HudLayout>,
 ) {
     if path_status.is_changed() {
         for (entity, kind, style) in &panels {
             if *kind == MenuItem::BlockedPanel {
                 if *path_status == PathStatus::Blocked {
                     commands.entity(entity).insert(style.clone().ease_to(
-                        Style {
-                            right: Val::Percent(0.0),
-                            ..style.clone()
-                        },
+                        hud_layout.place(HudPanel::Blocked, style),
                         EaseFunction::QuadraticOut,
                         EasingType::Once {
                             duration: Duration::from_secs_f32(1.0),
@@ -0,0 +1,329 @@
+//! Replay of the last attempt at a level, for designers looking at what went wrong. Obstacles
+//! placed and removed while playing are recorded, then on the win or lost screen press F10 to open
+//! a timeline that replays them in the headless simulation.
+//!
+//! Space pauses, the arrows step one frame back or forward, and clicking or dragging on the
+//! timeline jumps there. The physics can't be rewound, going back starts again from the last
+//! checkpoint before, taken every few seconds as the replay goes.
+
+use std::collections::HashMap;
+
+use bevy::{color::palettes, prelude::*, ui::RelativeCursorPosition};
+
+use crate::{
+    assets::GameAssets,
+    levels::Level,
+    play::{placed_bonus, GameInProgress, SpawnedObstacle},
+    simulation::{Action, ActionKind, Checkpoint, Simulation, TIMESTEP},
+    GameState,
+};
+
+/// Seconds between two checkpoints of the replay
+const CHECKPOINT_INTERVAL: f32 = 5.0;
+
+/// Obstacles placed and removed during the last attempt
+#[derive(Resource)]
+struct Recording {
+    level: usize,
+    actions: Vec<Action>,
+    /// Cell of each obstacle in the level, to know where one was removed from
+    obstacles: HashMap<Entity, (usize, usize)>,
+}
+
+#[derive(Resource)]
+struct Timeline {
+    level: Level,
+    actions: Vec<Action>,
+    simulation: Simulation,
+    /// Sorted by time, the first one after [`CHECKPOINT_INTERVAL`]
+    checkpoints: Vec<Checkpoint>,
+    /// Seconds the attempt lasted, until the replay is decided
+    end: f32,
+    paused: bool,
+    /// Time not simulated yet, less than a step
+    leftover: f32,
+}
+
+impl Timeline {
+    fn new(level: &Level, actions: &[Action], end: f32) -> Self {
+        Timeline {
+            level: level.clone(),
+            actions: actions.to_vec(),
+            simulation: Simulation::new(level, actions),
+            checkpoints: vec![],
+            end,
+            paused: false,
+            leftover: 0.0,
+        }
+    }
+
+    fn step(&mut self) {
+        self.simulation.step();
+        let time = self.simulation.time();
+        if self.simulation.verdict().is_some() {
+            self.end = time;
+        } else {
+            self.end = self.end.max(time);
+        }
+        let last = self.checkpoints.last().map_or(0.0, Checkpoint::time);
+        if time + TIMESTEP / 2.0 >= last + CHECKPOINT_INTERVAL {
+            self.checkpoints.push(self.simulation.checkpoint());
+        }
+    }
+
+    /// Simulate until `time`, starting again from a checkpoint to go back
+    fn seek(&mut self, time: f32) {
+        let time = time.clamp(0.0, self.end);
+        if time < self.simulation.time() {
+            self.simulation = match self
+                .checkpoints
+                .iter()
+                .rev()
+                .find(|checkpoint| checkpoint.time() <= time)
+            {
+                Some(checkpoint) => Simulation::resume(&self.level, &self.actions, checkpoint),
+                None => Simulation::new(&self.level, &self.actions),
+            };
+        }
+        while self.simulation.time() + TIMESTEP / 2.0 < time && self.simulation.verdict().is_none()
+        {
+            self.step();
+        }
+        self.leftover = 0.0;
+    }
+}
+
+#[derive(Component)]
+struct TimelineBar;
+
+#[derive(Component)]
+struct TimelineProgress;
+
+#[derive(Component)]
+struct TimelineText;
+
+pub struct Plugin;
+impl bevy::prelude::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::InGame), start_recording)
+            .add_systems(OnExit(GameState::Win), close_timeline)
+            .add_systems(OnExit(GameState::Lost), close_timeline)
+            .add_systems(Update, record.run_if(in_state(GameState::InGame)))
+            .add_systems(
+                Update,
+                (
+                    toggle_timeline,
+                    (control_timeline, play_timeline, display_timeline)
+                        .chain()
+                        .run_if(resource_exists::<Timeline>),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Win).or_else(in_state(GameState::Lost))),
+            );
+    }
+}
+
+fn start_recording(mut commands: Commands, game: Res<GameInProgress>) {
+    commands.insert_resource(Recording {
+        level: game.level,
+        actions: vec![],
+        obstacles: HashMap::new(),
+    });
+}
+
+fn record(
+    mut recording: ResMut<Recording>,
+    game: Res<GameInProgress>,
+    placed: Query<(Entity, &Transform, &SpawnedObstacle), Added<SpawnedObstacle>>,
+    mut removed: RemovedComponents<SpawnedObstacle>,
+) {
+    let at = game.elapsed;
+    for entity in removed.read() {
+        if let Some(cell) = recording.obstacles.remove(&entity) {
+            recording.actions.push(Action {
+                at,
+                cell,
+                kind: ActionKind::Remove,
+            });
+        }
+    }
+    for (entity, transform, obstacle) in &placed {
+        let (cell, bonus) = placed_bonus((transform, obstacle));
+        recording.obstacles.insert(entity, cell);
+        recording.actions.push(Action {
+            at,
+            cell,
+            kind: ActionKind::Place(Some(bonus)),
+        });
+    }
+}
+
+fn close_timeline(mut commands: Commands) {
+    commands.remove_resource::<Timeline>();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn toggle_timeline(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    timeline: Option<Res<Timeline>>,
+    bar: Query<Entity, With<TimelineBar>>,
+    recording: Option<Res<Recording>>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+    game: Res<GameInProgress>,
+    state: Res<State<GameState>>,
+) {
+    if !keyboard.just_pressed(KeyCode::F10) {
+        return;
+    }
+    if timeline.is_some() {
+        commands.remove_resource::<Timeline>();
+        for entity in &bar {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+    let Some(recording) = recording else {
+        info!("No attempt recorded yet");
+        return;
+    };
+    let Some(level) = levels.get(&assets.levels[recording.level]) else {
+        return;
+    };
+    // simulated as it's played, the attempt gives how long it should last
+    commands.insert_resource(Timeline::new(level, &recording.actions, game.elapsed));
+
+    commands
+        .spawn((
+            NodeBundle {
+                z_index: ZIndex::Global(2),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(10.0),
+                    left: Val::Percent(10.0),
+                    width: Val::Percent(80.0),
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                ..default()
+            },
+            TimelineBar,
+            StateScoped(*state.get()),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                TimelineText,
+            ));
+            parent
+                .spawn((
+                    ButtonBundle {
+                        background_color: palettes::tailwind::GRAY_800.into(),
+                        style: Style {
+                            width: Val::Percent(100.0),
+                            height: Val::Px(20.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    RelativeCursorPosition::default(),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            background_color: palettes::tailwind::INDIGO_400.into(),
+                            style: Style {
+                                width: Val::Percent(0.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        TimelineProgress,
+                    ));
+                });
+        });
+}
+
+fn control_timeline(
+    mut timeline: ResMut<Timeline>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    bar: Query<(&Interaction, &RelativeCursorPosition)>,
+) {
+    if keyboard.just_pressed(KeyCode::Space) {
+        timeline.paused = !timeline.paused;
+    }
+    if keyboard.just_pressed(KeyCode::ArrowRight) {
+        timeline.paused = true;
+        timeline.step();
+    }
+    if keyboard.just_pressed(KeyCode::ArrowLeft) {
+        timeline.paused = true;
+        let time = timeline.simulation.time() - TIMESTEP;
+        timeline.seek(time);
+    }
+    for (interaction, cursor) in &bar {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if let Some(position) = cursor.normalized {
+            let time = position.x.clamp(0.0, 1.0) * timeline.end;
+            timeline.seek(time);
+        }
+    }
+}
+
+fn play_timeline(mut timeline: ResMut<Timeline>, time: Res<Time>) {
+    if timeline.paused {
+        return;
+    }
+    timeline.leftover += time.delta_seconds();
+    while timeline.leftover >= TIMESTEP {
+        timeline.leftover -= TIMESTEP;
+        timeline.step();
+    }
+}
+
+fn display_timeline(
+    timeline: Res<Timeline>,
+    mut progress: Query<&mut Style, With<TimelineProgress>>,
+    mut text: Query<&mut Text, With<TimelineText>>,
+    mut gizmos: Gizmos,
+) {
+    let current = timeline.simulation.time();
+    for mut style in &mut progress {
+        style.width = Val::Percent(current / timeline.end.max(TIMESTEP) * 100.0);
+    }
+    for mut text in &mut text {
+        let game = timeline.simulation.game();
+        text.sections[0].value = format!(
+            "{:.2}s / {:.2}s{} - scores {:?}, {} lost{}",
+            current,
+            timeline.end,
+            if timeline.paused { " (paused)" } else { "" },
+            game.scores,
+            game.lost_hobbits,
+            timeline
+                .simulation
+                .verdict()
+                .map(|verdict| format!(" - {:?}", verdict))
+                .unwrap_or_default()
+        );
+    }
+    for (position, tired) in timeline.simulation.hobbits() {
+        let color = if tired {
+            palettes::tailwind::AMBER_400
+        } else {
+            palettes::tailwind::GREEN_400
+        };
+        gizmos.circle(Vec3::new(position.x, 1.0, position.y), Dir3::Y, 0.5, color);
+    }
+}
@@ -0,0 +1,209 @@
+//! Autosave of the level being played, so that it can be resumed when the game was closed in the
+//! middle of it. That happens a lot on the web, where closing the tab doesn't ask anything. The
+//! level is saved every few seconds, and the menu offers to resume it on the next launch.
+
+use std::time::Duration;
+
+use bevy::{prelude::*, time::common_conditions::on_real_timer};
+use bevy_pkv::PkvStore;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assets::GameAssets,
+    dialog::{AskConfirmation, ConfirmedAction},
+    difficulty::DifficultyMode,
+    game::{restore_hobbit, ActiveLevel, ChestTreasures, Hobbit, PlayLoop, SavedHobbit, Spawner},
+    levels::{Bonus, Level},
+    mutators::Mutators,
+    new_game_plus::NewGamePlus,
+    play::{self, placed_bonus, GameInProgress, SpawnedObstacle},
+    GameState,
+};
+
+/// Time between two autosaves
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+/// Key of the store with the autosave
+const RESUME_KEY: &str = "resume";
+
+/// A level being played, as it was saved
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    level: usize,
+    /// Hash of the level when it was saved, the autosave can't be resumed once it changed
+    level_hash: u64,
+    /// How the level was played, see [`play::played_level`]
+    mutators: Mutators,
+    new_game_plus: bool,
+    /// The difficulty is a setting, the autosave can't be resumed once it changed
+    difficulty: DifficultyMode,
+    scores: Vec<u32>,
+    lost_hobbits: u32,
+    spawned_hobbits: u32,
+    elapsed: f32,
+    escorted: Option<bool>,
+    /// Treasures left in each chest
+    chests: Vec<Option<u32>>,
+    obstacles: Vec<((usize, usize), Bonus)>,
+    hobbits: Vec<SavedHobbit>,
+    /// Seconds left before the next hobbits of each spawn point
+    spawn_timers: Vec<Option<f32>>,
+    spawned: usize,
+}
+
+impl Snapshot {
+    /// The autosave, if there is one for a level that didn't change since, in the same difficulty
+    pub fn load(store: &PkvStore, assets: &GameAssets, levels: &Assets<Level>) -> Option<Self> {
+        let snapshot = store.get::<Snapshot>(RESUME_KEY).ok()?;
+        let level = levels.get(assets.levels.get(snapshot.level)?)?;
+        let difficulty = DifficultyMode::load(store).unwrap_or_default();
+        (level.hash == snapshot.level_hash && difficulty == snapshot.difficulty).then_some(snapshot)
+    }
+
+    /// Play the level again with the mutators and New Game+ it was saved with
+    pub fn restore_settings(&self, mutators: &mut Mutators, new_game_plus: &mut NewGamePlus) {
+        mutators.clone_from(&self.mutators);
+        new_game_plus.active = self.new_game_plus;
+    }
+
+    /// Progress in the level when it was saved
+    pub fn game(&self) -> GameInProgress {
+        GameInProgress {
+            level: self.level,
+            scores: self.scores.clone(),
+            lost_hobbits: self.lost_hobbits,
+            spawned_hobbits: self.spawned_hobbits,
+            elapsed: self.elapsed,
+            escorted: self.escorted,
+            ..default()
+        }
+    }
+
+    pub(crate) fn obstacles(&self) -> &[((usize, usize), Bonus)] {
+        &self.obstacles
+    }
+}
+
+/// Autosave being resumed, restored once its level is loaded
+#[derive(Resource, Deref)]
+pub struct ResumedLevel(pub Snapshot);
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Menu), offer_resume)
+            .add_systems(
+                Update,
+                autosave.run_if(
+                    in_state(GameState::InGame)
+                        .and_then(not(resource_exists::<ResumedLevel>))
+                        .and_then(on_real_timer(AUTOSAVE_INTERVAL)),
+                ),
+            )
+            .add_systems(
+                Last,
+                autosave.run_if(in_state(GameState::InGame).and_then(on_event::<AppExit>())),
+            )
+            // the level was won, lost or left, there is nothing to resume
+            .add_systems(OnExit(GameState::InGame), |mut store: ResMut<PkvStore>| {
+                let _ = store.remove(RESUME_KEY);
+            })
+            // after the first step of the level, so that its chests and spawn points are ready
+            .add_systems(
+                FixedUpdate,
+                (play::restore_obstacles, restore_level)
+                    .chain()
+                    .after(PlayLoop::Movement)
+                    .run_if(
+                        resource_exists::<ResumedLevel>.and_then(resource_exists::<ActiveLevel>),
+                    ),
+            );
+    }
+}
+
+/// Ask once per launch whether to resume the level that was being played
+fn offer_resume(
+    store: Res<PkvStore>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+    mut ask_confirmation: EventWriter<AskConfirmation>,
+    mut asked: Local<bool>,
+) {
+    if *asked {
+        return;
+    }
+    *asked = true;
+    if let Some(snapshot) = Snapshot::load(&store, &assets, &levels) {
+        ask_confirmation.send(AskConfirmation {
+            message: format!("Resume level {} where you left it?", snapshot.level + 1),
+            confirm: "Resume".to_string(),
+            action: ConfirmedAction::ResumeLevel,
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn autosave(
+    mut store: ResMut<PkvStore>,
+    game: Res<GameInProgress>,
+    level: Option<Res<ActiveLevel>>,
+    mutators: Res<Mutators>,
+    new_game_plus: Res<NewGamePlus>,
+    difficulty: Res<DifficultyMode>,
+    chests: Res<ChestTreasures>,
+    spawner: Res<Spawner>,
+    obstacles: Query<(&Transform, &SpawnedObstacle)>,
+    hobbits: Query<(&Transform, &Hobbit)>,
+) {
+    // the level is being left
+    let Some(level) = level else {
+        return;
+    };
+    let snapshot = Snapshot {
+        level: game.level,
+        level_hash: level.0.hash,
+        mutators: mutators.clone(),
+        new_game_plus: new_game_plus.active,
+        difficulty: *difficulty,
+        scores: game.scores.clone(),
+        lost_hobbits: game.lost_hobbits,
+        spawned_hobbits: game.spawned_hobbits,
+        elapsed: game.elapsed,
+        escorted: game.escorted,
+        chests: chests.0.clone(),
+        obstacles: obstacles.iter().map(placed_bonus).collect(),
+        hobbits: hobbits
+            .iter()
+            .map(|(transform, hobbit)| hobbit.save(transform))
+            .collect(),
+        spawn_timers: spawner
+            .timers
+            .iter()
+            .map(|timer| timer.as_ref().map(Timer::remaining_secs))
+            .collect(),
+        spawned: spawner.spawned,
+    };
+    if let Err(err) = store.set(RESUME_KEY, &snapshot) {
+        warn!("failed to autosave the level: {}", err);
+    }
+}
+
+fn restore_level(
+    mut commands: Commands,
+    resumed: Res<ResumedLevel>,
+    assets: Res<GameAssets>,
+    mut chests: ResMut<ChestTreasures>,
+    mut spawner: ResMut<Spawner>,
+) {
+    info!("resuming level {}", resumed.level);
+    chests.0.clone_from(&resumed.chests);
+    spawner.timers = resumed
+        .spawn_timers
+        .iter()
+        .map(|left| left.map(|left| Timer::from_seconds(left, TimerMode::Once)))
+        .collect();
+    spawner.spawned = resumed.spawned;
+    for hobbit in &resumed.hobbits {
+        restore_hobbit(&mut commands, &assets, GameState::InGame, hobbit);
+    }
+    commands.remove_resource::<ResumedLevel>();
+}
@@ -0,0 +1,264 @@
+//! Progress of the player kept in the [`PkvStore`], gathered as a JSON object to move it to another
+//! store. Settings stay with each device.
+//!
+//! The progress is saved with the version of its format, and upgraded by [`migrate`] when the game
+//! starts or a progress is imported.
+
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+use serde_json::{Map, Value};
+
+use crate::{
+    achievements::{Achievement, Achievements},
+    assets::GameAssets,
+    difficulty::{DifficultyMode, DIFFICULTY_KEY},
+    layouts::{self, LastLayout, LevelRecord},
+    levels::Level,
+    mutators::Mutator,
+    new_game_plus::{NewGamePlus, NEW_GAME_PLUS_KEY},
+    stats::{self, Stats},
+    GameProgress,
+};
+
+/// Version of the format of the progress, a migration is added each time it changes
+const SAVE_VERSION: u32 = 2;
+/// Key of the store with the version of the format the progress is in
+const VERSION_KEY: &str = "save-version";
+
+/// Upgrades of the progress, the one at index `n` goes from version `n` to `n + 1`
+const MIGRATIONS: [fn(&mut PkvStore) -> Result<(), String>; SAVE_VERSION as usize] =
+    [level_records, level_3_tutorial];
+
+/// Upgrade the progress to the current format. Old keys are left in place so that a previous
+/// version of the game still finds its progress.
+pub fn migrate(store: &mut PkvStore) {
+    let mut version = store.get::<u32>(VERSION_KEY).unwrap_or(0);
+    while let Some(migration) = MIGRATIONS.get(version as usize) {
+        if let Err(err) = migration(store) {
+            error!(
+                "failed to upgrade the progress from version {}: {}",
+                version, err
+            );
+            return;
+        }
+        version += 1;
+        info!("progress upgraded to version {}", version);
+        let _ = store.set(VERSION_KEY, &version);
+    }
+}
+
+/// Version 0 kept the last level unlocked under `progress` and the version won of each level under
+/// `won-{index}`, they are merged in a [`LevelRecord`] per level
+fn level_records(store: &mut PkvStore) -> Result<(), String> {
+    let unlocked = store.get::<u32>("progress").unwrap_or(1) as usize;
+    for level in 0.. {
+        let won = store.get::<u64>(&format!("won-{}", level)).ok();
+        if level > unlocked && won.is_none() {
+            break;
+        }
+        LevelRecord {
+            unlocked: level <= unlocked || won.is_some(),
+            won,
+            skipped: false,
+        }
+        .save(store, level)
+        .map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+/// Index of `03.level`, given a tutorial instead of its message in version 1
+const LEVEL_3: usize = 3;
+const LEVEL_3_FILE: &str = "levels/03.level";
+/// Hash of level 3 before and after its tutorial
+const LEVEL_3_HASHES: (u64, u64) = (0x10a9b3fd0a4a0bb0, 0x768b68223e0eed8b);
+
+/// The tutorial of level 3 changed its hash, what was won or saved on the previous version is
+/// moved to the new one
+fn level_3_tutorial(store: &mut PkvStore) -> Result<(), String> {
+    let (before, after) = LEVEL_3_HASHES;
+    let mut record = LevelRecord::load(store, LEVEL_3);
+    if record.won == Some(before) {
+        record.won = Some(after);
+        record.save(store, LEVEL_3).map_err(|err| err.to_string())?;
+    }
+    let layouts_key = layouts::layouts_key(LEVEL_3);
+    let mut layouts = layouts::load_layouts(store, &layouts_key);
+    if layouts.iter().any(|layout| layout.level_hash == before) {
+        for layout in layouts
+            .iter_mut()
+            .filter(|layout| layout.level_hash == before)
+        {
+            layout.level_hash = after;
+        }
+        layouts::set_layouts(store, &layouts_key, &layouts).map_err(|err| err.to_string())?;
+    }
+    if let Some(solution) =
+        layouts::last_solution(store, LEVEL_3_FILE).filter(|solution| solution.level_hash == before)
+    {
+        layouts::record_solution(
+            store,
+            LEVEL_3_FILE,
+            &LastLayout {
+                layouts_key,
+                level_hash: after,
+                cells: solution.cells,
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Keys of the progress, for the levels currently loaded
+pub fn progress_keys(assets: &GameAssets, levels: &Assets<Level>) -> Vec<String> {
+    let mut keys = vec![
+        VERSION_KEY.to_string(),
+        "daily".to_string(),
+        layouts::SKIPS_USED_KEY.to_string(),
+        DIFFICULTY_KEY.to_string(),
+        NEW_GAME_PLUS_KEY.to_string(),
+    ];
+    keys.extend(stats::KEYS.iter().map(|key| key.to_string()));
+    keys.extend(
+        Mutator::all()
+            .iter()
+            .map(|mutator| mutator.key().to_string()),
+    );
+    keys.extend(
+        Achievement::all()
+            .iter()
+            .map(|achievement| achievement.key().to_string()),
+    );
+    for (index, handle) in assets.levels.iter().enumerate() {
+        if let Some(level) = levels.get(handle) {
+            keys.push(format!("failures-{}", level.file));
+        }
+        if assets.is_campaign(index) {
+            keys.push(layouts::record_key(index));
+            keys.push(layouts::layouts_key(index));
+        } else if let Some(level) = levels.get(handle) {
+            keys.push(layouts::custom_layouts_key(&level.file));
+            for prefix in ["won-custom", "solution", "attempted", "abandoned"] {
+                keys.push(format!("{}-{}", prefix, level.file));
+            }
+        }
+    }
+    keys
+}
+
+/// The progress saved under the given keys, as a JSON object
+pub fn export(store: &PkvStore, keys: &[String]) -> String {
+    let values = keys
+        .iter()
+        .filter_map(|key| {
+            store
+                .get::<Value>(key)
+                .ok()
+                .map(|value| (key.clone(), value))
+        })
+        .collect::<Map<_, _>>();
+    Value::Object(values).to_string()
+}
+
+/// Save the progress of a JSON object from [`export`], replacing the values of the same keys, and
+/// upgrade it if it was exported by a previous version of the game
+pub fn import(store: &mut PkvStore, json: &str) -> Result<(), String> {
+    let Value::Object(values) = serde_json::from_str(json).map_err(|err| err.to_string())? else {
+        return Err("not a saved progress".to_string());
+    };
+    // exports from before versioning don't have one
+    let version = values.get(VERSION_KEY).and_then(Value::as_u64).unwrap_or(0);
+    if version > SAVE_VERSION as u64 {
+        return Err("saved by a newer version of the game".to_string());
+    }
+    for (key, value) in values {
+        store.set(&key, &value).map_err(|err| err.to_string())?;
+    }
+    store
+        .set(VERSION_KEY, &(version as u32))
+        .map_err(|err| err.to_string())?;
+    migrate(store);
+    Ok(())
+}
+
+/// Name of the file the progress is exported to
+const EXPORT_FILE: &str = "progress.json";
+
+/// File the progress is exported to and imported from
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_path() -> Option<std::path::PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("ThereAndBackAgain").join(EXPORT_FILE))
+}
+
+/// Write an exported progress to a file, returns where it can be found
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_export(json: &str) -> Result<String, String> {
+    let path = export_path().ok_or("no data directory")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    std::fs::write(&path, json).map_err(|err| err.to_string())?;
+    Ok(path.display().to_string())
+}
+
+/// Let the browser download an exported progress, returns the name of the file
+#[cfg(target_arch = "wasm32")]
+pub fn write_export(json: &str) -> Result<String, String> {
+    use wasm_bindgen::{JsCast, JsValue};
+
+    let js_error = |err: JsValue| format!("{:?}", err);
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("no document")?;
+    let blob = web_sys::Blob::new_with_str_sequence(&js_sys::Array::of1(&JsValue::from_str(json)))
+        .map_err(js_error)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(js_error)?;
+    let link = document
+        .create_element("a")
+        .map_err(js_error)?
+        .dyn_into::<web_sys::HtmlAnchorElement>()
+        .map_err(|_| "not a link")?;
+    link.set_href(&url);
+    link.set_download(EXPORT_FILE);
+    link.click();
+    let _ = web_sys::Url::revoke_object_url(&url);
+    Ok(EXPORT_FILE.to_string())
+}
+
+/// Read a progress exported by [`write_export`]
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_import() -> Result<String, String> {
+    let path = export_path().ok_or("no data directory")?;
+    std::fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// Ask the player to paste an exported progress, there is no file to read on the web
+#[cfg(target_arch = "wasm32")]
+pub fn read_import() -> Result<String, String> {
+    web_sys::window()
+        .ok_or("no window")?
+        .prompt_with_message("Paste the content of your exported progress")
+        .map_err(|err| format!("{:?}", err))?
+        .filter(|json| !json.trim().is_empty())
+        .ok_or_else(|| "cancelled".to_string())
+}
+
+/// Load again the resources kept from the progress, after an import
+pub fn reload(commands: &mut Commands, store: &PkvStore) {
+    commands.insert_resource(GameProgress::load(store));
+    commands.insert_resource(Stats::load(store));
+    commands.insert_resource(Achievements::load(store));
+    commands.insert_resource(DifficultyMode::load(store).unwrap_or_default());
+    commands.insert_resource(NewGamePlus::load(store));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_3_is_migrated_to_its_current_hash() {
+        let level = Level::parse(include_str!("../assets/levels/03.level"), LEVEL_3_FILE).unwrap();
+        assert_eq!(level.hash, LEVEL_3_HASHES.1);
+    }
+}
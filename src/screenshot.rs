@@ -0,0 +1,76 @@
+//! Screenshots of the window, taken with F12. They are saved with a timestamp in their name next to
+//! the exported pictures of levels, or downloaded by the browser on the web.
+
+use bevy::{prelude::*, render::view::screenshot::ScreenshotManager, window::PrimaryWindow};
+
+use crate::ui;
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, take_screenshot);
+    }
+}
+
+/// Directory where screenshots are saved
+#[cfg(not(target_arch = "wasm32"))]
+pub fn screenshots_dir() -> Option<std::path::PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("ThereAndBackAgain").join("screenshots"))
+}
+
+/// Path to save a screenshot to, the browser only keeps the name of the file
+fn screenshot_path(name: &str) -> Result<std::path::PathBuf, String> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let dir = screenshots_dir().ok_or("no data directory")?;
+        std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        Ok(dir.join(name))
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        Ok(std::path::PathBuf::from(name))
+    }
+}
+
+fn take_screenshot(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    window: Query<Entity, With<PrimaryWindow>>,
+    mut screenshots: ResMut<ScreenshotManager>,
+    mut confirmation: Local<Option<String>>,
+) {
+    // shown the frame after the screenshot, so that it's not in it
+    if let Some(message) = confirmation.take() {
+        ui::spawn_toast(&mut commands, message);
+    }
+    if !keyboard.just_pressed(KeyCode::F12) {
+        return;
+    }
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default();
+    let name = format!("screenshot-{}.png", timestamp);
+    let saved = screenshot_path(&name).and_then(|path| {
+        screenshots
+            .save_screenshot_to_disk(window, &path)
+            .map_err(|_| "a screenshot is already being taken".to_string())
+            .map(|_| path)
+    });
+    *confirmation = Some(match saved {
+        Ok(path) => {
+            if cfg!(target_arch = "wasm32") {
+                format!("Screenshot downloaded as {}", name)
+            } else {
+                format!("Screenshot saved to {}", path.display())
+            }
+        }
+        Err(err) => {
+            warn!("failed to take a screenshot: {}", err);
+            "Screenshot failed".to_string()
+        }
+    });
+}
@@ -0,0 +1,637 @@
+use bevy::{color::palettes, prelude::*, ui::RelativeCursorPosition};
+use bevy_pkv::PkvStore;
+
+use crate::{
+    assets::GameAssets,
+    audio::AudioTrigger,
+    camera::{MAX_ZOOM, MIN_ZOOM},
+    dialog::{AskConfirmation, Confirmed, ConfirmedAction},
+    display::{FPS_CAPS, RESOLUTIONS},
+    levels::Level,
+    menu::SwitchState,
+    save, ui, GameState,
+};
+
+const CURRENT_STATE: GameState = GameState::Settings;
+
+/// Scales of the interface and of the text the player can pick from, in order
+const SCALES: [f32; 4] = [0.75, 1.0, 1.25, 1.5];
+
+/// Player preferences, saved between sessions
+#[derive(Resource)]
+pub struct Settings {
+    /// Display an indicator above each hobbit with what it's doing
+    pub hobbit_indicators: bool,
+    /// Draw the silhouette of hobbits hidden behind walls
+    pub hobbit_silhouettes: bool,
+    /// Celebrate every few treasures brought back home
+    pub celebrations: bool,
+    /// Zoom of the camera while playing, as a share of the distance of the default view
+    pub camera_zoom: f32,
+    /// Volume of the soundtrack, between 0 and 1
+    pub music_volume: f32,
+    /// Volume of sound effects, between 0 and 1
+    pub effects_volume: f32,
+    /// Colors that can be told apart with color blindness, for the interface and the grates
+    pub colorblind: bool,
+    /// Shorten or skip the big animations, for players sensitive to motion
+    pub reduced_motion: bool,
+    /// Scale of the whole interface, one of [`SCALES`]
+    pub ui_scale: f32,
+    /// Multiplier of the size of text, over the interface scale, one of [`SCALES`]
+    pub font_scale: f32,
+    /// Show a caption for each sound effect
+    pub captions: bool,
+    /// Borderless fullscreen window
+    pub fullscreen: bool,
+    /// Size of the window, index in [`RESOLUTIONS`]
+    pub resolution: usize,
+    /// On the web, the canvas fits the page instead of keeping the size of the resolution
+    pub fit_canvas: bool,
+    /// Wait for the display to refresh before showing a frame
+    pub vsync: bool,
+    /// Maximum frames per second, one of [`FPS_CAPS`], 0 for no limit
+    pub fps_cap: u32,
+}
+
+impl Settings {
+    pub fn load(store: &PkvStore) -> Self {
+        Settings {
+            hobbit_indicators: store.get::<bool>("hobbit-indicators").unwrap_or(true),
+            hobbit_silhouettes: store.get::<bool>("hobbit-silhouettes").unwrap_or(true),
+            celebrations: store.get::<bool>("celebrations").unwrap_or(true),
+            camera_zoom: store
+                .get::<f32>("camera-zoom")
+                .unwrap_or(1.0)
+                .clamp(MIN_ZOOM, MAX_ZOOM),
+            music_volume: store
+                .get::<f32>("music-volume")
+                .unwrap_or(1.0)
+                .clamp(0.0, 1.0),
+            effects_volume: store
+                .get::<f32>("effects-volume")
+                .unwrap_or(1.0)
+                .clamp(0.0, 1.0),
+            colorblind: store.get::<bool>("colorblind").unwrap_or(false),
+            reduced_motion: store.get::<bool>("reduced-motion").unwrap_or(false),
+            ui_scale: store
+                .get::<f32>("ui-scale")
+                .unwrap_or(1.0)
+                .clamp(SCALES[0], SCALES[SCALES.len() - 1]),
+            font_scale: store
+                .get::<f32>("font-scale")
+                .unwrap_or(1.0)
+                .clamp(SCALES[0], SCALES[SCALES.len() - 1]),
+            captions: store.get::<bool>("captions").unwrap_or(false),
+            fullscreen: store.get::<bool>("fullscreen").unwrap_or(false),
+            resolution: store
+                .get::<usize>("resolution")
+                .unwrap_or(0)
+                .min(RESOLUTIONS.len() - 1),
+            fit_canvas: cfg!(target_arch = "wasm32")
+                && store.get::<bool>("fit-canvas").unwrap_or(true),
+            vsync: store
+                .get::<bool>("vsync")
+                .unwrap_or(!cfg!(feature = "debug")),
+            fps_cap: store
+                .get::<u32>("fps-cap")
+                .ok()
+                .filter(|cap| FPS_CAPS.contains(cap))
+                .unwrap_or(0),
+        }
+    }
+
+    pub fn save(&self, store: &mut PkvStore) {
+        let _ = store.set("hobbit-indicators", &self.hobbit_indicators);
+        let _ = store.set("hobbit-silhouettes", &self.hobbit_silhouettes);
+        let _ = store.set("celebrations", &self.celebrations);
+        let _ = store.set("camera-zoom", &self.camera_zoom);
+        let _ = store.set("music-volume", &self.music_volume);
+        let _ = store.set("effects-volume", &self.effects_volume);
+        let _ = store.set("colorblind", &self.colorblind);
+        let _ = store.set("reduced-motion", &self.reduced_motion);
+        let _ = store.set("ui-scale", &self.ui_scale);
+        let _ = store.set("font-scale", &self.font_scale);
+        let _ = store.set("captions", &self.captions);
+        let _ = store.set("fullscreen", &self.fullscreen);
+        let _ = store.set("resolution", &self.resolution);
+        let _ = store.set("fit-canvas", &self.fit_canvas);
+        let _ = store.set("vsync", &self.vsync);
+        let _ = store.set("fps-cap", &self.fps_cap);
+    }
+}
+
+pub struct Plugin;
+impl bevy::prelude::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(CURRENT_STATE), spawn_settings)
+            .add_systems(
+                Update,
+                (
+                    button_system,
+                    progress_buttons,
+                    drag_volume_sliders,
+                    fullscreen_label.run_if(resource_changed::<Settings>),
+                    crate::menu::change_state_after_event,
+                )
+                    .run_if(in_state(CURRENT_STATE)),
+            );
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+fn percent(scale: f32) -> String {
+    format!("{}%", (scale * 100.0).round())
+}
+
+/// Scale after `scale`, going back to the smallest after the largest
+fn next_scale(scale: f32) -> f32 {
+    SCALES
+        .into_iter()
+        .find(|candidate| *candidate > scale + 0.01)
+        .unwrap_or(SCALES[0])
+}
+
+fn resolution(index: usize) -> String {
+    let (width, height) = RESOLUTIONS[index];
+    format!("{}x{}", width, height)
+}
+
+fn fit_canvas(fit: bool) -> &'static str {
+    if fit {
+        "Fit page"
+    } else {
+        "Fixed size"
+    }
+}
+
+fn fps_cap(cap: u32) -> String {
+    if cap == 0 {
+        "Off".to_string()
+    } else {
+        format!("{} FPS", cap)
+    }
+}
+
+fn spawn_settings(mut commands: Commands, settings: Res<Settings>) {
+    info!("Loading screen");
+
+    commands
+        .spawn((
+            ui::screen_root(20.0),
+            MenuItem::Root,
+            StateScoped(CURRENT_STATE),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((ui::panel(60.0, 80.0), MenuItem::Panel))
+                .with_children(|parent| {
+                    parent.spawn(ui::title("Settings"));
+
+                    spawn_volume_slider(
+                        parent,
+                        "Music volume",
+                        settings.music_volume,
+                        VolumeSlider::Music,
+                    );
+                    spawn_volume_slider(
+                        parent,
+                        "Effects volume",
+                        settings.effects_volume,
+                        VolumeSlider::Effects,
+                    );
+
+                    let buttons = [
+                        (
+                            SettingsButton::HobbitIndicators,
+                            format!("Hobbit indicators: {}", on_off(settings.hobbit_indicators)),
+                        ),
+                        (
+                            SettingsButton::HobbitSilhouettes,
+                            format!(
+                                "Hobbit silhouettes: {}",
+                                on_off(settings.hobbit_silhouettes)
+                            ),
+                        ),
+                        (
+                            SettingsButton::Celebrations,
+                            format!("Celebrations: {}", on_off(settings.celebrations)),
+                        ),
+                        (
+                            SettingsButton::Colorblind,
+                            format!("Colorblind mode: {}", on_off(settings.colorblind)),
+                        ),
+                        (
+                            SettingsButton::ReducedMotion,
+                            format!("Reduced motion: {}", on_off(settings.reduced_motion)),
+                        ),
+                        (
+                            SettingsButton::UiScale,
+                            format!("Interface size: {}", percent(settings.ui_scale)),
+                        ),
+                        (
+                            SettingsButton::FontScale,
+                            format!("Text size: {}", percent(settings.font_scale)),
+                        ),
+                        (
+                            SettingsButton::Captions,
+                            format!("Captions: {}", on_off(settings.captions)),
+                        ),
+                        (
+                            SettingsButton::Fullscreen,
+                            format!("Fullscreen: {}", on_off(settings.fullscreen)),
+                        ),
+                        // the size of the window is up to the browser on the web
+                        if cfg!(target_arch = "wasm32") {
+                            (
+                                SettingsButton::FitCanvas,
+                                format!("Canvas: {}", fit_canvas(settings.fit_canvas)),
+                            )
+                        } else {
+                            (
+                                SettingsButton::Resolution,
+                                format!("Resolution: {}", resolution(settings.resolution)),
+                            )
+                        },
+                        (
+                            SettingsButton::Vsync,
+                            format!("VSync: {}", on_off(settings.vsync)),
+                        ),
+                        (
+                            SettingsButton::FpsCap,
+                            format!("Frame limit: {}", fps_cap(settings.fps_cap)),
+                        ),
+                        (
+                            SettingsButton::ExportProgress,
+                            "Export progress".to_string(),
+                        ),
+                        (
+                            SettingsButton::ImportProgress,
+                            "Import progress".to_string(),
+                        ),
+                        (SettingsButton::Controls, "Controls".to_string()),
+                        (SettingsButton::Back, "Back to Menu".to_string()),
+                    ];
+                    // two columns, the panel is too short for a button per line
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                flex_wrap: FlexWrap::Wrap,
+                                justify_content: JustifyContent::Center,
+                                width: Val::Percent(95.0),
+                                margin: UiRect::top(Val::Percent(3.0)),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for (button, label) in buttons {
+                                ui::spawn_button(
+                                    parent,
+                                    label,
+                                    Vec2::new(280.0, 34.0),
+                                    UiRect::all(Val::Px(6.0)),
+                                )
+                                .insert((MenuItem::Button, button));
+                            }
+                        });
+                });
+        });
+}
+
+fn spawn_volume_slider(parent: &mut ChildBuilder, label: &str, volume: f32, slider: VolumeSlider) {
+    parent.spawn(TextBundle {
+        text: Text::from_section(
+            label,
+            TextStyle {
+                font_size: 20.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        ),
+        style: Style {
+            margin: UiRect::top(Val::Percent(3.0)),
+            ..default()
+        },
+        ..default()
+    });
+    parent
+        .spawn((
+            ButtonBundle {
+                background_color: palettes::tailwind::GRAY_800.into(),
+                border_radius: BorderRadius::all(Val::Px(5.0)),
+                style: Style {
+                    width: Val::Px(300.0),
+                    height: Val::Px(20.0),
+                    margin: UiRect::top(Val::Px(5.0)),
+                    ..default()
+                },
+                ..default()
+            },
+            RelativeCursorPosition::default(),
+            slider,
+        ))
+        .with_children(|parent| {
+            parent.spawn(NodeBundle {
+                background_color: palettes::tailwind::INDIGO_400.into(),
+                border_radius: BorderRadius::all(Val::Px(5.0)),
+                style: Style {
+                    width: Val::Percent(volume * 100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                ..default()
+            });
+        });
+}
+
+#[derive(Component, PartialEq, Eq)]
+enum MenuItem {
+    Root,
+    Panel,
+    Button,
+}
+
+#[derive(Component, Clone, Copy)]
+enum SettingsButton {
+    HobbitIndicators,
+    HobbitSilhouettes,
+    Celebrations,
+    Colorblind,
+    ReducedMotion,
+    UiScale,
+    FontScale,
+    Captions,
+    Fullscreen,
+    Resolution,
+    FitCanvas,
+    Vsync,
+    FpsCap,
+    ExportProgress,
+    ImportProgress,
+    Controls,
+    Back,
+}
+
+/// Volume changed by clicking or dragging on the bar
+#[derive(Component, Clone, Copy)]
+enum VolumeSlider {
+    Music,
+    Effects,
+}
+
+fn drag_volume_sliders(
+    sliders: Query<(
+        Entity,
+        &Interaction,
+        &RelativeCursorPosition,
+        &VolumeSlider,
+        &Children,
+    )>,
+    mut fills: Query<&mut Style>,
+    mut settings: ResMut<Settings>,
+    mut store: ResMut<PkvStore>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+    mut dragging: Local<Option<Entity>>,
+) {
+    for (entity, interaction, cursor, slider, children) in &sliders {
+        if *interaction != Interaction::Pressed {
+            if *dragging == Some(entity) {
+                // released, save once instead of on every frame of the drag
+                *dragging = None;
+                settings.save(&mut store);
+                // let the player hear the new volume
+                audio_trigger.send(AudioTrigger::Click);
+            }
+            continue;
+        }
+        *dragging = Some(entity);
+        let Some(position) = cursor.normalized else {
+            continue;
+        };
+        let volume = position.x.clamp(0.0, 1.0);
+        match slider {
+            VolumeSlider::Music => settings.music_volume = volume,
+            VolumeSlider::Effects => settings.effects_volume = volume,
+        }
+        if let Ok(mut style) = fills.get_mut(children[0]) {
+            style.width = Val::Percent(volume * 100.0);
+        }
+    }
+}
+
+/// Fullscreen can also be toggled with F11, keep its button up to date
+fn fullscreen_label(
+    settings: Res<Settings>,
+    buttons: Query<(&SettingsButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    for (button, children) in &buttons {
+        if !matches!(button, SettingsButton::Fullscreen) {
+            continue;
+        }
+        if let Ok(mut text) = texts.get_mut(children[0]) {
+            text.sections[0].value = format!("Fullscreen: {}", on_off(settings.fullscreen));
+        }
+    }
+}
+
+/// Export the progress to move it to another device, or import it back once confirmed
+fn progress_buttons(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &SettingsButton), Changed<Interaction>>,
+    mut ask_confirmation: EventWriter<AskConfirmation>,
+    mut confirmed: EventReader<Confirmed>,
+    mut store: ResMut<PkvStore>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            SettingsButton::ExportProgress => {
+                let json = save::export(&store, &save::progress_keys(&assets, &levels));
+                let message = match save::write_export(&json) {
+                    Ok(location) => format!("Progress exported to {}", location),
+                    Err(err) => {
+                        warn!("failed to export the progress: {}", err);
+                        "Export failed".to_string()
+                    }
+                };
+                ui::spawn_toast(&mut commands, message).insert(StateScoped(CURRENT_STATE));
+            }
+            SettingsButton::ImportProgress => {
+                ask_confirmation.send(AskConfirmation {
+                    message: "Replace your progress with the imported one?".to_string(),
+                    confirm: "Import".to_string(),
+                    action: ConfirmedAction::ImportProgress,
+                });
+            }
+            _ => (),
+        }
+    }
+
+    if !confirmed
+        .read()
+        .any(|confirmed| confirmed.0 == ConfirmedAction::ImportProgress)
+    {
+        return;
+    }
+    let message = match save::read_import().and_then(|json| save::import(&mut store, &json)) {
+        Ok(()) => {
+            save::reload(&mut commands, &store);
+            "Progress imported".to_string()
+        }
+        Err(err) => {
+            warn!("failed to import the progress: {}", err);
+            "Import failed".to_string()
+        }
+    };
+    ui::spawn_toast(&mut commands, message).insert(StateScoped(CURRENT_STATE));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn button_system(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &SettingsButton, &Children), Changed<Interaction>>,
+    mut next_state: EventWriter<SwitchState>,
+    ui_items: Query<(Entity, &MenuItem)>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+    mut settings: ResMut<Settings>,
+    mut store: ResMut<PkvStore>,
+    mut texts: Query<&mut Text>,
+) {
+    for (interaction, button, children) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        audio_trigger.send(AudioTrigger::Click);
+
+        match button {
+            SettingsButton::HobbitIndicators => {
+                settings.hobbit_indicators = !settings.hobbit_indicators;
+                settings.save(&mut store);
+                if let Ok(mut text) = texts.get_mut(children[0]) {
+                    text.sections[0].value =
+                        format!("Hobbit indicators: {}", on_off(settings.hobbit_indicators));
+                }
+            }
+            SettingsButton::HobbitSilhouettes => {
+                settings.hobbit_silhouettes = !settings.hobbit_silhouettes;
+                settings.save(&mut store);
+                if let Ok(mut text) = texts.get_mut(children[0]) {
+                    text.sections[0].value = format!(
+                        "Hobbit silhouettes: {}",
+                        on_off(settings.hobbit_silhouettes)
+                    );
+                }
+            }
+            SettingsButton::Celebrations => {
+                settings.celebrations = !settings.celebrations;
+                settings.save(&mut store);
+                if let Ok(mut text) = texts.get_mut(children[0]) {
+                    text.sections[0].value =
+                        format!("Celebrations: {}", on_off(settings.celebrations));
+                }
+            }
+            SettingsButton::Colorblind => {
+                settings.colorblind = !settings.colorblind;
+                settings.save(&mut store);
+                if let Ok(mut text) = texts.get_mut(children[0]) {
+                    text.sections[0].value =
+                        format!("Colorblind mode: {}", on_off(settings.colorblind));
+                }
+            }
+            SettingsButton::ReducedMotion => {
+                settings.reduced_motion = !settings.reduced_motion;
+                settings.save(&mut store);
+                if let Ok(mut text) = texts.get_mut(children[0]) {
+                    text.sections[0].value =
+                        format!("Reduced motion: {}", on_off(settings.reduced_motion));
+                }
+            }
+            SettingsButton::UiScale => {
+                settings.ui_scale = next_scale(settings.ui_scale);
+                settings.save(&mut store);
+                if let Ok(mut text) = texts.get_mut(children[0]) {
+                    text.sections[0].value =
+                        format!("Interface size: {}", percent(settings.ui_scale));
+                }
+            }
+            SettingsButton::FontScale => {
+                settings.font_scale = next_scale(settings.font_scale);
+                settings.save(&mut store);
+                if let Ok(mut text) = texts.get_mut(children[0]) {
+                    text.sections[0].value = format!("Text size: {}", percent(settings.font_scale));
+                }
+            }
+            SettingsButton::Captions => {
+                settings.captions = !settings.captions;
+                settings.save(&mut store);
+                if let Ok(mut text) = texts.get_mut(children[0]) {
+                    text.sections[0].value = format!("Captions: {}", on_off(settings.captions));
+                }
+            }
+            SettingsButton::Fullscreen => {
+                settings.fullscreen = !settings.fullscreen;
+                settings.save(&mut store);
+            }
+            SettingsButton::Resolution => {
+                settings.resolution = (settings.resolution + 1) % RESOLUTIONS.len();
+                settings.save(&mut store);
+                if let Ok(mut text) = texts.get_mut(children[0]) {
+                    text.sections[0].value =
+                        format!("Resolution: {}", resolution(settings.resolution));
+                }
+            }
+            SettingsButton::FitCanvas => {
+                settings.fit_canvas = !settings.fit_canvas;
+                settings.save(&mut store);
+                if let Ok(mut text) = texts.get_mut(children[0]) {
+                    text.sections[0].value = format!("Canvas: {}", fit_canvas(settings.fit_canvas));
+                }
+            }
+            SettingsButton::Vsync => {
+                settings.vsync = !settings.vsync;
+                settings.save(&mut store);
+                if let Ok(mut text) = texts.get_mut(children[0]) {
+                    text.sections[0].value = format!("VSync: {}", on_off(settings.vsync));
+                }
+            }
+            SettingsButton::FpsCap => {
+                let next = FPS_CAPS
+                    .iter()
+                    .position(|cap| *cap == settings.fps_cap)
+                    .map_or(0, |index| (index + 1) % FPS_CAPS.len());
+                settings.fps_cap = FPS_CAPS[next];
+                settings.save(&mut store);
+                if let Ok(mut text) = texts.get_mut(children[0]) {
+                    text.sections[0].value = format!("Frame limit: {}", fps_cap(settings.fps_cap));
+                }
+            }
+            // handled by `progress_buttons`
+            SettingsButton::ExportProgress | SettingsButton::ImportProgress => (),
+            SettingsButton::Controls | SettingsButton::Back => {
+                next_state.send(SwitchState(match button {
+                    SettingsButton::Controls => GameState::Controls,
+                    _ => GameState::Menu,
+                }));
+
+                for (entity, kind) in &ui_items {
+                    if *kind == MenuItem::Root {
+                        ui::slide_out(&mut commands, entity, 20.0);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,483 @@
+//! Headless simulation of a level, to check it can be won without running the game.
+//!
+//! The level is played by the systems of the game in their own world, with
+//! [`GameplayPlugin`](crate::game::GameplayPlugin) and the physics on top of `MinimalPlugins`.
+//! Each step advances the time by exactly one fixed timestep, and the randomness is seeded from
+//! the level, so the outcome only depends on the level and the actions.
+//!
+//! The physics can't be rewound, to go back a simulation is started again from a [`Checkpoint`].
+
+use std::time::Duration;
+
+use avian3d::prelude::PhysicsPlugins;
+use bevy::{
+    diagnostic::DiagnosticsStore, ecs::world::CommandQueue, hierarchy::HierarchyPlugin, prelude::*,
+    scene::ScenePlugin, state::app::StatesPlugin, time::TimeUpdateStrategy,
+    transform::TransformPlugin,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    assets::GameAssets,
+    celebrations::Milestone,
+    conditions::Conditions,
+    game::{
+        restore_hobbit, ActiveLevel, ChestTreasures, GameRng, GameplayPlugin, Hobbit, NavMesh,
+        SavedHobbit, Spawner,
+    },
+    levels::{spawn_level, Bonus, Level, Tile},
+    play::{
+        obstacle_body, obstacle_cell, update_progress, BlockedCells, GameInProgress,
+        SpawnedObstacle,
+    },
+    GameState,
+};
+
+/// Seconds simulated by each step, the fixed timestep of the gameplay
+pub const TIMESTEP: f32 = 1.0 / 64.0;
+/// Levels still undecided after this many seconds are given up
+const MAX_DURATION: f32 = 600.0;
+
+/// Something done by the player during the simulation
+#[derive(Debug, Clone, Copy)]
+pub struct Action {
+    /// Seconds the hobbits have been going, as in [`GameInProgress::elapsed`]
+    pub at: f32,
+    pub cell: (usize, usize),
+    pub kind: ActionKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionKind {
+    /// Place a bonus on the cell: this one, or the first of the level still free
+    Place(Option<Bonus>),
+    /// Remove the bonus on the cell, it can be placed again
+    Remove,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Won,
+    Lost,
+    /// Still undecided after [`MAX_DURATION`]
+    OutOfTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub verdict: Verdict,
+    /// Treasures brought back by each team
+    pub scores: Vec<u32>,
+    pub lost_hobbits: u32,
+    /// Seconds until the level was decided
+    pub elapsed: f32,
+}
+
+/// Marks everything spawned for the level in the simulation
+#[derive(Component)]
+struct SimulatedLevel;
+
+/// State of a simulation after a step, to play on from there. Like an autosave (see
+/// [`crate::resume`]), hobbits find their path again once restored.
+#[derive(Clone)]
+pub struct Checkpoint {
+    time: f32,
+    next_action: usize,
+    placed: Vec<((usize, usize), Bonus)>,
+    game: GameInProgress,
+    rng: StdRng,
+    /// Treasures left in each chest
+    chests: Vec<Option<u32>>,
+    spawn_timers: Vec<Option<Timer>>,
+    spawned: usize,
+    hobbits: Vec<SavedHobbit>,
+}
+
+impl Checkpoint {
+    /// Start again from a checkpoint of a simulation of the same level and actions
+    pub fn resume(level: &Level, actions: &[Action], checkpoint: &Checkpoint) -> Self {
+        let mut simulation = Simulation::new(level, actions);
+        // a first step sets up the chests and spawn points, then they are overwritten
+        simulation.world.run_schedule(Main);
+        simulation.world.clear_trackers();
+
+        let world = &mut simulation.world;
+        *world.resource_mut::<GameInProgress>() = checkpoint.game.clone();
+        world.resource_mut::<GameRng>().0 = checkpoint.rng.clone();
+        world
+            .resource_mut::<ChestTreasures>()
+            .0
+            .clone_from(&checkpoint.chests);
+        {
+            let mut spawner = world.resource_mut::<Spawner>();
+            spawner.timers.clone_from(&checkpoint.spawn_timers);
+            spawner.spawned = checkpoint.spawned;
+        }
+        for (cell, bonus) in &checkpoint.placed {
+            let body = obstacle_body(world.resource::<GameAssets>(), *bonus, *cell);
+            world.spawn(body);
+        }
+        world.resource_scope(|world, mut cells: Mut<BlockedCells>| {
+            cells.update(
+                &mut world.resource_mut::<NavMesh>(),
+                level,
+                &checkpoint.placed,
+            );
+        });
+        let mut queue = CommandQueue::default();
+        {
+            let mut commands = Commands::new(&mut queue, world);
+            for hobbit in &checkpoint.hobbits {
+                restore_hobbit(
+                    &mut commands,
+                    world.resource::<GameAssets>(),
+                    GameState::InGame,
+                    hobbit,
+                );
+            }
+        }
+        queue.apply(world);
+        world.clear_trackers();
+
+        simulation.next_action = checkpoint.next_action;
+        simulation.placed.clone_from(&checkpoint.placed);
+        simulation.time = checkpoint.time;
+        simulation.cache_hobbits();
+        simulation
+    }
+
+    /// Save the state after the last step, to [resume](Simulation::resume) from there later
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        let hobbits = self
+            .world
+            .query::<(&Transform, &Hobbit)>()
+            .iter(&self.world)
+            .map(|(transform, hobbit)| hobbit.save(transform))
+            .collect();
+        let spawner = self.world.resource::<Spawner>();
+        Checkpoint {
+            time: self.time,
+            next_action: self.next_action,
+            placed: self.placed.clone(),
+            game: self.game().clone(),
+            rng: self.world.resource::<GameRng>().0.clone(),
+            chests: self.world.resource::<ChestTreasures>().0.clone(),
+            spawn_timers: spawner.timers.clone(),
+            spawned: spawner.spawned,
+            hobbits,
+        }
+    }
+
+    /// Seconds since the start of the level
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+}
+
+/// A level being played, one step at a time. Going back means starting a new one from the start
+/// or from a [`Checkpoint`], the physics can't be rewound.
+pub struct Simulation {
+    /// World of the app running the game systems, stepped by hand
+    world: World,
+    level: Level,
+    conditions: Conditions,
+    /// All the actions, in the order they happen
+    actions: Vec<Action>,
+    /// Index in `actions` of the next one to do
+    next_action: usize,
+    placed: Vec<((usize, usize), Bonus)>,
+    /// Position on the ground of each hobbit after the last step, and whether it's going home
+    hobbits: Vec<(Vec2, bool)>,
+    time: f32,
+    verdict: Option<Verdict>,
+}
+
+impl Simulation {
+    pub fn new(level: &Level, actions: &[Action]) -> Self {
+        let mut actions = actions.to_vec();
+        // stable, actions done at the same time keep their order
+        actions.sort_by(|a, b| a.at.total_cmp(&b.at));
+
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            StatesPlugin,
+            AssetPlugin {
+                watch_for_changes_override: Some(false),
+                ..default()
+            },
+            ScenePlugin,
+            PhysicsPlugins::default(),
+            GameplayPlugin,
+        ))
+        .init_asset::<Mesh>()
+        .insert_state(GameState::InGame)
+        .init_resource::<DiagnosticsStore>()
+        .add_event::<Milestone>()
+        .add_systems(Update, update_progress)
+        // exactly one gameplay step each update, whatever the time it takes to run
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            TIMESTEP,
+        )))
+        .insert_resource(GameAssets::default())
+        .insert_resource(GameRng(StdRng::seed_from_u64(level.hash)))
+        .insert_resource(GameInProgress {
+            bonus: level.bonus.clone(),
+            ..default()
+        })
+        .insert_resource(ActiveLevel(level.clone()));
+        app.finish();
+        app.cleanup();
+
+        let mut world = std::mem::take(app.world_mut());
+        let mut queue = CommandQueue::default();
+        let (_, mut mesh) = spawn_level(
+            &mut Commands::new(&mut queue, &world),
+            level,
+            world.resource::<GameAssets>(),
+            SimulatedLevel,
+        );
+        queue.apply(&mut world);
+        world.insert_resource(BlockedCells::new(&mut mesh, level));
+        world.insert_resource(NavMesh(mesh));
+        // the first update starts the clock, nothing moves yet
+        world.run_schedule(Main);
+        world.clear_trackers();
+
+        Simulation {
+            world,
+            level: level.clone(),
+            conditions: Conditions::from_objectives(&level.objectives),
+            actions,
+            next_action: 0,
+            placed: vec![],
+            hobbits: vec![],
+            time: 0.0,
+            verdict: None,
+        }
+    }
+
+    /// Seconds since the start of the level
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Whether the level was won or lost, once it's decided
+    pub fn verdict(&self) -> Option<Verdict> {
+        self.verdict
+    }
+
+    pub fn game(&self) -> &GameInProgress {
+        self.world.resource::<GameInProgress>()
+    }
+
+    /// Position on the ground of each hobbit, and whether it's going home
+    pub fn hobbits(&self) -> impl Iterator<Item = (Vec2, bool)> + '_ {
+        self.hobbits.iter().copied()
+    }
+
+    /// Advance by one fixed timestep, until the level is decided
+    pub fn step(&mut self) {
+        if self.verdict.is_some() {
+            return;
+        }
+
+        // what the player did since the last step, before the next step as in the game
+        while let Some(action) = self
+            .actions
+            .get(self.next_action)
+            .filter(|action| action.at <= self.time)
+            .copied()
+        {
+            self.next_action += 1;
+            self.apply(action);
+        }
+
+        self.time += TIMESTEP;
+        self.world.resource_mut::<GameInProgress>().elapsed = self.time;
+        self.world.run_schedule(Main);
+        self.world.clear_trackers();
+        self.cache_hobbits();
+
+        let game = self.world.resource::<GameInProgress>();
+        if self.conditions.is_won(game, &self.level) {
+            self.verdict = Some(Verdict::Won);
+        } else if self.conditions.is_lost(game, &self.level) {
+            self.verdict = Some(Verdict::Lost);
+        } else if self.time >= MAX_DURATION {
+            self.verdict = Some(Verdict::OutOfTime);
+        }
+    }
+
+    fn apply(&mut self, action: Action) {
+        match action.kind {
+            ActionKind::Place(wanted) => {
+                let free = self.level.floors[0]
+                    .get(action.cell.1)
+                    .and_then(|row| row.get(action.cell.0))
+                    == Some(&Tile::Floor)
+                    && self.placed.iter().all(|(cell, _)| *cell != action.cell);
+                let (true, Some(bonus)) = (free, self.free_bonus(wanted)) else {
+                    return;
+                };
+                self.placed.push((action.cell, bonus));
+                let body = obstacle_body(self.world.resource::<GameAssets>(), bonus, action.cell);
+                self.world.spawn(body);
+            }
+            ActionKind::Remove => {
+                let Some(index) = self
+                    .placed
+                    .iter()
+                    .position(|(cell, _)| *cell == action.cell)
+                else {
+                    return;
+                };
+                self.placed.remove(index);
+                let obstacles = self
+                    .world
+                    .query_filtered::<(Entity, &Transform), With<SpawnedObstacle>>()
+                    .iter(&self.world)
+                    .filter(|(_, transform)| obstacle_cell(transform) == action.cell)
+                    .map(|(entity, _)| entity)
+                    .collect::<Vec<_>>();
+                for entity in obstacles {
+                    self.world.despawn(entity);
+                }
+            }
+        }
+        self.world
+            .resource_scope(|world, mut cells: Mut<BlockedCells>| {
+                cells.update(
+                    &mut world.resource_mut::<NavMesh>(),
+                    &self.level,
+                    &self.placed,
+                );
+            });
+    }
+
+    /// Bonus of the level not placed yet, the wanted kind or the first one
+    fn free_bonus(&self, wanted: Option<Bonus>) -> Option<Bonus> {
+        let mut free = self.level.bonus.clone();
+        for (_, bonus) in &self.placed {
+            if let Some(index) = free.iter().position(|free| free == bonus) {
+                free.remove(index);
+            }
+        }
+        free.into_iter()
+            .find(|bonus| wanted.map_or(true, |wanted| wanted == *bonus))
+    }
+
+    fn cache_hobbits(&mut self) {
+        self.hobbits = self
+            .world
+            .query::<(&Transform, &Hobbit)>()
+            .iter(&self.world)
+            .map(|(transform, hobbit)| (transform.translation.xz(), hobbit.is_tired()))
+            .collect();
+    }
+}
+
+/// Play the level, placing bonuses as the actions say, until it's won or lost
+pub fn simulate_level(level: &Level, actions: &[Action]) -> Outcome {
+    let mut simulation = Simulation::new(level, actions);
+    let verdict = loop {
+        simulation.step();
+        if let Some(verdict) = simulation.verdict {
+            break verdict;
+        }
+    };
+
+    let game = simulation.game();
+    Outcome {
+        verdict,
+        scores: game.scores.clone(),
+        lost_hobbits: game.lost_hobbits,
+        elapsed: simulation.time,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shipped_level(file: &str, content: &str) -> Level {
+        Level::parse(content, file).expect("shipped levels should be valid")
+    }
+
+    fn at_start(cells: &[(usize, usize)]) -> Vec<Action> {
+        cells
+            .iter()
+            .map(|cell| Action {
+                at: 0.0,
+                cell: *cell,
+                kind: ActionKind::Place(None),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn first_level_is_won_without_bonus() {
+        let level = shipped_level("01.level", include_str!("../assets/levels/01.level"));
+        let outcome = simulate_level(&level, &[]);
+        assert_eq!(outcome.verdict, Verdict::Won, "{:?}", outcome);
+        assert_eq!(outcome.scores, vec![1]);
+    }
+
+    #[test]
+    fn in_and_out_grates_keep_hobbits_apart() {
+        let level = shipped_level("02.level", include_str!("../assets/levels/02.level"));
+        let outcome = simulate_level(&level, &[]);
+        assert_eq!(outcome.verdict, Verdict::Won, "{:?}", outcome);
+    }
+
+    #[test]
+    fn obstacle_in_the_middle_splits_the_corridor() {
+        let level = shipped_level("03.level", include_str!("../assets/levels/03.level"));
+        let outcome = simulate_level(&level, &at_start(&[(6, 1)]));
+        assert_eq!(outcome.verdict, Verdict::Won, "{:?}", outcome);
+    }
+
+    #[test]
+    fn bonus_off_the_floor_is_not_placed() {
+        let level = shipped_level("03.level", include_str!("../assets/levels/03.level"));
+        let mut simulation = Simulation::new(&level, &at_start(&[(6, 0), (6, 1)]));
+        simulation.step();
+        assert_eq!(simulation.placed.len(), 1);
+        assert_eq!(simulation.placed[0].0, (6, 1));
+    }
+
+    #[test]
+    fn removed_bonus_can_be_placed_again() {
+        let level = shipped_level("03.level", include_str!("../assets/levels/03.level"));
+        let mut actions = at_start(&[(6, 1)]);
+        actions.push(Action {
+            at: 0.0,
+            cell: (6, 1),
+            kind: ActionKind::Remove,
+        });
+        actions.extend(at_start(&[(7, 1)]));
+        let mut simulation = Simulation::new(&level, &actions);
+        simulation.step();
+        assert_eq!(simulation.placed.len(), 1);
+        assert_eq!(simulation.placed[0].0, (7, 1));
+    }
+
+    #[test]
+    fn resumed_checkpoint_keeps_the_progress() {
+        let level = shipped_level("01.level", include_str!("../assets/levels/01.level"));
+        let mut simulation = Simulation::new(&level, &[]);
+        while simulation.hobbits().count() == 0 {
+            simulation.step();
+        }
+        let checkpoint = simulation.checkpoint();
+        let mut resumed = Simulation::resume(&level, &[], &checkpoint);
+        assert_eq!(resumed.time(), simulation.time());
+        assert_eq!(resumed.hobbits().count(), simulation.hobbits().count());
+        while resumed.verdict().is_none() {
+            resumed.step();
+        }
+        assert_eq!(resumed.verdict(), Some(Verdict::Won));
+    }
+}
@@ -0,0 +1,177 @@
+//! Lifetime statistics of the player, counted over every level played and saved between sessions,
+//! and the screen showing them.
+
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+
+use crate::{
+    audio::AudioTrigger, game::GameEvent, menu::SwitchState, play::SpawnedObstacle, ui, GameState,
+};
+
+const CURRENT_STATE: GameState = GameState::Statistics;
+
+/// Keys the stats are saved under
+pub(crate) const KEYS: [&str; 5] = [
+    "stats-hobbits-saved",
+    "stats-hobbits-lost",
+    "stats-treasures",
+    "stats-playtime",
+    "obstacles-placed",
+];
+
+/// Counted over every level played
+#[derive(Resource, Default)]
+pub struct Stats {
+    /// Hobbits that made it back home
+    pub hobbits_saved: u32,
+    pub hobbits_lost: u32,
+    /// Treasures brought back home
+    pub treasures: u32,
+    /// Seconds spent playing levels
+    pub playtime: f32,
+    pub obstacles_placed: u32,
+}
+
+impl Stats {
+    pub fn load(store: &PkvStore) -> Self {
+        Stats {
+            hobbits_saved: store.get::<u32>("stats-hobbits-saved").unwrap_or(0),
+            hobbits_lost: store.get::<u32>("stats-hobbits-lost").unwrap_or(0),
+            treasures: store.get::<u32>("stats-treasures").unwrap_or(0),
+            playtime: store.get::<f32>("stats-playtime").unwrap_or(0.0),
+            obstacles_placed: store.get::<u32>("obstacles-placed").unwrap_or(0),
+        }
+    }
+
+    pub fn save(&self, store: &mut PkvStore) {
+        let _ = store.set("stats-hobbits-saved", &self.hobbits_saved);
+        let _ = store.set("stats-hobbits-lost", &self.hobbits_lost);
+        let _ = store.set("stats-treasures", &self.treasures);
+        let _ = store.set("stats-playtime", &self.playtime);
+        let _ = store.set("obstacles-placed", &self.obstacles_placed);
+    }
+}
+
+pub struct Plugin;
+impl bevy::prelude::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (count_game_events, count_obstacles, count_playtime)
+                .run_if(in_state(GameState::InGame)),
+        )
+        // counted in memory while playing, saved once the level is left
+        .add_systems(
+            OnExit(GameState::InGame),
+            |stats: Res<Stats>, mut store: ResMut<PkvStore>| stats.save(&mut store),
+        )
+        .add_systems(OnEnter(CURRENT_STATE), spawn_statistics)
+        .add_systems(
+            Update,
+            (button_system, crate::menu::change_state_after_event).run_if(in_state(CURRENT_STATE)),
+        );
+    }
+}
+
+fn count_game_events(mut game_events: EventReader<GameEvent>, mut stats: ResMut<Stats>) {
+    for event in game_events.read() {
+        match event {
+            GameEvent::Home { .. } => stats.hobbits_saved += 1,
+            GameEvent::CollidedWithHobbit { .. } => stats.hobbits_lost += 1,
+            GameEvent::HomeWithTreasure { .. } => stats.treasures += 1,
+            _ => (),
+        }
+    }
+}
+
+fn count_obstacles(placed: Query<(), Added<SpawnedObstacle>>, mut stats: ResMut<Stats>) {
+    let count = placed.iter().count() as u32;
+    if count != 0 {
+        stats.obstacles_placed += count;
+    }
+}
+
+fn count_playtime(mut stats: ResMut<Stats>, time: Res<Time<Real>>) {
+    // don't mark the stats as changed every frame
+    stats.bypass_change_detection().playtime += time.delta_seconds();
+}
+
+fn playtime(seconds: f32) -> String {
+    let minutes = (seconds / 60.0) as u32;
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+fn spawn_statistics(mut commands: Commands, stats: Res<Stats>) {
+    info!("Loading screen");
+
+    commands
+        .spawn((
+            ui::screen_root(30.0),
+            MenuItem::Root,
+            StateScoped(CURRENT_STATE),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((ui::panel(40.0, 60.0), MenuItem::Panel))
+                .with_children(|parent| {
+                    parent.spawn(ui::title("Statistics"));
+                    for line in [
+                        format!("Hobbits saved: {}", stats.hobbits_saved),
+                        format!("Hobbits lost: {}", stats.hobbits_lost),
+                        format!("Treasures collected: {}", stats.treasures),
+                        format!("Obstacles placed: {}", stats.obstacles_placed),
+                        format!("Time played: {}", playtime(stats.playtime)),
+                    ] {
+                        parent.spawn(TextBundle {
+                            text: Text::from_section(
+                                line,
+                                TextStyle {
+                                    font_size: 30.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                },
+                            ),
+                            ..default()
+                        });
+                    }
+
+                    ui::spawn_button(
+                        parent,
+                        "Back to Menu",
+                        Vec2::new(200.0, 40.0),
+                        UiRect::top(Val::Percent(10.0)),
+                    )
+                    .insert(MenuItem::Button);
+                });
+        });
+}
+
+#[derive(Component, PartialEq, Eq)]
+enum MenuItem {
+    Root,
+    Panel,
+    Button,
+}
+
+fn button_system(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, Changed<Interaction>>,
+    mut next_state: EventWriter<SwitchState>,
+    ui_items: Query<(Entity, &MenuItem)>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+) {
+    for interaction in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        audio_trigger.send(AudioTrigger::Click);
+
+        next_state.send(SwitchState(GameState::Menu));
+
+        for (entity, kind) in &ui_items {
+            if *kind == MenuItem::Root {
+                ui::slide_out(&mut commands, entity, 30.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Statistics on every attempt at a level, for designers tuning the difficulty curve. Press F9 to
+//! print a report of all levels.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+
+use crate::{
+    assets::GameAssets,
+    game::{ColliderKind, GameEvent},
+    play::{obstacle_cell, GameInProgress, SpawnedObstacle},
+    GameState,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+const REPORT_FILE: &str = "difficulty-report.txt";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Won,
+    Lost,
+    Abandoned,
+}
+
+/// Attempt at a level currently being played
+#[derive(Resource)]
+struct Attempt {
+    level: usize,
+    /// Hobbits lost by colliding with another hobbit
+    hobbit_collisions: u32,
+    /// Hobbits lost on blades
+    blade_hits: u32,
+    obstacles: Vec<(usize, usize)>,
+}
+
+pub struct Plugin;
+impl bevy::prelude::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::InGame), start_attempt)
+            .add_systems(OnEnter(GameState::Win), record_attempt(Outcome::Won))
+            .add_systems(OnEnter(GameState::Lost), record_attempt(Outcome::Lost))
+            .add_systems(OnEnter(GameState::Menu), record_attempt(Outcome::Abandoned))
+            .add_systems(
+                OnEnter(GameState::LevelSelect),
+                record_attempt(Outcome::Abandoned),
+            )
+            .add_systems(Update, track_attempt.run_if(in_state(GameState::InGame)))
+            .add_systems(Update, print_report);
+    }
+}
+
+fn key(level: usize) -> String {
+    format!("telemetry-{}", level)
+}
+
+fn start_attempt(mut commands: Commands, game: Res<GameInProgress>) {
+    commands.insert_resource(Attempt {
+        level: game.level,
+        hobbit_collisions: 0,
+        blade_hits: 0,
+        obstacles: vec![],
+    });
+}
+
+fn track_attempt(
+    attempt: Option<ResMut<Attempt>>,
+    mut game_events: EventReader<GameEvent>,
+    obstacles: Query<&Transform, With<SpawnedObstacle>>,
+) {
+    let Some(mut attempt) = attempt else {
+        return;
+    };
+    for event in game_events.read() {
+        match event {
+            GameEvent::CollidedWithHobbit {
+                with: ColliderKind::Hobbit,
+            } => attempt.hobbit_collisions += 1,
+            GameEvent::CollidedWithHobbit {
+                with: ColliderKind::Blade,
+            } => attempt.blade_hits += 1,
+            _ => (),
+        }
+    }
+    // obstacles are despawned when leaving the game, keep track of them while they're there
+    attempt.obstacles = obstacles.iter().map(obstacle_cell).collect();
+}
+
+fn record_attempt(
+    outcome: Outcome,
+) -> impl FnMut(Commands, Option<Res<Attempt>>, ResMut<PkvStore>) {
+    move |mut commands, attempt, mut store| {
+        let Some(attempt) = attempt else {
+            return;
+        };
+        let mut attempts = store
+            .get::<Vec<(u8, u32, u32, Vec<(u32, u32)>)>>(&key(attempt.level))
+            .unwrap_or_default();
+        attempts.push((
+            outcome as u8,
+            attempt.hobbit_collisions,
+            attempt.blade_hits,
+            attempt
+                .obstacles
+                .iter()
+                .map(|(x, y)| (*x as u32, *y as u32))
+                .collect(),
+        ));
+        let _ = store.set(&key(attempt.level), &attempts);
+        commands.remove_resource::<Attempt>();
+    }
+}
+
+fn level_report(store: &PkvStore, level: usize) -> Option<String> {
+    let attempts = store
+        .get::<Vec<(u8, u32, u32, Vec<(u32, u32)>)>>(&key(level))
+        .unwrap_or_default();
+    if attempts.is_empty() {
+        return None;
+    }
+
+    let count = |outcome: Outcome| {
+        attempts
+            .iter()
+            .filter(|(o, ..)| *o == outcome as u8)
+            .count()
+    };
+    let wins = count(Outcome::Won);
+    let hobbit_collisions: u32 = attempts.iter().map(|(_, hobbits, ..)| hobbits).sum();
+    let blade_hits: u32 = attempts.iter().map(|(_, _, blades, _)| blades).sum();
+
+    let mut placements = HashMap::new();
+    for (.., obstacles) in &attempts {
+        for cell in obstacles {
+            *placements.entry(*cell).or_insert(0) += 1;
+        }
+    }
+    let mut placements = placements.into_iter().collect::<Vec<_>>();
+    placements.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut report = format!("Level {}\n", level);
+    report.push_str(&format!(
+        "  attempts: {} ({} won, {} lost, {} abandoned)\n",
+        attempts.len(),
+        wins,
+        count(Outcome::Lost),
+        count(Outcome::Abandoned)
+    ));
+    if wins > 0 {
+        report.push_str(&format!(
+            "  average attempts per win: {:.1}\n",
+            attempts.len() as f32 / wins as f32
+        ));
+    }
+    report.push_str(&format!(
+        "  hobbits lost: {} to other hobbits, {} to blades ({:.1} per attempt)\n",
+        hobbit_collisions,
+        blade_hits,
+        (hobbit_collisions + blade_hits) as f32 / attempts.len() as f32
+    ));
+    if !placements.is_empty() {
+        report.push_str("  common obstacle placements:");
+        for ((x, y), times) in placements.iter().take(5) {
+            report.push_str(&format!(" ({}, {}) x{}", x, y, times));
+        }
+        report.push('\n');
+    }
+    Some(report)
+}
+
+fn print_report(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    store: Res<PkvStore>,
+    assets: Option<Res<GameAssets>>,
+) {
+    if !keyboard.just_pressed(KeyCode::F9) {
+        return;
+    }
+    let Some(assets) = assets else {
+        return;
+    };
+
+    // level 0 is the menu background
+    let report = (1..assets.custom_levels.start)
+        .filter_map(|level| level_report(&store, level))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if report.is_empty() {
+        info!("No attempts recorded yet");
+        return;
+    }
+    info!("Difficulty report\n{}", report);
+
+    #[cfg(not(target_arch = "wasm32"))]
+    match std::fs::write(REPORT_FILE, &report) {
+        Ok(()) => info!("Report written to {}", REPORT_FILE),
+        Err(err) => warn!("Couldn't write report to {}: {}", REPORT_FILE, err),
+    }
+}
@@ -0,0 +1,74 @@
+//! Touch controls, for the web build on tablets: tap to place the selected bonus, drag with a finger
+//! to pan the camera and pinch to zoom.
+
+use bevy::{input::InputSystem, prelude::*};
+
+use crate::{camera::CameraRig, game::PlayLoop, GameState};
+
+/// Pixels a finger can move and still count as a tap. Further, it's a drag.
+const TAP_DISTANCE: f32 = 12.0;
+/// Units the camera moves for each pixel dragged, at the default zoom
+const PAN_PER_PIXEL: f32 = 0.08;
+
+/// A tap on the level, outside of the buttons, at a position in the window
+#[derive(Event)]
+pub struct Tap(pub Vec2);
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Tap>().add_systems(
+            PreUpdate,
+            (detect_taps, drag_and_pinch)
+                .after(InputSystem)
+                .before(PlayLoop::Placement)
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+fn detect_taps(
+    touches: Res<Touches>,
+    buttons: Query<(&Node, &GlobalTransform, &ViewVisibility), With<Interaction>>,
+    mut taps: EventWriter<Tap>,
+) {
+    for touch in touches.iter_just_released() {
+        if touch.start_position().distance(touch.position()) > TAP_DISTANCE {
+            continue;
+        }
+        let position = touch.position();
+        // buttons already react to taps
+        let on_button = buttons.iter().any(|(node, transform, visibility)| {
+            visibility.get() && node.logical_rect(transform).contains(position)
+        });
+        if !on_button {
+            taps.send(Tap(position));
+        }
+    }
+}
+
+fn drag_and_pinch(touches: Res<Touches>, rig: Option<ResMut<CameraRig>>) {
+    let Some(mut rig) = rig else {
+        return;
+    };
+    match touches.iter().collect::<Vec<_>>().as_slice() {
+        [finger] if finger.start_position().distance(finger.position()) > TAP_DISTANCE => {
+            // the level follows the finger
+            let delta = finger.delta();
+            let speed = PAN_PER_PIXEL * rig.zoom;
+            rig.pan(Vec2::new(-delta.x, delta.y) * speed);
+        }
+        [first, second] => {
+            let before = first
+                .previous_position()
+                .distance(second.previous_position());
+            let now = first.position().distance(second.position());
+            if before > 0.0 && now > 0.0 {
+                // spreading the fingers brings the camera closer
+                let zoom = rig.zoom;
+                rig.zoom_by(zoom * before / now - zoom);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,378 @@
+//! Tutorials scripted step by step, played at the start of a level. Each step shows a prompt at the
+//! bottom of the window and waits for the player: to read it, to select a bonus whose button is
+//! highlighted, or to place it on a cell pointed at by an arrow. Hobbits wait for the end of the
+//! tutorial.
+//!
+//! Scripts are `.tutorial` files with a step per line, `\n` starting a new line in a prompt:
+//! - `say:prompt`, continued with a button
+//! - `select:bonus:prompt`, continued once the bonus is selected
+//! - `place:x,y:prompt`, continued once a bonus is placed on the cell
+//! - `wait:seconds`, continued after a pause
+//!
+//! Lines starting with `#` are comments.
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext, LoadState},
+    color::palettes,
+    prelude::*,
+    reflect::TypePath,
+};
+use thiserror::Error;
+
+use crate::{
+    accessibility::Announce,
+    assets::GameAssets,
+    audio::AudioTrigger,
+    game::SimulationSpeed,
+    levels::{Bonus, Level},
+    play::{obstacle_cell, ButtonAction, GameInProgress, SelectedBonus, SpawnedObstacle},
+    ui, GameState,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TutorialStep {
+    /// Show a prompt until the player continues
+    Say(String),
+    /// Highlight the buttons of a bonus until the player selects one
+    Select { bonus: Bonus, prompt: String },
+    /// Point at a cell until the player places a bonus on it
+    Place {
+        cell: (usize, usize),
+        prompt: String,
+    },
+    /// Wait for some seconds
+    Wait(f32),
+}
+
+impl TutorialStep {
+    fn prompt(&self) -> Option<&str> {
+        match self {
+            TutorialStep::Say(prompt)
+            | TutorialStep::Select { prompt, .. }
+            | TutorialStep::Place { prompt, .. } => Some(prompt),
+            TutorialStep::Wait(_) => None,
+        }
+    }
+}
+
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct TutorialScript(pub Vec<TutorialStep>);
+
+impl TutorialScript {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let prompt = |text: &str| text.replace("\\n", "\n");
+        content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let invalid = || format!("invalid step '{}'", line);
+                let (kind, rest) = line.split_once(':').ok_or_else(invalid)?;
+                Ok(match kind {
+                    "say" => TutorialStep::Say(prompt(rest)),
+                    "select" => {
+                        let (bonus, text) = rest.split_once(':').ok_or_else(invalid)?;
+                        let bonus = match bonus {
+                            "Obstacle" => Bonus::Obstacle,
+                            "Torch" => Bonus::Torch,
+                            _ => return Err(invalid()),
+                        };
+                        TutorialStep::Select {
+                            bonus,
+                            prompt: prompt(text),
+                        }
+                    }
+                    "place" => {
+                        let (cell, text) = rest.split_once(':').ok_or_else(invalid)?;
+                        let (x, y) = cell.split_once(',').ok_or_else(invalid)?;
+                        TutorialStep::Place {
+                            cell: (
+                                x.parse().map_err(|_| invalid())?,
+                                y.parse().map_err(|_| invalid())?,
+                            ),
+                            prompt: prompt(text),
+                        }
+                    }
+                    "wait" => TutorialStep::Wait(rest.parse().map_err(|_| invalid())?),
+                    _ => return Err(invalid()),
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(TutorialScript)
+    }
+}
+
+#[derive(Default)]
+struct TutorialAssetLoader;
+
+/// Possible errors that can be produced by [`TutorialAssetLoader`]
+#[non_exhaustive]
+#[derive(Debug, Error)]
+enum TutorialAssetLoaderError {
+    /// An [IO](std::io) Error
+    #[error("Could not load file: {0}")]
+    Io(#[from] std::io::Error),
+    /// A line of the script is not a step
+    #[error("Invalid tutorial: {0}")]
+    Invalid(String),
+}
+
+impl AssetLoader for TutorialAssetLoader {
+    type Asset = TutorialScript;
+    type Settings = ();
+    type Error = TutorialAssetLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut content = String::new();
+        reader.read_to_string(&mut content).await?;
+        TutorialScript::parse(&content).map_err(TutorialAssetLoaderError::Invalid)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tutorial"]
+    }
+}
+
+/// Tutorial of the level being played
+#[derive(Resource)]
+struct Tutorial {
+    script: Handle<TutorialScript>,
+    /// Index of the current step
+    step: usize,
+    /// Step shown in the prompt, once it's shown
+    shown: Option<usize>,
+    /// Time left on a [`TutorialStep::Wait`]
+    wait: Timer,
+}
+
+impl Tutorial {
+    fn current<'a>(&self, scripts: &'a Assets<TutorialScript>) -> Option<&'a TutorialStep> {
+        scripts
+            .get(&self.script)
+            .and_then(|script| script.0.get(self.step))
+    }
+}
+
+#[derive(Component)]
+struct TutorialPanel;
+
+/// Button continuing after a [`TutorialStep::Say`]
+#[derive(Component)]
+struct TutorialNext;
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<TutorialScript>()
+            .init_asset_loader::<TutorialAssetLoader>()
+            .add_systems(OnEnter(GameState::InGame), start_tutorial)
+            .add_systems(OnExit(GameState::InGame), |mut commands: Commands| {
+                commands.remove_resource::<Tutorial>()
+            })
+            .add_systems(
+                Update,
+                (show_step, advance_tutorial, highlight_bonus, point_at_cell)
+                    .chain()
+                    .run_if(in_state(GameState::InGame).and_then(resource_exists::<Tutorial>)),
+            );
+    }
+}
+
+fn start_tutorial(
+    mut commands: Commands,
+    game: Res<GameInProgress>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+    asset_server: Res<AssetServer>,
+    mut speed: ResMut<SimulationSpeed>,
+) {
+    let Some(path) = levels
+        .get(&assets.levels[game.level])
+        .and_then(|level| level.tutorial.clone())
+    else {
+        return;
+    };
+    // hobbits wait for the player to go through the tutorial
+    speed.paused = true;
+    commands.insert_resource(Tutorial {
+        script: asset_server.load(path),
+        step: 0,
+        shown: None,
+        wait: Timer::default(),
+    });
+}
+
+fn show_step(
+    mut commands: Commands,
+    mut tutorial: ResMut<Tutorial>,
+    scripts: Res<Assets<TutorialScript>>,
+    panels: Query<Entity, With<TutorialPanel>>,
+    mut speed: ResMut<SimulationSpeed>,
+    mut announce: EventWriter<Announce>,
+    asset_server: Res<AssetServer>,
+) {
+    if tutorial.shown == Some(tutorial.step) {
+        return;
+    }
+    if matches!(
+        asset_server.load_state(&tutorial.script),
+        LoadState::Failed(_)
+    ) {
+        warn!("failed to load the tutorial, skipping it");
+        speed.paused = false;
+        commands.remove_resource::<Tutorial>();
+        return;
+    }
+    let Some(script) = scripts.get(&tutorial.script) else {
+        // still loading
+        return;
+    };
+    for panel in &panels {
+        commands.entity(panel).despawn_recursive();
+    }
+    let Some(step) = script.0.get(tutorial.step) else {
+        // done, hobbits can go
+        speed.paused = false;
+        commands.remove_resource::<Tutorial>();
+        return;
+    };
+    tutorial.shown = Some(tutorial.step);
+    if let TutorialStep::Wait(seconds) = step {
+        tutorial.wait = Timer::from_seconds(*seconds, TimerMode::Once);
+    }
+    let Some(prompt) = step.prompt() else {
+        return;
+    };
+    announce.send(Announce(prompt.to_string()));
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Percent(12.0),
+                    left: Val::Percent(25.0),
+                    width: Val::Percent(50.0),
+                    padding: UiRect::all(Val::Px(15.0)),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                border_radius: BorderRadius::all(Val::Px(10.0)),
+                z_index: ZIndex::Global(1),
+                ..default()
+            },
+            ui::Panel,
+            TutorialPanel,
+            StateScoped(GameState::InGame),
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                prompt,
+                TextStyle {
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+            if matches!(step, TutorialStep::Say(_)) {
+                ui::spawn_button(
+                    parent,
+                    "Continue",
+                    Vec2::new(150.0, 34.0),
+                    UiRect::top(Val::Px(10.0)),
+                )
+                .insert(TutorialNext);
+            }
+        });
+}
+
+fn advance_tutorial(
+    mut tutorial: ResMut<Tutorial>,
+    scripts: Res<Assets<TutorialScript>>,
+    next: Query<&Interaction, (Changed<Interaction>, With<TutorialNext>)>,
+    selected: Query<&ButtonAction, With<SelectedBonus>>,
+    obstacles: Query<&Transform, With<SpawnedObstacle>>,
+    time: Res<Time<Real>>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+) {
+    if tutorial.shown != Some(tutorial.step) {
+        return;
+    }
+    let Some(step) = tutorial.current(&scripts) else {
+        return;
+    };
+    let done = match step {
+        TutorialStep::Say(_) => {
+            let pressed = next
+                .iter()
+                .any(|interaction| *interaction == Interaction::Pressed);
+            if pressed {
+                audio_trigger.send(AudioTrigger::Click);
+            }
+            pressed
+        }
+        TutorialStep::Select { bonus, .. } => selected
+            .iter()
+            .any(|action| *action == ButtonAction::Bonus(*bonus)),
+        TutorialStep::Place { cell, .. } => obstacles
+            .iter()
+            .any(|transform| obstacle_cell(transform) == *cell),
+        TutorialStep::Wait(_) => tutorial.wait.tick(time.delta()).finished(),
+    };
+    if done {
+        tutorial.step += 1;
+    }
+}
+
+fn highlight_bonus(
+    tutorial: Res<Tutorial>,
+    scripts: Res<Assets<TutorialScript>>,
+    mut buttons: Query<(&ButtonAction, &mut BorderColor)>,
+) {
+    let highlighted = match tutorial.current(&scripts) {
+        Some(TutorialStep::Select { bonus, .. }) => Some(*bonus),
+        _ => None,
+    };
+    for (action, mut border) in &mut buttons {
+        let ButtonAction::Bonus(bonus) = action else {
+            continue;
+        };
+        let color: Color = if highlighted == Some(*bonus) {
+            palettes::tailwind::AMBER_400.into()
+        } else {
+            palettes::tailwind::INDIGO_400.into()
+        };
+        if border.0 != color {
+            border.0 = color;
+        }
+    }
+}
+
+fn point_at_cell(
+    tutorial: Res<Tutorial>,
+    scripts: Res<Assets<TutorialScript>>,
+    time: Res<Time<Real>>,
+    motion: Res<ui::MotionPreference>,
+    mut gizmos: Gizmos,
+) {
+    let Some(TutorialStep::Place { cell, .. }) = tutorial.current(&scripts) else {
+        return;
+    };
+    let bob = if motion.reduced() {
+        0.0
+    } else {
+        (time.elapsed_seconds() * 4.0).sin() * 0.5
+    };
+    let target = Vec3::new(cell.0 as f32 * 4.0, 1.0 + bob, cell.1 as f32 * 4.0);
+    gizmos.arrow(
+        target + Vec3::Y * 5.0,
+        target,
+        palettes::tailwind::AMBER_400,
+    );
+}
@@ -0,0 +1,514 @@
+//! Widgets shared by the screens: buttons and their colors, panels, and how screens slide in and
+//! out of the window.
+
+use std::time::Duration;
+
+use bevy::{color::palettes, ecs::system::EntityCommands, prelude::*, ui::widget};
+use bevy_easings::{Ease, EaseFunction, EasingChainComponent, EasingComponent, EasingType};
+use rand::Rng;
+
+use crate::{audio::AudioTrigger, settings::Settings};
+
+/// Longest animation when motion is reduced, enough to fade instead of cutting
+const REDUCED_MOTION_DURATION: f32 = 0.2;
+
+/// How long a toast stays on screen
+const TOAST_DURATION: f32 = 5.0;
+
+/// Colors of the interface, swapped for colorblind safe ones from the settings
+#[derive(Resource, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub button_idle: BackgroundColor,
+    pub button_hovered: BackgroundColor,
+    /// A button toggled on, like the selected bonus
+    pub button_selected: BackgroundColor,
+    pub panel: BackgroundColor,
+}
+
+impl Palette {
+    pub fn new(colorblind: bool) -> Self {
+        if colorblind {
+            // blue and orange stay apart for every kind of color blindness, and the panel is
+            // neutral so that it doesn't get mixed up with either
+            Palette {
+                button_idle: palettes::tailwind::BLUE_900.into(),
+                button_hovered: palettes::tailwind::ORANGE_500.into(),
+                button_selected: palettes::tailwind::SKY_200.into(),
+                panel: palettes::tailwind::SLATE_500.into(),
+            }
+        } else {
+            Palette {
+                button_idle: palettes::tailwind::INDIGO_800.into(),
+                button_hovered: palettes::tailwind::AMBER_600.into(),
+                button_selected: palettes::tailwind::SKY_300.into(),
+                panel: palettes::tailwind::GREEN_400.into(),
+            }
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::new(false)
+    }
+}
+
+/// How much the interface moves, from the settings. Spawns of the big animations check it to
+/// shorten or skip them.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum MotionPreference {
+    #[default]
+    Full,
+    /// Short fades instead of things flying around, and no pulsing buttons
+    Reduced,
+}
+
+impl MotionPreference {
+    pub fn reduced(self) -> bool {
+        self == MotionPreference::Reduced
+    }
+
+    /// Duration of an animation of `seconds`, shortened when motion is reduced
+    pub fn duration(self, seconds: f32) -> Duration {
+        match self {
+            MotionPreference::Full => Duration::from_secs_f32(seconds),
+            MotionPreference::Reduced => {
+                Duration::from_secs_f32(seconds.min(REDUCED_MOTION_DURATION))
+            }
+        }
+    }
+}
+
+/// Color of an image, that can be eased with `bevy_easings::custom_ease_system`
+#[derive(Component, Default, Clone)]
+pub struct ImageColor {
+    pub color: Srgba,
+}
+
+impl bevy_easings::Lerp for ImageColor {
+    type Scalar = f32;
+
+    fn lerp(&self, other: &Self, scalar: &Self::Scalar) -> Self {
+        ImageColor {
+            color: self.color.mix(&other.color, *scalar),
+        }
+    }
+}
+
+/// Buttons changing color when hovered or pressed
+#[derive(Component)]
+pub struct Highlight;
+
+/// Button colored from the palette, on a screen handling how it reacts to the pointer itself
+#[derive(Component)]
+pub struct PaletteButton;
+
+/// Font sizes of a text after the multiplier from the settings, to tell them apart from sizes the
+/// game sets again
+#[derive(Component)]
+struct ScaledFont {
+    scale: f32,
+    sizes: Vec<f32>,
+}
+
+/// Node with the background color of panels
+#[derive(Component)]
+pub struct Panel;
+
+/// Short message at the bottom of the window, going away on its own
+#[derive(Component)]
+pub struct Toast(Timer);
+
+/// Button with a border pulsing from the one in its style to `border` pixels, unless motion is
+/// reduced
+#[derive(Component, Clone, Copy)]
+pub struct Pulse {
+    pub border: f32,
+    /// Seconds to go from one border to the other
+    pub duration: f32,
+    /// Seconds to stay on each border
+    pub pause: Option<f32>,
+    /// Longest random wait before the first pulse, so that buttons next to each other don't pulse
+    /// together
+    pub desync: f32,
+}
+
+impl Default for Pulse {
+    fn default() -> Self {
+        Pulse {
+            border: 5.0,
+            duration: 1.0,
+            pause: Some(0.5),
+            desync: 0.0,
+        }
+    }
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Palette>()
+            .init_resource::<MotionPreference>()
+            .add_systems(
+                Update,
+                (
+                    highlight_buttons,
+                    (update_palette, update_motion, update_ui_scale)
+                        .run_if(resource_changed::<Settings>),
+                    apply_pulses,
+                    hide_toasts,
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    apply_palette,
+                    scale_fonts.before(widget::measure_text_system),
+                ),
+            );
+    }
+}
+
+fn update_palette(settings: Res<Settings>, mut palette: ResMut<Palette>) {
+    palette.set_if_neq(Palette::new(settings.colorblind));
+}
+
+fn update_motion(settings: Res<Settings>, mut motion: ResMut<MotionPreference>) {
+    motion.set_if_neq(if settings.reduced_motion {
+        MotionPreference::Reduced
+    } else {
+        MotionPreference::Full
+    });
+}
+
+fn update_ui_scale(settings: Res<Settings>, mut ui_scale: ResMut<UiScale>) {
+    if ui_scale.0 != settings.ui_scale {
+        ui_scale.0 = settings.ui_scale;
+    }
+}
+
+/// Apply the font size multiplier to new texts, to texts whose size changed, and to all of them
+/// when the multiplier changes
+fn scale_fonts(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut texts: Query<(Entity, &mut Text, Option<&mut ScaledFont>), With<Node>>,
+) {
+    for (entity, mut text, scaled) in &mut texts {
+        if !text.is_changed() && !settings.is_changed() {
+            continue;
+        }
+        let sizes = text
+            .sections
+            .iter()
+            .enumerate()
+            .map(|(index, section)| {
+                let size = section.style.font_size;
+                let base = match &scaled {
+                    // still the size set here, not one set by the game since
+                    Some(scaled) if scaled.sizes.get(index) == Some(&size) => size / scaled.scale,
+                    _ => size,
+                };
+                base * settings.font_scale
+            })
+            .collect::<Vec<_>>();
+        if text
+            .sections
+            .iter()
+            .zip(&sizes)
+            .any(|(section, size)| section.style.font_size != *size)
+        {
+            for (section, size) in text.sections.iter_mut().zip(&sizes) {
+                section.style.font_size = *size;
+            }
+        }
+        let scaled_font = ScaledFont {
+            scale: settings.font_scale,
+            sizes,
+        };
+        match scaled {
+            Some(mut scaled) => *scaled = scaled_font,
+            None => {
+                commands.entity(entity).insert(scaled_font);
+            }
+        }
+    }
+}
+
+/// Start the pulse of new buttons, and start or stop all of them when the preference changes
+fn apply_pulses(
+    mut commands: Commands,
+    motion: Res<MotionPreference>,
+    buttons: Query<(Entity, Ref<Pulse>, &Style)>,
+) {
+    for (entity, pulse, style) in &buttons {
+        if !pulse.is_added() && !motion.is_changed() {
+            continue;
+        }
+        if motion.reduced() {
+            commands
+                .entity(entity)
+                .remove::<(EasingComponent<Style>, EasingChainComponent<Style>)>();
+            continue;
+        }
+        commands.entity(entity).insert(
+            style
+                .clone()
+                .ease_to(
+                    Style {
+                        border: UiRect::all(Val::Px(pulse.border)),
+                        ..style.clone()
+                    },
+                    EaseFunction::QuadraticInOut,
+                    EasingType::PingPong {
+                        duration: Duration::from_secs_f32(pulse.duration),
+                        pause: pulse.pause.map(Duration::from_secs_f32),
+                    },
+                )
+                .delay(Duration::from_secs_f32(
+                    rand::thread_rng().gen::<f32>() * pulse.desync,
+                )),
+        );
+    }
+}
+
+/// Color new buttons and panels, and all of them when the palette changes
+#[allow(clippy::type_complexity)]
+fn apply_palette(
+    palette: Res<Palette>,
+    mut buttons: Query<
+        (Ref<Interaction>, &mut BackgroundColor),
+        (Or<(With<Highlight>, With<PaletteButton>)>, Without<Panel>),
+    >,
+    mut panels: Query<(Ref<Panel>, &mut BackgroundColor), Without<Interaction>>,
+) {
+    for (interaction, mut color) in &mut buttons {
+        if interaction.is_added() || palette.is_changed() {
+            *color = match *interaction {
+                Interaction::None => palette.button_idle,
+                _ => palette.button_hovered,
+            };
+        }
+    }
+    for (panel, mut color) in &mut panels {
+        if panel.is_added() || palette.is_changed() {
+            *color = palette.panel;
+        }
+    }
+}
+
+/// Ease the color of a button to `to`
+pub fn ease_color(
+    commands: &mut Commands,
+    entity: Entity,
+    from: &BackgroundColor,
+    to: BackgroundColor,
+) {
+    commands.entity(entity).insert(from.ease_to(
+        to,
+        EaseFunction::QuadraticInOut,
+        EasingType::Once {
+            duration: Duration::from_secs_f32(0.25),
+        },
+    ));
+}
+
+fn highlight_buttons(
+    mut commands: Commands,
+    buttons: Query<(Entity, Ref<Interaction>, &BackgroundColor), With<Highlight>>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    palette: Res<Palette>,
+) {
+    for (entity, interaction, color) in &buttons {
+        if !interaction.is_changed() || interaction.is_added() {
+            continue;
+        }
+        let to = match *interaction {
+            Interaction::Pressed => palette.button_hovered,
+            Interaction::Hovered => {
+                // still hovered after a click
+                if !mouse.just_released(MouseButton::Left) {
+                    audio_trigger.send(AudioTrigger::Hover);
+                }
+                palette.button_hovered
+            }
+            Interaction::None => palette.button_idle,
+        };
+        ease_color(&mut commands, entity, color, to);
+    }
+}
+
+/// Style of the root node of a screen, `left` being its offset in percent of the window
+pub fn screen_style(left: f32) -> Style {
+    Style {
+        width: Val::Percent(100.0),
+        height: Val::Percent(100.0),
+        left: Val::Percent(left),
+        align_items: AlignItems::Center,
+        justify_content: JustifyContent::Start,
+        ..default()
+    }
+}
+
+/// Root node of a screen, sliding in from the left of the window to `left`
+pub fn screen_root(left: f32) -> (NodeBundle, EasingComponent<Style>) {
+    (
+        NodeBundle {
+            style: screen_style(-100.0),
+            ..default()
+        },
+        screen_style(-100.0).ease_to(
+            screen_style(left),
+            EaseFunction::QuadraticOut,
+            EasingType::Once {
+                duration: Duration::from_secs_f32(1.0),
+            },
+        ),
+    )
+}
+
+/// Slide the root node of a screen from `left` out of the window
+pub fn slide_out(commands: &mut Commands, root: Entity, left: f32) {
+    commands.entity(root).insert(screen_style(left).ease_to(
+        screen_style(-100.0),
+        EaseFunction::QuadraticOut,
+        EasingType::Once {
+            duration: Duration::from_secs_f32(1.0),
+        },
+    ));
+}
+
+/// Panel holding the content of a screen, in percent of the window
+pub fn panel(width: f32, height: f32) -> (NodeBundle, Panel) {
+    (
+        NodeBundle {
+            border_radius: BorderRadius::all(Val::Percent(5.0)),
+            z_index: ZIndex::Global(1),
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                width: Val::Percent(width),
+                height: Val::Percent(height),
+                ..default()
+            },
+            ..default()
+        },
+        Panel,
+    )
+}
+
+/// Title at the top of a panel
+pub fn title(text: &str) -> TextBundle {
+    TextBundle {
+        text: Text::from_section(
+            text,
+            TextStyle {
+                font_size: 60.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        ),
+        style: Style {
+            margin: UiRect::bottom(Val::Percent(5.0)),
+            ..default()
+        },
+        ..default()
+    }
+}
+
+/// Spawn a button with a label and a pulsing border, `size` being in pixels. Insert the action of
+/// the button on the returned entity.
+pub fn spawn_button<'a>(
+    parent: &'a mut ChildBuilder,
+    label: impl Into<String>,
+    size: Vec2,
+    margin: UiRect,
+) -> EntityCommands<'a> {
+    let mut button = parent.spawn((
+        ButtonBundle {
+            border_radius: BorderRadius::all(Val::Percent(10.0)),
+            border_color: BorderColor(palettes::tailwind::INDIGO_400.into()),
+            style: Style {
+                width: Val::Px(size.x),
+                height: Val::Px(size.y),
+                border: UiRect::all(Val::Px(3.0)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                margin,
+                ..default()
+            },
+            ..default()
+        },
+        Pulse {
+            desync: 1.0,
+            ..default()
+        },
+        Highlight,
+    ));
+    button.with_children(|p| {
+        p.spawn(TextBundle::from_section(
+            label,
+            TextStyle {
+                font_size: 20.0,
+                ..default()
+            },
+        ));
+    });
+    button
+}
+
+/// Show a message at the bottom of the window for a few seconds
+pub fn spawn_toast<'a>(commands: &'a mut Commands, text: impl Into<String>) -> EntityCommands<'a> {
+    let mut toast = commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                bottom: Val::Percent(5.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            z_index: ZIndex::Global(2),
+            ..default()
+        },
+        Toast(Timer::from_seconds(TOAST_DURATION, TimerMode::Once)),
+    ));
+    toast.with_children(|parent| {
+        parent
+            .spawn(NodeBundle {
+                background_color: palettes::tailwind::INDIGO_800.into(),
+                border_radius: BorderRadius::all(Val::Px(10.0)),
+                style: Style {
+                    height: Val::Px(40.0),
+                    padding: UiRect::horizontal(Val::Px(15.0)),
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    text,
+                    TextStyle {
+                        font_size: 20.0,
+                        ..default()
+                    },
+                ));
+            });
+    });
+    toast
+}
+
+fn hide_toasts(
+    mut commands: Commands,
+    mut toasts: Query<(Entity, &mut Toast)>,
+    time: Res<Time<Real>>,
+) {
+    for (entity, mut toast) in &mut toasts {
+        if toast.0.tick(time.delta()).just_finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
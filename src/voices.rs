@@ -0,0 +1,271 @@
+//! Gibberish said by the hobbits when something happens to them. The voices are synthesized when
+//! loading, a few variants for each line, and are never said too often.
+
+use std::f32::consts::{PI, TAU};
+
+use bevy::{
+    audio::{AddAudioSource, AudioSourceBundle, Decodable, Source, Volume},
+    prelude::*,
+    reflect::TypePath,
+    utils::{Duration, HashMap},
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{assets::GameAssets, game::GameEvent};
+
+const SAMPLE_RATE: u32 = 22050;
+/// Silence between two syllables, in seconds
+const SYLLABLE_GAP: f32 = 0.03;
+/// Variants synthesized for each line
+const VARIANTS: usize = 4;
+/// Seconds between two voices, whatever they say
+const VOICE_GAP: f32 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VoiceLine {
+    Spawn,
+    Treasure,
+    Delivery,
+    NearMiss,
+}
+
+impl VoiceLine {
+    const ALL: [VoiceLine; 4] = [
+        VoiceLine::Spawn,
+        VoiceLine::Treasure,
+        VoiceLine::Delivery,
+        VoiceLine::NearMiss,
+    ];
+
+    fn from_event(event: &GameEvent) -> Option<Self> {
+        match event {
+            GameEvent::Spawned { .. } => Some(VoiceLine::Spawn),
+            GameEvent::TreasureTaken { .. } => Some(VoiceLine::Treasure),
+            GameEvent::HomeWithTreasure { .. } => Some(VoiceLine::Delivery),
+            GameEvent::NearMiss => Some(VoiceLine::NearMiss),
+            _ => None,
+        }
+    }
+
+    /// Seconds before the line can be said again
+    fn cooldown(&self) -> f32 {
+        match self {
+            VoiceLine::Spawn => 1.0,
+            VoiceLine::Treasure => 0.5,
+            VoiceLine::Delivery => 0.5,
+            VoiceLine::NearMiss => 1.5,
+        }
+    }
+
+    /// Pitch range, number of syllables and how much the pitch rises in each syllable
+    fn tone(&self) -> (std::ops::Range<f32>, std::ops::RangeInclusive<usize>, f32) {
+        match self {
+            VoiceLine::Spawn => (260.0..340.0, 2..=3, 0.2),
+            VoiceLine::Treasure => (330.0..420.0, 2..=3, 0.4),
+            VoiceLine::Delivery => (280.0..360.0, 3..=4, 0.1),
+            VoiceLine::NearMiss => (380.0..480.0, 1..=2, 0.7),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Syllable {
+    pitch: f32,
+    /// Share of the pitch added by the end of the syllable
+    glide: f32,
+    duration: f32,
+    /// Weight of each harmonic compared to the previous one, higher for open vowels
+    brightness: f32,
+}
+
+/// A short gibberish line
+#[derive(Asset, TypePath, Clone)]
+pub struct Voice {
+    syllables: Vec<Syllable>,
+}
+
+impl Voice {
+    fn random(rng: &mut impl Rng, line: VoiceLine) -> Self {
+        let (pitch, syllables, glide) = line.tone();
+        let count = rng.gen_range(syllables);
+        Voice {
+            syllables: (0..count)
+                .map(|_| Syllable {
+                    pitch: rng.gen_range(pitch.clone()),
+                    glide: rng.gen_range(-0.1..glide),
+                    duration: rng.gen_range(0.06..0.12),
+                    brightness: rng.gen_range(0.3..0.7),
+                })
+                .collect(),
+        }
+    }
+
+    fn samples(&self) -> Vec<f32> {
+        let mut samples = vec![];
+        for syllable in &self.syllables {
+            let len = (syllable.duration * SAMPLE_RATE as f32) as usize;
+            let mut phase = 0.0;
+            for i in 0..len {
+                let t = i as f32 / len as f32;
+                let frequency = syllable.pitch * (1.0 + syllable.glide * t);
+                phase = (phase + frequency / SAMPLE_RATE as f32).fract();
+                let wave = (1..=4)
+                    .map(|harmonic| {
+                        (TAU * phase * harmonic as f32).sin()
+                            * syllable.brightness.powi(harmonic - 1)
+                    })
+                    .sum::<f32>();
+                samples.push(wave * (PI * t).sin() * 0.3);
+            }
+            let gap = (SYLLABLE_GAP * SAMPLE_RATE as f32) as usize;
+            samples.extend(std::iter::repeat(0.0).take(gap));
+        }
+        samples
+    }
+}
+
+pub struct VoiceDecoder(std::vec::IntoIter<f32>);
+
+impl Iterator for VoiceDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl Source for VoiceDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Decodable for Voice {
+    type DecoderItem = f32;
+    type Decoder = VoiceDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        VoiceDecoder(self.samples().into_iter())
+    }
+}
+
+/// Variants of each line, kept in [`GameAssets`]
+#[derive(Default)]
+pub struct VoiceBank(HashMap<VoiceLine, Vec<Handle<Voice>>>);
+
+impl VoiceBank {
+    /// Always the same voices, so that hobbits sound the same from one game to the next
+    pub fn generate(voices: &mut Assets<Voice>) -> Self {
+        let mut rng = StdRng::seed_from_u64(0);
+        VoiceBank(
+            VoiceLine::ALL
+                .iter()
+                .map(|line| {
+                    (
+                        *line,
+                        (0..VARIANTS)
+                            .map(|_| voices.add(Voice::random(&mut rng, *line)))
+                            .collect(),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    fn pick(&self, line: VoiceLine) -> Option<Handle<Voice>> {
+        self.0
+            .get(&line)
+            .and_then(|variants| variants.choose(&mut rand::thread_rng()))
+            .cloned()
+    }
+}
+
+/// Volume of the voices, overall and for each line
+#[derive(Resource)]
+pub struct VoiceVolume {
+    pub global: f32,
+    pub lines: HashMap<VoiceLine, f32>,
+}
+
+impl Default for VoiceVolume {
+    fn default() -> Self {
+        VoiceVolume {
+            global: 0.4,
+            lines: HashMap::from_iter([
+                (VoiceLine::Spawn, 0.6),
+                (VoiceLine::Treasure, 1.0),
+                (VoiceLine::Delivery, 1.0),
+                (VoiceLine::NearMiss, 0.8),
+            ]),
+        }
+    }
+}
+
+impl VoiceVolume {
+    pub fn volume(&self, line: VoiceLine) -> f32 {
+        self.global * self.lines.get(&line).copied().unwrap_or(1.0)
+    }
+}
+
+/// When each line, and any line, was last said
+#[derive(Default)]
+struct Throttle {
+    lines: HashMap<VoiceLine, f32>,
+    any: Option<f32>,
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<Voice>()
+            .init_resource::<VoiceVolume>()
+            .add_systems(Update, say_lines.run_if(resource_exists::<GameAssets>));
+    }
+}
+
+fn say_lines(
+    mut commands: Commands,
+    mut game_events: EventReader<GameEvent>,
+    assets: Res<GameAssets>,
+    volume: Res<VoiceVolume>,
+    time: Res<Time>,
+    mut throttle: Local<Throttle>,
+) {
+    let now = time.elapsed_seconds();
+    for line in game_events.read().filter_map(VoiceLine::from_event) {
+        if throttle.any.is_some_and(|last| now - last < VOICE_GAP)
+            || throttle
+                .lines
+                .get(&line)
+                .is_some_and(|last| now - last < line.cooldown())
+        {
+            continue;
+        }
+        let Some(voice) = assets.voices.pick(line) else {
+            continue;
+        };
+        throttle.any = Some(now);
+        throttle.lines.insert(line, now);
+        commands.spawn(AudioSourceBundle {
+            source: voice,
+            settings: PlaybackSettings {
+                mode: bevy::audio::PlaybackMode::Despawn,
+                volume: Volume::new(volume.volume(line)),
+                speed: rand::thread_rng().gen_range(0.9..1.15),
+                ..default()
+            },
+        });
+    }
+}
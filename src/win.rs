@@ -2,22 +2,40 @@ use std::time::Duration;
 
 use bevy::{color::palettes, prelude::*};
 use bevy_easings::{Ease, EaseFunction, EasingType};
-use rand::Rng;
+use bevy_pkv::PkvStore;
 
 use crate::{
-    assets::GameAssets, audio::AudioTrigger, menu::SwitchState, play::GameInProgress, GameProgress,
-    GameState,
+    assets::GameAssets,
+    audio::AudioTrigger,
+    difficulty::DifficultyMode,
+    layouts::{save_layout, LastLayout},
+    menu::SwitchState,
+    mutators::Mutators,
+    new_game_plus::NewGamePlus,
+    play::GameInProgress,
+    ui, GameProgress, GameState,
 };
 
 const CURRENT_STATE: GameState = GameState::Win;
 
+/// Seconds for each result to count up to its value
+const TALLY_DURATION: f32 = 0.8;
+/// Seconds between the start of two results
+const TALLY_STAGGER: f32 = 0.4;
+/// Delay before the first result, while the panel slides in
+const TALLY_START: f32 = 1.0;
+
 pub struct Plugin;
 impl bevy::prelude::Plugin for Plugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(CURRENT_STATE), (spawn_win_screen,))
             .add_systems(
                 Update,
-                (button_system, crate::menu::change_state_after_event)
+                (
+                    button_system,
+                    count_up,
+                    crate::menu::change_state_after_event,
+                )
                     .run_if(in_state(CURRENT_STATE)),
             );
     }
@@ -26,190 +44,157 @@ impl bevy::prelude::Plugin for Plugin {
 fn spawn_win_screen(
     mut commands: Commands,
     progress: Res<GameProgress>,
+    game: Res<GameInProgress>,
     assets: Res<GameAssets>,
-    mut audio_trigger: EventWriter<AudioTrigger>,
+    last_layout: Option<Res<LastLayout>>,
+    difficulty: Res<DifficultyMode>,
+    new_game_plus: Res<NewGamePlus>,
+    mutators: Res<Mutators>,
 ) {
     info!("Loading screen");
-    audio_trigger.send(AudioTrigger::Win);
+    let bonus_used = last_layout
+        .as_ref()
+        .map(|layout| layout.cells.len())
+        .unwrap_or(0);
+    let can_save_layout = bonus_used > 0;
+    let results = [
+        ("Treasures", Tallied::Count(game.scores.iter().sum::<u32>())),
+        ("Hobbits lost", Tallied::Count(game.lost_hobbits)),
+        ("Time", Tallied::Seconds(game.elapsed)),
+        (
+            "Bonus used",
+            Tallied::OutOf(bonus_used as u32, game.bonus.len() as u32),
+        ),
+    ];
+    // the fanfare and the stars come once every result is counted
+    let tally_end = TALLY_START + TALLY_STAGGER * (results.len() - 1) as f32 + TALLY_DURATION;
+    let stars = star_rating(game.lost_hobbits, bonus_used, game.bonus.len());
 
     commands
         .spawn((
-            NodeBundle {
-                style: Style {
-                    width: Val::Percent(100.0),
-                    height: Val::Percent(100.0),
-                    left: Val::Percent(-100.0),
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::Start,
-                    ..default()
-                },
-                ..default()
-            },
-            Style {
-                width: Val::Percent(100.0),
-                height: Val::Percent(100.0),
-                left: Val::Percent(-100.0),
-                align_items: AlignItems::Center,
-                justify_content: JustifyContent::Start,
-                ..default()
-            }
-            .ease_to(
-                Style {
-                    width: Val::Percent(100.0),
-                    height: Val::Percent(100.0),
-                    left: Val::Percent(30.0),
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::Start,
-                    ..default()
-                },
-                EaseFunction::QuadraticOut,
-                EasingType::Once {
-                    duration: Duration::from_secs_f32(1.0),
-                },
-            ),
+            ui::screen_root(30.0),
             MenuItem::Root,
             StateScoped(CURRENT_STATE),
         ))
         .with_children(|parent| {
             parent
-                .spawn((
-                    NodeBundle {
-                        background_color: palettes::tailwind::GREEN_400.into(),
-                        border_radius: BorderRadius::all(Val::Percent(5.0)),
-                        z_index: ZIndex::Global(1),
-                        style: Style {
-                            flex_direction: FlexDirection::Column,
-                            justify_content: JustifyContent::Center,
-                            align_items: AlignItems::Center,
-                            width: Val::Percent(40.0),
-                            height: Val::Percent(60.0),
-                            ..default()
-                        },
-                        ..default()
-                    },
-                    MenuItem::Panel,
-                ))
+                .spawn((ui::panel(40.0, 80.0), MenuItem::Panel))
                 .with_children(|parent| {
-                    parent.spawn(TextBundle {
-                        text: Text::from_section(
-                            "Success!",
+                    parent.spawn(ui::title("Success!"));
+                    if assets.is_campaign(game.level) {
+                        parent.spawn(TextBundle::from_section(
+                            format!("Difficulty: {}", difficulty.label()),
                             TextStyle {
-                                font_size: 60.0,
+                                font_size: 20.0,
                                 color: Color::WHITE,
                                 ..default()
                             },
-                        ),
-                        style: Style {
-                            margin: UiRect::bottom(Val::Percent(5.0)),
-                            ..default()
-                        },
-                        ..default()
-                    });
-
-                    let button_height = 40.0;
-                    let style_easing = Style {
-                        width: Val::Px(200.0),
-                        height: Val::Px(button_height),
-                        border: UiRect::all(Val::Px(3.0)),
-                        align_items: AlignItems::Center,
-                        justify_content: JustifyContent::Center,
-                        margin: UiRect::top(Val::Percent(10.0)),
-                        justify_self: JustifySelf::End,
-                        ..default()
-                    };
+                        ));
+                    }
+                    if mutators.any() {
+                        let labels = mutators
+                            .active()
+                            .iter()
+                            .map(|mutator| mutator.label())
+                            .collect::<Vec<_>>();
+                        parent.spawn(TextBundle::from_section(
+                            format!("Mutators: {}", labels.join(", ")),
+                            TextStyle {
+                                font_size: 20.0,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        ));
+                    }
 
-                    let style_easing = style_easing.ease_to(
-                        Style {
-                            width: Val::Px(200.0),
-                            height: Val::Px(button_height),
-                            border: UiRect::all(Val::Px(5.0)),
-                            margin: UiRect::top(Val::Percent(10.0)),
-                            justify_self: JustifySelf::End,
+                    for (index, (label, value)) in results.into_iter().enumerate() {
+                        parent.spawn((
+                            TextBundle {
+                                text: Text::from_sections([
+                                    TextSection {
+                                        value: format!("{}: ", label),
+                                        style: TextStyle {
+                                            font_size: 25.0,
+                                            color: Color::WHITE,
+                                            ..default()
+                                        },
+                                    },
+                                    TextSection {
+                                        value: value.at(0.0),
+                                        style: TextStyle {
+                                            font_size: 25.0,
+                                            color: palettes::tailwind::AMBER_300.into(),
+                                            ..default()
+                                        },
+                                    },
+                                ]),
+                                ..default()
+                            },
+                            Tally {
+                                value,
+                                elapsed: -(TALLY_START + TALLY_STAGGER * index as f32),
+                                fanfare: index == results.len() - 1,
+                            },
+                        ));
+                    }
 
-                            ..default()
-                        },
-                        EaseFunction::QuadraticInOut,
-                        EasingType::PingPong {
-                            duration: Duration::from_secs_f32(1.0),
-                            pause: Some(Duration::from_secs_f32(0.5)),
-                        },
-                    );
                     parent
-                        .spawn((
-                            ButtonBundle {
-                                background_color: palettes::tailwind::INDIGO_800.into(),
-                                border_radius: BorderRadius::all(Val::Percent(10.0)),
-                                border_color: BorderColor(palettes::tailwind::INDIGO_400.into()),
-                                style: Style {
-                                    width: Val::Px(200.0),
-                                    height: Val::Px(button_height),
-                                    border: UiRect::all(Val::Px(0.0)),
-                                    align_items: AlignItems::Center,
-                                    justify_content: JustifyContent::Center,
-                                    margin: UiRect::top(Val::Percent(10.0)),
-                                    justify_self: JustifySelf::End,
-                                    ..default()
-                                },
+                        .spawn(NodeBundle {
+                            style: Style {
+                                margin: UiRect::top(Val::Percent(3.0)),
+                                column_gap: Val::Px(10.0),
                                 ..default()
                             },
-                            style_easing.clone().delay(Duration::from_secs_f32(
-                                rand::thread_rng().gen_range(0.0..1.0),
-                            )),
-                            MenuItem::Button,
-                            ButtonAction::Back,
-                        ))
-                        .with_children(|p| {
-                            p.spawn(TextBundle {
-                                text: Text::from_section(
-                                    "Back to Menu",
-                                    TextStyle {
-                                        font_size: 20.0,
-                                        ..default()
-                                    },
-                                ),
-                                ..default()
-                            });
-                        });
-                    if progress.current_level < assets.levels.len() {
-                        parent
-                            .spawn((
-                                ButtonBundle {
-                                    background_color: palettes::tailwind::INDIGO_800.into(),
-                                    border_radius: BorderRadius::all(Val::Percent(10.0)),
-                                    border_color: BorderColor(
-                                        palettes::tailwind::INDIGO_400.into(),
-                                    ),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for star in 0..MAX_STARS {
+                                let mut node = parent.spawn(NodeBundle {
                                     style: Style {
-                                        width: Val::Px(200.0),
-                                        height: Val::Px(button_height),
-                                        border: UiRect::all(Val::Px(0.0)),
-                                        align_items: AlignItems::Center,
-                                        justify_content: JustifyContent::Center,
-                                        margin: UiRect::top(Val::Percent(10.0)),
-                                        justify_self: JustifySelf::End,
+                                        width: Val::Px(30.0),
+                                        height: Val::Px(30.0),
                                         ..default()
                                     },
-                                    ..default()
-                                },
-                                style_easing.delay(Duration::from_secs_f32(
-                                    rand::thread_rng().gen_range(0.0..1.0),
-                                )),
-                                MenuItem::Button,
-                                ButtonAction::Next,
-                            ))
-                            .with_children(|p| {
-                                p.spawn(TextBundle {
-                                    text: Text::from_section(
-                                        "Next Level",
-                                        TextStyle {
-                                            font_size: 20.0,
-                                            ..default()
-                                        },
-                                    ),
+                                    background_color: STAR_MISSED,
+                                    border_radius: BorderRadius::all(Val::Percent(50.0)),
                                     ..default()
                                 });
-                            });
+                                if star < stars {
+                                    node.insert(
+                                        STAR_MISSED
+                                            .ease_to(
+                                                STAR_EARNED,
+                                                EaseFunction::BounceOut,
+                                                EasingType::Once {
+                                                    duration: Duration::from_secs_f32(0.4),
+                                                },
+                                            )
+                                            .delay(Duration::from_secs_f32(
+                                                tally_end + 0.3 * star as f32,
+                                            )),
+                                    );
+                                }
+                            }
+                        });
+
+                    let size = Vec2::new(200.0, 40.0);
+                    let margin = UiRect::top(Val::Percent(10.0));
+                    ui::spawn_button(parent, "Back to Menu", size, margin)
+                        .insert((MenuItem::Button, ButtonAction::Back));
+                    if can_save_layout {
+                        ui::spawn_button(parent, "Save Layout", size, margin)
+                            .insert((MenuItem::Button, ButtonAction::SaveLayout));
+                    }
+                    if next_level(&progress, &new_game_plus) < assets.custom_levels.start
+                        && assets.is_campaign(game.level)
+                    {
+                        ui::spawn_button(parent, "Next Level", size, margin)
+                            .insert((MenuItem::Button, ButtonAction::Next));
                     } else {
+                        if assets.is_campaign(game.level) && !new_game_plus.active {
+                            ui::spawn_button(parent, "New Game+", size, margin)
+                                .insert((MenuItem::Button, ButtonAction::NewGamePlus));
+                        }
                         parent.spawn(TextBundle {
                             text: Text::from_section(
                                 "And you finished the game!",
@@ -230,17 +215,68 @@ fn spawn_win_screen(
         });
 }
 
-#[derive(Component, Default, Clone)]
-struct ImageColor {
-    color: Srgba,
+/// Stars for a win: one for winning, one for losing no hobbit, and one for leaving a bonus unused
+const MAX_STARS: usize = 3;
+
+fn star_rating(lost_hobbits: u32, bonus_used: usize, bonus_available: usize) -> usize {
+    1 + usize::from(lost_hobbits == 0) + usize::from(bonus_used < bonus_available)
+}
+
+const STAR_MISSED: BackgroundColor = BackgroundColor(Color::Srgba(palettes::tailwind::GREEN_700));
+const STAR_EARNED: BackgroundColor = BackgroundColor(Color::Srgba(palettes::tailwind::AMBER_400));
+
+/// A value of the results
+#[derive(Clone, Copy)]
+enum Tallied {
+    Count(u32),
+    Seconds(f32),
+    /// A count out of a total
+    OutOf(u32, u32),
+}
+
+impl Tallied {
+    /// Text of the value, `progress` of the way from zero
+    fn at(&self, progress: f32) -> String {
+        match self {
+            Tallied::Count(count) => format!("{}", (*count as f32 * progress).round() as u32),
+            Tallied::Seconds(seconds) => {
+                let seconds = (seconds * progress) as u32;
+                format!("{}:{:02}", seconds / 60, seconds % 60)
+            }
+            Tallied::OutOf(count, total) => {
+                format!("{} / {}", (*count as f32 * progress).round() as u32, total)
+            }
+        }
+    }
 }
 
-impl bevy_easings::Lerp for ImageColor {
-    type Scalar = f32;
+/// A result counting up to its value. `elapsed` starts negative to wait for the results before.
+#[derive(Component)]
+struct Tally {
+    value: Tallied,
+    elapsed: f32,
+    /// Play the fanfare once counted
+    fanfare: bool,
+}
 
-    fn lerp(&self, other: &Self, scalar: &Self::Scalar) -> Self {
-        ImageColor {
-            color: self.color.mix(&other.color, *scalar),
+fn count_up(
+    mut commands: Commands,
+    mut tallies: Query<(Entity, &mut Tally, &mut Text)>,
+    time: Res<Time>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+) {
+    for (entity, mut tally, mut text) in &mut tallies {
+        tally.elapsed += time.delta_seconds();
+        if tally.elapsed < 0.0 {
+            continue;
+        }
+        let progress = (tally.elapsed / TALLY_DURATION).min(1.0);
+        text.sections[1].value = tally.value.at(progress);
+        if progress >= 1.0 {
+            if tally.fanfare {
+                audio_trigger.send(AudioTrigger::Win);
+            }
+            commands.entity(entity).remove::<Tally>();
         }
     }
 }
@@ -256,134 +292,86 @@ enum MenuItem {
 enum ButtonAction {
     Back,
     Next,
+    SaveLayout,
+    /// Play the campaign again, remixed
+    NewGamePlus,
+}
+
+/// Level unlocked by the last win, in the campaign or in New Game+
+fn next_level(progress: &GameProgress, new_game_plus: &NewGamePlus) -> usize {
+    if new_game_plus.active {
+        new_game_plus.reached.unwrap_or(1)
+    } else {
+        progress.current_level
+    }
 }
 
 fn button_system(
     mut commands: Commands,
-    interaction_query: Query<
-        (Ref<Interaction>, &BackgroundColor, Entity, &ButtonAction),
-        Changed<Interaction>,
-    >,
+    interaction_query: Query<(&Interaction, Entity, &ButtonAction), Changed<Interaction>>,
     mut next_state: EventWriter<SwitchState>,
     ui_items: Query<(Entity, &MenuItem)>,
     progress: Res<GameProgress>,
     mut audio_trigger: EventWriter<AudioTrigger>,
+    last_layout: Option<Res<LastLayout>>,
+    mut progress_storage: ResMut<PkvStore>,
+    mut new_game_plus: ResMut<NewGamePlus>,
 ) {
-    for (interaction, color, entity, action) in &interaction_query {
-        if interaction.is_added() {
+    for (interaction, entity, action) in &interaction_query {
+        if *interaction != Interaction::Pressed {
             continue;
         }
-        match *interaction {
-            Interaction::Pressed => match action {
-                ButtonAction::Back => {
-                    audio_trigger.send(AudioTrigger::Click);
-                    next_state.send(SwitchState(GameState::Menu));
-
-                    for (entity, kind) in &ui_items {
-                        if *kind == MenuItem::Root {
-                            commands.entity(entity).insert(
-                                Style {
-                                    width: Val::Percent(100.0),
-                                    height: Val::Percent(100.0),
-                                    left: Val::Percent(30.0),
-                                    align_items: AlignItems::Center,
-                                    justify_content: JustifyContent::Start,
+        match action {
+            ButtonAction::Back => {
+                audio_trigger.send(AudioTrigger::Click);
+                next_state.send(SwitchState(GameState::Menu));
+            }
+            ButtonAction::SaveLayout => {
+                audio_trigger.send(AudioTrigger::Click);
+                let Some(last_layout) = last_layout.as_ref() else {
+                    continue;
+                };
+                let name = save_layout(&mut progress_storage, last_layout);
+                commands
+                    .entity(entity)
+                    .remove::<ButtonAction>()
+                    .despawn_descendants()
+                    .with_children(|p| {
+                        p.spawn(TextBundle {
+                            text: Text::from_section(
+                                format!("Saved as {}", name),
+                                TextStyle {
+                                    font_size: 20.0,
                                     ..default()
-                                }
-                                .ease_to(
-                                    Style {
-                                        width: Val::Percent(100.0),
-                                        height: Val::Percent(100.0),
-                                        left: Val::Percent(-100.0),
-                                        align_items: AlignItems::Center,
-                                        justify_content: JustifyContent::Start,
-                                        ..default()
-                                    },
-                                    EaseFunction::QuadraticOut,
-                                    EasingType::Once {
-                                        duration: Duration::from_secs_f32(1.0),
-                                    },
-                                ),
-                            );
-                        }
-                    }
-
-                    commands.entity(entity).insert(color.ease_to(
-                        BUTTON_HOVERED,
-                        EaseFunction::QuadraticInOut,
-                        EasingType::Once {
-                            duration: Duration::from_secs_f32(0.25),
-                        },
-                    ));
-                }
-                ButtonAction::Next => {
-                    audio_trigger.send(AudioTrigger::Start);
-                    next_state.send(SwitchState(GameState::InGame));
-                    commands.insert_resource(GameInProgress {
-                        level: progress.current_level,
-                        ..default()
+                                },
+                            ),
+                            ..default()
+                        });
                     });
-
-                    for (entity, kind) in &ui_items {
-                        if *kind == MenuItem::Root {
-                            commands.entity(entity).insert(
-                                Style {
-                                    width: Val::Percent(100.0),
-                                    height: Val::Percent(100.0),
-                                    left: Val::Percent(30.0),
-                                    align_items: AlignItems::Center,
-                                    justify_content: JustifyContent::Start,
-                                    ..default()
-                                }
-                                .ease_to(
-                                    Style {
-                                        width: Val::Percent(100.0),
-                                        height: Val::Percent(100.0),
-                                        left: Val::Percent(-100.0),
-                                        align_items: AlignItems::Center,
-                                        justify_content: JustifyContent::Start,
-                                        ..default()
-                                    },
-                                    EaseFunction::QuadraticOut,
-                                    EasingType::Once {
-                                        duration: Duration::from_secs_f32(1.0),
-                                    },
-                                ),
-                            );
-                        }
-                    }
-
-                    commands.entity(entity).insert(color.ease_to(
-                        BUTTON_HOVERED,
-                        EaseFunction::QuadraticInOut,
-                        EasingType::Once {
-                            duration: Duration::from_secs_f32(0.25),
-                        },
-                    ));
-                }
-            },
-            Interaction::Hovered => {
-                commands.entity(entity).insert(color.ease_to(
-                    BUTTON_HOVERED,
-                    EaseFunction::QuadraticInOut,
-                    EasingType::Once {
-                        duration: Duration::from_secs_f32(0.25),
-                    },
-                ));
+                continue;
+            }
+            ButtonAction::Next => {
+                audio_trigger.send(AudioTrigger::Start);
+                next_state.send(SwitchState(GameState::InGame));
+                commands.insert_resource(GameInProgress {
+                    level: next_level(&progress, &new_game_plus),
+                    ..default()
+                });
             }
-            Interaction::None => {
-                commands.entity(entity).insert(color.ease_to(
-                    BUTTON_IDLE,
-                    EaseFunction::QuadraticInOut,
-                    EasingType::Once {
-                        duration: Duration::from_secs_f32(0.25),
-                    },
-                ));
+            ButtonAction::NewGamePlus => {
+                audio_trigger.send(AudioTrigger::Start);
+                next_state.send(SwitchState(GameState::InGame));
+                commands.insert_resource(GameInProgress {
+                    level: new_game_plus.start(&mut progress_storage),
+                    ..default()
+                });
+            }
+        }
+
+        for (entity, kind) in &ui_items {
+            if *kind == MenuItem::Root {
+                ui::slide_out(&mut commands, entity, 30.0);
             }
         }
     }
 }
-
-const BUTTON_IDLE: BackgroundColor = BackgroundColor(Color::Srgba(palettes::tailwind::INDIGO_800));
-const BUTTON_HOVERED: BackgroundColor =
-    BackgroundColor(Color::Srgba(palettes::tailwind::AMBER_600));
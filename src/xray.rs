@@ -0,0 +1,96 @@
+//! Silhouettes of hobbits hidden behind walls, drawn over the level so that they can still be
+//! followed when walking behind a wall near the bottom of the screen.
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+use crate::{
+    game::{ActiveLevel, Hobbit},
+    levels::{TEAM_COLORS, WALL_HEIGHT},
+    settings::Settings,
+    GameState,
+};
+
+/// Height above the hobbit origin of the point that must be seen, around its shoulders
+const SEEN_HEIGHT: f32 = 0.5;
+/// Walls are checked along the ray, most rays cross a few walls at most
+const MAX_WALLS: u32 = 8;
+
+/// Silhouettes are drawn in front of everything else
+#[derive(Default, Reflect, GizmoConfigGroup)]
+struct XRayGizmos;
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.insert_gizmo_config(
+            XRayGizmos,
+            GizmoConfig {
+                depth_bias: -1.0,
+                ..default()
+            },
+        )
+        .add_systems(
+            Update,
+            draw_silhouettes.run_if(
+                in_state(GameState::InGame)
+                    .and_then(resource_exists::<ActiveLevel>)
+                    .and_then(|settings: Res<Settings>| settings.hobbit_silhouettes),
+            ),
+        );
+    }
+}
+
+fn draw_silhouettes(
+    cameras: Query<&GlobalTransform, With<Camera3d>>,
+    hobbits: Query<(&Transform, &Hobbit)>,
+    spatial_query: SpatialQuery,
+    mut gizmos: Gizmos<XRayGizmos>,
+) {
+    let Ok(camera) = cameras.get_single() else {
+        return;
+    };
+    let eye = camera.translation();
+    let facing = camera.to_scale_rotation_translation().1;
+    // walls are on the second collision layer
+    let walls = SpatialQueryFilter::from_mask(0b010);
+
+    for (transform, hobbit) in &hobbits {
+        let scale = hobbit.kind().scale();
+        let seen = transform.translation + Vec3::Y * SEEN_HEIGHT * scale;
+        let Ok(direction) = Dir3::new(seen - eye) else {
+            continue;
+        };
+        // wall colliders are much higher than the walls so that nothing jumps over them, only
+        // count the walls the ray crosses below their visible height
+        let hidden = spatial_query
+            .ray_hits(
+                eye,
+                direction,
+                eye.distance(seen),
+                MAX_WALLS,
+                true,
+                walls.clone(),
+            )
+            .iter()
+            .any(|hit| (eye + *direction * hit.time_of_impact).y < WALL_HEIGHT);
+        if !hidden {
+            continue;
+        }
+
+        let color = TEAM_COLORS[hobbit.team() % TEAM_COLORS.len()];
+        let radius = hobbit.kind().radius();
+        gizmos.ellipse(
+            transform.translation,
+            facing,
+            Vec2::new(radius, radius * 1.6) * scale,
+            color,
+        );
+        gizmos.ellipse(
+            transform.translation + Vec3::Y * 1.1 * scale,
+            facing,
+            Vec2::splat(radius * 0.6) * scale,
+            color,
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! Zones drawn by the player on the floor, changing how hobbits walk inside them. Each level gives
+//! a few zones to draw, one rule each: pick one in the bottom panel then drag on the floor.
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    assets::GameAssets,
+    audio::AudioTrigger,
+    game::{ActiveLevel, Hobbit, PlayLoop, SteeringModifier},
+    levels::{Level, ZoneRule},
+    play::{cursor_cell, GameInProgress, SelectedBonus},
+    ui, GameState,
+};
+
+/// Speed multiplier of hobbits in a slow zone
+const SLOW_SPEED: f32 = 0.5;
+/// How much hobbits steer to the left in a prefer left zone
+const LEFT_BIAS: f32 = 0.5;
+/// Seconds hobbits stop when entering a wait zone
+const WAIT_DURATION: f32 = 1.5;
+
+/// A zone drawn on the floor
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ControlZone {
+    pub rule: ZoneRule,
+    /// First cell of the zone
+    pub from: (usize, usize),
+    /// Last cell of the zone, included
+    pub to: (usize, usize),
+}
+
+impl ControlZone {
+    pub fn contains(&self, position: Vec3) -> bool {
+        let (x, z) = ((position.x / 4.0).round(), (position.z / 4.0).round());
+        x >= self.from.0 as f32
+            && x <= self.to.0 as f32
+            && z >= self.from.1 as f32
+            && z <= self.to.1 as f32
+    }
+}
+
+/// A zone still to be drawn
+#[derive(Component)]
+struct ZoneButton(ZoneRule);
+
+#[derive(Component)]
+struct SelectedZone;
+
+/// Wait zones a hobbit already stopped in, and how long it still has to wait
+#[derive(Default)]
+struct Waiting {
+    done: Vec<Entity>,
+    left: f32,
+}
+
+pub struct Plugin;
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::InGame), spawn_zone_buttons)
+            .add_systems(
+                Update,
+                (select_zone, draw_zone)
+                    .chain()
+                    .run_if(in_state(GameState::InGame).and_then(resource_exists::<ActiveLevel>)),
+            )
+            .add_systems(
+                FixedUpdate,
+                apply_zones.before(PlayLoop::Movement).run_if(
+                    in_state(GameState::InGame).and_then(any_with_component::<ControlZone>),
+                ),
+            );
+    }
+}
+
+fn spawn_zone_buttons(
+    mut commands: Commands,
+    game: Res<GameInProgress>,
+    assets: Res<GameAssets>,
+    levels: Res<Assets<Level>>,
+) {
+    let level = levels.get(&assets.levels[game.level]).unwrap();
+    if level.zones.is_empty() {
+        return;
+    }
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    column_gap: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            },
+            StateScoped(GameState::InGame),
+        ))
+        .with_children(|parent| {
+            for rule in &level.zones {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                padding: UiRect::axes(Val::Px(10.0), Val::Px(5.0)),
+                                border: UiRect::bottom(Val::Px(4.0)),
+                                ..default()
+                            },
+                            border_color: BorderColor(rule.color().into()),
+                            border_radius: BorderRadius::all(Val::Px(5.0)),
+                            ..default()
+                        },
+                        ZoneButton(*rule),
+                        ui::PaletteButton,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            rule.label(),
+                            TextStyle {
+                                font_size: 25.0,
+                                ..default()
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+fn select_zone(
+    mut commands: Commands,
+    buttons: Query<(Entity, &Interaction, Has<SelectedZone>), Changed<Interaction>>,
+    mut colors: Query<(Entity, &mut BackgroundColor), With<ZoneButton>>,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+    palette: Res<ui::Palette>,
+) {
+    for (entity, interaction, selected) in &buttons {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        audio_trigger.send(AudioTrigger::Click);
+        for (other, mut color) in &mut colors {
+            if other == entity && !selected {
+                *color = palette.button_selected;
+                commands.entity(other).insert(SelectedZone);
+            } else {
+                *color = palette.button_idle;
+                commands.entity(other).remove::<SelectedZone>();
+            }
+        }
+    }
+}
+
+/// Corners of the zone between two cells, clamped to the level
+fn zone_cells(level: &Level, a: Vec3, b: Vec3) -> ((usize, usize), (usize, usize)) {
+    let width = level.floors[0][0].len() as f32 - 1.0;
+    let height = level.floors[0].len() as f32 - 1.0;
+    let clamp = |v: Vec3| (v.x.clamp(0.0, width), v.z.clamp(0.0, height));
+    let (a, b) = (clamp(a), clamp(b));
+    (
+        (a.0.min(b.0) as usize, a.1.min(b.1) as usize),
+        (a.0.max(b.0) as usize, a.1.max(b.1) as usize),
+    )
+}
+
+/// Center and size of the zone, in world coordinates
+fn zone_rect(from: (usize, usize), to: (usize, usize)) -> (Vec3, Vec2) {
+    let center = Vec3::new(
+        (from.0 + to.0) as f32 * 2.0,
+        0.1,
+        (from.1 + to.1) as f32 * 2.0,
+    );
+    let size = Vec2::new(
+        (to.0 - from.0 + 1) as f32 * 4.0,
+        (to.1 - from.1 + 1) as f32 * 4.0,
+    );
+    (center, size)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_zone(
+    mut commands: Commands,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    windows: Query<&Window>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    level: Res<ActiveLevel>,
+    selected: Query<(Entity, &ZoneButton), With<SelectedZone>>,
+    selected_bonus: Query<(), With<SelectedBonus>>,
+    interactions: Query<&Interaction>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut gizmos: Gizmos,
+    mut audio_trigger: EventWriter<AudioTrigger>,
+    mut start: Local<Option<Vec3>>,
+) {
+    let Ok((button, ZoneButton(rule))) = selected.get_single() else {
+        *start = None;
+        return;
+    };
+    let (camera, camera_transform) = camera_query.single();
+    let Some(cell) = cursor_cell(camera, camera_transform, windows.single()) else {
+        return;
+    };
+    if mouse_input.just_pressed(MouseButton::Left)
+        && selected_bonus.is_empty()
+        && interactions
+            .iter()
+            .all(|interaction| *interaction == Interaction::None)
+    {
+        *start = Some(cell);
+    }
+    let Some(from) = *start else {
+        return;
+    };
+    let (from, to) = zone_cells(&level.0, from, cell);
+    let (center, size) = zone_rect(from, to);
+    gizmos.rect(
+        center,
+        Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+        size,
+        rule.color(),
+    );
+    if !mouse_input.just_released(MouseButton::Left) {
+        return;
+    }
+    *start = None;
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Plane3d::default().mesh().size(size.x, size.y)),
+            material: materials.add(StandardMaterial {
+                base_color: rule.color().with_alpha(0.35).into(),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            transform: Transform::from_translation(center),
+            ..default()
+        },
+        ControlZone {
+            rule: *rule,
+            from,
+            to,
+        },
+        StateScoped(GameState::InGame),
+    ));
+    commands.entity(button).despawn_recursive();
+    audio_trigger.send(AudioTrigger::Obstacle);
+}
+
+fn apply_zones(
+    mut commands: Commands,
+    mut hobbits: Query<(Entity, &Transform, Option<&mut SteeringModifier>), With<Hobbit>>,
+    zones: Query<(Entity, &ControlZone)>,
+    time: Res<Time>,
+    mut waiting: Local<HashMap<Entity, Waiting>>,
+) {
+    let delta_time = time.delta_seconds();
+    waiting.retain(|entity, _| hobbits.contains(*entity));
+
+    for (entity, transform, current) in &mut hobbits {
+        let mut modifier = SteeringModifier::default();
+        for (zone_entity, zone) in &zones {
+            if !zone.contains(transform.translation) {
+                continue;
+            }
+            match zone.rule {
+                ZoneRule::Slow => modifier.speed *= SLOW_SPEED,
+                ZoneRule::PreferLeft => modifier.lateral = LEFT_BIAS,
+                ZoneRule::Wait => {
+                    let waiting = waiting.entry(entity).or_default();
+                    if !waiting.done.contains(&zone_entity) {
+                        waiting.done.push(zone_entity);
+                        waiting.left = WAIT_DURATION;
+                    }
+                }
+            }
+        }
+        if let Some(waiting) = waiting.get_mut(&entity) {
+            modifier.waiting = waiting.left > 0.0;
+            waiting.left -= delta_time;
+        }
+        match current {
+            Some(mut current) => {
+                if *current != modifier {
+                    *current = modifier;
+                }
+            }
+            None => {
+                if modifier != SteeringModifier::default() {
+                    commands.entity(entity).try_insert(modifier);
+                }
+            }
+        }
+    }
+}